@@ -15,6 +15,7 @@
 */
 
 use crate::CONF_FILE_PATH_MSG_STARTER;
+use pyrsia::artifact_service::model::{BundlePackage, PackageType};
 use pyrsia::cli_commands::config;
 use pyrsia::cli_commands::model::BuildResultResponse;
 use pyrsia::cli_commands::node;
@@ -23,8 +24,10 @@ use std::collections::HashSet;
 use std::future::Future;
 use std::io;
 use std::io::BufRead;
+use std::path::Path;
 
 const CONF_REMINDER_MESSAGE: &str = "Please make sure the pyrsia CLI config is up to date and matches the node configuration. For more information, run 'pyrsia config --show'";
+const DISK_SPACE_DEPRECATION_MESSAGE: &str = "Warning: the disk space in the pyrsia CLI config is deprecated and not used by the node. Start the node with '--disk-allocated <DISK_SPACE>' to limit the disk space it uses";
 
 pub fn config_add() -> anyhow::Result<()> {
     let default_config = config::CliConfig {
@@ -52,6 +55,9 @@ pub fn config_add() -> anyhow::Result<()> {
         &default_config.disk_allocated,
         &config::valid_disk_space,
     );
+    if new_cfg.disk_allocated != default_config.disk_allocated {
+        eprintln!("{}", DISK_SPACE_DEPRECATION_MESSAGE);
+    }
 
     config::add_config(new_cfg)
 }
@@ -61,6 +67,9 @@ pub fn config_edit(
     port: Option<String>,
     disk_space: Option<String>,
 ) -> anyhow::Result<()> {
+    if disk_space.is_some() {
+        eprintln!("{}", DISK_SPACE_DEPRECATION_MESSAGE);
+    }
     config::config_edit(host_name, port, disk_space)
 }
 
@@ -119,6 +128,30 @@ pub async fn request_maven_build(gav: &str) {
     handle_request_build_result(build_result);
 }
 
+pub async fn request_cargo_build(package: &str) {
+    let build_result = node::request_cargo_build(RequestCargoBuild {
+        package: package.to_owned(),
+    })
+    .await;
+    handle_request_build_result(build_result);
+}
+
+pub async fn request_npm_build(package: &str) {
+    let build_result = node::request_npm_build(RequestNpmBuild {
+        package: package.to_owned(),
+    })
+    .await;
+    handle_request_build_result(build_result);
+}
+
+pub async fn request_pypi_build(package: &str) {
+    let build_result = node::request_pypi_build(RequestPypiBuild {
+        package: package.to_owned(),
+    })
+    .await;
+    handle_request_build_result(build_result);
+}
+
 pub async fn request_build_status(build_id: &str) {
     let result = node::request_build_status(RequestBuildStatus {
         build_id: String::from(build_id),
@@ -156,6 +189,101 @@ fn handle_request_build_result(result: Result<BuildResultResponse, anyhow::Error
     }
 }
 
+pub async fn export_bundle(docker_images: Vec<String>, maven_gavs: Vec<String>, output: &str) {
+    let packages = docker_images
+        .into_iter()
+        .map(|image| BundlePackage {
+            package_type: PackageType::Docker,
+            package_specific_id: image,
+        })
+        .chain(maven_gavs.into_iter().map(|gav| BundlePackage {
+            package_type: PackageType::Maven2,
+            package_specific_id: gav,
+        }))
+        .collect();
+
+    match node::export_bundle(RequestBundleExport { packages }, Path::new(output)).await {
+        Ok(size) => println!(
+            "Bundle of {} successfully written to {}",
+            config::format_disk_space(size),
+            output
+        ),
+        Err(error) => println!("Bundle export failed with error: {}", error),
+    }
+}
+
+pub async fn import_bundle(input: &str) {
+    match node::import_bundle(Path::new(input)).await {
+        Ok(report) => {
+            println!("Bundle signed by {} successfully imported.", report.signer);
+            println!(
+                "Imported Artifacts:          {}",
+                report.imported_artifacts.len()
+            );
+            println!(
+                "Already Stored Artifacts:    {}",
+                report.existing_artifacts.len()
+            );
+            println!("Imported Blocks:             {}", report.imported_blocks);
+        }
+        Err(error) => println!("Bundle import failed with error: {}", error),
+    }
+}
+
+pub async fn pin_package(docker_image: Option<String>, maven_gav: Option<String>) {
+    let request = pin_request(docker_image, maven_gav);
+    match node::pin_package(request).await {
+        Ok(pin) => println!(
+            "Package {} pinned, its artifacts are being fetched. Run 'pyrsia pin list' to follow the progress.",
+            pin.package_specific_id
+        ),
+        Err(error) => println!("Pin request failed with error: {}", error),
+    }
+}
+
+pub async fn unpin_package(docker_image: Option<String>, maven_gav: Option<String>) {
+    let request = pin_request(docker_image, maven_gav);
+    let package_specific_id = request.package_specific_id.clone();
+    match node::unpin_package(request).await {
+        Ok(_) => println!("Package {} unpinned", package_specific_id),
+        Err(error) => println!("Unpin request failed with error: {}", error),
+    }
+}
+
+pub async fn list_pins() {
+    match node::pins().await {
+        Ok(pins) if pins.is_empty() => println!("No packages are pinned"),
+        Ok(pins) => {
+            for pin in pins {
+                println!("{} ({})", pin.package_specific_id, pin.package_type);
+                println!("  State:                     {}", pin.state);
+                println!("  Artifacts:                 {}", pin.artifacts);
+                println!(
+                    "  Size:                      {}",
+                    config::format_disk_space(pin.size)
+                );
+                if let Some(error) = pin.error {
+                    println!("  Error:                     {}", error);
+                }
+            }
+        }
+        Err(error) => println!("Error: {}. {}", error, CONF_REMINDER_MESSAGE),
+    }
+}
+
+fn pin_request(docker_image: Option<String>, maven_gav: Option<String>) -> RequestPin {
+    match (docker_image, maven_gav) {
+        (Some(image), _) => RequestPin {
+            package_type: PackageType::Docker,
+            package_specific_id: image,
+        },
+        (None, gav) => RequestPin {
+            package_type: PackageType::Maven2,
+            package_specific_id: gav.unwrap_or_default(),
+        },
+    }
+}
+
 pub async fn node_ping() {
     let result = node::ping().await;
     match result {
@@ -173,6 +301,23 @@ pub async fn node_status() {
     match result {
         Ok(resp) => {
             println!("Connected Peers Count:       {}", resp.peers_count);
+            match resp.disk_quota {
+                Some(disk_quota) => println!(
+                    "Disk Usage:                  {} of {}",
+                    config::format_disk_space(resp.disk_usage),
+                    config::format_disk_space(disk_quota)
+                ),
+                None => println!(
+                    "Disk Usage:                  {}",
+                    config::format_disk_space(resp.disk_usage)
+                ),
+            }
+            if resp.compression_savings > 0 {
+                println!(
+                    "Saved By Compression:        {}",
+                    config::format_disk_space(resp.compression_savings)
+                );
+            }
         }
         Err(error) => {
             println!("Error: {}. {}", error, CONF_REMINDER_MESSAGE);
@@ -14,7 +14,7 @@
    limitations under the License.
 */
 
-use clap::{arg, command, crate_version, ArgGroup, ArgMatches, Command};
+use clap::{arg, command, crate_version, ArgAction, ArgGroup, ArgMatches, Command};
 use const_format::formatcp;
 use pyrsia::node_api::model::request::Content;
 
@@ -49,6 +49,24 @@ pub fn cli_parser() -> ArgMatches {
                         .args(&[
                             arg!(--gav <GAV> "The maven GAV (e.g. org.myorg:my-artifact:1.1.0)"),
                         ]),
+                    Command::new("cargo")
+                        .about("Request a new build for a crate")
+                        .arg_required_else_help(true)
+                        .args(&[
+                            arg!(--package <PACKAGE> "The crate and its version (e.g. serde@1.0.152)"),
+                        ]),
+                    Command::new("npm")
+                        .about("Request a new build for an npm package")
+                        .arg_required_else_help(true)
+                        .args(&[
+                            arg!(--package <PACKAGE> "The npm package and its version (e.g. left-pad@1.3.0)"),
+                        ]),
+                    Command::new("pypi")
+                        .about("Request a new build for a python project")
+                        .arg_required_else_help(true)
+                        .args(&[
+                            arg!(--package <PACKAGE> "The python project and its version (e.g. requests==2.28.2)"),
+                        ]),
                     Command::new("status")
                         .about("Request a build status")
                         .arg_required_else_help(true)
@@ -56,6 +74,31 @@ pub fn cli_parser() -> ArgMatches {
                             arg!(--id <ID> "The build ID"),
                         ]),
                 ]),
+            Command::new("bundle")
+                .about("Export packages into an offline bundle, or import one")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommands(vec![
+                    Command::new("export")
+                        .about("Export packages into a signed bundle that nodes without access to the Pyrsia network can import")
+                        .arg_required_else_help(true)
+                        .args(&[
+                            arg!(--docker <IMAGE> "A docker image to export (e.g. alpine:3.15.3), can be repeated")
+                                .required(false)
+                                .action(ArgAction::Append),
+                            arg!(--maven <GAV> "A maven artifact to export (e.g. org.myorg:my-artifact:1.1.0), can be repeated")
+                                .required(false)
+                                .action(ArgAction::Append),
+                            arg!(-o --output <FILE> "The file to write the bundle to"),
+                        ])
+                        .group(ArgGroup::new("packages").args(["docker", "maven"]).required(true).multiple(true)),
+                    Command::new("import")
+                        .about("Import a bundle, after verifying every artifact against the blockchain in the bundle")
+                        .arg_required_else_help(true)
+                        .args(&[
+                            arg!(-i --input <FILE> "The bundle file to import"),
+                        ]),
+                ]),
             Command::new("config")
                 .short_flag('c')
                 .about("Configure Pyrsia")
@@ -66,7 +109,7 @@ pub fn cli_parser() -> ArgMatches {
                         .about("Edits a node configuration")
                         .arg(arg!(-H --host <HOST> "Hostname").required(false))
                         .arg(arg!(-p --port <PORT> "Port number").required(false))
-                        .arg(arg!(-d --diskspace <DISK_SPACE> "Disk space to be allocated to Pyrsia node").required(false))
+                        .arg(arg!(-d --diskspace <DISK_SPACE> "Disk space to be allocated to Pyrsia node (deprecated, start the node with --disk-allocated instead)").required(false))
                         .group(ArgGroup::new("node_config").args(["host", "port", "diskspace"]).required(false).multiple(true))
                 ])
                 .args(&[
@@ -108,6 +151,30 @@ pub fn cli_parser() -> ArgMatches {
             Command::new("list")
                 .short_flag('l')
                 .about("Show a list of connected peers"),
+            Command::new("pin")
+                .about("Keep packages available on the node, or show the pinned packages")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommands(vec![
+                    Command::new("add")
+                        .about("Pin a package, the node fetches its artifacts ahead of time and never evicts them")
+                        .arg_required_else_help(true)
+                        .args(&[
+                            arg!(--docker <IMAGE> "The docker image to pin (e.g. alpine:3.15.3)").required(false),
+                            arg!(--maven <GAV> "The maven artifact to pin (e.g. org.myorg:my-artifact:1.1.0)").required(false),
+                        ])
+                        .group(ArgGroup::new("package").args(["docker", "maven"]).required(true)),
+                    Command::new("list")
+                        .about("Show the pinned packages and their status"),
+                    Command::new("remove")
+                        .about("Unpin a package")
+                        .arg_required_else_help(true)
+                        .args(&[
+                            arg!(--docker <IMAGE> "The docker image to unpin (e.g. alpine:3.15.3)").required(false),
+                            arg!(--maven <GAV> "The maven artifact to unpin (e.g. org.myorg:my-artifact:1.1.0)").required(false),
+                        ])
+                        .group(ArgGroup::new("package").args(["docker", "maven"]).required(true)),
+                ]),
             Command::new("ping").about("Pings configured pyrsia node"),
             Command::new("status")
                 .short_flag('s')
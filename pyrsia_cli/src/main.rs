@@ -81,14 +81,65 @@ async fn main() {
             Some(("maven", maven_matches)) => {
                 request_maven_build(maven_matches.get_one::<String>("gav").unwrap()).await;
             }
+            Some(("cargo", cargo_matches)) => {
+                request_cargo_build(cargo_matches.get_one::<String>("package").unwrap()).await;
+            }
+            Some(("npm", npm_matches)) => {
+                request_npm_build(npm_matches.get_one::<String>("package").unwrap()).await;
+            }
+            Some(("pypi", pypi_matches)) => {
+                request_pypi_build(pypi_matches.get_one::<String>("package").unwrap()).await;
+            }
             Some(("status", status_matches)) => {
                 request_build_status(status_matches.get_one::<String>("id").unwrap()).await;
             }
             _ => {}
         },
+        Some(("bundle", bundle_matches)) => match bundle_matches.subcommand() {
+            Some(("export", export_matches)) => {
+                export_bundle(
+                    export_matches
+                        .get_many::<String>("docker")
+                        .unwrap_or_default()
+                        .cloned()
+                        .collect(),
+                    export_matches
+                        .get_many::<String>("maven")
+                        .unwrap_or_default()
+                        .cloned()
+                        .collect(),
+                    export_matches.get_one::<String>("output").unwrap(),
+                )
+                .await;
+            }
+            Some(("import", import_matches)) => {
+                import_bundle(import_matches.get_one::<String>("input").unwrap()).await;
+            }
+            _ => {}
+        },
         Some(("list", _config_matches)) => {
             node_list().await;
         }
+        Some(("pin", pin_matches)) => match pin_matches.subcommand() {
+            Some(("add", add_matches)) => {
+                pin_package(
+                    add_matches.get_one::<String>("docker").cloned(),
+                    add_matches.get_one::<String>("maven").cloned(),
+                )
+                .await;
+            }
+            Some(("list", _list_matches)) => {
+                list_pins().await;
+            }
+            Some(("remove", remove_matches)) => {
+                unpin_package(
+                    remove_matches.get_one::<String>("docker").cloned(),
+                    remove_matches.get_one::<String>("maven").cloned(),
+                )
+                .await;
+            }
+            _ => {}
+        },
         Some(("ping", _config_matches)) => {
             node_ping().await;
         }
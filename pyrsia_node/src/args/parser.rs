@@ -16,14 +16,21 @@
 
 use clap::Parser;
 use libp2p::Multiaddr;
+use pyrsia::cli_commands::config::disk_space_to_bytes;
 
+const DEFAULT_COMPRESSION_LEVEL: &str = "0";
+const DEFAULT_DISK_ALLOCATED: &str = "10 GB";
+const DEFAULT_GC_INTERVAL: &str = "86400";
 const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_IDLE_METRIC_TIMEOUT: &str = "2000";
 const DEFAULT_LISTEN_ADDRESS: &str = "/ip4/0.0.0.0/tcp/0";
 const DEFAULT_MAX_PROVIDED_KEYS: &str = "32768";
 const DEFAULT_MAPPING_SERVICE_ENDPOINT: &str =
     "https://raw.githubusercontent.com/pyrsia/pyrsia-mappings/main/";
+const DEFAULT_PIN_INTERVAL: &str = "3600";
 const DEFAULT_PIPELINE_SERVICE_ENDPOINT: &str = "http://localhost:8080";
 const DEFAULT_PORT: &str = "7888";
+const DEFAULT_SCRUB_INTERVAL: &str = "86400";
 const DEFAULT_BOOTSTRAP_URL: &str = "http://boot.pyrsia.link/status";
 
 /// Application to connect to and participate in the Pyrsia network
@@ -53,6 +60,32 @@ pub struct PyrsiaNodeArgs {
     pub listen_only: bool,
     #[clap(long, short = 'B', default_value = DEFAULT_BOOTSTRAP_URL)]
     pub bootstrap_url: String,
+    /// The time in milliseconds that peers get to answer a request for their idle metric.
+    #[clap(long, default_value = DEFAULT_IDLE_METRIC_TIMEOUT)]
+    pub idle_metric_timeout: u64,
+    /// The disk space that may be used to store artifacts, e.g. "10 GB". Least recently
+    /// used artifacts are evicted when this quota is exceeded.
+    #[clap(long, default_value = DEFAULT_DISK_ALLOCATED, value_parser = disk_space_to_bytes)]
+    pub disk_allocated: u64,
+    /// The zstd level (1-22) to compress stored artifacts with. Artifacts that are
+    /// already compressed, like gzipped Docker layers, are always stored as is. Set
+    /// to 0 to disable compression.
+    #[clap(long, default_value = DEFAULT_COMPRESSION_LEVEL, value_parser = clap::value_parser!(i32).range(0..=22))]
+    pub compression_level: i32,
+    /// The time in seconds between two scrubs of the stored artifacts, which finds and
+    /// repairs artifacts that were corrupted on disk. Set to 0 to disable scrubbing.
+    #[clap(long, default_value = DEFAULT_SCRUB_INTERVAL)]
+    pub scrub_interval: u64,
+    /// The time in seconds between two garbage collections, which remove the stored artifacts
+    /// that are no longer in the transparency log and the build directories that were left
+    /// behind. Set to 0 to disable garbage collection.
+    #[clap(long, default_value = DEFAULT_GC_INTERVAL)]
+    pub gc_interval: u64,
+    /// The time in seconds between two refreshes of the pinned packages, which fetch the
+    /// artifacts of the pinned packages that aren't stored yet and keep providing them on the
+    /// network. Set to 0 to only fetch pinned packages when they are pinned.
+    #[clap(long, default_value = DEFAULT_PIN_INTERVAL)]
+    pub pin_interval: u64,
     /// The maximum number of keys that can be provided on the network by this Pyrsia Node.
     #[clap(long, default_value = DEFAULT_MAX_PROVIDED_KEYS)]
     pub max_provided_keys: usize,
@@ -23,19 +23,23 @@ use libp2p::identity::Keypair;
 use libp2p::PeerId;
 use network::handlers;
 use pyrsia::artifact_service::service::ArtifactService;
-use pyrsia::artifact_service::storage::ARTIFACTS_DIR;
+use pyrsia::artifact_service::storage::{ARTIFACTS_DIR, PARTIAL_ARTIFACT_MAX_AGE};
 use pyrsia::blockchain_service::event::{BlockchainEventClient, BlockchainEventLoop};
 use pyrsia::blockchain_service::service::BlockchainService;
 use pyrsia::build_service::event::{BuildEventClient, BuildEventLoop};
 use pyrsia::build_service::service::BuildService;
 use pyrsia::docker::error_util::*;
 use pyrsia::docker::v2::routes::make_docker_routes;
+use pyrsia::go::goproxy::routes::make_goproxy_routes;
 use pyrsia::java::maven2::routes::make_maven_routes;
+use pyrsia::javascript::npm::routes::make_npm_routes;
 use pyrsia::logging::*;
 use pyrsia::network::client::Client;
 use pyrsia::network::p2p;
 use pyrsia::node_api::routes::make_node_routes;
 use pyrsia::peer_metrics::metrics::PeerMetrics;
+use pyrsia::python::pypi::routes::make_pypi_routes;
+use pyrsia::rust::cargo::routes::make_cargo_routes;
 use pyrsia::transparency_log::log::TransparencyLogService;
 use pyrsia::util::env_util::read_var;
 use pyrsia::util::keypair_util::{self, KEYPAIR_FILENAME};
@@ -44,8 +48,10 @@ use pyrsia::verification_service::service::VerificationService;
 use clap::Parser;
 use log::{debug, info, warn};
 use std::error::Error;
+use std::future::Future;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 use warp::Filter;
@@ -62,6 +68,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
     debug!("Create p2p components");
     let (p2p_client, local_keypair, mut p2p_events, event_loop) =
         p2p::setup_libp2p_swarm(args.max_provided_keys)?;
+    let p2p_client =
+        p2p_client.with_idle_metric_timeout(Duration::from_millis(args.idle_metric_timeout));
 
     debug!("Start p2p event loop");
     tokio::spawn(event_loop.run());
@@ -82,21 +90,61 @@ async fn main() -> Result<(), Box<dyn Error>> {
     )
     .await;
 
+    debug!("Enforce disk quota");
+    artifact_service.enforce_disk_quota(None).await?;
+
     debug!("Provide local artifacts");
     artifact_service.clone().provide_local_artifacts().await?;
 
+    debug!("Start scrubbing local artifacts");
+    let scrubbed_service = artifact_service.clone();
+    spawn_periodic(
+        "Scrubbing local artifacts",
+        args.scrub_interval,
+        false,
+        move || {
+            let artifact_service = scrubbed_service.clone();
+            async move { artifact_service.scrub_artifacts().await }
+        },
+    );
+
+    debug!("Start collecting garbage");
+    let collected_service = artifact_service.clone();
+    spawn_periodic("Collecting garbage", args.gc_interval, false, move || {
+        let artifact_service = collected_service.clone();
+        async move { artifact_service.collect_garbage(false).await }
+    });
+
+    // the first refresh happens right away, so pinned packages are fetched
+    // and provided again after a restart
+    debug!("Start refreshing pinned packages");
+    let refreshed_service = artifact_service.clone();
+    spawn_periodic(
+        "Refreshing pinned packages",
+        args.pin_interval,
+        true,
+        move || {
+            let artifact_service = refreshed_service.clone();
+            async move { artifact_service.refresh_pins().await }
+        },
+    );
+
     debug!("Listen for p2p events");
     loop {
         if let Some(event) = p2p_events.next().await {
             match event {
-                // Reply with the content of the artifact on incoming requests.
+                // Reply with a chunk of the artifact on incoming requests.
                 pyrsia::network::event_loop::PyrsiaEvent::RequestArtifact {
                     artifact_id,
+                    offset,
+                    length,
                     channel,
                 } => {
                     if let Err(error) = handlers::handle_request_artifact(
                         artifact_service.clone(),
                         &artifact_id,
+                        offset,
+                        length,
                         channel,
                     )
                     .await
@@ -219,6 +267,36 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
 }
 
+// Run `task` every `period` seconds in the background, the first time right
+// away when `run_immediately` is set and after a period otherwise. A period of
+// 0 disables the task. Failures are logged with the `name` of the task.
+fn spawn_periodic<F, Fut, T>(name: &'static str, period: u64, run_immediately: bool, task: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<T>> + Send,
+{
+    if period == 0 {
+        info!("{} is disabled", name);
+        return;
+    }
+
+    let period = Duration::from_secs(period);
+    let start = if run_immediately {
+        tokio::time::Instant::now()
+    } else {
+        tokio::time::Instant::now() + period
+    };
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval_at(start, period);
+        loop {
+            interval.tick().await;
+            if let Err(error) = task().await {
+                warn!("{} failed: {:?}", name, error);
+            }
+        }
+    });
+}
+
 async fn establish_connection_with_p2p_network(
     p2p_client: Client,
     artifact_service: ArtifactService,
@@ -371,7 +449,17 @@ async fn setup_pyrsia_services(
         transparency_log_service.clone(),
         build_event_client.clone(),
         p2p_client.clone(),
+        args.disk_allocated,
+        args.compression_level,
     )?;
+    let migrated = artifact_service
+        .artifact_storage
+        .migrate_legacy_artifacts()
+        .await?;
+    debug!("Migrated {} artifacts of older releases", migrated);
+    artifact_service
+        .retain_artifacts_of_older_releases()
+        .await?;
 
     debug!("Create build service");
     let build_service = setup_build_service(
@@ -414,13 +502,22 @@ fn setup_artifact_service(
     transparency_log_service: TransparencyLogService,
     build_event_client: BuildEventClient,
     p2p_client: Client,
+    disk_quota: u64,
+    compression_level: i32,
 ) -> Result<ArtifactService> {
     let artifact_service = ArtifactService::new(
         artifact_path,
         transparency_log_service,
         build_event_client,
         p2p_client,
-    )?;
+    )?
+    .with_disk_quota(disk_quota)
+    .with_compression_level(compression_level);
+
+    let removed = artifact_service
+        .artifact_storage
+        .remove_stale_files(PARTIAL_ARTIFACT_MAX_AGE)?;
+    debug!("Removed {} stale artifact files", removed);
 
     Ok(artifact_service)
 }
@@ -459,8 +556,18 @@ fn setup_http(args: &PyrsiaNodeArgs, artifact_service: ArtifactService, p2p_clie
     debug!("Setup HTTP routing");
     let docker_routes = make_docker_routes(artifact_service.clone());
     let maven_routes = make_maven_routes(artifact_service.clone());
+    let npm_routes = make_npm_routes(artifact_service.clone());
+    let pypi_routes = make_pypi_routes(artifact_service.clone());
+    let cargo_routes = make_cargo_routes(artifact_service.clone());
+    let goproxy_routes = make_goproxy_routes(artifact_service.clone());
     let node_api_routes = make_node_routes(artifact_service, p2p_client);
-    let all_routes = docker_routes.or(maven_routes).or(node_api_routes);
+    let all_routes = docker_routes
+        .or(maven_routes)
+        .or(npm_routes)
+        .or(pypi_routes)
+        .or(cargo_routes)
+        .or(goproxy_routes)
+        .or(node_api_routes);
 
     debug!("Setup HTTP server");
     let (addr, server) = warp::serve(
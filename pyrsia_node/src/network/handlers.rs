@@ -25,11 +25,11 @@ use pyrsia::artifact_service::service::ArtifactService;
 use pyrsia::blockchain_service::event::BlockchainEventClient;
 use pyrsia::blockchain_service::service::BlockchainCommand;
 use pyrsia::build_service::event::BuildEventClient;
-use pyrsia::network::artifact_protocol::ArtifactResponse;
+use pyrsia::network::artifact_protocol::{ArtifactResponse, MAX_CHUNK_SIZE};
 use pyrsia::network::build_protocol::BuildResponse;
 use pyrsia::network::build_status_protocol::BuildStatusResponse;
 use pyrsia::network::client::Client;
-use pyrsia::network::idle_metric_protocol::{IdleMetricResponse, PeerMetrics};
+use pyrsia::network::idle_metric_protocol::IdleMetricResponse;
 use pyrsia::peer_metrics::metrics;
 use pyrsia_blockchain_network::structures::block::Block;
 use pyrsia_blockchain_network::structures::header::Ordinal;
@@ -56,20 +56,36 @@ pub async fn probe_other_peer(p2p_client: Client, to_probe: &Multiaddr) -> anyho
     }
 }
 
-/// Respond to a RequestArtifact event by getting the artifact
-/// based on the provided artifact id.
+/// Respond to a RequestArtifact event by reading the requested
+/// chunk of the artifact based on the provided artifact id.
 pub async fn handle_request_artifact(
-    mut artifact_service: ArtifactService,
+    artifact_service: ArtifactService,
     artifact_id: &str,
+    offset: u64,
+    length: u64,
     channel: ResponseChannel<ArtifactResponse>,
 ) -> anyhow::Result<()> {
-    debug!("Handling request artifact: {:?}", artifact_id);
+    debug!(
+        "Handling request artifact: {:?} offset={} length={}",
+        artifact_id, offset, length
+    );
+    let _transfer = metrics::start_transfer();
 
-    let content = artifact_service.get_artifact_locally(artifact_id).await?;
+    let (total_size, data) = artifact_service
+        .artifact_storage
+        .read_artifact_chunk(artifact_id, offset, length.min(MAX_CHUNK_SIZE))
+        .await?;
 
     artifact_service
         .p2p_client
-        .respond_artifact(content, channel)
+        .respond_artifact(
+            ArtifactResponse {
+                total_size,
+                offset,
+                data,
+            },
+            channel,
+        )
         .await
 }
 
@@ -120,10 +136,7 @@ pub async fn handle_request_idle_metric(
     peer_metrics: &mut metrics::PeerMetrics,
     channel: ResponseChannel<IdleMetricResponse>,
 ) -> anyhow::Result<()> {
-    let metric = peer_metrics.get_quality_metric();
-    let peer_metrics = PeerMetrics {
-        idle_metric: metric.to_le_bytes(),
-    };
+    let peer_metrics = peer_metrics.get_peer_metrics();
     p2p_client.respond_idle_metric(peer_metrics, channel).await
 }
 
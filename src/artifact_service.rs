@@ -14,6 +14,9 @@
    limitations under the License.
 */
 
+pub mod bundle;
+pub mod download_lock;
 pub mod model;
+pub mod pin;
 pub mod service;
 pub mod storage;
@@ -0,0 +1,602 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The file format of offline bundles, which carry packages into networks that
+//! can't reach the rest of the pyrsia network.
+//!
+//! A bundle starts with [`BUNDLE_MAGIC`], followed by the length of the signed
+//! manifest as a little endian `u64` and the signed manifest itself. The
+//! manifest holds the transparency logs of the packages and the blocks of the
+//! blockchain up to the last block that published one of them. The content of
+//! the artifacts follows the manifest, in the order in which the manifest lists
+//! them. The signature only covers the manifest, the artifacts are verified by
+//! the hashes in their transparency logs.
+//!
+//! A bundle is self-signed, so a node only imports bundles that come from a
+//! trusted source, see [`BundleTrust`].
+
+use super::model::BundlePackage;
+use crate::transparency_log::log::{Operation, TransparencyLog};
+use crate::util::env_util::read_var;
+use anyhow::{bail, Context};
+use libp2p::identity;
+use libp2p::PeerId;
+use pyrsia_blockchain_network::crypto::hash_algorithm::HashDigest;
+use pyrsia_blockchain_network::signature::Signature;
+use pyrsia_blockchain_network::structures::block::{Block, PublicKey};
+use pyrsia_blockchain_network::structures::header::{Address, Ordinal};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read};
+use std::str::FromStr;
+
+/// The bytes every bundle starts with.
+pub const BUNDLE_MAGIC: &[u8; 8] = b"PYRSIABN";
+
+/// The version of the manifest format.
+pub const BUNDLE_VERSION: u32 = 1;
+
+// Bundles with a larger manifest are rejected before the manifest is read.
+const MAX_SIGNED_MANIFEST_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The description of the content of a bundle.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BundleManifest {
+    pub version: u32,
+    /// Seconds since the unix epoch at which the bundle was exported.
+    pub created_at: u64,
+    pub packages: Vec<BundlePackage>,
+    /// The blocks of the blockchain, starting with the genesis block.
+    pub blocks: Vec<Block>,
+    /// The AddArtifact transparency logs of the packages.
+    pub transparency_logs: Vec<TransparencyLog>,
+    /// The artifacts in the order in which their content follows the manifest.
+    pub artifacts: Vec<BundledArtifact>,
+}
+
+/// An artifact of which the content is included in a bundle.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BundledArtifact {
+    pub artifact_id: String,
+    pub artifact_hash: String,
+    pub size: u64,
+}
+
+/// The nodes and the blockchain that the bundles imported by a node must come
+/// from. The authorized nodes of the local transparency log and the local node
+/// itself are trusted as well.
+#[derive(Clone, Debug, Default)]
+pub struct BundleTrust {
+    /// The nodes that may sign bundles and the blocks in them.
+    pub trusted_signers: HashSet<PeerId>,
+    /// The hex encoded hash of the genesis block that the blockchain of a
+    /// bundle must start from when the local blockchain is empty. Otherwise it
+    /// must start from the genesis block of the local blockchain.
+    pub genesis_hash: Option<String>,
+}
+
+impl BundleTrust {
+    /// Read the trusted signers from `PYRSIA_BUNDLE_TRUSTED_SIGNERS`, a comma
+    /// separated list of peer ids, and the genesis hash from
+    /// `PYRSIA_BUNDLE_GENESIS_HASH`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let trusted_signers = read_var("PYRSIA_BUNDLE_TRUSTED_SIGNERS", "")
+            .split(',')
+            .map(str::trim)
+            .filter(|peer_id| !peer_id.is_empty())
+            .map(|peer_id| {
+                PeerId::from_str(peer_id)
+                    .with_context(|| format!("Invalid trusted bundle signer {}", peer_id))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let genesis_hash = read_var("PYRSIA_BUNDLE_GENESIS_HASH", "");
+        Ok(BundleTrust {
+            trusted_signers,
+            genesis_hash: (!genesis_hash.is_empty()).then(|| genesis_hash.to_lowercase()),
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct SignedManifest {
+    manifest: Vec<u8>,
+    public_key: PublicKey,
+    signature: Signature,
+}
+
+/// Serialize the manifest into the bytes that are signed.
+pub fn serialize_manifest(manifest: &BundleManifest) -> anyhow::Result<Vec<u8>> {
+    Ok(bincode::serialize(manifest)?)
+}
+
+/// Create the start of a bundle, up to the content of its first artifact, from
+/// the serialized manifest and its signature.
+pub fn encode_header(
+    manifest: Vec<u8>,
+    public_key: PublicKey,
+    signature: Signature,
+) -> anyhow::Result<Vec<u8>> {
+    let signed_manifest = bincode::serialize(&SignedManifest {
+        manifest,
+        public_key,
+        signature,
+    })?;
+
+    let mut header = Vec::with_capacity(BUNDLE_MAGIC.len() + 8 + signed_manifest.len());
+    header.extend_from_slice(BUNDLE_MAGIC);
+    header.extend_from_slice(&(signed_manifest.len() as u64).to_le_bytes());
+    header.extend_from_slice(&signed_manifest);
+    Ok(header)
+}
+
+/// Read the manifest from the start of a bundle and verify its signature.
+/// Returns the manifest together with the peer id of the node that signed it.
+/// The reader is left at the content of the first artifact.
+pub fn read_manifest(reader: &mut impl Read) -> anyhow::Result<(BundleManifest, PeerId)> {
+    let mut magic = [0; BUNDLE_MAGIC.len()];
+    reader
+        .read_exact(&mut magic)
+        .context("The bundle is too short")?;
+    if &magic != BUNDLE_MAGIC {
+        bail!("The file is not a pyrsia bundle");
+    }
+
+    let mut length = [0; 8];
+    reader
+        .read_exact(&mut length)
+        .context("The bundle is too short")?;
+    let length = u64::from_le_bytes(length);
+    if length > MAX_SIGNED_MANIFEST_SIZE {
+        bail!("The manifest of the bundle is too large: {} bytes", length);
+    }
+
+    let mut signed_manifest = vec![0; length as usize];
+    reader
+        .read_exact(&mut signed_manifest)
+        .context("The manifest of the bundle is truncated")?;
+    let signed_manifest: SignedManifest =
+        bincode::deserialize(&signed_manifest).context("The manifest of the bundle is invalid")?;
+
+    let public_key = identity::ed25519::PublicKey::decode(&signed_manifest.public_key)
+        .context("The public key of the bundle is invalid")?;
+    if !public_key.verify(
+        &signed_manifest.manifest,
+        &signed_manifest.signature.to_bytes(),
+    ) {
+        bail!("The signature of the bundle is invalid");
+    }
+    let signer = PeerId::from_public_key(&identity::PublicKey::Ed25519(public_key));
+
+    let manifest: BundleManifest = bincode::deserialize(&signed_manifest.manifest)
+        .context("The manifest of the bundle is invalid")?;
+    if manifest.version != BUNDLE_VERSION {
+        bail!(
+            "The bundle has version {}, only version {} is supported",
+            manifest.version,
+            BUNDLE_VERSION
+        );
+    }
+
+    Ok((manifest, signer))
+}
+
+/// Find the transparency logs that were published in the blocks, by their id.
+pub fn find_logs_on_chain(blocks: &[Block]) -> HashMap<String, (Ordinal, TransparencyLog)> {
+    let mut logs = HashMap::new();
+    for block in blocks {
+        for payload in block.fetch_payload() {
+            if let Ok(log) = serde_json::from_slice::<TransparencyLog>(&payload) {
+                logs.insert(log.id.clone(), (block.header.ordinal, log));
+            }
+        }
+    }
+    logs
+}
+
+/// Verify that the blocks form an unbroken chain from the genesis block on,
+/// in which every block is signed and holds the transactions it was made of.
+pub fn verify_chain(blocks: &[Block]) -> anyhow::Result<()> {
+    let mut parent_hash = HashDigest::new(b"");
+    for (ordinal, block) in blocks.iter().enumerate() {
+        if block.header.ordinal != ordinal as Ordinal {
+            bail!(
+                "Block {} is out of order, expected block {}",
+                block.header.ordinal,
+                ordinal
+            );
+        }
+        if !block.header.verify_hash() {
+            bail!("The header of block {} was modified", ordinal);
+        }
+        if block.header.parent_hash != parent_hash {
+            bail!("Block {} doesn't refer to its parent block", ordinal);
+        }
+        if block.header.transactions_hash
+            != HashDigest::new(&bincode::serialize(&block.transactions)?)
+        {
+            bail!("The transactions of block {} were modified", ordinal);
+        }
+        if !block.verify() {
+            bail!("The signature of block {} is invalid", ordinal);
+        }
+        parent_hash = block.header.hash();
+    }
+    Ok(())
+}
+
+/// The hex encoded hash of the block.
+pub fn block_hash(block: &Block) -> String {
+    hex::encode(block.header.hash().to_slice())
+}
+
+// The peer id of the node that signed the block, which must be its committer.
+fn block_signer(block: &Block) -> anyhow::Result<PeerId> {
+    let public_key = identity::ed25519::PublicKey::decode(&block.signature().public_key())
+        .with_context(|| {
+            format!(
+                "The public key of block {} is invalid",
+                block.header.ordinal
+            )
+        })?;
+    let public_key = identity::PublicKey::Ed25519(public_key);
+    if block.header.committer != Address::from(public_key.clone()) {
+        bail!(
+            "Block {} was not signed by its committer",
+            block.header.ordinal
+        );
+    }
+    Ok(PeerId::from_public_key(&public_key))
+}
+
+/// Verify that a bundle comes from a trusted source. Its blockchain must start
+/// from the genesis block with `genesis_hash`, and the manifest as well as the
+/// blocks that the local blockchain doesn't have yet, from `first_new_block`
+/// on, must be signed by one of the `trusted_signers`.
+pub fn verify_trust(
+    manifest: &BundleManifest,
+    signer: &PeerId,
+    genesis_hash: &str,
+    first_new_block: usize,
+    trusted_signers: &HashSet<PeerId>,
+) -> anyhow::Result<()> {
+    match manifest.blocks.first() {
+        Some(genesis) if block_hash(genesis) == genesis_hash.to_lowercase() => {}
+        Some(_) => {
+            bail!("The blockchain of the bundle doesn't start from the trusted genesis block")
+        }
+        None => bail!("The bundle has no blockchain"),
+    }
+    if !trusted_signers.contains(signer) {
+        bail!("The bundle is signed by untrusted node {}", signer);
+    }
+    for block in manifest.blocks.iter().skip(first_new_block) {
+        let block_signer = block_signer(block)?;
+        if !trusted_signers.contains(&block_signer) {
+            bail!(
+                "Block {} is signed by untrusted node {}",
+                block.header.ordinal,
+                block_signer
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Verify the manifest of a bundle: the blocks must form a valid chain, every
+/// transparency log must have been published on that chain, and every artifact
+/// must have the hash of the transparency log that added it.
+pub fn verify_manifest(manifest: &BundleManifest) -> anyhow::Result<()> {
+    verify_chain(&manifest.blocks)?;
+
+    let logs_on_chain = find_logs_on_chain(&manifest.blocks);
+    for log in &manifest.transparency_logs {
+        match logs_on_chain.get(&log.id) {
+            Some((_, log_on_chain)) if log_on_chain == log => {}
+            Some(_) => bail!("Transparency log {} doesn't match the blockchain", log.id),
+            None => bail!("Transparency log {} is not on the blockchain", log.id),
+        }
+    }
+
+    for artifact in &manifest.artifacts {
+        let verified = manifest.transparency_logs.iter().any(|log| {
+            log.operation == Operation::AddArtifact
+                && log.artifact_id == artifact.artifact_id
+                && log
+                    .artifact_hash
+                    .eq_ignore_ascii_case(&artifact.artifact_hash)
+        });
+        if !verified {
+            bail!(
+                "Artifact {} has no matching transparency log",
+                artifact.artifact_id
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Verify that the content of every artifact, which follows the manifest in the
+/// reader, matches the hash of the artifact.
+pub fn verify_artifacts(
+    reader: &mut impl Read,
+    artifacts: &[BundledArtifact],
+) -> anyhow::Result<()> {
+    for artifact in artifacts {
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut reader.by_ref().take(artifact.size), &mut hasher)?;
+        if size != artifact.size {
+            bail!(
+                "The content of artifact {} is truncated",
+                artifact.artifact_id
+            );
+        }
+        if hex::encode(hasher.finalize()) != artifact.artifact_hash.to_lowercase() {
+            bail!(
+                "The content of artifact {} doesn't match its hash",
+                artifact.artifact_id
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::artifact_service::model::PackageType;
+    use crate::transparency_log::log::AddArtifactRequest;
+    use pyrsia_blockchain_network::structures::header::Address;
+    use pyrsia_blockchain_network::structures::transaction::{Transaction, TransactionType};
+
+    fn create_log(package_specific_artifact_id: &str) -> TransparencyLog {
+        TransparencyLog::from(AddArtifactRequest {
+            package_type: PackageType::Docker,
+            package_specific_id: "library/alpine:3.15".to_owned(),
+            num_artifacts: 1,
+            package_specific_artifact_id: package_specific_artifact_id.to_owned(),
+            artifact_hash: "0123456789abcdef".to_owned(),
+        })
+    }
+
+    fn create_chain(keypair: &identity::ed25519::Keypair, logs: &[&TransparencyLog]) -> Vec<Block> {
+        let submitter = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut blocks = vec![Block::new(HashDigest::new(b""), 0, vec![], keypair)];
+        for log in logs {
+            let transaction = Transaction::new(
+                TransactionType::Create,
+                submitter,
+                serde_json::to_vec(log).unwrap(),
+                keypair,
+            );
+            let parent = blocks.last().unwrap();
+            let block = Block::new(
+                parent.header.hash(),
+                parent.header.ordinal + 1,
+                vec![transaction],
+                keypair,
+            );
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    fn create_manifest(keypair: &identity::ed25519::Keypair) -> BundleManifest {
+        let log = create_log("sha256:1234");
+        BundleManifest {
+            version: BUNDLE_VERSION,
+            created_at: 1,
+            packages: vec![BundlePackage {
+                package_type: PackageType::Docker,
+                package_specific_id: "library/alpine:3.15".to_owned(),
+            }],
+            blocks: create_chain(keypair, &[&log]),
+            artifacts: vec![BundledArtifact {
+                artifact_id: log.artifact_id.clone(),
+                artifact_hash: log.artifact_hash.clone(),
+                size: 4,
+            }],
+            transparency_logs: vec![log],
+        }
+    }
+
+    fn sign(manifest: &BundleManifest, keypair: &identity::ed25519::Keypair) -> Vec<u8> {
+        let manifest = serialize_manifest(manifest).unwrap();
+        let signature = Signature::new(&manifest, keypair);
+        encode_header(manifest, keypair.public().encode(), signature).unwrap()
+    }
+
+    #[test]
+    fn read_a_signed_manifest() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let manifest = create_manifest(&keypair);
+        let mut bundle = sign(&manifest, &keypair);
+        bundle.extend_from_slice(b"data");
+
+        let mut reader = bundle.as_slice();
+        let (read_manifest, signer) = read_manifest(&mut reader).unwrap();
+        assert_eq!(
+            signer,
+            PeerId::from_public_key(&identity::PublicKey::Ed25519(keypair.public()))
+        );
+        assert_eq!(read_manifest.artifacts, manifest.artifacts);
+        assert_eq!(read_manifest.blocks, manifest.blocks);
+        assert_eq!(reader, b"data");
+        verify_manifest(&read_manifest).unwrap();
+    }
+
+    #[test]
+    fn reject_a_modified_manifest() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let manifest = create_manifest(&keypair);
+        let mut bundle = sign(&manifest, &keypair);
+        let last = bundle.len() - 1;
+        bundle[last] ^= 1;
+
+        let error = read_manifest(&mut bundle.as_slice()).unwrap_err();
+        assert_eq!(error.to_string(), "The signature of the bundle is invalid");
+
+        let error = read_manifest(&mut b"NOBUNDLE".as_slice()).unwrap_err();
+        assert_eq!(error.to_string(), "The file is not a pyrsia bundle");
+    }
+
+    #[test]
+    fn reject_a_manifest_that_doesnt_match_its_chain() {
+        let keypair = identity::ed25519::Keypair::generate();
+
+        let mut manifest = create_manifest(&keypair);
+        manifest.transparency_logs[0].artifact_hash = "fedcba9876543210".to_owned();
+        manifest.artifacts[0].artifact_hash = "fedcba9876543210".to_owned();
+        let error = verify_manifest(&manifest).unwrap_err();
+        assert!(error.to_string().contains("doesn't match the blockchain"));
+
+        let mut manifest = create_manifest(&keypair);
+        manifest.artifacts[0].artifact_hash = "fedcba9876543210".to_owned();
+        let error = verify_manifest(&manifest).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("has no matching transparency log"));
+
+        let mut manifest = create_manifest(&keypair);
+        manifest.transparency_logs.push(create_log("sha256:5678"));
+        let error = verify_manifest(&manifest).unwrap_err();
+        assert!(error.to_string().contains("is not on the blockchain"));
+    }
+
+    #[test]
+    fn verify_the_content_of_artifacts() {
+        let artifacts = vec![
+            BundledArtifact {
+                artifact_id: "first".to_owned(),
+                artifact_hash: hex::encode(Sha256::digest("JAR")).to_uppercase(),
+                size: 3,
+            },
+            BundledArtifact {
+                artifact_id: "second".to_owned(),
+                artifact_hash: hex::encode(Sha256::digest("POM")),
+                size: 3,
+            },
+        ];
+        verify_artifacts(&mut b"JARPOM".as_slice(), &artifacts).unwrap();
+
+        let error = verify_artifacts(&mut b"JARPUM".as_slice(), &artifacts).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The content of artifact second doesn't match its hash"
+        );
+
+        let error = verify_artifacts(&mut b"JARPO".as_slice(), &artifacts).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The content of artifact second is truncated"
+        );
+    }
+
+    #[test]
+    fn reject_a_bundle_from_an_untrusted_source() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let peer_id = PeerId::from_public_key(&identity::PublicKey::Ed25519(keypair.public()));
+        let manifest = create_manifest(&keypair);
+        let genesis_hash = block_hash(&manifest.blocks[0]);
+        let trusted_signers = HashSet::from([peer_id]);
+
+        verify_trust(&manifest, &peer_id, &genesis_hash, 0, &trusted_signers).unwrap();
+
+        let error =
+            verify_trust(&manifest, &peer_id, &genesis_hash, 0, &HashSet::new()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("The bundle is signed by untrusted node {}", peer_id)
+        );
+
+        let other_manifest = create_manifest(&identity::ed25519::Keypair::generate());
+        let error = verify_trust(
+            &manifest,
+            &peer_id,
+            &block_hash(&other_manifest.blocks[0]),
+            0,
+            &trusted_signers,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The blockchain of the bundle doesn't start from the trusted genesis block"
+        );
+
+        // a trusted node may sign a bundle with blocks of untrusted nodes, as long
+        // as the local blockchain already has them
+        let other_genesis_hash = block_hash(&other_manifest.blocks[0]);
+        let error = verify_trust(
+            &other_manifest,
+            &peer_id,
+            &other_genesis_hash,
+            1,
+            &trusted_signers,
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Block 1 is signed by untrusted node"));
+        verify_trust(
+            &other_manifest,
+            &peer_id,
+            &other_genesis_hash,
+            2,
+            &trusted_signers,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reject_a_broken_chain() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let log = create_log("sha256:1234");
+        let other_log = create_log("sha256:5678");
+
+        let mut blocks = create_chain(&keypair, &[&log, &other_log]);
+        verify_chain(&blocks).unwrap();
+        blocks.remove(1);
+        assert!(verify_chain(&blocks).is_err());
+
+        let mut blocks = create_chain(&keypair, &[&log]);
+        blocks[1].transactions = create_chain(&keypair, &[&other_log])[1]
+            .transactions
+            .clone();
+        let error = verify_chain(&blocks).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The transactions of block 1 were modified"
+        );
+
+        let mut blocks = create_chain(&keypair, &[&log]);
+        blocks[1].header.timestamp += 1;
+        let error = verify_chain(&blocks).unwrap_err();
+        assert_eq!(error.to_string(), "The header of block 1 was modified");
+
+        let other_keypair = identity::ed25519::Keypair::generate();
+        let mut blocks = create_chain(&keypair, &[&log]);
+        let forged_block = Block::new(
+            blocks[0].header.hash(),
+            1,
+            blocks[1].transactions.clone(),
+            &other_keypair,
+        );
+        blocks[1].header = forged_block.header;
+        let error = verify_chain(&blocks).unwrap_err();
+        assert_eq!(error.to_string(), "The signature of block 1 is invalid");
+    }
+}
@@ -0,0 +1,118 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Locks that serialize the downloads of an artifact. All downloads of an
+//! artifact write to the same partial file, so only one download of an
+//! artifact may run at a time.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+type ArtifactLock = Arc<tokio::sync::Mutex<()>>;
+
+#[derive(Clone, Default)]
+pub struct DownloadLocks {
+    locks: Arc<Mutex<HashMap<String, ArtifactLock>>>,
+}
+
+/// Holds the download lock of an artifact until it is dropped.
+pub struct DownloadGuard {
+    locks: DownloadLocks,
+    artifact_id: String,
+    lock: ArtifactLock,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl DownloadLocks {
+    /// Acquire the download lock of the artifact, waiting for a download of
+    /// the same artifact that is in progress. The returned flag is true when
+    /// another download had to be waited for, in which case that download may
+    /// already have stored the artifact.
+    pub async fn lock(&self, artifact_id: &str) -> (DownloadGuard, bool) {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(artifact_id.to_owned())
+            .or_default()
+            .clone();
+
+        let (guard, waited) = match lock.clone().try_lock_owned() {
+            Ok(guard) => (guard, false),
+            Err(_) => (lock.clone().lock_owned().await, true),
+        };
+        (
+            DownloadGuard {
+                locks: self.clone(),
+                artifact_id: artifact_id.to_owned(),
+                lock,
+                _guard: guard,
+            },
+            waited,
+        )
+    }
+}
+
+impl Drop for DownloadGuard {
+    fn drop(&mut self) {
+        // The lock is forgotten when nobody else holds or waits for it. New
+        // holders clone the lock while the map is locked, so the count can't
+        // change underneath.
+        let mut locks = self.locks.locks.lock().unwrap();
+        // one reference is kept by the map, one by this guard and one by the
+        // owned mutex guard
+        if Arc::strong_count(&self.lock) <= 3 {
+            locks.remove(&self.artifact_id);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn downloads_of_an_artifact_are_serialized() {
+        let download_locks = DownloadLocks::default();
+
+        let (first, waited) = download_locks.lock("artifact").await;
+        assert!(!waited);
+        let (_other, waited) = download_locks.lock("other_artifact").await;
+        assert!(!waited);
+
+        let second = tokio::spawn({
+            let download_locks = download_locks.clone();
+            async move { download_locks.lock("artifact").await.1 }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!second.is_finished());
+
+        drop(first);
+        assert!(second.await.unwrap());
+        assert_eq!(
+            download_locks
+                .locks
+                .lock()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["other_artifact"]
+        );
+    }
+}
@@ -14,9 +14,10 @@
    limitations under the License.
 */
 
-use rusqlite::types::ToSqlOutput;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
 use rusqlite::ToSql;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 #[derive(
     Clone,
@@ -31,8 +32,12 @@ use serde::{Deserialize, Serialize};
     strum_macros::EnumString,
 )]
 pub enum PackageType {
+    Cargo,
     Docker,
+    GoModule,
     Maven2,
+    Npm,
+    PyPI,
 }
 
 impl ToSql for PackageType {
@@ -40,3 +45,125 @@ impl ToSql for PackageType {
         Ok(ToSqlOutput::from(self.to_string()))
     }
 }
+
+impl FromSql for PackageType {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        PackageType::from_str(value.as_str()?).map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// The outcome of a scrub of the artifact storage, see
+/// [`ArtifactService::scrub_artifacts`](super::service::ArtifactService::scrub_artifacts).
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ScrubReport {
+    /// Seconds since the unix epoch at which the scrub started.
+    pub started_at: u64,
+    /// Seconds since the unix epoch at which the scrub finished.
+    pub finished_at: u64,
+    /// The number of stored contents that were checked.
+    pub scanned: usize,
+    /// The number of stored contents of which the hash matched.
+    pub healthy: usize,
+    /// The number of stored contents without a known hash to compare with.
+    pub unverifiable: usize,
+    pub corrupt: Vec<CorruptArtifact>,
+}
+
+/// The outcome of a garbage collection of the artifact storage, see
+/// [`ArtifactService::collect_garbage`](super::service::ArtifactService::collect_garbage).
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GarbageCollectionReport {
+    /// Seconds since the unix epoch at which the garbage collection started.
+    pub started_at: u64,
+    /// Seconds since the unix epoch at which the garbage collection finished.
+    pub finished_at: u64,
+    /// Whether the garbage was only reported, without removing it.
+    pub dry_run: bool,
+    /// The ids of the stored artifacts that no transparency log refers to.
+    pub orphaned_artifacts: Vec<String>,
+    /// The ids of the builds that left their build directory behind.
+    pub stale_builds: Vec<String>,
+    /// The number of bytes of stored content that is no longer referenced.
+    pub reclaimed_bytes: u64,
+}
+
+/// A package that is exported into an offline bundle, see
+/// [`ArtifactService::export_bundle`](super::service::ArtifactService::export_bundle).
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BundlePackage {
+    pub package_type: PackageType,
+    pub package_specific_id: String,
+}
+
+/// The outcome of the import of an offline bundle, see
+/// [`ArtifactService::import_bundle`](super::service::ArtifactService::import_bundle).
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BundleImportReport {
+    /// The peer id of the node that exported and signed the bundle.
+    pub signer: String,
+    /// Seconds since the unix epoch at which the bundle was exported.
+    pub created_at: u64,
+    pub packages: Vec<BundlePackage>,
+    /// The ids of the artifacts that were added to the artifact storage.
+    pub imported_artifacts: Vec<String>,
+    /// The ids of the artifacts that were already stored.
+    pub existing_artifacts: Vec<String>,
+    /// The number of blocks that were appended to the local blockchain.
+    pub imported_blocks: usize,
+}
+
+/// The state of a pinned package, see [`PinStatus`].
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Serialize,
+    strum_macros::Display,
+    strum_macros::EnumString,
+)]
+pub enum PinState {
+    /// The artifacts of the package weren't fetched yet.
+    Pending,
+    /// All artifacts of the package are stored locally.
+    Available,
+    /// The last attempt to fetch the artifacts of the package failed.
+    Failed,
+}
+
+/// A package that is pinned to the local artifact storage, see
+/// [`ArtifactService::pin_package`](super::service::ArtifactService::pin_package).
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PinStatus {
+    pub package_type: PackageType,
+    pub package_specific_id: String,
+    /// Seconds since the unix epoch at which the package was pinned.
+    pub pinned_at: u64,
+    pub state: PinState,
+    /// Seconds since the unix epoch at which the artifacts of the package were
+    /// last fetched, if ever.
+    pub refreshed_at: Option<u64>,
+    /// The number of artifacts of the package that are stored locally.
+    pub artifacts: usize,
+    /// The number of bytes of the artifacts of the package.
+    pub size: u64,
+    /// Why the last attempt to fetch the artifacts failed.
+    pub error: Option<String>,
+}
+
+/// Stored content of which the hash didn't match its transparency log.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CorruptArtifact {
+    /// The key the content was stored under.
+    pub content_hash: String,
+    /// The ids of the artifacts that referred to the content.
+    pub artifact_ids: Vec<String>,
+    pub expected_hash: String,
+    /// The hash of the stored content, if it could be read at all.
+    pub actual_hash: Option<String>,
+    /// Whether the artifacts were downloaded again from other providers.
+    pub repaired: bool,
+    pub error: Option<String>,
+}
@@ -0,0 +1,220 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The list of packages that are pinned to the local artifact storage. The
+//! list is kept in a JSON file next to the stored artifacts, so pins survive
+//! restarts of the node.
+
+use super::model::{PackageType, PinState, PinStatus};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+const PIN_LIST_FILE_NAME: &str = "pins.json";
+
+#[derive(Clone)]
+pub struct PinList {
+    path: PathBuf,
+    pins: Arc<Mutex<Vec<PinStatus>>>,
+}
+
+impl PinList {
+    /// Load the pin list that is kept in `repository_path`. The list is empty
+    /// when nothing was pinned yet.
+    pub fn load(repository_path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = repository_path.as_ref().join(PIN_LIST_FILE_NAME);
+        let pins = match fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(PinList {
+            path,
+            pins: Arc::new(Mutex::new(pins)),
+        })
+    }
+
+    /// The status of all pinned packages, in the order in which they were pinned.
+    pub fn list(&self) -> Vec<PinStatus> {
+        self.pins.lock().unwrap().clone()
+    }
+
+    /// The status of the pinned package, if it is pinned.
+    pub fn get(&self, package_type: PackageType, package_specific_id: &str) -> Option<PinStatus> {
+        self.pins
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|pin| {
+                pin.package_type == package_type && pin.package_specific_id == package_specific_id
+            })
+            .cloned()
+    }
+
+    /// Pin a package. Pinning a package that is already pinned keeps its status.
+    pub fn add(
+        &self,
+        package_type: PackageType,
+        package_specific_id: &str,
+        pinned_at: u64,
+    ) -> io::Result<PinStatus> {
+        let mut pins = self.pins.lock().unwrap();
+        if let Some(pin) = pins.iter().find(|pin| {
+            pin.package_type == package_type && pin.package_specific_id == package_specific_id
+        }) {
+            return Ok(pin.clone());
+        }
+
+        let pin = PinStatus {
+            package_type,
+            package_specific_id: package_specific_id.to_owned(),
+            pinned_at,
+            state: PinState::Pending,
+            refreshed_at: None,
+            artifacts: 0,
+            size: 0,
+            error: None,
+        };
+        let mut updated = pins.clone();
+        updated.push(pin.clone());
+        self.save(&updated)?;
+        *pins = updated;
+        Ok(pin)
+    }
+
+    /// Unpin a package. Returns false when the package wasn't pinned.
+    pub fn remove(&self, package_type: PackageType, package_specific_id: &str) -> io::Result<bool> {
+        let mut pins = self.pins.lock().unwrap();
+        let updated: Vec<PinStatus> = pins
+            .iter()
+            .filter(|pin| {
+                pin.package_type != package_type || pin.package_specific_id != package_specific_id
+            })
+            .cloned()
+            .collect();
+        if updated.len() == pins.len() {
+            return Ok(false);
+        }
+        self.save(&updated)?;
+        *pins = updated;
+        Ok(true)
+    }
+
+    /// Replace the status of a pinned package. Nothing changes when the package
+    /// was unpinned in the meantime.
+    pub fn update(&self, status: &PinStatus) -> io::Result<()> {
+        let mut pins = self.pins.lock().unwrap();
+        let mut updated = pins.clone();
+        match updated.iter_mut().find(|pin| {
+            pin.package_type == status.package_type
+                && pin.package_specific_id == status.package_specific_id
+        }) {
+            Some(pin) => *pin = status.clone(),
+            None => return Ok(()),
+        }
+        self.save(&updated)?;
+        *pins = updated;
+        Ok(())
+    }
+
+    // The list is written to a temporary file that replaces the previous list,
+    // so a crash never leaves a truncated list behind.
+    fn save(&self, pins: &[PinStatus]) -> io::Result<()> {
+        let temp_path = self.path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+        let result = fs::File::create(&temp_path).and_then(|mut file| {
+            file.write_all(&serde_json::to_vec_pretty(pins)?)?;
+            file.sync_all()
+        });
+        match result.and_then(|_| fs::rename(&temp_path, &self.path)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::util::test_util;
+
+    #[test]
+    fn pins_survive_a_restart() {
+        let tmp_dir = test_util::tests::setup();
+
+        let pin_list = PinList::load(&tmp_dir).unwrap();
+        assert!(pin_list.list().is_empty());
+
+        let pin = pin_list
+            .add(PackageType::Docker, "library/alpine:3.15", 10)
+            .unwrap();
+        assert_eq!(pin.state, PinState::Pending);
+        pin_list
+            .add(PackageType::Maven2, "group:artifact:1.0", 20)
+            .unwrap();
+        assert_eq!(
+            pin_list
+                .add(PackageType::Docker, "library/alpine:3.15", 30)
+                .unwrap(),
+            pin
+        );
+
+        let refreshed = PinStatus {
+            state: PinState::Available,
+            refreshed_at: Some(40),
+            artifacts: 2,
+            size: 1024,
+            ..pin
+        };
+        pin_list.update(&refreshed).unwrap();
+
+        let pin_list = PinList::load(&tmp_dir).unwrap();
+        assert_eq!(
+            pin_list
+                .list()
+                .iter()
+                .map(|pin| pin.package_specific_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["library/alpine:3.15", "group:artifact:1.0"]
+        );
+        assert_eq!(
+            pin_list.get(PackageType::Docker, "library/alpine:3.15"),
+            Some(refreshed.clone())
+        );
+
+        assert!(pin_list
+            .remove(PackageType::Docker, "library/alpine:3.15")
+            .unwrap());
+        assert!(!pin_list
+            .remove(PackageType::Docker, "library/alpine:3.15")
+            .unwrap());
+        pin_list.update(&refreshed).unwrap();
+
+        let pin_list = PinList::load(&tmp_dir).unwrap();
+        assert_eq!(pin_list.list().len(), 1);
+        assert_eq!(
+            pin_list.get(PackageType::Docker, "library/alpine:3.15"),
+            None
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+}
@@ -14,24 +14,70 @@
    limitations under the License.
 */
 
-use super::model::PackageType;
-use super::storage::ArtifactStorage;
+use super::bundle::{self, BundleManifest, BundleTrust, BundledArtifact, BUNDLE_VERSION};
+use super::download_lock::DownloadLocks;
+use super::model::{
+    BundleImportReport, BundlePackage, CorruptArtifact, GarbageCollectionReport, PackageType,
+    PinState, PinStatus, ScrubReport,
+};
+use super::pin::PinList;
+use super::storage::{backend, ArtifactRange, ArtifactStorage, ArtifactStream};
 use crate::build_service::error::BuildError;
 use crate::build_service::event::BuildEventClient;
 use crate::build_service::model::{BuildResult, BuildStatus};
+use crate::network::artifact_protocol::MAX_CHUNK_SIZE;
 use crate::network::client::Client;
+use crate::network::reputation::PeerOffense;
+use crate::peer_metrics::metrics;
 use crate::transparency_log::log::{
-    AddArtifactRequest, TransparencyLog, TransparencyLogError, TransparencyLogService,
+    AddArtifactRequest, Operation, TransparencyLog, TransparencyLogError, TransparencyLogService,
 };
-use anyhow::{bail, Context};
+use crate::util::env_util::read_var;
+use crate::util::range_util::{self, RangeRequest};
+use anyhow::{anyhow, bail, Context};
+use bytes::Bytes;
+use futures::stream::{self, FuturesUnordered, StreamExt, TryStreamExt};
 use itertools::Itertools;
 use libp2p::PeerId;
 use log::{debug, info, warn};
 use multihash::Hasher;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 use std::str;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+
+/// The maximum number of attempts to download an artifact from the p2p network.
+const MAX_FETCH_ATTEMPTS: usize = 3;
+/// The maximum number of peers an artifact is downloaded from at once.
+const MAX_SWARM_PEERS: usize = 8;
+/// The time a peer gets to answer a chunk request during a swarm download.
+const SWARM_CHUNK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A chunk of an artifact that was received from a peer during a swarm download.
+#[derive(Debug, PartialEq, Eq)]
+struct SwarmChunk {
+    peer_id: PeerId,
+    offset: u64,
+    length: u64,
+    hash: Vec<u8>,
+}
+
+impl SwarmChunk {
+    fn new(peer_id: PeerId, offset: u64, data: &[u8]) -> Self {
+        let mut sha256 = multihash::Sha2_256::default();
+        sha256.update(data);
+        SwarmChunk {
+            peer_id,
+            offset,
+            length: data.len() as u64,
+            hash: sha256.finalize().to_vec(),
+        }
+    }
+}
 
 /// The artifact service is the component that handles everything related to
 /// pyrsia artifacts. It allows artifacts to be retrieved and added to the
@@ -42,6 +88,13 @@ pub struct ArtifactService {
     build_event_client: BuildEventClient,
     pub transparency_log_service: TransparencyLogService,
     pub p2p_client: Client,
+    swarm_download: bool,
+    disk_quota: Option<u64>,
+    scrub_report: Arc<Mutex<Option<ScrubReport>>>,
+    gc_report: Arc<Mutex<Option<GarbageCollectionReport>>>,
+    pin_list: PinList,
+    download_locks: DownloadLocks,
+    bundle_trust: BundleTrust,
 }
 
 impl ArtifactService {
@@ -51,15 +104,114 @@ impl ArtifactService {
         build_event_client: BuildEventClient,
         p2p_client: Client,
     ) -> anyhow::Result<Self> {
-        let artifact_storage = ArtifactStorage::new(&artifact_path)?;
+        let artifact_storage =
+            ArtifactStorage::with_backend(&artifact_path, backend::from_env(&artifact_path)?)?;
+        let pin_list = PinList::load(&artifact_path)?;
         Ok(ArtifactService {
             artifact_storage,
             build_event_client,
             transparency_log_service,
             p2p_client,
+            swarm_download: read_var("PYRSIA_SWARM_DOWNLOAD", "on").to_lowercase() == "on",
+            disk_quota: None,
+            scrub_report: Arc::new(Mutex::new(None)),
+            gc_report: Arc::new(Mutex::new(None)),
+            pin_list,
+            download_locks: DownloadLocks::default(),
+            bundle_trust: BundleTrust::from_env()?,
         })
     }
 
+    /// Retain the artifacts that this node stored with an older release, that
+    /// didn't mark the artifacts it built as retained yet. Only authorized nodes
+    /// build artifacts, and which of their artifacts they built is unknown, so
+    /// an authorized node retains all of them. This only happens once, and
+    /// must happen before the disk quota is enforced for the first time.
+    pub async fn retain_artifacts_of_older_releases(&self) -> anyhow::Result<()> {
+        let authorized = self
+            .transparency_log_service
+            .get_authorized_nodes()?
+            .contains(&self.p2p_client.local_peer_id);
+        let retained = self
+            .artifact_storage
+            .retain_artifacts_of_older_releases(authorized)
+            .await?;
+        if retained > 0 {
+            info!(
+                "Retained {} artifacts that this node may have built",
+                retained
+            );
+        }
+        Ok(())
+    }
+
+    /// Limit the number of bytes the local artifact storage may use to
+    /// `disk_quota`, see [`ArtifactService::enforce_disk_quota`].
+    pub fn with_disk_quota(self, disk_quota: u64) -> Self {
+        ArtifactService {
+            disk_quota: Some(disk_quota),
+            ..self
+        }
+    }
+
+    /// Compress the artifacts that are stored from now on with the specified
+    /// zstd level, see [`ArtifactStorage::with_compression_level`].
+    pub fn with_compression_level(self, compression_level: i32) -> Self {
+        ArtifactService {
+            artifact_storage: self
+                .artifact_storage
+                .with_compression_level(compression_level),
+            ..self
+        }
+    }
+
+    /// The number of bytes the local artifact storage may use, if limited.
+    pub fn disk_quota(&self) -> Option<u64> {
+        self.disk_quota
+    }
+
+    /// Evict the least recently used artifacts that this node doesn't need to
+    /// keep until the local artifact storage fits in the disk quota again. The
+    /// artifact with id `in_use` and the artifacts of pinned packages are never
+    /// evicted. Content that is shared with
+    /// other artifacts is only removed once all of them are evicted, and is no
+    /// longer provided on the p2p network from then on.
+    pub async fn enforce_disk_quota(&self, in_use: Option<&str>) -> anyhow::Result<()> {
+        let disk_quota = match self.disk_quota {
+            Some(disk_quota) => disk_quota,
+            None => return Ok(()),
+        };
+
+        let mut usage = self.artifact_storage.usage().await?;
+        if usage <= disk_quota {
+            return Ok(());
+        }
+
+        let pinned_artifact_ids = self.pinned_artifact_ids()?;
+        for (artifact_id, size) in self.artifact_storage.eviction_candidates().await? {
+            if in_use == Some(artifact_id.as_str()) || pinned_artifact_ids.contains(&artifact_id) {
+                continue;
+            }
+            info!(
+                "Evicting artifact {} to stay within the disk quota of {} bytes",
+                artifact_id, disk_quota
+            );
+            if let Some(content_hash) = self.artifact_storage.remove_artifact(&artifact_id).await? {
+                self.p2p_client.stop_providing(&content_hash).await?;
+                usage = usage.saturating_sub(size);
+                if usage <= disk_quota {
+                    return Ok(());
+                }
+            }
+        }
+
+        warn!(
+            "Artifact storage uses {} bytes, which exceeds the disk quota of {} bytes",
+            usage, disk_quota
+        );
+        Ok(())
+    }
+
     pub async fn request_build(
         &self,
         package_type: PackageType,
@@ -158,15 +310,23 @@ impl ArtifactService {
                 build_id
             );
 
-            self.put_artifact_from_build_result(
-                &artifact.artifact_location,
-                &add_artifact_transparency_log.artifact_id,
-            )
-            .await?;
+            let content_hash = self
+                .put_artifact_from_build_result(
+                    &artifact.artifact_location,
+                    &add_artifact_transparency_log.artifact_id,
+                    &artifact.artifact_hash,
+                )
+                .await?;
 
-            self.p2p_client
-                .provide(&add_artifact_transparency_log.artifact_id)
+            self.artifact_storage
+                .retain_artifact(&add_artifact_transparency_log.artifact_id)
                 .await?;
+
+            self.p2p_client.provide(&content_hash).await?;
+        }
+
+        if let Err(error) = self.enforce_disk_quota(None).await {
+            warn!("Unable to enforce the disk quota: {:?}", error);
         }
 
         self.transparency_log_service
@@ -229,18 +389,15 @@ impl ArtifactService {
         &self,
         artifact_location: &Path,
         artifact_id: &str,
-    ) -> Result<(), anyhow::Error> {
+        artifact_hash: &str,
+    ) -> Result<String, anyhow::Error> {
         let artifact_file = File::open(artifact_location)?;
-        let mut artifact_reader = BufReader::new(artifact_file);
-        self.put_artifact(artifact_id, &mut artifact_reader)
-    }
-
-    /// Given artifact_id & reader, push artifact to artifact_storage
-    fn put_artifact(&self, artifact_id: &str, reader: &mut impl Read) -> Result<(), anyhow::Error> {
-        info!("put_artifact with id: {}", artifact_id);
+        let artifact_reader = BufReader::new(artifact_file);
+        info!("put_artifact_from_build_result with id: {}", artifact_id);
         self.artifact_storage
-            .push_artifact(reader, artifact_id)
-            .context("Error from put_artifact")
+            .push_verified_artifact(artifact_reader, artifact_id, artifact_hash)
+            .await
+            .context("Error from put_artifact_from_build_result")
     }
 
     /// Retrieve the artifact data for the specified package. If the artifact
@@ -259,12 +416,17 @@ impl ArtifactService {
             .get_artifact_locally(&transparency_log.artifact_id)
             .await
         {
-            Ok(artifact) => Ok(artifact),
+            Ok(artifact) => artifact,
             Err(_) => {
-                self.get_artifact_from_peers(&transparency_log.artifact_id)
-                    .await
+                self.get_artifact_from_peers(
+                    &transparency_log.artifact_id,
+                    &transparency_log.artifact_hash,
+                )
+                .await?;
+                self.get_artifact_locally(&transparency_log.artifact_id)
+                    .await?
             }
-        }?;
+        };
 
         self.verify_artifact(&transparency_log, &artifact).await?;
 
@@ -280,18 +442,131 @@ impl ArtifactService {
         package_specific_id: &str,
         package_specific_artifact_id: &str,
     ) -> anyhow::Result<Vec<u8>> {
-        self.get_artifact(package_type, package_specific_artifact_id).await.map_err(|e| {
-                warn!("Error looking for artifact: {:?}. A new build will be started. Try again later", e);
-                let new_artifact_service = self.clone();
-                let new_package_specific_id = package_specific_id.to_string();
-                tokio::spawn(async move {
-                    debug!("Spawning a build...");
-                    let build_result = new_artifact_service.clone().request_build(package_type, new_package_specific_id).await;
-                    debug!("Build result {:?}", build_result);
-                });
-                // in any case, return the error
-                e
-            })
+        self.get_artifact(package_type, package_specific_artifact_id)
+            .await
+            .map_err(|e| self.request_build_after_failure(package_type, package_specific_id, e))
+    }
+
+    /// Retrieve the artifact data for the specified package as a stream, together
+    /// with its size. If the artifact is not available locally, the service will
+    /// first fetch the artifact from the p2p network. The hash of the artifact is
+    /// verified while the stream is consumed, see [`ArtifactStorage::stream_artifact`].
+    pub async fn get_artifact_stream(
+        &mut self,
+        package_type: PackageType,
+        package_specific_artifact_id: &str,
+    ) -> anyhow::Result<(u64, ArtifactStream)> {
+        let transparency_log = self
+            .transparency_log_service
+            .get_artifact(&package_type, package_specific_artifact_id)?;
+
+        if let Ok(artifact_stream) = self
+            .artifact_storage
+            .stream_artifact(
+                &transparency_log.artifact_id,
+                &transparency_log.artifact_hash,
+            )
+            .await
+        {
+            return Ok(artifact_stream);
+        }
+
+        self.get_artifact_from_peers(
+            &transparency_log.artifact_id,
+            &transparency_log.artifact_hash,
+        )
+        .await?;
+
+        Ok(self
+            .artifact_storage
+            .stream_artifact(
+                &transparency_log.artifact_id,
+                &transparency_log.artifact_hash,
+            )
+            .await?)
+    }
+
+    /// Retrieve the byte range of the artifact requested by the `range_header`.
+    /// Ranges are only served for artifacts that are available in the local
+    /// storage and of which the hash has been verified. In all other cases,
+    /// including a range header that can't be parsed, `None` is returned and
+    /// the full artifact should be served instead.
+    pub async fn get_artifact_range(
+        &mut self,
+        package_type: PackageType,
+        package_specific_artifact_id: &str,
+        range_header: &str,
+    ) -> anyhow::Result<Option<ArtifactRange>> {
+        let transparency_log = self
+            .transparency_log_service
+            .get_artifact(&package_type, package_specific_artifact_id)?;
+
+        let size = match self
+            .artifact_storage
+            .verify_local_artifact(
+                &transparency_log.artifact_id,
+                &transparency_log.artifact_hash,
+            )
+            .await?
+        {
+            Some(size) => size,
+            None => return Ok(None),
+        };
+
+        match range_util::parse_range(range_header, size) {
+            RangeRequest::Satisfiable { start, end } => {
+                let stream = self
+                    .artifact_storage
+                    .stream_artifact_range(&transparency_log.artifact_id, start, end)
+                    .await?;
+                Ok(Some(ArtifactRange::Partial {
+                    start,
+                    end,
+                    size,
+                    stream,
+                }))
+            }
+            RangeRequest::Unsatisfiable => Ok(Some(ArtifactRange::Unsatisfiable { size })),
+            RangeRequest::Ignored => Ok(None),
+        }
+    }
+
+    /// Retrieve the artifact data for the specified package as a stream. If the
+    /// artifact is not found, the service start a request to build it on an
+    /// authorized node.
+    pub async fn get_artifact_stream_or_build(
+        &mut self,
+        package_type: PackageType,
+        package_specific_id: &str,
+        package_specific_artifact_id: &str,
+    ) -> anyhow::Result<(u64, ArtifactStream)> {
+        self.get_artifact_stream(package_type, package_specific_artifact_id)
+            .await
+            .map_err(|e| self.request_build_after_failure(package_type, package_specific_id, e))
+    }
+
+    fn request_build_after_failure(
+        &self,
+        package_type: PackageType,
+        package_specific_id: &str,
+        error: anyhow::Error,
+    ) -> anyhow::Error {
+        warn!(
+            "Error looking for artifact: {:?}. A new build will be started. Try again later",
+            error
+        );
+        let new_artifact_service = self.clone();
+        let new_package_specific_id = package_specific_id.to_string();
+        tokio::spawn(async move {
+            debug!("Spawning a build...");
+            let build_result = new_artifact_service
+                .clone()
+                .request_build(package_type, new_package_specific_id)
+                .await;
+            debug!("Build result {:?}", build_result);
+        });
+        // in any case, return the error
+        error
     }
 
     /// Retrieve the artifact data specified by `artifact_id` from the local storage.
@@ -299,11 +574,7 @@ impl ArtifactService {
         &mut self,
         artifact_id: &str,
     ) -> Result<Vec<u8>, anyhow::Error> {
-        let artifact = self.artifact_storage.pull_artifact(artifact_id)?;
-        let mut buf_reader = BufReader::new(artifact);
-        let mut blob_content = Vec::new();
-        buf_reader.read_to_end(&mut blob_content)?;
-        Ok(blob_content)
+        Ok(self.artifact_storage.pull_artifact(artifact_id).await?)
     }
 
     /// Retrieve the artifact logs for the specified package.
@@ -322,310 +593,2417 @@ impl ArtifactService {
     }
 
     pub async fn provide_local_artifacts(&self) -> anyhow::Result<()> {
-        for path in self.artifact_storage.list_artifacts()? {
-            if let Some(artifact_id) = path.file_stem() {
-                debug!("Providing artifact_id: {:?}", artifact_id);
-                self.p2p_client
-                    .clone()
-                    .provide(artifact_id.to_str().expect("error getting artifact_id"))
-                    .await?
-            }
+        for artifact_id in self.artifact_storage.list_artifacts().await? {
+            debug!("Providing artifact_id: {:?}", artifact_id);
+            self.p2p_client.clone().provide(&artifact_id).await?
         }
         Ok(())
     }
 
-    async fn get_artifact_from_peers(
-        &mut self,
-        artifact_id: &str,
-    ) -> Result<Vec<u8>, anyhow::Error> {
-        let providers = self.p2p_client.list_providers(artifact_id).await?;
+    /// The report of the last scrub of the artifact storage, if any.
+    pub fn scrub_report(&self) -> Option<ScrubReport> {
+        self.scrub_report.lock().unwrap().clone()
+    }
 
-        match self.p2p_client.get_idle_peer(providers).await? {
-            Some(peer_id) => self.get_artifact_from_peer(&peer_id, artifact_id).await,
-            None => {
-                bail!(
-                    "Artifact with id {} is not available on the p2p network.",
-                    artifact_id
-                )
+    /// Hash every artifact in the local storage again and compare it to the
+    /// artifact hash in its transparency log, to find artifacts that were
+    /// corrupted after they were stored. Corrupt content is quarantined, no
+    /// longer provided on the p2p network and downloaded again from other
+    /// providers. The report is kept, see [`ArtifactService::scrub_report`].
+    pub async fn scrub_artifacts(&self) -> anyhow::Result<ScrubReport> {
+        info!("Scrubbing the artifact storage");
+        let mut report = ScrubReport {
+            started_at: unix_time(),
+            ..Default::default()
+        };
+
+        for (content_key, artifact_ids) in self.artifact_storage.stored_contents().await? {
+            let artifact_ids = if artifact_ids.is_empty() {
+                vec![content_key.clone()]
+            } else {
+                artifact_ids
+            };
+            let expected_hash = match self.expected_artifact_hash(&content_key, &artifact_ids) {
+                Some(expected_hash) => expected_hash,
+                None => {
+                    debug!("No known hash for content {}", content_key);
+                    report.scanned += 1;
+                    report.unverifiable += 1;
+                    continue;
+                }
+            };
+
+            let actual_hash = match self.artifact_storage.hash_content(&content_key).await {
+                Ok(actual_hash) => Some(actual_hash),
+                // the content was evicted in the meantime
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    warn!("Unable to read content {}: {:?}", content_key, e);
+                    None
+                }
+            };
+            report.scanned += 1;
+            if actual_hash.as_deref() == Some(expected_hash.as_str()) {
+                report.healthy += 1;
+                continue;
             }
-        }
-    }
 
-    async fn get_artifact_from_peer(
-        &mut self,
-        peer_id: &PeerId,
-        artifact_id: &str,
-    ) -> Result<Vec<u8>, anyhow::Error> {
-        let artifact = self
-            .p2p_client
-            .request_artifact(peer_id, artifact_id)
-            .await?;
+            warn!(
+                "Content {} of artifacts {:?} is corrupt, expected hash {} but was {:?}",
+                content_key, artifact_ids, expected_hash, actual_hash
+            );
+            let repair_result = self
+                .repair_corrupt_content(&content_key, &artifact_ids, &expected_hash)
+                .await;
+            if let Err(error) = &repair_result {
+                warn!("Unable to repair content {}: {:?}", content_key, error);
+            }
+            report.corrupt.push(CorruptArtifact {
+                content_hash: content_key,
+                artifact_ids,
+                expected_hash,
+                actual_hash,
+                repaired: repair_result.is_ok(),
+                error: repair_result.err().map(|error| format!("{:#}", error)),
+            });
+        }
 
-        let mut buf_reader = BufReader::new(artifact.as_slice());
+        report.finished_at = unix_time();
+        info!(
+            "Scrubbed {} artifacts, {} are corrupt",
+            report.scanned,
+            report.corrupt.len()
+        );
+        *self.scrub_report.lock().unwrap() = Some(report.clone());
+        Ok(report)
+    }
 
-        self.put_artifact(artifact_id, &mut buf_reader)?;
-        self.get_artifact_locally(artifact_id).await
+    // The hash that stored content must have, according to the transparency
+    // log of the artifacts that refer to it. Content-addressed content that
+    // has no transparency log must match its key.
+    fn expected_artifact_hash(&self, content_key: &str, artifact_ids: &[String]) -> Option<String> {
+        artifact_ids
+            .iter()
+            .find_map(|artifact_id| {
+                self.transparency_log_service
+                    .find_transparency_log_by_artifact_id(artifact_id)
+                    .ok()
+            })
+            .map(|transparency_log| transparency_log.artifact_hash.to_lowercase())
+            .or_else(|| {
+                let is_hash =
+                    content_key.len() == 64 && content_key.chars().all(|c| c.is_ascii_hexdigit());
+                is_hash.then(|| content_key.to_lowercase())
+            })
     }
 
-    async fn verify_artifact(
-        &mut self,
-        transparency_log: &TransparencyLog,
-        artifact: &[u8],
-    ) -> Result<(), TransparencyLogError> {
-        let mut sha256 = multihash::Sha2_256::default();
-        sha256.update(artifact);
-        let calculated_hash = hex::encode(sha256.finalize());
+    // Quarantine corrupt content and stop providing it, then download the
+    // artifacts that referred to it again and provide them once more.
+    async fn repair_corrupt_content(
+        &self,
+        content_key: &str,
+        artifact_ids: &[String],
+        expected_hash: &str,
+    ) -> anyhow::Result<()> {
+        self.artifact_storage
+            .quarantine_content(content_key)
+            .await?;
+        self.p2p_client.stop_providing(content_key).await?;
 
-        if transparency_log.artifact_hash == calculated_hash {
-            Ok(())
-        } else {
-            Err(TransparencyLogError::InvalidHash {
-                id: transparency_log.package_specific_artifact_id.clone(),
-                invalid_hash: calculated_hash,
-                actual_hash: transparency_log.artifact_hash.clone(),
-            })
+        for artifact_id in artifact_ids {
+            self.clone()
+                .get_artifact_from_peers(artifact_id, expected_hash)
+                .await?;
         }
+        self.p2p_client.provide(expected_hash).await?;
+        info!("Repaired content {}", content_key);
+        Ok(())
     }
-}
-
-#[cfg(test)]
-#[cfg(not(tarpaulin_include))]
-mod tests {
-    use super::*;
-    use crate::blockchain_service::event::BlockchainEvent;
-    use crate::build_service::event::BuildEvent;
-    use crate::network::client::command::Command;
-    use crate::network::idle_metric_protocol::PeerMetrics;
-    use crate::util::test_util;
-    use libp2p::identity::ed25519::Keypair;
-    use libp2p::identity::PublicKey;
-    use sha2::{Digest, Sha256};
-    use std::collections::HashSet;
-    use std::env;
-    use std::path::PathBuf;
-    use tokio::task;
 
-    const VALID_ARTIFACT_HASH: [u8; 32] = [
-        0x86, 0x5c, 0x8d, 0x98, 0x8b, 0xe4, 0x66, 0x9f, 0x3e, 0x48, 0xf7, 0x3b, 0x98, 0xf9, 0xbc,
-        0x25, 0x7, 0xbe, 0x2, 0x46, 0xea, 0x35, 0xe0, 0x9, 0x8c, 0xf6, 0x5, 0x4d, 0x36, 0x44, 0xc1,
-        0x4f,
-    ];
+    /// The report of the last garbage collection, if any.
+    pub fn gc_report(&self) -> Option<GarbageCollectionReport> {
+        self.gc_report.lock().unwrap().clone()
+    }
 
-    #[tokio::test]
-    async fn test_put_and_get_artifact() {
-        let tmp_dir = test_util::tests::setup();
+    /// Remove the garbage this node accumulated: the stored artifacts that no
+    /// transparency log refers to anymore, because the artifact was never added
+    /// or was removed afterwards, and the build directories that builds left
+    /// behind. Content that is removed is no longer provided on the p2p network.
+    /// A dry run only reports the garbage.
+    pub async fn collect_garbage(&self, dry_run: bool) -> anyhow::Result<GarbageCollectionReport> {
+        info!("Collecting garbage, dry run: {}", dry_run);
+        let mut report = GarbageCollectionReport {
+            started_at: unix_time(),
+            dry_run,
+            ..Default::default()
+        };
 
-        let (mut artifact_service, mut blockchain_event_receiver, _, mut p2p_command_receiver) =
-            test_util::tests::create_artifact_service(&tmp_dir);
+        // Artifacts are referred to by their id, and content that was stored
+        // under its hash by the hash of the artifact.
+        let mut referenced = HashSet::new();
+        for transparency_log in self.transparency_log_service.find_added_artifacts()? {
+            referenced.insert(transparency_log.artifact_id);
+            referenced.insert(transparency_log.artifact_hash.to_lowercase());
+        }
 
-        tokio::spawn(async move {
-            loop {
-                match p2p_command_receiver.recv().await {
-                    Some(Command::ListPeers { sender, .. }) => {
-                        let _ = sender.send(HashSet::new());
-                    }
-                    _ => panic!("Command must match Command::ListPeers"),
+        for (content_key, artifact_ids) in self.artifact_storage.stored_contents().await? {
+            let artifact_ids = if artifact_ids.is_empty() {
+                vec![content_key.clone()]
+            } else {
+                artifact_ids
+            };
+            let orphaned: Vec<String> = artifact_ids
+                .iter()
+                .filter(|artifact_id| !referenced.contains(*artifact_id))
+                .cloned()
+                .collect();
+            if orphaned.len() == artifact_ids.len() {
+                match self
+                    .artifact_storage
+                    .stored_content_size(&content_key)
+                    .await
+                {
+                    Ok(size) => report.reclaimed_bytes += size,
+                    // the content was evicted in the meantime
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e.into()),
                 }
             }
-        });
 
-        tokio::spawn(async move {
-            loop {
-                match blockchain_event_receiver.recv().await {
-                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
-                        let _ = sender.send(Ok(()));
+            for artifact_id in orphaned {
+                info!("Artifact {} is no longer referenced", artifact_id);
+                if !dry_run {
+                    if let Some(content_hash) =
+                        self.artifact_storage.remove_artifact(&artifact_id).await?
+                    {
+                        self.p2p_client.stop_providing(&content_hash).await?;
                     }
-                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
                 }
+                report.orphaned_artifacts.push(artifact_id);
             }
-        });
+        }
 
-        let package_type = PackageType::Docker;
-        let package_specific_id = "package_specific_id";
-        let package_specific_artifact_id = "package_specific_artifact_id";
-        let transparency_log_tuple = artifact_service
-            .transparency_log_service
-            .add_artifact(AddArtifactRequest {
-                package_type,
-                package_specific_id: package_specific_id.to_owned(),
-                num_artifacts: 8,
-                package_specific_artifact_id: package_specific_artifact_id.to_owned(),
-                artifact_hash: hex::encode(VALID_ARTIFACT_HASH),
-            })
-            .await
-            .unwrap();
-        let transparency_log = transparency_log_tuple.0;
+        report.stale_builds = self.build_event_client.collect_garbage(dry_run).await?;
 
-        //put the artifact
-        artifact_service
-            .put_artifact(
-                &transparency_log.artifact_id,
-                &mut get_file_reader().unwrap(),
-            )
-            .context("Error from put_artifact")
-            .unwrap();
+        report.finished_at = unix_time();
+        info!(
+            "Collected {} orphaned artifacts of {} bytes and {} stale build directories",
+            report.orphaned_artifacts.len(),
+            report.reclaimed_bytes,
+            report.stale_builds.len()
+        );
+        *self.gc_report.lock().unwrap() = Some(report.clone());
+        Ok(report)
+    }
 
-        // pull artifact
-        let future = {
-            artifact_service
-                .get_artifact(package_type, package_specific_artifact_id)
-                .await
-                .context("Error from get_artifact")
-        };
-        let file = task::spawn_blocking(|| future).await.unwrap().unwrap();
+    /// The status of all pinned packages.
+    pub fn pins(&self) -> Vec<PinStatus> {
+        self.pin_list.list()
+    }
 
-        //validate pulled artifact with the actual data
-        let mut s = String::new();
-        get_file_reader().unwrap().read_to_string(&mut s).unwrap();
+    /// Pin a package to the local artifact storage. The artifacts of pinned
+    /// packages are fetched ahead of time by [`ArtifactService::refresh_pin`],
+    /// kept provided on the p2p network and never evicted.
+    pub fn pin_package(
+        &self,
+        package_type: PackageType,
+        package_specific_id: &str,
+    ) -> anyhow::Result<PinStatus> {
+        info!(
+            "Pinning package {} of type {}",
+            package_specific_id, package_type
+        );
+        Ok(self
+            .pin_list
+            .add(package_type, package_specific_id, unix_time())?)
+    }
+
+    /// Unpin a package, after which its artifacts can be evicted again. Returns
+    /// false when the package wasn't pinned.
+    pub fn unpin_package(
+        &self,
+        package_type: PackageType,
+        package_specific_id: &str,
+    ) -> anyhow::Result<bool> {
+        info!(
+            "Unpinning package {} of type {}",
+            package_specific_id, package_type
+        );
+        Ok(self.pin_list.remove(package_type, package_specific_id)?)
+    }
+
+    /// Fetch the artifacts of a pinned package that aren't stored locally yet,
+    /// and provide all of them on the p2p network. The outcome is kept as the
+    /// status of the pin.
+    pub async fn refresh_pin(
+        &self,
+        package_type: PackageType,
+        package_specific_id: &str,
+    ) -> anyhow::Result<PinStatus> {
+        let mut status = self
+            .pin_list
+            .get(package_type, package_specific_id)
+            .with_context(|| {
+                format!(
+                    "Package {} of type {} is not pinned",
+                    package_specific_id, package_type
+                )
+            })?;
+
+        match self
+            .fetch_pinned_package(package_type, package_specific_id)
+            .await
+        {
+            Ok((artifacts, size)) => {
+                status.state = PinState::Available;
+                status.artifacts = artifacts;
+                status.size = size;
+                status.error = None;
+            }
+            Err(error) => {
+                warn!(
+                    "Unable to fetch pinned package {} of type {}: {:?}",
+                    package_specific_id, package_type, error
+                );
+                status.state = PinState::Failed;
+                status.error = Some(error.to_string());
+            }
+        }
+        status.refreshed_at = Some(unix_time());
+        self.pin_list.update(&status)?;
+        Ok(status)
+    }
+
+    /// Refresh all pinned packages, see [`ArtifactService::refresh_pin`].
+    pub async fn refresh_pins(&self) -> anyhow::Result<Vec<PinStatus>> {
+        let mut statuses = vec![];
+        for pin in self.pin_list.list() {
+            statuses.push(
+                self.refresh_pin(pin.package_type, &pin.package_specific_id)
+                    .await?,
+            );
+        }
+        Ok(statuses)
+    }
+
+    // Fetch every artifact of the package through `get_artifact` and provide
+    // its content. Returns the number of artifacts and their total size.
+    async fn fetch_pinned_package(
+        &self,
+        package_type: PackageType,
+        package_specific_id: &str,
+    ) -> anyhow::Result<(usize, u64)> {
+        let package_specific_artifact_ids = self
+            .pinned_package_logs(package_type, package_specific_id)?
+            .into_iter()
+            .map(|log| log.package_specific_artifact_id)
+            .unique()
+            .collect_vec();
+        if package_specific_artifact_ids.is_empty() {
+            bail!(
+                "Package {} of type {} not found in transparency log",
+                package_specific_id,
+                package_type
+            );
+        }
+
+        let mut size = 0;
+        for package_specific_artifact_id in &package_specific_artifact_ids {
+            let artifact = self
+                .clone()
+                .get_artifact(package_type, package_specific_artifact_id)
+                .await?;
+            size += artifact.len() as u64;
+
+            let transparency_log = self
+                .transparency_log_service
+                .clone()
+                .get_artifact(&package_type, package_specific_artifact_id)?;
+            self.p2p_client
+                .clone()
+                .provide(&transparency_log.artifact_hash.to_lowercase())
+                .await?;
+        }
+        Ok((package_specific_artifact_ids.len(), size))
+    }
+
+    fn pinned_package_logs(
+        &self,
+        package_type: PackageType,
+        package_specific_id: &str,
+    ) -> anyhow::Result<Vec<TransparencyLog>> {
+        Ok(self
+            .transparency_log_service
+            .search_transparency_logs(&package_type, package_specific_id, false)?
+            .into_iter()
+            .filter(|log| log.operation == Operation::AddArtifact)
+            .collect())
+    }
+
+    // The ids of the artifacts of all pinned packages.
+    fn pinned_artifact_ids(&self) -> anyhow::Result<HashSet<String>> {
+        let mut artifact_ids = HashSet::new();
+        for pin in self.pin_list.list() {
+            for log in self.pinned_package_logs(pin.package_type, &pin.package_specific_id)? {
+                artifact_ids.insert(log.artifact_id);
+            }
+        }
+        Ok(artifact_ids)
+    }
+
+    /// Export the specified packages into an offline bundle, which can be
+    /// imported by nodes that can't reach this node, see
+    /// [`ArtifactService::import_bundle`]. The bundle holds the AddArtifact
+    /// transparency logs of the packages, the blocks of the blockchain up to the
+    /// last block that published one of them and the content of their artifacts,
+    /// and is signed by this node. Artifacts that aren't stored locally are
+    /// downloaded from the p2p network first. Returns the size of the bundle and
+    /// a stream of its content.
+    pub async fn export_bundle(
+        &self,
+        packages: Vec<BundlePackage>,
+    ) -> anyhow::Result<(u64, ArtifactStream)> {
+        info!("Exporting bundle of packages {:?}", packages);
+        let blockchain_event_client = self.transparency_log_service.blockchain_event_client();
+        let mut blocks = blockchain_event_client.pull_all_blocks_local().await?;
+        let logs_on_chain = bundle::find_logs_on_chain(&blocks);
+
+        let mut transparency_logs: Vec<TransparencyLog> = vec![];
+        for package in &packages {
+            let package_logs = self
+                .transparency_log_service
+                .search_transparency_logs(
+                    &package.package_type,
+                    &package.package_specific_id,
+                    false,
+                )?
+                .into_iter()
+                .filter(|log| log.operation == Operation::AddArtifact)
+                .collect_vec();
+            if package_logs.is_empty() {
+                bail!(
+                    "Package {} of type {} not found in transparency log",
+                    package.package_specific_id,
+                    package.package_type
+                );
+            }
+            for log in package_logs {
+                if !transparency_logs.iter().any(|added| added.id == log.id) {
+                    transparency_logs.push(log);
+                }
+            }
+        }
+
+        let mut last_ordinal = 0;
+        for log in &transparency_logs {
+            let (ordinal, _) = logs_on_chain.get(&log.id).ok_or_else(|| {
+                anyhow!(
+                    "Transparency log {} of artifact {} is not on the blockchain yet",
+                    log.id,
+                    log.package_specific_artifact_id
+                )
+            })?;
+            last_ordinal = last_ordinal.max(*ordinal);
+        }
+        blocks.retain(|block| block.header.ordinal <= last_ordinal);
+
+        let mut artifacts = vec![];
+        for log in transparency_logs.iter().unique_by(|log| &log.artifact_id) {
+            let size = match self
+                .artifact_storage
+                .verify_local_artifact(&log.artifact_id, &log.artifact_hash)
+                .await?
+            {
+                Some(size) => size,
+                None => {
+                    self.clone()
+                        .get_artifact_from_peers(&log.artifact_id, &log.artifact_hash)
+                        .await?;
+                    self.artifact_storage
+                        .verify_local_artifact(&log.artifact_id, &log.artifact_hash)
+                        .await?
+                        .with_context(|| format!("Artifact {} is not available", log.artifact_id))?
+                }
+            };
+            artifacts.push(BundledArtifact {
+                artifact_id: log.artifact_id.clone(),
+                artifact_hash: log.artifact_hash.clone(),
+                size,
+            });
+        }
+
+        let manifest = BundleManifest {
+            version: BUNDLE_VERSION,
+            created_at: unix_time(),
+            packages,
+            blocks,
+            transparency_logs,
+            artifacts,
+        };
+        let serialized_manifest = bundle::serialize_manifest(&manifest)?;
+        let (public_key, signature) = blockchain_event_client
+            .sign_payload(serialized_manifest.clone())
+            .await?;
+        let header = bundle::encode_header(serialized_manifest, public_key, signature)?;
+
+        let size = header.len() as u64
+            + manifest
+                .artifacts
+                .iter()
+                .map(|artifact| artifact.size)
+                .sum::<u64>();
+        let artifact_storage = self.artifact_storage.clone();
+        let artifact_streams = stream::iter(manifest.artifacts)
+            .then(move |artifact| {
+                let artifact_storage = artifact_storage.clone();
+                async move {
+                    artifact_storage
+                        .stream_artifact(&artifact.artifact_id, &artifact.artifact_hash)
+                        .await
+                        .map(|(_, artifact_stream)| artifact_stream)
+                }
+            })
+            .try_flatten();
+        info!("Exported bundle of {} bytes", size);
+        Ok((
+            size,
+            Box::pin(stream::once(async { Ok(Bytes::from(header)) }).chain(artifact_streams)),
+        ))
+    }
+
+    /// Import an offline bundle that was exported by
+    /// [`ArtifactService::export_bundle`]. The bundle is stored in a temporary
+    /// file first, see [`ArtifactService::import_bundle_file`].
+    pub async fn import_bundle(
+        &self,
+        mut bundle: ArtifactStream,
+    ) -> anyhow::Result<BundleImportReport> {
+        let bundle_path = self.artifact_storage.temp_file_path("bundle");
+        let result = async {
+            let mut bundle_file = tokio::fs::File::create(&bundle_path).await?;
+            while let Some(chunk) = bundle.next().await {
+                bundle_file.write_all(&chunk?).await?;
+            }
+            bundle_file.sync_all().await?;
+            self.import_bundle_file(&bundle_path).await
+        }
+        .await;
+
+        if let Err(e) = std::fs::remove_file(&bundle_path) {
+            warn!("Unable to remove bundle file {:?}: {:?}", bundle_path, e);
+        }
+        result
+    }
+
+    /// Import the offline bundle at `bundle_path`. Nothing is imported unless the
+    /// signature of the bundle is valid, its blocks form a valid chain that
+    /// doesn't conflict with the local blockchain, the bundle comes from a
+    /// trusted source (see [`BundleTrust`]), its transparency logs were
+    /// published on that chain and the content of every artifact matches the
+    /// hash of its transparency log. The blocks that the local blockchain is
+    /// missing are appended to it. Imported artifacts are retained, because they
+    /// can't be downloaded again from the p2p network.
+    pub async fn import_bundle_file(
+        &self,
+        bundle_path: &Path,
+    ) -> anyhow::Result<BundleImportReport> {
+        info!("Importing bundle {:?}", bundle_path);
+        // Reading and verifying the bundle is blocking file I/O and hashing, so
+        // it runs on the threads for blocking tasks.
+        let path = bundle_path.to_owned();
+        let (manifest, signer, content_offset) = tokio::task::spawn_blocking(move || {
+            let mut reader = BufReader::new(File::open(path)?);
+            let (manifest, signer) = bundle::read_manifest(&mut reader)?;
+            bundle::verify_manifest(&manifest)?;
+            let content_offset = reader.stream_position()?;
+            anyhow::Ok((manifest, signer, content_offset))
+        })
+        .await??;
+
+        let blockchain_event_client = self.transparency_log_service.blockchain_event_client();
+        let local_blocks = blockchain_event_client.pull_all_blocks_local().await?;
+        if let Some((local_block, _)) = local_blocks
+            .iter()
+            .zip(&manifest.blocks)
+            .find(|(local_block, block)| local_block.header.hash() != block.header.hash())
+        {
+            bail!(
+                "The blockchain of the bundle conflicts with block {} of the local blockchain",
+                local_block.header.ordinal
+            );
+        }
+        let genesis_hash = match local_blocks.first() {
+            Some(genesis) => bundle::block_hash(genesis),
+            None => self.bundle_trust.genesis_hash.clone().context(
+                "The local blockchain is empty, set PYRSIA_BUNDLE_GENESIS_HASH to import bundles",
+            )?,
+        };
+        let mut trusted_signers = self.bundle_trust.trusted_signers.clone();
+        trusted_signers.extend(self.transparency_log_service.get_authorized_nodes()?);
+        trusted_signers.insert(self.p2p_client.local_peer_id);
+        bundle::verify_trust(
+            &manifest,
+            &signer,
+            &genesis_hash,
+            local_blocks.len(),
+            &trusted_signers,
+        )?;
+
+        let path = bundle_path.to_owned();
+        let artifacts = manifest.artifacts.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut content = File::open(path)?;
+            content.seek(SeekFrom::Start(content_offset))?;
+            bundle::verify_artifacts(&mut BufReader::new(content), &artifacts)
+        })
+        .await??;
+
+        let mut report = BundleImportReport {
+            signer: signer.to_string(),
+            created_at: manifest.created_at,
+            packages: manifest.packages.clone(),
+            ..Default::default()
+        };
+        for block in manifest.blocks.iter().skip(local_blocks.len()) {
+            blockchain_event_client
+                .handle_broadcast_blockchain(block.header.ordinal, block.clone())
+                .await?;
+            report.imported_blocks += 1;
+        }
+
+        // The transparency logs are written before the artifacts are stored, so
+        // the garbage collection never considers the artifacts orphaned.
+        let mut transparency_log_service = self.transparency_log_service.clone();
+        for log in &manifest.transparency_logs {
+            transparency_log_service.write_if_not_exists(log).await?;
+        }
+
+        // Every artifact is read through its own file handle, because its
+        // content is written to the storage on a thread for blocking tasks.
+        let mut artifact_offset = content_offset;
+        for artifact in &manifest.artifacts {
+            let mut content = File::open(bundle_path)?;
+            content.seek(SeekFrom::Start(artifact_offset))?;
+            artifact_offset += artifact.size;
+            match self
+                .artifact_storage
+                .push_verified_artifact(
+                    BufReader::new(content).take(artifact.size),
+                    &artifact.artifact_id,
+                    &artifact.artifact_hash,
+                )
+                .await
+            {
+                Ok(content_hash) => {
+                    self.artifact_storage
+                        .retain_artifact(&artifact.artifact_id)
+                        .await?;
+                    self.p2p_client.clone().provide(&content_hash).await?;
+                    report.imported_artifacts.push(artifact.artifact_id.clone());
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    report.existing_artifacts.push(artifact.artifact_id.clone());
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Unable to import artifact {}", artifact.artifact_id)
+                    })
+                }
+            }
+        }
+
+        if let Err(error) = self.enforce_disk_quota(None).await {
+            warn!("Unable to enforce the disk quota: {:?}", error);
+        }
+
+        info!(
+            "Imported {} artifacts and {} blocks from bundle signed by {}",
+            report.imported_artifacts.len(),
+            report.imported_blocks,
+            report.signer
+        );
+        Ok(report)
+    }
+
+    // Download the artifact from the p2p network, then make room for it by
+    // enforcing the disk quota. Nothing is downloaded when the content of the
+    // artifact is already stored for another artifact. Concurrent downloads of
+    // the same artifact would write to the same partial file, so they wait for
+    // each other and a download that waited reuses the artifact that the
+    // previous download stored.
+    async fn get_artifact_from_peers(
+        &mut self,
+        artifact_id: &str,
+        artifact_hash: &str,
+    ) -> anyhow::Result<()> {
+        let (_download_guard, waited) = self.download_locks.lock(artifact_id).await;
+        if waited
+            && self
+                .artifact_storage
+                .verify_local_artifact(artifact_id, artifact_hash)
+                .await?
+                .is_some()
+        {
+            debug!("Artifact {} was downloaded concurrently", artifact_id);
+            return Ok(());
+        }
+
+        if !self
+            .artifact_storage
+            .link_artifact(artifact_id, artifact_hash)
+            .await?
+        {
+            self.download_artifact_from_peers(artifact_id, artifact_hash)
+                .await?;
+        }
+        if let Err(error) = self.enforce_disk_quota(Some(artifact_id)).await {
+            warn!("Unable to enforce the disk quota: {:?}", error);
+        }
+        Ok(())
+    }
+
+    // Download the artifact from the p2p network. When several peers provide
+    // the artifact and swarm downloads are enabled, chunks are downloaded from
+    // all of them at once, otherwise the whole artifact is downloaded from the
+    // most idle peer. When a download fails, the next most idle peer is tried
+    // until `MAX_FETCH_ATTEMPTS` downloads were attempted. Artifacts are
+    // provided and requested by their content hash, which the downloaded
+    // artifact must match.
+    async fn download_artifact_from_peers(
+        &mut self,
+        artifact_id: &str,
+        artifact_hash: &str,
+    ) -> anyhow::Result<()> {
+        let providers = self.p2p_client.list_providers(artifact_hash).await?;
+
+        let mut attempts = 0;
+        let mut last_error = None;
+        let mut suspect_chunks = Vec::new();
+        if self.swarm_download && providers.len() > 1 {
+            attempts += 1;
+            match self
+                .get_artifact_from_swarm(
+                    providers.clone(),
+                    artifact_id,
+                    artifact_hash,
+                    &mut suspect_chunks,
+                )
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    warn!(
+                        "Swarm download of artifact {} failed: {:?}",
+                        artifact_id, error
+                    );
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        let mut tried_peers: HashSet<PeerId> = HashSet::new();
+        while attempts < MAX_FETCH_ATTEMPTS {
+            let candidates: HashSet<PeerId> = providers.difference(&tried_peers).copied().collect();
+            let peer_id = match self.p2p_client.get_idle_peer(candidates).await? {
+                Some(peer_id) => peer_id,
+                None => break,
+            };
+
+            attempts += 1;
+            tried_peers.insert(peer_id);
+            match self
+                .get_artifact_from_peer(&peer_id, artifact_id, artifact_hash)
+                .await
+            {
+                Ok(()) => {
+                    // peers that failed on their own were already reported
+                    tried_peers.remove(&peer_id);
+                    self.report_corrupt_chunks(artifact_id, &suspect_chunks, &tried_peers)
+                        .await;
+                    return Ok(());
+                }
+                Err(error) => {
+                    warn!(
+                        "Attempt {} of {} to download artifact {} from peer {} failed: {:?}",
+                        attempts, MAX_FETCH_ATTEMPTS, artifact_id, peer_id, error
+                    );
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        match last_error {
+            Some(error) => Err(error.context(format!(
+                "Unable to download artifact {} after {} attempts",
+                artifact_id, attempts
+            ))),
+            None => bail!(
+                "Artifact with id {} is not available on the p2p network.",
+                artifact_id
+            ),
+        }
+    }
+
+    // Download the artifact from the peer in chunks of at most `MAX_CHUNK_SIZE`
+    // bytes. Every chunk is written to a partial file in the artifact storage
+    // so an interrupted download resumes where it left off. The artifact is
+    // only moved into the artifact storage once all chunks are received and
+    // its hash is verified, so a failed download never replaces a good local
+    // copy. A hash mismatch is only attributed to the peer when it sent all
+    // chunks of the artifact.
+    async fn get_artifact_from_peer(
+        &mut self,
+        peer_id: &PeerId,
+        artifact_id: &str,
+        artifact_hash: &str,
+    ) -> anyhow::Result<()> {
+        let _transfer = metrics::start_transfer();
+        let mut offset = self
+            .artifact_storage
+            .partial_artifact_size(artifact_id)
+            .await?;
+        let resumed = offset > 0;
+        if resumed {
+            info!(
+                "Resuming download of artifact {} from peer {} at offset {}",
+                artifact_id, peer_id, offset
+            );
+        }
+
+        loop {
+            let chunk = match self
+                .p2p_client
+                .request_artifact(peer_id, artifact_hash, offset, MAX_CHUNK_SIZE)
+                .await
+            {
+                Ok(chunk) => chunk,
+                Err(error) => {
+                    self.report_failed_request(peer_id, &error).await;
+                    return Err(error);
+                }
+            };
+
+            if chunk.offset != offset || chunk.total_size < offset {
+                self.artifact_storage
+                    .discard_partial_artifact(artifact_id)?;
+                self.p2p_client
+                    .report_peer(peer_id, PeerOffense::ProtocolError)
+                    .await?;
+                bail!(
+                    "Peer {} returned chunk at offset {} of {} bytes for artifact {}, expected offset {}",
+                    peer_id,
+                    chunk.offset,
+                    chunk.total_size,
+                    artifact_id,
+                    offset
+                );
+            }
+
+            self.artifact_storage
+                .write_partial_artifact(artifact_id, offset, &chunk.data)
+                .await?;
+            offset += chunk.data.len() as u64;
+
+            if offset >= chunk.total_size {
+                break;
+            }
+            if chunk.data.is_empty() {
+                self.p2p_client
+                    .report_peer(peer_id, PeerOffense::ProtocolError)
+                    .await?;
+                bail!(
+                    "Peer {} returned an empty chunk at offset {} for artifact {}",
+                    peer_id,
+                    offset,
+                    artifact_id
+                );
+            }
+        }
+
+        match self
+            .artifact_storage
+            .complete_partial_artifact(artifact_id, artifact_hash)
+            .await
+        {
+            Ok(()) => {
+                self.p2p_client.report_success(peer_id);
+                Ok(())
+            }
+            Err(error) => {
+                if error.kind() == io::ErrorKind::InvalidData && !resumed {
+                    self.p2p_client
+                        .report_peer(peer_id, PeerOffense::HashMismatch)
+                        .await?;
+                }
+                Err(error).context("Error from get_artifact_from_peer")
+            }
+        }
+    }
+
+    // Download the artifact from several providers at once. The artifact is
+    // split in chunks of at most `MAX_CHUNK_SIZE` bytes that are handed out to
+    // idle providers. A provider that fails or doesn't answer within
+    // `SWARM_CHUNK_TIMEOUT` is dropped and its chunk is handed out to another
+    // provider. The size of the artifact is learned from the first chunk.
+    // Providers with a low reputation score are skipped. A hash mismatch is
+    // attributed to a provider right away when it sent all chunks of the
+    // artifact. Otherwise the received chunks are added to `suspect_chunks`,
+    // so the provider that sent corrupt chunks can be found once the artifact
+    // was downloaded from a single peer.
+    async fn get_artifact_from_swarm(
+        &mut self,
+        providers: HashSet<PeerId>,
+        artifact_id: &str,
+        artifact_hash: &str,
+        suspect_chunks: &mut Vec<SwarmChunk>,
+    ) -> anyhow::Result<()> {
+        let _transfer = metrics::start_transfer();
+        let start = self
+            .artifact_storage
+            .partial_artifact_size(artifact_id)
+            .await?;
+        let mut idle_peers: Vec<PeerId> = providers
+            .into_iter()
+            .filter(|peer_id| self.p2p_client.is_trusted_peer(peer_id))
+            .take(MAX_SWARM_PEERS)
+            .collect();
+        info!(
+            "Downloading artifact {} from {} peers starting at offset {}",
+            artifact_id,
+            idle_peers.len(),
+            start
+        );
+
+        let mut total_size: Option<u64> = None;
+        let mut pending: VecDeque<(u64, u64)> = VecDeque::from([(start, MAX_CHUNK_SIZE)]);
+        let mut received: BTreeMap<u64, u64> = BTreeMap::new();
+        let mut contributors: HashSet<PeerId> = HashSet::new();
+        let mut chunks: Vec<SwarmChunk> = Vec::new();
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            while !pending.is_empty() && !idle_peers.is_empty() {
+                let peer_id = idle_peers.pop().unwrap();
+                let (offset, length) = pending.pop_front().unwrap();
+                let p2p_client = self.p2p_client.clone();
+                let artifact_hash = artifact_hash.to_owned();
+                in_flight.push(async move {
+                    let result = tokio::time::timeout(
+                        SWARM_CHUNK_TIMEOUT,
+                        p2p_client.request_artifact(&peer_id, &artifact_hash, offset, length),
+                    )
+                    .await;
+                    (peer_id, offset, length, result)
+                });
+            }
+
+            let (peer_id, offset, length, result) = match in_flight.next().await {
+                Some(chunk_result) => chunk_result,
+                None => break,
+            };
+
+            let chunk = match result {
+                Ok(Ok(chunk)) => chunk,
+                Ok(Err(error)) => {
+                    warn!(
+                        "Peer {} failed to provide chunk at offset {} of artifact {}: {:?}",
+                        peer_id, offset, artifact_id, error
+                    );
+                    self.report_failed_request(&peer_id, &error).await;
+                    pending.push_front((offset, length));
+                    continue;
+                }
+                Err(_) => {
+                    warn!(
+                        "Peer {} stalled on chunk at offset {} of artifact {}",
+                        peer_id, offset, artifact_id
+                    );
+                    self.p2p_client
+                        .report_peer(&peer_id, PeerOffense::Timeout)
+                        .await?;
+                    pending.push_front((offset, length));
+                    continue;
+                }
+            };
+
+            let first_chunk = total_size.is_none();
+            let size = *total_size.get_or_insert(chunk.total_size);
+            let expected_end = size.min(offset + length);
+            let received_end = offset + chunk.data.len() as u64;
+            if chunk.offset != offset
+                || chunk.total_size != size
+                || received_end > expected_end
+                || (chunk.data.is_empty() && offset < expected_end)
+            {
+                warn!(
+                    "Peer {} returned an invalid chunk at offset {} of artifact {}",
+                    peer_id, offset, artifact_id
+                );
+                if first_chunk {
+                    total_size = None;
+                }
+                self.p2p_client
+                    .report_peer(&peer_id, PeerOffense::ProtocolError)
+                    .await?;
+                pending.push_front((offset, length));
+                continue;
+            }
+
+            if first_chunk {
+                // hand out the rest of the artifact
+                let mut next_offset = offset + length;
+                while next_offset < size {
+                    pending.push_back((next_offset, MAX_CHUNK_SIZE.min(size - next_offset)));
+                    next_offset += MAX_CHUNK_SIZE;
+                }
+            }
+            if received_end < expected_end {
+                pending.push_front((received_end, expected_end - received_end));
+            }
+
+            self.artifact_storage
+                .write_partial_artifact(artifact_id, offset, &chunk.data)
+                .await?;
+            received.insert(offset, chunk.data.len() as u64);
+            contributors.insert(peer_id);
+            chunks.push(SwarmChunk::new(peer_id, offset, &chunk.data));
+            idle_peers.push(peer_id);
+        }
+
+        if total_size.is_none() || !pending.is_empty() {
+            // keep the chunks that were received without gaps, to resume later on
+            let mut resume_offset = start;
+            while let Some(length) = received.get(&resume_offset).filter(|length| **length > 0) {
+                resume_offset += length;
+            }
+            self.artifact_storage
+                .truncate_partial_artifact(artifact_id, resume_offset)
+                .await
+                .ok();
+            bail!(
+                "No peer left to download artifact {} from, received {} of {:?} bytes",
+                artifact_id,
+                resume_offset,
+                total_size
+            );
+        }
+
+        match self
+            .artifact_storage
+            .complete_partial_artifact(artifact_id, artifact_hash)
+            .await
+        {
+            Ok(()) => {
+                contributors
+                    .iter()
+                    .for_each(|peer_id| self.p2p_client.report_success(peer_id));
+                Ok(())
+            }
+            Err(error) => {
+                if error.kind() == io::ErrorKind::InvalidData {
+                    // the chunks of an earlier download that were resumed may
+                    // have come from another peer
+                    if contributors.len() == 1 && start == 0 {
+                        let peer_id = contributors.iter().next().unwrap();
+                        self.p2p_client
+                            .report_peer(peer_id, PeerOffense::HashMismatch)
+                            .await?;
+                    } else {
+                        *suspect_chunks = chunks;
+                    }
+                }
+                Err(error).context("Error from get_artifact_from_swarm")
+            }
+        }
+    }
+
+    // Compare the chunks of a swarm download that failed its hash check with
+    // the verified artifact that was downloaded afterwards, and report the
+    // providers that sent chunks that differ from it. Providers in `excluded`
+    // were already reported.
+    async fn report_corrupt_chunks(
+        &self,
+        artifact_id: &str,
+        suspect_chunks: &[SwarmChunk],
+        excluded: &HashSet<PeerId>,
+    ) {
+        let mut offenders: HashSet<PeerId> = HashSet::new();
+        for chunk in suspect_chunks {
+            if offenders.contains(&chunk.peer_id) || excluded.contains(&chunk.peer_id) {
+                continue;
+            }
+            match self
+                .artifact_storage
+                .read_artifact_chunk(artifact_id, chunk.offset, chunk.length)
+                .await
+            {
+                Ok((_, data)) => {
+                    if SwarmChunk::new(chunk.peer_id, chunk.offset, &data) != *chunk {
+                        warn!(
+                            "Peer {} sent a corrupt chunk at offset {} of artifact {}",
+                            chunk.peer_id, chunk.offset, artifact_id
+                        );
+                        offenders.insert(chunk.peer_id);
+                    }
+                }
+                Err(error) => {
+                    warn!(
+                        "Unable to read chunk at offset {} of artifact {}: {:?}",
+                        chunk.offset, artifact_id, error
+                    );
+                    return;
+                }
+            }
+        }
+
+        for peer_id in offenders {
+            if let Err(e) = self
+                .p2p_client
+                .report_peer(&peer_id, PeerOffense::HashMismatch)
+                .await
+            {
+                warn!("Unable to report peer {}: {:?}", peer_id, e);
+            }
+        }
+    }
+
+    // Lower the reputation of a peer that failed to answer a request, when
+    // the failure is the fault of the peer.
+    async fn report_failed_request(&self, peer_id: &PeerId, error: &anyhow::Error) {
+        if let Some(offense) = PeerOffense::from_error(error) {
+            if let Err(e) = self.p2p_client.report_peer(peer_id, offense).await {
+                warn!("Unable to report peer {}: {:?}", peer_id, e);
+            }
+        }
+    }
+
+    async fn verify_artifact(
+        &mut self,
+        transparency_log: &TransparencyLog,
+        artifact: &[u8],
+    ) -> Result<(), TransparencyLogError> {
+        let mut sha256 = multihash::Sha2_256::default();
+        sha256.update(artifact);
+        let calculated_hash = hex::encode(sha256.finalize());
+
+        if transparency_log.artifact_hash == calculated_hash {
+            Ok(())
+        } else {
+            Err(TransparencyLogError::InvalidHash {
+                id: transparency_log.package_specific_artifact_id.clone(),
+                invalid_hash: calculated_hash,
+                actual_hash: transparency_log.artifact_hash.clone(),
+            })
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::blockchain_service::event::BlockchainEvent;
+    use crate::build_service::event::BuildEvent;
+    use crate::network::artifact_protocol::ArtifactResponse;
+    use crate::network::client::command::Command;
+    use crate::network::idle_metric_protocol::PeerMetrics;
+    use crate::util::test_util;
+    use libp2p::identity::ed25519::Keypair;
+    use libp2p::identity::PublicKey;
+    use pyrsia_blockchain_network::crypto::hash_algorithm::HashDigest;
+    use pyrsia_blockchain_network::signature::Signature;
+    use pyrsia_blockchain_network::structures::block::Block;
+    use pyrsia_blockchain_network::structures::header::Address;
+    use pyrsia_blockchain_network::structures::transaction::{Transaction, TransactionType};
+    use sha2::{Digest, Sha256};
+    use std::collections::HashSet;
+    use std::env;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use tokio::sync::mpsc;
+    use tokio::task;
+
+    const VALID_ARTIFACT_HASH: [u8; 32] = [
+        0x86, 0x5c, 0x8d, 0x98, 0x8b, 0xe4, 0x66, 0x9f, 0x3e, 0x48, 0xf7, 0x3b, 0x98, 0xf9, 0xbc,
+        0x25, 0x7, 0xbe, 0x2, 0x46, 0xea, 0x35, 0xe0, 0x9, 0x8c, 0xf6, 0x5, 0x4d, 0x36, 0x44, 0xc1,
+        0x4f,
+    ];
+
+    #[tokio::test]
+    async fn test_put_and_get_artifact() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (mut artifact_service, mut blockchain_event_receiver, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    }
+                    _ => panic!("Command must match Command::ListPeers"),
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        let package_type = PackageType::Docker;
+        let package_specific_id = "package_specific_id";
+        let package_specific_artifact_id = "package_specific_artifact_id";
+        let transparency_log_tuple = artifact_service
+            .transparency_log_service
+            .add_artifact(AddArtifactRequest {
+                package_type,
+                package_specific_id: package_specific_id.to_owned(),
+                num_artifacts: 8,
+                package_specific_artifact_id: package_specific_artifact_id.to_owned(),
+                artifact_hash: hex::encode(VALID_ARTIFACT_HASH),
+            })
+            .await
+            .unwrap();
+        let transparency_log = transparency_log_tuple.0;
+
+        //put the artifact
+        artifact_service
+            .artifact_storage
+            .push_artifact(get_file_reader().unwrap(), &transparency_log.artifact_id)
+            .await
+            .context("Error from push_artifact")
+            .unwrap();
+
+        // pull artifact
+        let future = {
+            artifact_service
+                .get_artifact(package_type, package_specific_artifact_id)
+                .await
+                .context("Error from get_artifact")
+        };
+        let file = task::spawn_blocking(|| future).await.unwrap().unwrap();
+
+        //validate pulled artifact with the actual data
+        let mut s = String::new();
+        get_file_reader().unwrap().read_to_string(&mut s).unwrap();
 
         let s1 = match str::from_utf8(file.as_slice()) {
             Ok(v) => v,
             Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
         };
-        assert_eq!(s, s1);
+        assert_eq!(s, s1);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_put_and_list_artifact() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, mut blockchain_event_receiver, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    }
+                    Some(Command::Provide { sender, .. }) => {
+                        let _ = sender.send(());
+                    }
+                    _ => panic!("Command must match Command::ListPeers or Command::Provide"),
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        let package_type = PackageType::Docker;
+        let package_specific_id = "package_specific_id";
+        let package_specific_artifact_id = "package_specific_artifact_id";
+        let transparency_log_tuple = artifact_service
+            .transparency_log_service
+            .add_artifact(AddArtifactRequest {
+                package_type,
+                package_specific_id: package_specific_id.to_owned(),
+                num_artifacts: 8,
+                package_specific_artifact_id: package_specific_artifact_id.to_owned(),
+                artifact_hash: hex::encode(VALID_ARTIFACT_HASH),
+            })
+            .await
+            .unwrap();
+
+        let transparency_log = transparency_log_tuple.0;
+        //put the artifact
+        artifact_service
+            .artifact_storage
+            .push_artifact(get_file_reader().unwrap(), &transparency_log.artifact_id)
+            .await
+            .context("Error from push_artifact")
+            .unwrap();
+
+        // provide artifacts
+        let future = {
+            artifact_service
+                .provide_local_artifacts()
+                .await
+                .context("Error from provide_local_artifacts")
+        };
+        let files = task::spawn_blocking(|| future).await.unwrap();
+        assert!(files.is_ok());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_from_peers() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (p2p_client, mut p2p_command_receiver) = test_util::tests::create_p2p_client();
+        let (mut artifact_service, mut blockchain_event_receiver, _) =
+            test_util::tests::create_artifact_service_with_p2p_client(&tmp_dir, p2p_client.clone());
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    },
+                    Some(Command::ListProviders { sender, .. }) => {
+                        let mut set = HashSet::new();
+                        set.insert(p2p_client.local_peer_id);
+                        let _ = sender.send(set);
+                    },
+                    Some(Command::RequestIdleMetric { sender, .. }) => {
+                        let _ = sender.send(Ok(PeerMetrics {
+                            idle_metric: 0.1_f64,
+                            ..Default::default()
+                        }));
+                    },
+                    Some(Command::RequestArtifact { offset, sender, .. }) => {
+                        let _ = sender.send(Ok(ArtifactResponse {
+                            total_size: 11,
+                            offset,
+                            data: b"SAMPLE_DATA"[offset as usize..].to_vec(),
+                        }));
+                    },
+                    _ => panic!("Command must match Command::ListPeers, Command::ListProviders, Command::RequestIdleMetric, Command::RequestArtifact"),
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"SAMPLE_DATA");
+        let random_hash = hex::encode(hasher.finalize());
+
+        let package_type = PackageType::Docker;
+        let package_specific_id = "package_specific_id";
+        let package_specific_artifact_id = "package_specific_artifact_id";
+        artifact_service
+            .transparency_log_service
+            .add_artifact(AddArtifactRequest {
+                package_type,
+                package_specific_id: package_specific_id.to_owned(),
+                num_artifacts: 8,
+                package_specific_artifact_id: package_specific_artifact_id.to_owned(),
+                artifact_hash: random_hash.clone(),
+            })
+            .await
+            .unwrap();
+
+        let future = {
+            artifact_service
+                .get_artifact(package_type, package_specific_artifact_id)
+                .await
+        };
+        let result = task::spawn_blocking(|| future).await.unwrap();
+        assert!(result.is_ok());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_downloads_of_an_artifact_download_it_once() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (mut artifact_service, _, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        let artifact_data = b"SAMPLE_DATA_IN_MANY_CHUNKS";
+        let artifact_hash = hex::encode(Sha256::digest(artifact_data));
+        let provider = PeerId::random();
+
+        let requests = tokio::spawn(async move {
+            let mut requests = 0;
+            while let Some(command) = p2p_command_receiver.recv().await {
+                match command {
+                    Command::ListProviders { sender, .. } => {
+                        let _ = sender.send(HashSet::from([provider]));
+                    }
+                    Command::RequestIdleMetric { sender, .. } => {
+                        let _ = sender.send(Ok(PeerMetrics::default()));
+                    }
+                    Command::RequestArtifact { offset, sender, .. } => {
+                        requests += 1;
+                        // answer slowly and with small chunks, so concurrent
+                        // downloads would interleave
+                        tokio::spawn(async move {
+                            tokio::time::sleep(Duration::from_millis(10)).await;
+                            let end = artifact_data.len().min(offset as usize + 4);
+                            let _ = sender.send(Ok(ArtifactResponse {
+                                total_size: artifact_data.len() as u64,
+                                offset,
+                                data: artifact_data[offset as usize..end].to_vec(),
+                            }));
+                        });
+                    }
+                    _ => panic!("Unexpected command"),
+                }
+            }
+            requests
+        });
+
+        let download = |mut artifact_service: ArtifactService| {
+            let artifact_hash = artifact_hash.clone();
+            async move {
+                artifact_service
+                    .get_artifact_from_peers("artifact_id", &artifact_hash)
+                    .await
+            }
+        };
+        let (first, second) = tokio::join!(
+            tokio::spawn(download(artifact_service.clone())),
+            tokio::spawn(download(artifact_service.clone()))
+        );
+        first.unwrap().unwrap();
+        second.unwrap().unwrap();
+
+        assert_eq!(
+            artifact_service
+                .get_artifact_locally("artifact_id")
+                .await
+                .unwrap(),
+            artifact_data
+        );
+
+        drop(artifact_service);
+        assert_eq!(requests.await.unwrap(), 7);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_from_peer_in_chunks_resumes_partial_download() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (mut artifact_service, _, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        let artifact_data = b"SAMPLE_DATA_IN_MANY_CHUNKS";
+        let artifact_id = hex::encode(Sha256::digest(artifact_data));
+
+        tokio::spawn(async move {
+            let mut expected_offset = 6;
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::RequestArtifact {
+                        offset,
+                        length,
+                        sender,
+                        ..
+                    }) => {
+                        assert_eq!(offset, expected_offset);
+                        assert_eq!(length, MAX_CHUNK_SIZE);
+                        // answer with smaller chunks than requested
+                        let end = artifact_data.len().min(offset as usize + 4);
+                        expected_offset = end as u64;
+                        let _ = sender.send(Ok(ArtifactResponse {
+                            total_size: artifact_data.len() as u64,
+                            offset,
+                            data: artifact_data[offset as usize..end].to_vec(),
+                        }));
+                    }
+                    _ => panic!("Command must match Command::RequestArtifact"),
+                }
+            }
+        });
+
+        artifact_service
+            .artifact_storage
+            .write_partial_artifact(&artifact_id, 0, &artifact_data[..6])
+            .await
+            .unwrap();
+
+        let peer_id = PeerId::random();
+        artifact_service
+            .get_artifact_from_peer(&peer_id, &artifact_id, &artifact_id)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            artifact_service
+                .get_artifact_locally(&artifact_id)
+                .await
+                .unwrap(),
+            artifact_data.to_vec()
+        );
+        assert_eq!(
+            artifact_service
+                .artifact_storage
+                .partial_artifact_size(&artifact_id)
+                .await
+                .unwrap(),
+            0
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_from_peer_with_invalid_hash_reports_peer() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (mut artifact_service, _, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::RequestArtifact { offset, sender, .. }) => {
+                        let _ = sender.send(Ok(ArtifactResponse {
+                            total_size: 8,
+                            offset,
+                            data: b"BAD_DATA"[offset as usize..].to_vec(),
+                        }));
+                    }
+                    _ => panic!("Command must match Command::RequestArtifact"),
+                }
+            }
+        });
+
+        let artifact_hash = hex::encode(Sha256::digest(b"SAMPLE_DATA"));
+        let peer_id = PeerId::random();
+        let result = artifact_service
+            .get_artifact_from_peer(&peer_id, "artifact_id", &artifact_hash)
+            .await;
+
+        assert!(result.is_err());
+        assert!(artifact_service
+            .get_artifact_locally("artifact_id")
+            .await
+            .is_err());
+        let peer_scores = artifact_service.p2p_client.peer_scores();
+        assert_eq!(peer_scores.len(), 1);
+        assert_eq!(peer_scores[0].peer_id, peer_id.to_string());
+        assert_eq!(peer_scores[0].hash_mismatches, 1);
+        // a single mismatch may be corruption in transit
+        assert!(artifact_service.p2p_client.is_trusted_peer(&peer_id));
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_from_swarm_reassigns_chunks_of_failing_peer() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (mut artifact_service, _, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        let artifact_data: Vec<u8> = (0..=255)
+            .cycle()
+            .take(2 * MAX_CHUNK_SIZE as usize + 1000)
+            .collect();
+        let artifact_hash = hex::encode(Sha256::digest(&artifact_data));
+        let artifact_id = "swarm_artifact_id";
+
+        let failing_peer = PeerId::random();
+        let providers = HashSet::from([failing_peer, PeerId::random(), PeerId::random()]);
+        let cloned_artifact_data = artifact_data.clone();
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListProviders { sender, .. }) => {
+                        let _ = sender.send(providers.clone());
+                    }
+                    Some(Command::RequestArtifact {
+                        peer,
+                        offset,
+                        length,
+                        sender,
+                        ..
+                    }) => {
+                        if peer == failing_peer {
+                            let _ = sender.send(Err(anyhow::anyhow!("connection closed")));
+                        } else {
+                            let total_size = cloned_artifact_data.len() as u64;
+                            let end = total_size.min(offset + length) as usize;
+                            let _ = sender.send(Ok(ArtifactResponse {
+                                total_size,
+                                offset,
+                                data: cloned_artifact_data[offset as usize..end].to_vec(),
+                            }));
+                        }
+                    }
+                    _ => panic!(
+                        "Command must match Command::ListProviders, Command::RequestArtifact"
+                    ),
+                }
+            }
+        });
+
+        artifact_service
+            .get_artifact_from_peers(artifact_id, &artifact_hash)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            artifact_service
+                .get_artifact_locally(artifact_id)
+                .await
+                .unwrap(),
+            artifact_data
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_from_swarm_reports_peer_that_sent_corrupt_chunk() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (mut artifact_service, _, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        let artifact_data: Vec<u8> = (0..=255)
+            .cycle()
+            .take(2 * MAX_CHUNK_SIZE as usize + 1000)
+            .collect();
+        let artifact_hash = hex::encode(Sha256::digest(&artifact_data));
+        let artifact_id = "swarm_artifact_id";
+
+        let good_peer = PeerId::random();
+        let corrupting_peer = PeerId::random();
+        let cloned_artifact_data = artifact_data.clone();
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListProviders { sender, .. }) => {
+                        let _ = sender.send(HashSet::from([good_peer, corrupting_peer]));
+                    }
+                    Some(Command::RequestIdleMetric { peer, sender }) => {
+                        let _ = sender.send(Ok(PeerMetrics {
+                            idle_metric: if peer == good_peer { 0.1 } else { 0.9 },
+                            ..Default::default()
+                        }));
+                    }
+                    Some(Command::RequestArtifact {
+                        peer,
+                        offset,
+                        length,
+                        sender,
+                        ..
+                    }) => {
+                        let total_size = cloned_artifact_data.len() as u64;
+                        let end = total_size.min(offset + length) as usize;
+                        let mut data = cloned_artifact_data[offset as usize..end].to_vec();
+                        if peer == corrupting_peer {
+                            data[0] = data[0].wrapping_add(1);
+                        }
+                        let _ = sender.send(Ok(ArtifactResponse {
+                            total_size,
+                            offset,
+                            data,
+                        }));
+                    }
+                    _ => panic!("Command must match Command::ListProviders, Command::RequestIdleMetric, Command::RequestArtifact"),
+                }
+            }
+        });
+
+        artifact_service
+            .get_artifact_from_peers(artifact_id, &artifact_hash)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            artifact_service
+                .get_artifact_locally(artifact_id)
+                .await
+                .unwrap(),
+            artifact_data
+        );
+        let scores = artifact_service.p2p_client.peer_scores();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].peer_id, corrupting_peer.to_string());
+        assert_eq!(scores[0].hash_mismatches, 1);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    // Answer p2p commands for `providers`, that have increasing idle metrics in the
+    // given order. Only the last provider returns the correct artifact data, the
+    // others fail the request or return bad data. Returns the receiver of the peers
+    // that an artifact was requested from.
+    fn mock_providers_with_failures(
+        mut p2p_command_receiver: mpsc::Receiver<Command>,
+        providers: Vec<PeerId>,
+        artifact_data: &'static [u8],
+    ) -> mpsc::UnboundedReceiver<PeerId> {
+        let (requested_peers_sender, requested_peers_receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListProviders { sender, .. }) => {
+                        let _ = sender.send(providers.iter().copied().collect());
+                    },
+                    Some(Command::RequestIdleMetric { peer, sender }) => {
+                        let position = providers.iter().position(|p| *p == peer).unwrap();
+                        let _ = sender.send(Ok(PeerMetrics {
+                            idle_metric: 0.6 + position as f64 / 100.0,
+                            ..Default::default()
+                        }));
+                    },
+                    Some(Command::RequestArtifact { peer, offset, sender, .. }) => {
+                        let _ = requested_peers_sender.send(peer);
+                        let position = providers.iter().position(|p| *p == peer).unwrap();
+                        if position == providers.len() - 1 {
+                            let _ = sender.send(Ok(ArtifactResponse {
+                                total_size: artifact_data.len() as u64,
+                                offset,
+                                data: artifact_data[offset as usize..].to_vec(),
+                            }));
+                        } else if position % 2 == 0 {
+                            let _ = sender.send(Err(anyhow::anyhow!("connection closed")));
+                        } else {
+                            let _ = sender.send(Ok(ArtifactResponse {
+                                total_size: 8,
+                                offset,
+                                data: b"BAD_DATA"[offset as usize..].to_vec(),
+                            }));
+                        }
+                    },
+                    _ => panic!("Command must match Command::ListProviders, Command::RequestIdleMetric, Command::RequestArtifact"),
+                }
+            }
+        });
+        requested_peers_receiver
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_from_peers_retries_alternate_providers() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (mut artifact_service, _, _, p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+        artifact_service.swarm_download = false;
+
+        let providers: Vec<PeerId> = (0..3).map(|_| PeerId::random()).collect();
+        let mut requested_peers =
+            mock_providers_with_failures(p2p_command_receiver, providers.clone(), b"SAMPLE_DATA");
+
+        let artifact_hash = hex::encode(Sha256::digest(b"SAMPLE_DATA"));
+        artifact_service
+            .get_artifact_from_peers("artifact_id", &artifact_hash)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            artifact_service
+                .get_artifact_locally("artifact_id")
+                .await
+                .unwrap(),
+            b"SAMPLE_DATA".to_vec()
+        );
+        for provider in providers {
+            assert_eq!(requested_peers.recv().await, Some(provider));
+        }
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_from_peers_gives_up_after_retry_budget() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (mut artifact_service, _, _, p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+        artifact_service.swarm_download = false;
+
+        let providers: Vec<PeerId> = (0..MAX_FETCH_ATTEMPTS + 1)
+            .map(|_| PeerId::random())
+            .collect();
+        let mut requested_peers =
+            mock_providers_with_failures(p2p_command_receiver, providers, b"SAMPLE_DATA");
+
+        let artifact_hash = hex::encode(Sha256::digest(b"SAMPLE_DATA"));
+        let result = artifact_service
+            .get_artifact_from_peers("artifact_id", &artifact_hash)
+            .await;
+
+        assert!(result.is_err());
+        assert!(artifact_service
+            .get_artifact_locally("artifact_id")
+            .await
+            .is_err());
+        let mut requests = 0;
+        while requested_peers.try_recv().is_ok() {
+            requests += 1;
+        }
+        assert_eq!(requests, MAX_FETCH_ATTEMPTS);
 
         test_util::tests::teardown(tmp_dir);
     }
 
     #[tokio::test]
-    async fn test_put_and_list_artifact() {
+    async fn test_enforce_disk_quota_evicts_least_recently_used_artifacts() {
         let tmp_dir = test_util::tests::setup();
 
-        let (artifact_service, mut blockchain_event_receiver, _, mut p2p_command_receiver) =
+        let (artifact_service, _, _, mut p2p_command_receiver) =
             test_util::tests::create_artifact_service(&tmp_dir);
+        let artifact_service = artifact_service.with_disk_quota(20);
+
+        let content = |artifact_id: &str| format!("{:<10}", artifact_id.replace("_alias", ""));
+        for artifact_id in [
+            "built",
+            "oldest",
+            "in_use_alias",
+            "older",
+            "in_use",
+            "newest",
+        ] {
+            artifact_service
+                .artifact_storage
+                .push_artifact(io::Cursor::new(content(artifact_id)), artifact_id)
+                .await
+                .unwrap();
+        }
+        artifact_service
+            .artifact_storage
+            .retain_artifact("built")
+            .await
+            .unwrap();
 
-        tokio::spawn(async move {
-            loop {
-                match p2p_command_receiver.recv().await {
-                    Some(Command::ListPeers { sender, .. }) => {
-                        let _ = sender.send(HashSet::new());
-                    }
-                    Some(Command::Provide { sender, .. }) => {
-                        let _ = sender.send(());
-                    }
-                    _ => panic!("Command must match Command::ListPeers or Command::Provide"),
-                }
+        let stopped_providing = tokio::spawn(async move {
+            let mut stopped_providing = Vec::new();
+            while let Some(Command::StopProviding { artifact_id }) =
+                p2p_command_receiver.recv().await
+            {
+                stopped_providing.push(artifact_id);
             }
+            stopped_providing
         });
 
-        tokio::spawn(async move {
-            loop {
-                match blockchain_event_receiver.recv().await {
-                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
-                        let _ = sender.send(Ok(()));
-                    }
-                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
-                }
-            }
-        });
+        artifact_service
+            .enforce_disk_quota(Some("in_use"))
+            .await
+            .unwrap();
 
-        let package_type = PackageType::Docker;
-        let package_specific_id = "package_specific_id";
-        let package_specific_artifact_id = "package_specific_artifact_id";
-        let transparency_log_tuple = artifact_service
-            .transparency_log_service
-            .add_artifact(AddArtifactRequest {
-                package_type,
-                package_specific_id: package_specific_id.to_owned(),
-                num_artifacts: 8,
-                package_specific_artifact_id: package_specific_artifact_id.to_owned(),
-                artifact_hash: hex::encode(VALID_ARTIFACT_HASH),
-            })
+        assert_eq!(artifact_service.artifact_storage.usage().await.unwrap(), 20);
+        assert!(artifact_service
+            .artifact_storage
+            .pull_artifact("built")
+            .await
+            .is_ok());
+        assert!(artifact_service
+            .artifact_storage
+            .pull_artifact("in_use")
+            .await
+            .is_ok());
+        assert!(artifact_service
+            .artifact_storage
+            .pull_artifact("in_use_alias")
+            .await
+            .is_err());
+
+        drop(artifact_service);
+        let expected_hashes: Vec<String> = ["oldest", "older", "newest"]
+            .iter()
+            .map(|artifact_id| hex::encode(Sha256::digest(content(artifact_id))))
+            .collect();
+        assert_eq!(stopped_providing.await.unwrap(), expected_hashes);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_get_from_peers_links_content_that_is_already_stored() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (mut artifact_service, _, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+        let artifact_hash = artifact_service
+            .artifact_storage
+            .push_artifact("SAMPLE_DATA".as_bytes(), "artifact_id")
             .await
             .unwrap();
 
-        let transparency_log = transparency_log_tuple.0;
-        //put the artifact
         artifact_service
-            .put_artifact(
-                &transparency_log.artifact_id,
-                &mut get_file_reader().unwrap(),
-            )
-            .context("Error from put_artifact")
+            .get_artifact_from_peers("other_artifact_id", &artifact_hash)
+            .await
             .unwrap();
 
-        // provide artifacts
-        let future = {
+        assert!(p2p_command_receiver.try_recv().is_err());
+        assert_eq!(
             artifact_service
-                .provide_local_artifacts()
+                .get_artifact_locally("other_artifact_id")
                 .await
-                .context("Error from provide_local_artifacts")
-        };
-        let files = task::spawn_blocking(|| future).await.unwrap();
-        assert!(files.is_ok());
+                .unwrap(),
+            b"SAMPLE_DATA"
+        );
+        assert_eq!(
+            artifact_service
+                .artifact_storage
+                .list_artifacts()
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
 
         test_util::tests::teardown(tmp_dir);
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_get_from_peers() {
+    #[tokio::test]
+    async fn test_scrub_repairs_corrupt_artifacts() {
         let tmp_dir = test_util::tests::setup();
 
-        let (p2p_client, mut p2p_command_receiver) = test_util::tests::create_p2p_client();
-        let (mut artifact_service, mut blockchain_event_receiver, _) =
-            test_util::tests::create_artifact_service_with_p2p_client(&tmp_dir, p2p_client.clone());
-
-        tokio::spawn(async move {
-            loop {
-                match p2p_command_receiver.recv().await {
-                    Some(Command::ListPeers { sender, .. }) => {
-                        let _ = sender.send(HashSet::new());
-                    },
-                    Some(Command::ListProviders { sender, .. }) => {
-                        let mut set = HashSet::new();
-                        set.insert(p2p_client.local_peer_id);
-                        let _ = sender.send(set);
-                    },
-                    Some(Command::RequestIdleMetric { sender, .. }) => {
+        let (artifact_service, _, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+        let corrupt_hash = artifact_service
+            .artifact_storage
+            .push_artifact("SAMPLE_DATA".as_bytes(), "corrupt")
+            .await
+            .unwrap();
+        let healthy_hash = artifact_service
+            .artifact_storage
+            .push_artifact("HEALTHY".as_bytes(), "healthy")
+            .await
+            .unwrap();
+        std::fs::write(tmp_dir.join(format!("{}.file", corrupt_hash)), "BAD_DATA").unwrap();
+
+        let other_peer = PeerId::random();
+        let p2p_commands = tokio::spawn(async move {
+            let mut p2p_commands = Vec::new();
+            while let Some(command) = p2p_command_receiver.recv().await {
+                match command {
+                    Command::StopProviding { artifact_id } => {
+                        p2p_commands.push(format!("stop providing {}", artifact_id));
+                    }
+                    Command::ListProviders { sender, .. } => {
+                        let _ = sender.send(HashSet::from([other_peer]));
+                    }
+                    Command::RequestIdleMetric { sender, .. } => {
                         let _ = sender.send(Ok(PeerMetrics {
-                            idle_metric: (0.1_f64).to_le_bytes()
+                            idle_metric: 0.1_f64,
+                            ..Default::default()
                         }));
-                    },
-                    Some(Command::RequestArtifact { sender, .. }) => {
-                        let _ = sender.send(Ok(b"SAMPLE_DATA".to_vec()));
-                    },
-                    _ => panic!("Command must match Command::ListPeers, Command::ListProviders, Command::RequestIdleMetric, Command::RequestArtifact"),
+                    }
+                    Command::RequestArtifact { offset, sender, .. } => {
+                        let _ = sender.send(Ok(ArtifactResponse {
+                            total_size: 11,
+                            offset,
+                            data: b"SAMPLE_DATA"[offset as usize..].to_vec(),
+                        }));
+                    }
+                    Command::Provide {
+                        artifact_id,
+                        sender,
+                    } => {
+                        p2p_commands.push(format!("provide {}", artifact_id));
+                        let _ = sender.send(());
+                    }
+                    _ => panic!("Unexpected command"),
                 }
             }
+            p2p_commands
         });
 
-        tokio::spawn(async move {
-            loop {
-                match blockchain_event_receiver.recv().await {
-                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
-                        let _ = sender.send(Ok(()));
+        let report = artifact_service.scrub_artifacts().await.unwrap();
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.healthy, 1);
+        assert_eq!(
+            report.corrupt,
+            vec![CorruptArtifact {
+                content_hash: corrupt_hash.clone(),
+                artifact_ids: vec!["corrupt".to_owned()],
+                expected_hash: corrupt_hash.clone(),
+                actual_hash: Some(hex::encode(Sha256::digest("BAD_DATA"))),
+                repaired: true,
+                error: None,
+            }]
+        );
+        assert_eq!(artifact_service.scrub_report(), Some(report));
+        assert_eq!(
+            artifact_service
+                .artifact_storage
+                .pull_artifact("corrupt")
+                .await
+                .unwrap(),
+            b"SAMPLE_DATA"
+        );
+        assert!(tmp_dir
+            .join("quarantine")
+            .join(format!("{}.file", corrupt_hash))
+            .exists());
+        assert_eq!(
+            artifact_service
+                .artifact_storage
+                .hash_content(&healthy_hash)
+                .await
+                .unwrap(),
+            healthy_hash
+        );
+
+        drop(artifact_service);
+        assert_eq!(
+            p2p_commands.await.unwrap(),
+            vec![
+                format!("stop providing {}", corrupt_hash),
+                format!("provide {}", corrupt_hash)
+            ]
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_collect_garbage() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, _, mut build_event_receiver, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+        let (transparency_log, _) = artifact_service
+            .transparency_log_service
+            .add_artifact(AddArtifactRequest {
+                package_type: PackageType::Maven2,
+                package_specific_id: "group:artifact:1.0".to_owned(),
+                num_artifacts: 1,
+                package_specific_artifact_id: "group/artifact/1.0/artifact-1.0.jar".to_owned(),
+                artifact_hash: hex::encode(Sha256::digest("REFERENCED")),
+            })
+            .await
+            .unwrap();
+        let storage = &artifact_service.artifact_storage;
+        storage
+            .push_artifact("REFERENCED".as_bytes(), &transparency_log.artifact_id)
+            .await
+            .unwrap();
+        storage
+            .push_artifact("REFERENCED".as_bytes(), "shared")
+            .await
+            .unwrap();
+        let orphaned_hash = storage
+            .push_artifact("ORPHANED".as_bytes(), "orphaned")
+            .await
+            .unwrap();
+
+        let build_events = tokio::spawn(async move {
+            let mut dry_runs = Vec::new();
+            while let Some(build_event) = build_event_receiver.recv().await {
+                match build_event {
+                    BuildEvent::CollectGarbage { dry_run, sender } => {
+                        dry_runs.push(dry_run);
+                        let _ = sender.send(Ok(vec!["build_id".to_owned()]));
                     }
-                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                    _ => panic!("Unexpected build event"),
+                }
+            }
+            dry_runs
+        });
+        let p2p_commands = tokio::spawn(async move {
+            let mut p2p_commands = Vec::new();
+            while let Some(command) = p2p_command_receiver.recv().await {
+                match command {
+                    Command::StopProviding { artifact_id } => {
+                        p2p_commands.push(format!("stop providing {}", artifact_id));
+                    }
+                    _ => panic!("Unexpected command"),
                 }
             }
+            p2p_commands
         });
 
-        let mut hasher = Sha256::new();
-        hasher.update(b"SAMPLE_DATA");
-        let random_hash = hex::encode(hasher.finalize());
+        let report = artifact_service.collect_garbage(true).await.unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.orphaned_artifacts, vec!["orphaned", "shared"]);
+        assert_eq!(report.stale_builds, vec!["build_id"]);
+        assert_eq!(report.reclaimed_bytes, 8);
+        assert_eq!(storage.list_artifacts().await.unwrap().len(), 2);
+
+        let report = artifact_service.collect_garbage(false).await.unwrap();
+        assert!(!report.dry_run);
+        assert_eq!(report.orphaned_artifacts, vec!["orphaned", "shared"]);
+        assert_eq!(artifact_service.gc_report(), Some(report));
+        assert!(storage.pull_artifact("orphaned").await.is_err());
+        assert!(storage.pull_artifact("shared").await.is_err());
+        assert_eq!(
+            storage
+                .pull_artifact(&transparency_log.artifact_id)
+                .await
+                .unwrap(),
+            b"REFERENCED"
+        );
 
-        let package_type = PackageType::Docker;
-        let package_specific_id = "package_specific_id";
-        let package_specific_artifact_id = "package_specific_artifact_id";
-        artifact_service
+        drop(artifact_service);
+        assert_eq!(build_events.await.unwrap(), vec![true, false]);
+        assert_eq!(
+            p2p_commands.await.unwrap(),
+            vec![format!("stop providing {}", orphaned_hash)]
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_pin_package() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, _, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+        let artifact_service = artifact_service.with_disk_quota(12);
+        let (transparency_log, _) = artifact_service
             .transparency_log_service
             .add_artifact(AddArtifactRequest {
-                package_type,
-                package_specific_id: package_specific_id.to_owned(),
-                num_artifacts: 8,
-                package_specific_artifact_id: package_specific_artifact_id.to_owned(),
-                artifact_hash: random_hash.clone(),
+                package_type: PackageType::Maven2,
+                package_specific_id: "group:artifact:1.0".to_owned(),
+                num_artifacts: 1,
+                package_specific_artifact_id: "group/artifact/1.0/artifact-1.0.jar".to_owned(),
+                artifact_hash: hex::encode(Sha256::digest("PINNED_DATA")),
             })
             .await
             .unwrap();
+        let storage = &artifact_service.artifact_storage;
+        storage
+            .push_artifact("PINNED_DATA".as_bytes(), &transparency_log.artifact_id)
+            .await
+            .unwrap();
 
-        let future = {
-            artifact_service
-                .get_artifact(package_type, package_specific_artifact_id)
+        let p2p_commands = tokio::spawn(async move {
+            let mut p2p_commands = Vec::new();
+            while let Some(command) = p2p_command_receiver.recv().await {
+                match command {
+                    Command::Provide {
+                        artifact_id,
+                        sender,
+                    } => {
+                        p2p_commands.push(format!("provide {}", artifact_id));
+                        let _ = sender.send(());
+                    }
+                    Command::StopProviding { artifact_id } => {
+                        p2p_commands.push(format!("stop providing {}", artifact_id));
+                    }
+                    _ => panic!("Unexpected command"),
+                }
+            }
+            p2p_commands
+        });
+
+        let pin = artifact_service
+            .pin_package(PackageType::Maven2, "group:artifact:1.0")
+            .unwrap();
+        assert_eq!(pin.state, PinState::Pending);
+        artifact_service
+            .pin_package(PackageType::Maven2, "group:artifact:2.0")
+            .unwrap();
+
+        let statuses = artifact_service.refresh_pins().await.unwrap();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[0].state, PinState::Available);
+        assert_eq!(statuses[0].artifacts, 1);
+        assert_eq!(statuses[0].size, 11);
+        assert_eq!(statuses[0].error, None);
+        assert!(statuses[0].refreshed_at.is_some());
+        assert_eq!(statuses[1].state, PinState::Failed);
+        assert_eq!(
+            statuses[1].error.as_deref(),
+            Some("Package group:artifact:2.0 of type Maven2 not found in transparency log")
+        );
+        assert_eq!(artifact_service.pins(), statuses);
+
+        let other_hash = storage
+            .push_artifact("OTHER".as_bytes(), "other")
+            .await
+            .unwrap();
+        artifact_service.enforce_disk_quota(None).await.unwrap();
+        assert!(storage.pull_artifact("other").await.is_err());
+        assert!(storage
+            .pull_artifact(&transparency_log.artifact_id)
+            .await
+            .is_ok());
+
+        assert!(artifact_service
+            .unpin_package(PackageType::Maven2, "group:artifact:1.0")
+            .unwrap());
+        assert!(artifact_service
+            .refresh_pin(PackageType::Maven2, "group:artifact:1.0")
+            .await
+            .is_err());
+        artifact_service
+            .unpin_package(PackageType::Maven2, "group:artifact:2.0")
+            .unwrap();
+        assert!(artifact_service.pins().is_empty());
+
+        drop(artifact_service);
+        assert_eq!(
+            p2p_commands.await.unwrap(),
+            vec![
+                format!("provide {}", hex::encode(Sha256::digest("PINNED_DATA"))),
+                format!("stop providing {}", other_hash)
+            ]
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    // Answer the blockchain events of a node of which the blockchain holds the
+    // specified blocks. Blocks that are broadcast to the node are appended.
+    fn handle_bundle_blockchain_events(
+        mut blockchain_event_receiver: mpsc::Receiver<BlockchainEvent>,
+        keypair: Keypair,
+        mut blocks: Vec<Block>,
+    ) -> task::JoinHandle<Vec<Block>> {
+        tokio::spawn(async move {
+            while let Some(blockchain_event) = blockchain_event_receiver.recv().await {
+                match blockchain_event {
+                    BlockchainEvent::PullAllBlocksLocal { sender } => {
+                        let _ = sender.send(Ok(blocks.clone()));
+                    }
+                    BlockchainEvent::SignPayload { payload, sender } => {
+                        let _ = sender.send((
+                            keypair.public().encode(),
+                            Signature::new(&payload, &keypair),
+                        ));
+                    }
+                    BlockchainEvent::HandleBlockBroadcast { block, sender, .. } => {
+                        blocks.push(*block);
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("Unexpected blockchain event"),
+                }
+            }
+            blocks
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_export_and_import_bundle() {
+        let tmp_dir = test_util::tests::setup();
+        let exporter_dir = tmp_dir.join("exporter");
+        let importer_dir = tmp_dir.join("importer");
+        std::fs::create_dir_all(&exporter_dir).unwrap();
+        std::fs::create_dir_all(&importer_dir).unwrap();
+
+        let (exporter, blockchain_event_receiver, _, _) =
+            test_util::tests::create_artifact_service(&exporter_dir);
+        let keypair = Keypair::generate();
+        let submitter = Address::from(PublicKey::Ed25519(keypair.public()));
+        let mut blocks = vec![Block::new(HashDigest::new(b""), 0, vec![], &keypair)];
+        let mut transparency_logs = vec![];
+        for (file_name, content) in [("artifact-1.0.jar", "JAR"), ("artifact-1.0.pom", "POM")] {
+            let (transparency_log, payload) = exporter
+                .transparency_log_service
+                .add_artifact(AddArtifactRequest {
+                    package_type: PackageType::Maven2,
+                    package_specific_id: "group:artifact:1.0".to_owned(),
+                    num_artifacts: 2,
+                    package_specific_artifact_id: format!("group/artifact/1.0/{}", file_name),
+                    artifact_hash: hex::encode(Sha256::digest(content)),
+                })
+                .await
+                .unwrap();
+            exporter
+                .artifact_storage
+                .push_artifact(content.as_bytes(), &transparency_log.artifact_id)
                 .await
+                .unwrap();
+            let transaction = Transaction::new(
+                TransactionType::Create,
+                submitter,
+                payload.into_bytes(),
+                &keypair,
+            );
+            let parent = blocks.last().unwrap();
+            let block = Block::new(
+                parent.header.hash(),
+                parent.header.ordinal + 1,
+                vec![transaction],
+                &keypair,
+            );
+            blocks.push(block);
+            transparency_logs.push(transparency_log);
+        }
+        let exported_genesis_hash = bundle::block_hash(&blocks[0]);
+        let exporter_peer_id = PeerId::from_public_key(&PublicKey::Ed25519(keypair.public()));
+        let exporter_blockchain =
+            handle_bundle_blockchain_events(blockchain_event_receiver, keypair.clone(), blocks);
+
+        let packages = vec![BundlePackage {
+            package_type: PackageType::Maven2,
+            package_specific_id: "group:artifact:1.0".to_owned(),
+        }];
+        let (size, bundle_stream) = exporter.export_bundle(packages.clone()).await.unwrap();
+        let bundle = bundle_stream
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await
+            .unwrap();
+        assert_eq!(size, bundle.len() as u64);
+        let bundle_path = tmp_dir.join("bundle");
+        std::fs::write(&bundle_path, &bundle).unwrap();
+
+        let missing_package = BundlePackage {
+            package_type: PackageType::Maven2,
+            package_specific_id: "group:missing:1.0".to_owned(),
         };
-        let result = task::spawn_blocking(|| future).await.unwrap();
-        assert!(result.is_ok());
+        assert!(exporter.export_bundle(vec![missing_package]).await.is_err());
+
+        let (mut importer, blockchain_event_receiver, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&importer_dir);
+        let importer_blockchain =
+            handle_bundle_blockchain_events(blockchain_event_receiver, keypair.clone(), vec![]);
+        let p2p_commands = tokio::spawn(async move {
+            let mut provided = Vec::new();
+            while let Some(command) = p2p_command_receiver.recv().await {
+                match command {
+                    Command::Provide {
+                        artifact_id,
+                        sender,
+                    } => {
+                        provided.push(artifact_id);
+                        let _ = sender.send(());
+                    }
+                    _ => panic!("Unexpected command"),
+                }
+            }
+            provided
+        });
+
+        // an empty node only imports bundles of trusted nodes
+        let error = importer.import_bundle_file(&bundle_path).await.unwrap_err();
+        assert!(error.to_string().contains("PYRSIA_BUNDLE_GENESIS_HASH"));
+        importer.bundle_trust.genesis_hash = Some(exported_genesis_hash);
+        let error = importer.import_bundle_file(&bundle_path).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "The bundle is signed by untrusted node {}",
+                exporter_peer_id
+            )
+        );
+        importer.bundle_trust.trusted_signers = HashSet::from([exporter_peer_id]);
+
+        // the content of the artifacts follows the manifest
+        let mut tampered_bundle = bundle.clone();
+        let first_artifact_offset = bundle.len() - "JARPOM".len();
+        tampered_bundle[first_artifact_offset] = b'W';
+        let tampered_bundle_path = tmp_dir.join("tampered_bundle");
+        std::fs::write(&tampered_bundle_path, &tampered_bundle).unwrap();
+        assert!(importer
+            .import_bundle_file(&tampered_bundle_path)
+            .await
+            .is_err());
+        assert!(importer
+            .artifact_storage
+            .pull_artifact(&transparency_logs[0].artifact_id)
+            .await
+            .is_err());
+
+        let bundle_chunks: Vec<io::Result<Bytes>> = bundle
+            .chunks(7)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        let report = importer
+            .import_bundle(Box::pin(stream::iter(bundle_chunks)))
+            .await
+            .unwrap();
+        assert_eq!(report.signer, exporter_peer_id.to_string());
+        assert_eq!(report.packages, packages);
+        assert_eq!(
+            report.imported_artifacts,
+            vec![
+                transparency_logs[0].artifact_id.clone(),
+                transparency_logs[1].artifact_id.clone()
+            ]
+        );
+        assert!(report.existing_artifacts.is_empty());
+        assert_eq!(report.imported_blocks, 3);
+        for (transparency_log, content) in transparency_logs.iter().zip(["JAR", "POM"]) {
+            assert_eq!(
+                importer
+                    .artifact_storage
+                    .pull_artifact(&transparency_log.artifact_id)
+                    .await
+                    .unwrap(),
+                content.as_bytes()
+            );
+            assert!(importer
+                .artifact_storage
+                .is_artifact_retained(&transparency_log.artifact_id)
+                .await
+                .unwrap());
+            assert_eq!(
+                importer
+                    .transparency_log_service
+                    .find_transparency_log(&transparency_log.id)
+                    .unwrap(),
+                *transparency_log
+            );
+        }
+
+        let report = importer.import_bundle_file(&bundle_path).await.unwrap();
+        assert!(report.imported_artifacts.is_empty());
+        assert_eq!(report.existing_artifacts.len(), 2);
+        assert_eq!(report.imported_blocks, 0);
+
+        drop(exporter);
+        drop(importer);
+        assert_eq!(
+            exporter_blockchain.await.unwrap(),
+            importer_blockchain.await.unwrap()
+        );
+        assert_eq!(
+            p2p_commands.await.unwrap(),
+            vec![
+                transparency_logs[0].artifact_hash.clone(),
+                transparency_logs[1].artifact_hash.clone()
+            ]
+        );
 
         test_util::tests::teardown(tmp_dir);
     }
@@ -655,7 +3033,11 @@ mod tests {
         let hash_bytes = hasher.finalize();
         let artifact_id = hex::encode(hash_bytes);
 
-        let future = { artifact_service.get_artifact_from_peers(&artifact_id).await };
+        let future = {
+            artifact_service
+                .get_artifact_from_peers(&artifact_id, &artifact_id)
+                .await
+        };
         let result = task::spawn_blocking(|| future).await.unwrap();
         assert!(result.is_err());
 
@@ -14,16 +14,62 @@
    limitations under the License.
 */
 
+pub mod backend;
+pub mod compression;
+pub mod file_system;
+pub mod s3;
+
 use crate::util::env_util::read_var;
 use anyhow::{anyhow, Context, Result};
+use backend::{ObjectMetadata, StorageBackend};
+use bytes::Bytes;
+use compression::SeekTable;
+use file_system::FileSystemBackend;
+use futures::stream::{self, Stream, StreamExt};
 use lazy_static::lazy_static;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
-use std::io::{self, BufWriter, Read, Write};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
 use std::panic::UnwindSafe;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
 
 const FILE_EXTENSION: &str = "file";
+const COMPRESSED_FILE_EXTENSION: &str = "zst";
+const PARTIAL_FILE_EXTENSION: &str = "part";
+const TEMP_FILE_EXTENSION: &str = "tmp";
+const RETAINED_FILE_EXTENSION: &str = "keep";
+const ALIAS_FILE_EXTENSION: &str = "ref";
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+const HASHED_CHUNKS_QUEUE_LENGTH: usize = 16;
+const QUARANTINE_DIR: &str = "quarantine";
+const RETENTION_UPGRADE_MARKER: &str = "retention_upgraded";
+
+/// The time after which a partial download that wasn't resumed is considered
+/// abandoned, see [`ArtifactStorage::remove_stale_files`].
+pub const PARTIAL_ARTIFACT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A stream of the bytes of an artifact, as returned by [`ArtifactStorage::stream_artifact`].
+pub type ArtifactStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+/// A byte range of an artifact, as requested by a `Range` header.
+pub enum ArtifactRange {
+    /// The inclusive byte range `start..=end` of an artifact of `size` bytes.
+    Partial {
+        start: u64,
+        end: u64,
+        size: u64,
+        stream: ArtifactStream,
+    },
+    /// The requested range doesn't overlap with the artifact of `size` bytes.
+    Unsatisfiable { size: u64 },
+}
 
 lazy_static! {
     pub static ref ARTIFACTS_DIR: String = {
@@ -65,17 +111,56 @@ fn log_static_initialization_failure<T: UnwindSafe>(
     }
 }
 
+/// The artifact storage is content-addressed: the bytes of an artifact are
+/// stored once in an object that is named after their SHA-256 hash, and every
+/// artifact id that refers to these bytes is an alias of the content hash.
+/// The content is removed when the last alias that refers to it is removed.
+///
+/// The objects are kept in a [`StorageBackend`]. Partial downloads and
+/// artifacts that are being pushed are always staged in the local repository
+/// path, and only moved to the backend once they are complete.
+///
+/// Content can optionally be compressed with zstd, see
+/// [`ArtifactStorage::with_compression_level`]. Compressed content is stored
+/// in its own kind of object, but is still named after the hash of the
+/// uncompressed bytes, and is decompressed whenever it is read. It is
+/// compressed in independent frames, so reading a range of it only
+/// decompresses the frames that hold the range.
 #[derive(Clone)]
 pub struct ArtifactStorage {
     repository_path: PathBuf,
+    backend: Arc<dyn StorageBackend>,
+    compression_level: Option<i32>,
+    verified_artifacts: Arc<Mutex<HashSet<String>>>,
+    access_times: Arc<Mutex<HashMap<String, SystemTime>>>,
+    aliases: Arc<Mutex<Option<HashMap<String, String>>>>,
+    objects: Arc<Mutex<Option<ObjectIndex>>>,
 }
 
 impl ArtifactStorage {
+    /// Create an artifact storage that keeps its objects in files in the
+    /// local repository path.
     pub fn new<P: AsRef<Path>>(repository_path: P) -> Result<ArtifactStorage, anyhow::Error> {
+        let backend = FileSystemBackend::new(&repository_path)?;
+        ArtifactStorage::with_backend(repository_path, Arc::new(backend))
+    }
+
+    /// Create an artifact storage that keeps its objects in `backend`, and
+    /// stages partial artifacts in the local repository path.
+    pub fn with_backend<P: AsRef<Path>>(
+        repository_path: P,
+        backend: Arc<dyn StorageBackend>,
+    ) -> Result<ArtifactStorage, anyhow::Error> {
         let absolute_path = repository_path.as_ref().to_path_buf().canonicalize()?;
         if absolute_path.is_dir() {
             Ok(ArtifactStorage {
                 repository_path: absolute_path,
+                backend,
+                compression_level: None,
+                verified_artifacts: Arc::new(Mutex::new(HashSet::new())),
+                access_times: Arc::new(Mutex::new(HashMap::new())),
+                aliases: Arc::new(Mutex::new(None)),
+                objects: Arc::new(Mutex::new(None)),
             })
         } else {
             error!(
@@ -86,6 +171,16 @@ impl ArtifactStorage {
         }
     }
 
+    /// Compress the content of artifacts that are stored from now on with the
+    /// specified zstd level, unless their media type tells they are already
+    /// compressed. A level of 0 disables compression.
+    pub fn with_compression_level(self, compression_level: i32) -> Self {
+        ArtifactStorage {
+            compression_level: Some(compression_level).filter(|level| *level != 0),
+            ..self
+        }
+    }
+
     // The base file path (no extension on the file name) that will correspond to this artifact id.
     // The structure of the path is: `repo_root_dir/artifact_id`. This consists of the artifact
     // repository root directory and a file name that is the artifact id. For example:
@@ -99,181 +194,2201 @@ impl ArtifactStorage {
         path
     }
 
-    fn artifact_file_path(&self, artifact_id: &str) -> io::Result<PathBuf> {
+    fn partial_artifact_file_path(&self, artifact_id: &str) -> PathBuf {
         let mut base_file_path: PathBuf = self.base_file_path(artifact_id);
-        base_file_path.set_extension(FILE_EXTENSION);
-        Ok(base_file_path)
+        base_file_path.set_extension(PARTIAL_FILE_EXTENSION);
+        base_file_path
+    }
+
+    // A new temporary file to stage an artifact that is being pushed. Every
+    // push gets its own file, so concurrent pushes never write to the same file.
+    pub(crate) fn temp_file_path(&self, artifact_id: &str) -> PathBuf {
+        self.repository_path.join(format!(
+            "{}.{}.{}",
+            artifact_id,
+            Uuid::new_v4(),
+            TEMP_FILE_EXTENSION
+        ))
+    }
+
+    // Remember that the artifact was accessed just now. Access times are only
+    // kept in memory, artifacts that weren't accessed since the node started
+    // fall back to the time they were written.
+    fn touch_artifact(&self, artifact_id: &str) {
+        self.access_times
+            .lock()
+            .unwrap()
+            .insert(artifact_id.to_owned(), SystemTime::now());
+    }
+
+    // The objects are listed from the backend the first time they are needed,
+    // and kept up to date by this storage from then on. Listing all objects
+    // is slow for object stores, so this keeps the usage of the storage and
+    // the eviction candidates cheap. Only this storage writes to the backend.
+    async fn load_objects(&self) -> io::Result<()> {
+        if self.objects.lock().unwrap().is_some() {
+            return Ok(());
+        }
+        let objects = self.backend.list().await?;
+        self.objects
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| objects.into_iter().collect());
+        Ok(())
+    }
+
+    // The metadata of all objects in the backend.
+    async fn list_objects(&self) -> io::Result<Vec<ObjectMetadata>> {
+        self.load_objects().await?;
+        Ok(self.with_objects(|objects| objects.objects.values().cloned().collect()))
+    }
+
+    fn with_objects<T>(&self, f: impl FnOnce(&mut ObjectIndex) -> T) -> T {
+        f(self
+            .objects
+            .lock()
+            .unwrap()
+            .get_or_insert_with(ObjectIndex::default))
+    }
+
+    // Record a change of the backend in the object index, unless the objects
+    // weren't listed yet.
+    fn update_objects(&self, f: impl FnOnce(&mut ObjectIndex)) {
+        if let Some(objects) = self.objects.lock().unwrap().as_mut() {
+            f(objects);
+        }
+    }
+
+    async fn put_object(&self, key: &str, path: &Path) -> io::Result<()> {
+        let size = tokio::fs::metadata(path).await?.len();
+        self.backend.put(key, path).await?;
+        self.update_objects(|objects| objects.insert(new_object(key, size)));
+        Ok(())
+    }
+
+    async fn put_object_bytes(&self, key: &str, data: Vec<u8>) -> io::Result<()> {
+        let size = data.len() as u64;
+        self.backend.put_bytes(key, data).await?;
+        self.update_objects(|objects| objects.insert(new_object(key, size)));
+        Ok(())
+    }
+
+    async fn delete_object(&self, key: &str) -> io::Result<()> {
+        self.backend.delete(key).await?;
+        self.update_objects(|objects| objects.remove(key));
+        Ok(())
+    }
+
+    // The aliases are read from the backend the first time they are needed.
+    async fn load_aliases(&self) -> io::Result<()> {
+        if self.aliases.lock().unwrap().is_some() {
+            return Ok(());
+        }
+        let aliases = self.read_aliases().await?;
+        self.aliases.lock().unwrap().get_or_insert(aliases);
+        Ok(())
+    }
+
+    // Read the aliases that are stored in the backend, mapping artifact ids to
+    // content hashes.
+    async fn read_aliases(&self) -> io::Result<HashMap<String, String>> {
+        let mut aliases = HashMap::new();
+        for object in self.list_objects().await? {
+            if let (artifact_id, ALIAS_FILE_EXTENSION) = object_key_parts(&object.key) {
+                let content_hash = self.backend.read(&object.key).await?;
+                aliases.insert(
+                    artifact_id.to_owned(),
+                    String::from_utf8_lossy(&content_hash).trim().to_owned(),
+                );
+            }
+        }
+        Ok(aliases)
+    }
+
+    fn with_aliases<T>(&self, f: impl FnOnce(&mut HashMap<String, String>) -> T) -> T {
+        f(self
+            .aliases
+            .lock()
+            .unwrap()
+            .get_or_insert_with(HashMap::new))
+    }
+
+    // The key of the content an artifact id refers to. This is the content hash
+    // for aliases. Artifacts that were stored before the storage became
+    // content-addressed, or that are requested by their content hash, are
+    // their own key.
+    async fn content_key(&self, artifact_id: &str) -> io::Result<String> {
+        self.load_aliases().await?;
+        Ok(self.with_aliases(|aliases| match aliases.get(artifact_id) {
+            Some(content_hash) => content_hash.clone(),
+            None => artifact_id.to_owned(),
+        }))
+    }
+
+    // The stored content an artifact id refers to.
+    async fn content(&self, artifact_id: &str) -> io::Result<StoredContent> {
+        let content_key = self.content_key(artifact_id).await?;
+        self.stored_content(&content_key).await
+    }
+
+    // The stored content with the specified key, which is either stored as is
+    // or compressed.
+    async fn stored_content(&self, content_key: &str) -> io::Result<StoredContent> {
+        match self
+            .backend
+            .metadata(&content_object_key(content_key))
+            .await
+        {
+            Ok(object) => {
+                return Ok(StoredContent {
+                    size: object.size,
+                    object,
+                    seek_table: None,
+                })
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let object = self
+            .backend
+            .metadata(&compressed_object_key(content_key))
+            .await?;
+        let seek_table = self.seek_table(&object).await?;
+        Ok(StoredContent {
+            size: seek_table.size(),
+            object,
+            seek_table: Some(seek_table),
+        })
+    }
+
+    // The seek table of a compressed object. Objects that were compressed
+    // before content was split in frames have no seek table, and hold a single
+    // frame that has the size of the uncompressed content in its header.
+    async fn seek_table(&self, object: &ObjectMetadata) -> io::Result<SeekTable> {
+        let footer_size = compression::SEEK_TABLE_FOOTER_SIZE.min(object.size);
+        let footer = read_stream(
+            self.backend
+                .get(&object.key, object.size - footer_size, Some(footer_size))
+                .await?,
+        )
+        .await?;
+        if let Some(table_size) = SeekTable::stored_size(&footer) {
+            let table_size = table_size.min(object.size);
+            let table = read_stream(
+                self.backend
+                    .get(&object.key, object.size - table_size, Some(table_size))
+                    .await?,
+            )
+            .await?;
+            return SeekTable::parse(&table);
+        }
+
+        let header = read_stream(
+            self.backend
+                .get(&object.key, 0, Some(compression::FRAME_HEADER_SIZE_MAX))
+                .await?,
+        )
+        .await?;
+        Ok(SeekTable::single_frame(
+            object.size,
+            compression::decompressed_size(&header)?,
+        ))
+    }
+
+    // Stream the uncompressed bytes of stored content, starting at `offset`.
+    // At most `length` bytes are streamed when a length is specified.
+    async fn read_content(
+        &self,
+        content: &StoredContent,
+        offset: u64,
+        length: Option<u64>,
+    ) -> io::Result<ArtifactStream> {
+        let seek_table = match &content.seek_table {
+            Some(seek_table) => seek_table,
+            None => return self.backend.get(&content.object.key, offset, length).await,
+        };
+        // only the frames that hold the range are decompressed
+        let frames = seek_table.frame_range(offset, length);
+        if frames.length == 0 {
+            return Ok(Box::pin(stream::empty()));
+        }
+        let compressed = self
+            .backend
+            .get(&content.object.key, frames.offset, Some(frames.length))
+            .await?;
+        Ok(compression::slice_stream(
+            compression::decompress_stream(compressed)?,
+            frames.skip,
+            length,
+        ))
+    }
+
+    async fn content_exists(&self, content_key: &str) -> io::Result<bool> {
+        Ok(self.object_exists(&content_object_key(content_key)).await?
+            || self
+                .object_exists(&compressed_object_key(content_key))
+                .await?)
+    }
+
+    async fn object_exists(&self, key: &str) -> io::Result<bool> {
+        match self.backend.metadata(key).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
     }
 
-    fn create_artifact_file(&self, artifact_id: &str) -> io::Result<File> {
-        let artifact_file_path = self.artifact_file_path(artifact_id)?;
-        OpenOptions::new()
-            .write(true)
-            .create_new(true)
-            .open(artifact_file_path)
+    async fn artifact_exists(&self, artifact_id: &str) -> io::Result<bool> {
+        self.load_aliases().await?;
+        if self.with_aliases(|aliases| aliases.contains_key(artifact_id)) {
+            return Ok(true);
+        }
+        self.content_exists(artifact_id).await
+    }
+
+    // Make `artifact_id` an alias of the content with the specified hash.
+    async fn add_alias(&self, artifact_id: &str, content_hash: &str) -> io::Result<()> {
+        self.put_object_bytes(
+            &alias_object_key(artifact_id),
+            content_hash.as_bytes().to_vec(),
+        )
+        .await?;
+        self.with_aliases(|aliases| {
+            aliases.insert(artifact_id.to_owned(), content_hash.to_owned())
+        });
+        self.touch_artifact(artifact_id);
+        Ok(())
+    }
+
+    // Move the file at `path` into the backend as the content with the
+    // specified hash, unless that content is already stored, and make
+    // `artifact_id` an alias of it.
+    async fn store_content(
+        &self,
+        artifact_id: &str,
+        path: &Path,
+        content_hash: &str,
+    ) -> io::Result<()> {
+        if self.content_exists(content_hash).await? {
+            info!(
+                "Content of artifact {} is already stored as {}",
+                artifact_id, content_hash
+            );
+            std::fs::remove_file(path)?;
+            return self.add_alias(artifact_id, content_hash).await;
+        }
+
+        let storage = self.clone();
+        let (compressed_id, uncompressed_path) = (artifact_id.to_owned(), path.to_owned());
+        let compressed_path =
+            run_blocking(move || Ok(storage.compress_content(&compressed_id, &uncompressed_path)))
+                .await?;
+        if let Some(compressed_path) = compressed_path {
+            if let Err(e) = self
+                .put_object(&compressed_object_key(content_hash), &compressed_path)
+                .await
+            {
+                let _ = std::fs::remove_file(&compressed_path);
+                return Err(e);
+            }
+            std::fs::remove_file(path)?;
+        } else {
+            self.put_object(&content_object_key(content_hash), path)
+                .await?;
+        }
+
+        self.add_alias(artifact_id, content_hash).await
+    }
+
+    // Compress the file at `path` into a new temporary file, when compression
+    // is enabled, the media type of the content is compressible and the
+    // compressed file is smaller. The content is stored as is otherwise.
+    fn compress_content(&self, artifact_id: &str, path: &Path) -> Option<PathBuf> {
+        let compression_level = self.compression_level?;
+        let media_type = match compression::detect_file_media_type(path) {
+            Ok(media_type) => media_type,
+            Err(e) => {
+                warn!(
+                    "Unable to detect the media type of {}: {:?}",
+                    artifact_id, e
+                );
+                return None;
+            }
+        };
+        if !compression::is_compressible(media_type) {
+            debug!(
+                "Not compressing artifact {} of media type {}",
+                artifact_id, media_type
+            );
+            return None;
+        }
+
+        let compressed_path = self.temp_file_path(artifact_id);
+        let compressed = compression::compress_file(path, &compressed_path, compression_level)
+            .and_then(|compressed_size| Ok((compressed_size, std::fs::metadata(path)?.len())));
+        match compressed {
+            Ok((compressed_size, size)) if compressed_size < size => {
+                debug!(
+                    "Compressed artifact {} of media type {} from {} to {} bytes",
+                    artifact_id, media_type, size, compressed_size
+                );
+                return Some(compressed_path);
+            }
+            Ok(_) => debug!("Compression doesn't make artifact {} smaller", artifact_id),
+            Err(e) => warn!("Unable to compress artifact {}: {:?}", artifact_id, e),
+        }
+        let _ = std::fs::remove_file(&compressed_path);
+        None
     }
 
-    /// Push an artifact to this node's local repository.
+    /// Push an artifact to this node's local repository. The content is only
+    /// written once when it's identical to that of an artifact with another id.
+    /// Returns the SHA-256 hash of the content.
     /// Parameters are:
     /// * reader — An object that this method will use to read the bytes of the artifact being
-    ///            pushed.
+    ///   pushed. It is read on a thread for blocking tasks.
     /// * artifact_id — The id that the pushed artifact is expected to have.
-    pub fn push_artifact(&self, reader: &mut impl Read, artifact_id: &str) -> io::Result<()> {
+    pub async fn push_artifact(
+        &self,
+        reader: impl Read + Send + 'static,
+        artifact_id: &str,
+    ) -> io::Result<String> {
+        self.write_artifact(reader, artifact_id, None).await
+    }
+
+    /// Push an artifact to this node's local repository, like
+    /// [`ArtifactStorage::push_artifact`], but only when the SHA-256 hash of the
+    /// content matches `expected_hash`. Fails with [`io::ErrorKind::InvalidData`]
+    /// otherwise, in which case nothing is stored.
+    pub async fn push_verified_artifact(
+        &self,
+        reader: impl Read + Send + 'static,
+        artifact_id: &str,
+        expected_hash: &str,
+    ) -> io::Result<String> {
+        self.write_artifact(reader, artifact_id, Some(expected_hash))
+            .await
+    }
+
+    // The artifact is written to a temporary file and synced to disk before it
+    // is moved into the storage backend, so an interrupted push never leaves a
+    // truncated artifact behind under its id.
+    async fn write_artifact(
+        &self,
+        reader: impl Read + Send + 'static,
+        artifact_id: &str,
+        expected_hash: Option<&str>,
+    ) -> io::Result<String> {
         info!(
             "An artifact is being pushed to the artifact manager {}",
             artifact_id
         );
+        if self.artifact_exists(artifact_id).await? {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+
+        let temp_file_path = self.temp_file_path(artifact_id);
+        let path = temp_file_path.clone();
+        let result = match run_blocking(move || write_synced_file(reader, &path)).await {
+            Ok(content_hash) => match expected_hash {
+                Some(expected_hash) if content_hash != expected_hash.to_lowercase() => {
+                    warn!(
+                        "Hash of pushed artifact {} does not match, expected {} but was {}",
+                        artifact_id, expected_hash, content_hash
+                    );
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Hash of pushed artifact {} does not match", artifact_id),
+                    ))
+                }
+                _ => self
+                    .store_content(artifact_id, &temp_file_path, &content_hash)
+                    .await
+                    .map(|_| content_hash),
+            },
+            Err(e) => Err(e),
+        };
+
+        if result.is_err() {
+            if let Err(e) = std::fs::remove_file(&temp_file_path) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    warn!(
+                        "Unable to remove temporary file {:?}: {:?}",
+                        temp_file_path, e
+                    );
+                }
+            }
+        }
+        result
+    }
+
+    /// Make `artifact_id` an alias of content that is already stored under
+    /// another artifact id. Returns false when the content isn't stored.
+    pub async fn link_artifact(&self, artifact_id: &str, content_hash: &str) -> io::Result<bool> {
+        let content_hash = content_hash.to_lowercase();
+        self.load_aliases().await?;
+        if !self.content_exists(&content_hash).await? {
+            return Ok(false);
+        }
 
-        let artifact_file = self.create_artifact_file(artifact_id)?;
-        let mut writer = BufWriter::new(artifact_file);
-        io::copy(reader, &mut writer)?;
-        writer.flush()
+        info!(
+            "Artifact {} refers to stored content {}",
+            artifact_id, content_hash
+        );
+        self.add_alias(artifact_id, &content_hash).await?;
+        Ok(true)
     }
 
     /// Pull an artifact. The current implementation only looks in the local node's repository.
-    pub fn pull_artifact(&self, artifact_id: &str) -> io::Result<impl Read> {
+    pub async fn pull_artifact(&self, artifact_id: &str) -> io::Result<Vec<u8>> {
         info!(
             "An artifact is being pulled from the artifact manager {}",
             artifact_id
         );
-        let artifact_file_path = self.artifact_file_path(artifact_id)?;
-        File::open(artifact_file_path)
+        let content = self.content(artifact_id).await?;
+        let artifact = read_stream(self.read_content(&content, 0, None).await?).await?;
+        self.touch_artifact(artifact_id);
+        Ok(artifact)
     }
 
-    /// List all artifacts found in the repository path.
-    /// The current implementation only looks in the local node's repository.
-    pub fn list_artifacts(&self) -> Result<Vec<PathBuf>> {
-        let root: PathBuf = PathBuf::from(&self.repository_path);
-        debug!("Finding stored artifacts");
-        if root.is_dir() {
-            let vec: Vec<PathBuf> = std::fs::read_dir(root)?
-                .filter_map(|entry| {
-                    let path = entry.unwrap().path();
-                    match path.extension() {
-                        Some(ext) if ext.eq(FILE_EXTENSION) => Some(path),
-                        _ => None,
-                    }
-                })
-                .collect();
-            debug!("There are {} stored artifacts ", vec.len());
-            return Ok(vec);
+    /// Stream an artifact from the local node's repository, together with its size.
+    /// The SHA-256 hash of the artifact is calculated while the bytes are read and
+    /// compared to `expected_hash` once the end of the file is reached. The last chunk
+    /// is only released after a successful verification. When the hash doesn't match,
+    /// the stream ends with an error instead, so consumers never receive the complete
+    /// content of a corrupt artifact.
+    pub async fn stream_artifact(
+        &self,
+        artifact_id: &str,
+        expected_hash: &str,
+    ) -> io::Result<(u64, ArtifactStream)> {
+        info!(
+            "An artifact is being streamed from the artifact manager {}",
+            artifact_id
+        );
+        let content = self.content(artifact_id).await?;
+        let size = content.size;
+        let content = self.read_content(&content, 0, None).await?;
+        self.touch_artifact(artifact_id);
+
+        let state = VerifyingReader {
+            artifact_id: artifact_id.to_owned(),
+            content,
+            hasher: Sha256::new(),
+            expected_hash: expected_hash.to_lowercase(),
+            pending: None,
+            done: false,
+        };
+
+        let stream = stream::try_unfold(state, |mut state| async move {
+            match state.next_chunk().await? {
+                Some(chunk) => Ok(Some((chunk, state))),
+                None => Ok(None),
+            }
+        });
+
+        Ok((size, Box::pin(stream)))
+    }
+
+    /// Verify the hash of an artifact in the local node's repository without loading it
+    /// in memory. Returns the size of the artifact when it's available locally and its
+    /// hash matches `expected_hash`, or `None` otherwise. Artifacts are never modified
+    /// once pushed, so successful verifications are remembered.
+    pub async fn verify_local_artifact(
+        &self,
+        artifact_id: &str,
+        expected_hash: &str,
+    ) -> io::Result<Option<u64>> {
+        let content = match self.content(artifact_id).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let content_key = self.content_key(artifact_id).await?;
+
+        if self
+            .verified_artifacts
+            .lock()
+            .unwrap()
+            .contains(&content_key)
+        {
+            return Ok(Some(content.size));
+        }
+
+        let actual_hash = hash_stream(self.read_content(&content, 0, None).await?).await?;
+        if actual_hash != expected_hash.to_lowercase() {
+            warn!("Hash of local artifact {} does not match", artifact_id);
+            return Ok(None);
         }
-        Ok(Vec::new())
+
+        self.verified_artifacts.lock().unwrap().insert(content_key);
+        Ok(Some(content.size))
     }
-}
 
-#[cfg(test)]
-#[cfg(not(tarpaulin_include))]
-mod tests {
-    use super::*;
-    use crate::util::test_util;
-    use std::path::PathBuf;
-    use stringreader::StringReader;
-    use uuid::Uuid;
+    /// Stream the inclusive byte range `start..=end` of an artifact from the local node's
+    /// repository. The range is not verified, so callers should only use this for
+    /// artifacts that passed [`ArtifactStorage::verify_local_artifact`].
+    pub async fn stream_artifact_range(
+        &self,
+        artifact_id: &str,
+        start: u64,
+        end: u64,
+    ) -> io::Result<ArtifactStream> {
+        let content = self.content(artifact_id).await?;
+        let stream = self
+            .read_content(&content, start, Some(end.saturating_sub(start) + 1))
+            .await?;
+        self.touch_artifact(artifact_id);
+        Ok(stream)
+    }
 
-    #[test]
-    pub fn new_artifact_storage_with_valid_directory() {
-        let tmp_dir = test_util::tests::setup();
+    /// Read at most `length` bytes of an artifact in the local node's repository,
+    /// starting at `offset`. Returns the total size of the artifact together with
+    /// the bytes that were read, which are empty when `offset` is past the end of
+    /// the artifact.
+    pub async fn read_artifact_chunk(
+        &self,
+        artifact_id: &str,
+        offset: u64,
+        length: u64,
+    ) -> io::Result<(u64, Vec<u8>)> {
+        let content = self.content(artifact_id).await?;
+        self.touch_artifact(artifact_id);
 
-        ArtifactStorage::new(&tmp_dir).expect("ArtifactStorage should be created.");
+        let mut data = Vec::new();
+        if offset < content.size {
+            data = read_stream(self.read_content(&content, offset, Some(length)).await?).await?;
+        }
 
-        test_util::tests::teardown(tmp_dir);
+        Ok((content.size, data))
     }
 
-    const TEST_ARTIFACT_DATA: &str = "Incumbent nonsense text, sesquipedalian and obfuscatory. Exhortations to the mother lode. Dendrites for all.";
+    /// The number of bytes of an artifact that were already written by
+    /// [`ArtifactStorage::write_partial_artifact`], or 0 when there is no
+    /// partial download of the artifact.
+    pub async fn partial_artifact_size(&self, artifact_id: &str) -> io::Result<u64> {
+        match tokio::fs::metadata(self.partial_artifact_file_path(artifact_id)).await {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
 
-    #[test]
-    pub fn new_artifact_storage_with_non_existing_directory() {
-        assert!(ArtifactStorage::new(PathBuf::from("bogus")).is_err());
+    /// Write a chunk of an artifact that is being downloaded at `offset` of its
+    /// partial file. Chunks can be written in any order. Partial artifacts are
+    /// not listed or pulled until they are completed with
+    /// [`ArtifactStorage::complete_partial_artifact`].
+    pub async fn write_partial_artifact(
+        &self,
+        artifact_id: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let path = self.partial_artifact_file_path(artifact_id);
+        let data = data.to_vec();
+        run_blocking(move || {
+            let mut partial_file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?;
+            partial_file.seek(SeekFrom::Start(offset))?;
+            partial_file.write_all(&data)?;
+            partial_file.flush()
+        })
+        .await
     }
 
-    #[test]
-    pub fn new_artifact_storage_with_file_as_repository_path() {
-        let tmp_dir = test_util::tests::setup();
-        let tmp_file_path = PathBuf::from(&tmp_dir).join("sample.file");
-        File::create(&tmp_file_path).unwrap();
+    /// Truncate the partial download of an artifact to `size` bytes, so a
+    /// later download resumes at `size`.
+    pub async fn truncate_partial_artifact(&self, artifact_id: &str, size: u64) -> io::Result<()> {
+        let path = self.partial_artifact_file_path(artifact_id);
+        run_blocking(move || OpenOptions::new().write(true).open(path)?.set_len(size)).await
+    }
 
-        assert!(ArtifactStorage::new(&tmp_file_path).is_err());
+    /// Move a completely downloaded partial artifact into the local node's
+    /// repository, after verifying that its SHA-256 hash matches `expected_hash`.
+    /// A partial artifact with a different hash is discarded. Fails when the
+    /// artifact is already present.
+    pub async fn complete_partial_artifact(
+        &self,
+        artifact_id: &str,
+        expected_hash: &str,
+    ) -> io::Result<()> {
+        if self.artifact_exists(artifact_id).await? {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
 
-        test_util::tests::teardown(tmp_dir);
+        let partial_file_path = self.partial_artifact_file_path(artifact_id);
+        let path = partial_file_path.clone();
+        let content_hash = run_blocking(move || {
+            let mut partial_file = OpenOptions::new().read(true).write(true).open(path)?;
+            let mut hasher = Sha256::new();
+            io::copy(&mut partial_file, &mut hasher)?;
+            partial_file.sync_all()?;
+            Ok(hex::encode(hasher.finalize()))
+        })
+        .await?;
+        if content_hash != expected_hash.to_lowercase() {
+            warn!(
+                "Hash of downloaded artifact {} does not match, discarding it",
+                artifact_id
+            );
+            self.discard_partial_artifact(artifact_id)?;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Hash of downloaded artifact {} does not match", artifact_id),
+            ));
+        }
+
+        info!("Completed the download of artifact {}", artifact_id);
+        self.store_content(
+            artifact_id,
+            &partial_file_path,
+            &expected_hash.to_lowercase(),
+        )
+        .await
     }
 
-    #[test]
-    pub fn push_artifact_then_pull_it() {
-        let tmp_dir = test_util::tests::setup();
+    /// Remove the partial download of an artifact, if any.
+    pub fn discard_partial_artifact(&self, artifact_id: &str) -> io::Result<()> {
+        match std::fs::remove_file(self.partial_artifact_file_path(artifact_id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
 
-        let mut string_reader = StringReader::new(TEST_ARTIFACT_DATA);
-        let artifact_id = Uuid::new_v4().to_string();
-        let artifact_storage =
-            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+    /// Remove the files in the local node's repository that were left behind
+    /// by writes that were interrupted, e.g. by a crash: the temporary files of
+    /// pushed artifacts, and the partial downloads that weren't written to for
+    /// `partial_max_age`. Partial downloads that are more recent are kept, so
+    /// their download can be resumed. Only call this when nothing is written
+    /// to the storage. Returns the number of removed files.
+    pub fn remove_stale_files(&self, partial_max_age: Duration) -> io::Result<usize> {
+        let mut removed = 0;
+        for entry in std::fs::read_dir(&self.repository_path)? {
+            let entry = entry?;
+            let path = entry.path();
+            let stale = match path.extension() {
+                Some(ext) if ext.eq(TEMP_FILE_EXTENSION) => true,
+                Some(ext) if ext.eq(PARTIAL_FILE_EXTENSION) => entry
+                    .metadata()?
+                    .modified()?
+                    .elapsed()
+                    .map_or(false, |age| age >= partial_max_age),
+                _ => false,
+            };
+            if stale && entry.file_type()?.is_file() {
+                info!("Removing stale file {:?}", path);
+                std::fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
 
-        artifact_storage
-            .push_artifact(&mut string_reader, &artifact_id)
-            .context("Error from push_artifact")
-            .unwrap();
+    /// Mark an artifact as one this node needs to keep, like the artifacts it
+    /// built itself. Retained artifacts are never evicted.
+    pub async fn retain_artifact(&self, artifact_id: &str) -> io::Result<()> {
+        self.put_object_bytes(&retained_object_key(artifact_id), Vec::new())
+            .await
+    }
 
-        check_artifact_is_written_correctly(&tmp_dir, &artifact_id).unwrap();
+    /// Retain all stored artifacts when `retain_stored` is true, the first time
+    /// this is called for the local node's repository. Releases before the one
+    /// that introduced [`ArtifactStorage::retain_artifact`] didn't mark the
+    /// artifacts a node built, so these must be retained before the disk quota
+    /// is enforced for the first time. A marker file records that this
+    /// happened, so artifacts that are downloaded later can still be evicted.
+    /// Returns the number of retained artifacts.
+    pub async fn retain_artifacts_of_older_releases(
+        &self,
+        retain_stored: bool,
+    ) -> io::Result<usize> {
+        let marker_path = self.repository_path.join(RETENTION_UPGRADE_MARKER);
+        if marker_path.exists() {
+            return Ok(0);
+        }
 
-        check_able_to_pull_artifact(&artifact_id, &artifact_storage).unwrap();
+        let mut retained = 0;
+        if retain_stored {
+            for (content_key, mut artifact_ids) in self.stored_contents().await? {
+                if artifact_ids.is_empty() {
+                    artifact_ids.push(content_key);
+                }
+                for artifact_id in artifact_ids {
+                    self.retain_artifact(&artifact_id).await?;
+                    retained += 1;
+                }
+            }
+        }
+        File::create(&marker_path)?.sync_all()?;
+        Ok(retained)
+    }
 
-        test_util::tests::teardown(tmp_dir);
+    /// Whether the artifact was marked with [`ArtifactStorage::retain_artifact`].
+    pub async fn is_artifact_retained(&self, artifact_id: &str) -> io::Result<bool> {
+        self.object_exists(&retained_object_key(artifact_id)).await
     }
 
-    fn check_artifact_is_written_correctly(dir_name: &Path, artifact_id: &str) -> Result<()> {
-        let mut dir_name = dir_name.to_path_buf();
-        dir_name.push(artifact_id);
-        dir_name.set_extension(FILE_EXTENSION);
-        let content_vec = std::fs::read(dir_name.as_path())
-            .context("reading pushed file")
-            .unwrap();
-        assert_eq!(content_vec.as_slice(), TEST_ARTIFACT_DATA.as_bytes());
+    /// The number of bytes used by the artifacts in the storage backend and
+    /// the partial downloads in the local node's repository. Compressed
+    /// artifacts count with their compressed size. The size of the artifacts
+    /// is kept up to date in memory, the backend is only listed once.
+    pub async fn usage(&self) -> io::Result<u64> {
+        self.load_objects().await?;
+        let mut usage = self.with_objects(|objects| objects.content_size);
+        for entry in std::fs::read_dir(&self.repository_path)? {
+            let entry = entry?;
+            match entry.path().extension() {
+                Some(ext) if ext.eq(PARTIAL_FILE_EXTENSION) => {
+                    usage += entry.metadata()?.len();
+                }
+                _ => {}
+            }
+        }
+        Ok(usage)
+    }
 
-        Ok(())
+    /// The ids and sizes of the artifacts that can be evicted from the local
+    /// node's repository, least recently used first. Retained artifacts are
+    /// not included. Aliases of the same content are listed separately.
+    pub async fn eviction_candidates(&self) -> io::Result<Vec<(String, u64)>> {
+        self.load_aliases().await?;
+        let objects: HashMap<String, ObjectMetadata> = self
+            .list_objects()
+            .await?
+            .into_iter()
+            .map(|object| (object.key.clone(), object))
+            .collect();
+        let access_times = self.access_times.lock().unwrap().clone();
+        let aliases = self.with_aliases(|aliases| aliases.clone());
+        let referenced: HashSet<&str> = aliases.values().map(String::as_str).collect();
+
+        // the artifact id, the key of its content and the object it was written as
+        let mut artifacts: Vec<(&str, &str, String)> = aliases
+            .iter()
+            .map(|(artifact_id, content_hash)| {
+                (
+                    artifact_id.as_str(),
+                    content_hash.as_str(),
+                    alias_object_key(artifact_id),
+                )
+            })
+            .collect();
+        for key in objects.keys() {
+            match content_key_of(key) {
+                Some(content_key) if !referenced.contains(content_key) => {
+                    artifacts.push((content_key, content_key, key.clone()));
+                }
+                _ => {}
+            }
+        }
+
+        let mut candidates = Vec::new();
+        for (artifact_id, content_key, written_as) in artifacts {
+            if objects.contains_key(&retained_object_key(artifact_id)) {
+                continue;
+            }
+            let size = match objects
+                .get(&content_object_key(content_key))
+                .or_else(|| objects.get(&compressed_object_key(content_key)))
+            {
+                Some(content) => content.size,
+                None => continue,
+            };
+            let accessed = match (access_times.get(artifact_id), objects.get(&written_as)) {
+                (Some(accessed), _) => *accessed,
+                (None, Some(object)) => object.modified,
+                (None, None) => continue,
+            };
+            candidates.push((accessed, artifact_id.to_owned(), size));
+        }
+        candidates.sort();
+        Ok(candidates
+            .into_iter()
+            .map(|(_, artifact_id, size)| (artifact_id, size))
+            .collect())
     }
 
-    fn check_able_to_pull_artifact(
-        artifact_id: &str,
-        artifact_storage: &ArtifactStorage,
-    ) -> Result<()> {
-        let mut reader = artifact_storage
-            .pull_artifact(artifact_id)
-            .context("Error from pull_artifact")?;
-        let mut read_buffer = String::new();
-        reader.read_to_string(&mut read_buffer).unwrap();
-        assert_eq!(TEST_ARTIFACT_DATA, read_buffer);
+    /// Remove an artifact from the local node's repository. Its content is
+    /// only removed when no other artifact id refers to it. Returns the hash
+    /// of the content when it was removed.
+    pub async fn remove_artifact(&self, artifact_id: &str) -> io::Result<Option<String>> {
+        info!(
+            "Removing artifact {} from the artifact manager",
+            artifact_id
+        );
+        let content_key = self.content_key(artifact_id).await?;
+        self.access_times.lock().unwrap().remove(artifact_id);
+
+        let (removed, still_referenced) = self.with_aliases(|aliases| {
+            let removed = aliases.remove(artifact_id).is_some();
+            let still_referenced = aliases
+                .values()
+                .any(|content_hash| *content_hash == content_key);
+            (removed, still_referenced)
+        });
+        if removed {
+            self.delete_object(&alias_object_key(artifact_id)).await?;
+        }
+        self.delete_object(&retained_object_key(artifact_id))
+            .await?;
+        if still_referenced {
+            debug!(
+                "Content {} of artifact {} is still referenced",
+                content_key, artifact_id
+            );
+            return Ok(None);
+        }
+
+        self.delete_object(&content_object_key(&content_key))
+            .await?;
+        self.delete_object(&compressed_object_key(&content_key))
+            .await?;
+        self.verified_artifacts.lock().unwrap().remove(&content_key);
+        Ok(Some(content_key))
+    }
+
+    /// The keys of all stored contents, together with the ids of the artifacts
+    /// that refer to them. Contents that were stored before the storage became
+    /// content-addressed have no aliases, their key is the artifact id.
+    pub async fn stored_contents(&self) -> io::Result<Vec<(String, Vec<String>)>> {
+        self.load_aliases().await?;
+        let mut contents: HashMap<String, Vec<String>> = HashMap::new();
+        for object in self.list_objects().await? {
+            if let Some(content_key) = content_key_of(&object.key) {
+                contents.entry(content_key.to_owned()).or_default();
+            }
+        }
+        self.with_aliases(|aliases| {
+            for (artifact_id, content_hash) in aliases.iter() {
+                if let Some(artifact_ids) = contents.get_mut(content_hash) {
+                    artifact_ids.push(artifact_id.clone());
+                }
+            }
+        });
+
+        let mut contents: Vec<(String, Vec<String>)> = contents.into_iter().collect();
+        contents
+            .iter_mut()
+            .for_each(|(_, artifact_ids)| artifact_ids.sort());
+        contents.sort();
+        Ok(contents)
+    }
+
+    /// Calculate the SHA-256 hash of stored content. Unlike
+    /// [`ArtifactStorage::verify_local_artifact`], the content is always read
+    /// again, even when it was verified before.
+    pub async fn hash_content(&self, content_key: &str) -> io::Result<String> {
+        let content = self.stored_content(content_key).await?;
+        hash_stream(self.read_content(&content, 0, None).await?).await
+    }
+
+    /// The number of bytes stored content takes in the storage backend, which
+    /// is less than its size when it is compressed.
+    pub async fn stored_content_size(&self, content_key: &str) -> io::Result<u64> {
+        Ok(self.stored_content(content_key).await?.object.size)
+    }
 
+    /// The number of bytes that are saved by storing artifacts compressed.
+    pub async fn compression_savings(&self) -> io::Result<u64> {
+        let mut savings = 0;
+        for object in self.list_objects().await? {
+            if object_key_parts(&object.key).1 == COMPRESSED_FILE_EXTENSION {
+                let size = match self.seek_table(&object).await {
+                    Ok(seek_table) => seek_table.size(),
+                    // the content was evicted in the meantime
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e),
+                };
+                savings += size.saturating_sub(object.size);
+            }
+        }
+        Ok(savings)
+    }
+
+    /// Move corrupt content out of the storage backend, into the quarantine
+    /// directory of the local node's repository, and remove all aliases that
+    /// refer to it, so the artifacts can be downloaded again.
+    pub async fn quarantine_content(&self, content_key: &str) -> io::Result<()> {
+        warn!("Moving content {} to the quarantine", content_key);
+        let object_key = self.stored_content(content_key).await?.object.key;
+        let quarantine_path = self.repository_path.join(QUARANTINE_DIR);
+        tokio::fs::create_dir_all(&quarantine_path).await?;
+
+        let mut quarantined_file =
+            tokio::fs::File::create(quarantine_path.join(&object_key)).await?;
+        let mut content = self.backend.get(&object_key, 0, None).await?;
+        while let Some(chunk) = content.next().await {
+            quarantined_file.write_all(&chunk?).await?;
+        }
+        quarantined_file.sync_all().await?;
+        self.delete_object(&object_key).await?;
+
+        self.load_aliases().await?;
+        let artifact_ids: Vec<String> = self.with_aliases(|aliases| {
+            let artifact_ids: Vec<String> = aliases
+                .iter()
+                .filter(|(_, content_hash)| *content_hash == content_key)
+                .map(|(artifact_id, _)| artifact_id.clone())
+                .collect();
+            artifact_ids.iter().for_each(|artifact_id| {
+                aliases.remove(artifact_id);
+            });
+            artifact_ids
+        });
+        for artifact_id in artifact_ids {
+            self.delete_object(&alias_object_key(&artifact_id)).await?;
+        }
+        self.verified_artifacts.lock().unwrap().remove(content_key);
         Ok(())
     }
 
-    #[test]
-    pub fn pull_nonexistent_test() {
-        let tmp_dir = test_util::tests::setup();
+    /// Move the content of artifacts that were stored before the storage became
+    /// content-addressed, under their artifact id, to objects that are named
+    /// after its hash, and make the artifact id an alias of it. Peers request
+    /// artifacts by their hash, so they can't find these artifacts until then.
+    /// Returns the number of migrated artifacts.
+    pub async fn migrate_legacy_artifacts(&self) -> io::Result<usize> {
+        self.load_aliases().await?;
+        let mut migrated = 0;
+        for object in self.list_objects().await? {
+            let artifact_id = match content_key_of(&object.key) {
+                Some(content_key) if !is_content_hash(content_key) => content_key.to_owned(),
+                _ => continue,
+            };
 
-        let artifact_id = Uuid::new_v4().to_string();
-        let artifact_storage =
-            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
-        assert!(artifact_storage.pull_artifact(&artifact_id).is_err());
+            let content_hash = self.hash_content(&artifact_id).await?;
+            if !self.content_exists(&content_hash).await? {
+                let migrated_key = match object_key_parts(&object.key).1 {
+                    COMPRESSED_FILE_EXTENSION => compressed_object_key(&content_hash),
+                    _ => content_object_key(&content_hash),
+                };
+                self.copy_object(&object.key, &migrated_key).await?;
+            }
+            self.add_alias(&artifact_id, &content_hash).await?;
+            self.delete_object(&object.key).await?;
+            info!(
+                "Migrated artifact {} to content {}",
+                artifact_id, content_hash
+            );
+            migrated += 1;
+        }
+        Ok(migrated)
+    }
 
-        test_util::tests::teardown(tmp_dir);
+    // Copy an object of the backend to a new key, through a temporary file.
+    async fn copy_object(&self, key: &str, new_key: &str) -> io::Result<()> {
+        let temp_file_path = self.temp_file_path(key);
+        let result = async {
+            let mut temp_file = tokio::fs::File::create(&temp_file_path).await?;
+            let mut content = self.backend.get(key, 0, None).await?;
+            while let Some(chunk) = content.next().await {
+                temp_file.write_all(&chunk?).await?;
+            }
+            temp_file.sync_all().await?;
+            self.put_object(new_key, &temp_file_path).await
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_file_path);
+        }
+        result
     }
 
-    #[test]
-    pub fn list_artifacts_test() {
-        let tmp_dir = test_util::tests::setup();
+    /// List the content hashes of all artifacts in the storage backend.
+    pub async fn list_artifacts(&self) -> Result<Vec<String>> {
+        debug!("Finding stored artifacts");
+        let content_keys: Vec<String> = self
+            .list_objects()
+            .await?
+            .into_iter()
+            .filter_map(|object| content_key_of(&object.key).map(str::to_owned))
+            .collect();
+        debug!("There are {} stored artifacts ", content_keys.len());
+        Ok(content_keys)
+    }
+}
 
-        let mut string_reader = StringReader::new(TEST_ARTIFACT_DATA);
-        let artifact_id = Uuid::new_v4().to_string();
-        let artifact_storage =
-            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+// Write all bytes of the reader to a new file at `path` and sync it to disk.
+// Returns the SHA-256 hash of the bytes.
+fn write_synced_file(mut reader: impl Read, path: &Path) -> io::Result<String> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+    }
+    writer.into_inner()?.sync_all()?;
+    Ok(hex::encode(hasher.finalize()))
+}
 
-        artifact_storage
-            .push_artifact(&mut string_reader, &artifact_id)
-            .context("Error from push_artifact")
-            .unwrap();
+// Run blocking file I/O or hashing on the threads for blocking tasks, so it
+// doesn't stall the async runtime.
+async fn run_blocking<T, F>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+}
 
-        let result = artifact_storage.list_artifacts();
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap().len(), 1);
+// Stream the bytes of an artifact to memory.
+async fn read_stream(mut content: ArtifactStream) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    while let Some(chunk) = content.next().await {
+        data.extend_from_slice(&chunk?);
+    }
+    Ok(data)
+}
+
+// Calculate the SHA-256 hash of the bytes of an artifact. The bytes are hashed
+// on a thread for blocking tasks, while the next ones are read.
+async fn hash_stream(mut content: ArtifactStream) -> io::Result<String> {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<Bytes>(HASHED_CHUNKS_QUEUE_LENGTH);
+    let hasher = tokio::task::spawn_blocking(move || {
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = receiver.blocking_recv() {
+            hasher.update(&chunk);
+        }
+        hex::encode(hasher.finalize())
+    });
+
+    while let Some(chunk) = content.next().await {
+        if sender.send(chunk?).await.is_err() {
+            break;
+        }
+    }
+    drop(sender);
+    hasher
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+// The object that holds stored content, the size of the uncompressed content
+// and, when the content is compressed, the seek table of its frames.
+struct StoredContent {
+    object: ObjectMetadata,
+    size: u64,
+    seek_table: Option<SeekTable>,
+}
+
+// Whether a content key is the SHA-256 hash of the content, rather than the
+// id of an artifact that was stored before the storage became content-addressed.
+fn is_content_hash(content_key: &str) -> bool {
+    content_key.len() == 64 && content_key.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn content_object_key(content_hash: &str) -> String {
+    format!("{}.{}", content_hash, FILE_EXTENSION)
+}
+
+fn compressed_object_key(content_hash: &str) -> String {
+    format!("{}.{}", content_hash, COMPRESSED_FILE_EXTENSION)
+}
+
+// The key of the content an object holds, when it holds content, either as
+// is or compressed.
+fn content_key_of(object_key: &str) -> Option<&str> {
+    match object_key_parts(object_key) {
+        (content_key, FILE_EXTENSION | COMPRESSED_FILE_EXTENSION) => Some(content_key),
+        _ => None,
+    }
+}
+
+fn alias_object_key(artifact_id: &str) -> String {
+    format!("{}.{}", artifact_id, ALIAS_FILE_EXTENSION)
+}
+
+fn retained_object_key(artifact_id: &str) -> String {
+    format!("{}.{}", artifact_id, RETAINED_FILE_EXTENSION)
+}
+
+// Split an object key in the artifact id or content hash and the extension
+// that tells what kind of object it is.
+fn object_key_parts(key: &str) -> (&str, &str) {
+    key.rsplit_once('.').unwrap_or((key, ""))
+}
+
+// The metadata of an object that was written just now.
+fn new_object(key: &str, size: u64) -> ObjectMetadata {
+    ObjectMetadata {
+        key: key.to_owned(),
+        size,
+        modified: SystemTime::now(),
+    }
+}
+
+// The objects in the storage backend by key, and the total size of the objects
+// that hold content, which is the usage of the storage.
+#[derive(Default)]
+struct ObjectIndex {
+    objects: HashMap<String, ObjectMetadata>,
+    content_size: u64,
+}
+
+impl ObjectIndex {
+    fn insert(&mut self, object: ObjectMetadata) {
+        if content_key_of(&object.key).is_some() {
+            self.content_size += object.size;
+        }
+        if let Some(replaced) = self.objects.insert(object.key.clone(), object) {
+            if content_key_of(&replaced.key).is_some() {
+                self.content_size = self.content_size.saturating_sub(replaced.size);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(removed) = self.objects.remove(key) {
+            if content_key_of(&removed.key).is_some() {
+                self.content_size = self.content_size.saturating_sub(removed.size);
+            }
+        }
+    }
+}
+
+impl FromIterator<ObjectMetadata> for ObjectIndex {
+    fn from_iter<I: IntoIterator<Item = ObjectMetadata>>(objects: I) -> Self {
+        let mut index = ObjectIndex::default();
+        objects.into_iter().for_each(|object| index.insert(object));
+        index
+    }
+}
+
+struct VerifyingReader {
+    artifact_id: String,
+    content: ArtifactStream,
+    hasher: Sha256,
+    expected_hash: String,
+    pending: Option<Bytes>,
+    done: bool,
+}
+
+impl VerifyingReader {
+    /// Returns the next chunk of the artifact. Every chunk is held back until the
+    /// following one has been read, so the final chunk is only returned when the
+    /// hash of the complete artifact has been verified.
+    async fn next_chunk(&mut self) -> io::Result<Option<Bytes>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        loop {
+            let chunk = match self.content.next().await {
+                Some(chunk) => chunk?,
+                None => {
+                    self.done = true;
+                    let actual_hash = hex::encode(self.hasher.finalize_reset());
+                    if actual_hash != self.expected_hash {
+                        warn!(
+                            "Hash of artifact {} does not match, expected {} but was {}",
+                            self.artifact_id, self.expected_hash, actual_hash
+                        );
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "Hash of artifact {} does not match, expected {} but was {}",
+                                self.artifact_id, self.expected_hash, actual_hash
+                            ),
+                        ));
+                    }
+                    return Ok(self.pending.take());
+                }
+            };
+
+            self.hasher.update(&chunk);
+            if let Some(chunk) = self.pending.replace(chunk) {
+                return Ok(Some(chunk));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::util::test_util;
+    use futures::StreamExt;
+    use std::path::PathBuf;
+    use stringreader::StringReader;
+    use uuid::Uuid;
+
+    #[test]
+    pub fn new_artifact_storage_with_valid_directory() {
+        let tmp_dir = test_util::tests::setup();
+
+        ArtifactStorage::new(&tmp_dir).expect("ArtifactStorage should be created.");
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    const TEST_ARTIFACT_DATA: &str = "Incumbent nonsense text, sesquipedalian and obfuscatory. Exhortations to the mother lode. Dendrites for all.";
+
+    #[test]
+    pub fn new_artifact_storage_with_non_existing_directory() {
+        assert!(ArtifactStorage::new(PathBuf::from("bogus")).is_err());
+    }
+
+    #[test]
+    pub fn new_artifact_storage_with_file_as_repository_path() {
+        let tmp_dir = test_util::tests::setup();
+        let tmp_file_path = PathBuf::from(&tmp_dir).join("sample.file");
+        File::create(&tmp_file_path).unwrap();
+
+        assert!(ArtifactStorage::new(&tmp_file_path).is_err());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn stream_artifact_with_valid_hash() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_data = vec![7u8; 3 * STREAM_CHUNK_SIZE + 10];
+        let artifact_hash = hex::encode(Sha256::digest(&artifact_data));
+        let artifact_id = Uuid::new_v4().to_string();
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+
+        artifact_storage
+            .push_artifact(io::Cursor::new(artifact_data.clone()), &artifact_id)
+            .await
+            .unwrap();
+
+        let (size, mut stream) = artifact_storage
+            .stream_artifact(&artifact_id, &artifact_hash)
+            .await
+            .unwrap();
+        assert_eq!(size, artifact_data.len() as u64);
+
+        let mut streamed_data = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            streamed_data.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(streamed_data, artifact_data);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn stream_artifact_with_invalid_hash() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_data = vec![7u8; 3 * STREAM_CHUNK_SIZE + 10];
+        let artifact_id = Uuid::new_v4().to_string();
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+
+        artifact_storage
+            .push_artifact(io::Cursor::new(artifact_data.clone()), &artifact_id)
+            .await
+            .unwrap();
+
+        let (_, mut stream) = artifact_storage
+            .stream_artifact(&artifact_id, &"0".repeat(64))
+            .await
+            .unwrap();
+
+        let mut streamed_length = 0;
+        let mut error = None;
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(chunk) => streamed_length += chunk.len(),
+                Err(e) => error = Some(e),
+            }
+        }
+        assert!(streamed_length < artifact_data.len());
+        assert_eq!(error.unwrap().kind(), io::ErrorKind::InvalidData);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn verify_local_artifact_and_stream_range() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_data: Vec<u8> = (0..=255).cycle().take(2 * STREAM_CHUNK_SIZE).collect();
+        let artifact_hash = hex::encode(Sha256::digest(&artifact_data));
+        let artifact_id = Uuid::new_v4().to_string();
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+
+        assert_eq!(
+            artifact_storage
+                .verify_local_artifact(&artifact_id, &artifact_hash)
+                .await
+                .unwrap(),
+            None
+        );
+
+        artifact_storage
+            .push_artifact(io::Cursor::new(artifact_data.clone()), &artifact_id)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            artifact_storage
+                .verify_local_artifact(&artifact_id, &"0".repeat(64))
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            artifact_storage
+                .verify_local_artifact(&artifact_id, &artifact_hash)
+                .await
+                .unwrap(),
+            Some(artifact_data.len() as u64)
+        );
+
+        let start = STREAM_CHUNK_SIZE as u64 - 10;
+        let end = STREAM_CHUNK_SIZE as u64 + 9;
+        let mut stream = artifact_storage
+            .stream_artifact_range(&artifact_id, start, end)
+            .await
+            .unwrap();
+
+        let mut streamed_data = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            streamed_data.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(
+            streamed_data,
+            artifact_data[start as usize..=end as usize].to_vec()
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn read_artifact_chunks() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_id = Uuid::new_v4().to_string();
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+
+        artifact_storage
+            .push_artifact(TEST_ARTIFACT_DATA.as_bytes(), &artifact_id)
+            .await
+            .unwrap();
+
+        let total_size = TEST_ARTIFACT_DATA.len() as u64;
+        assert_eq!(
+            artifact_storage
+                .read_artifact_chunk(&artifact_id, 10, 5)
+                .await
+                .unwrap(),
+            (total_size, TEST_ARTIFACT_DATA.as_bytes()[10..15].to_vec())
+        );
+        assert_eq!(
+            artifact_storage
+                .read_artifact_chunk(&artifact_id, total_size - 3, 5)
+                .await
+                .unwrap(),
+            (
+                total_size,
+                TEST_ARTIFACT_DATA.as_bytes()[total_size as usize - 3..].to_vec()
+            )
+        );
+        assert_eq!(
+            artifact_storage
+                .read_artifact_chunk(&artifact_id, total_size, 5)
+                .await
+                .unwrap(),
+            (total_size, vec![])
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn write_partial_artifact_then_complete_it() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_id = Uuid::new_v4().to_string();
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+        let artifact_data = TEST_ARTIFACT_DATA.as_bytes();
+        let artifact_hash = hex::encode(Sha256::digest(artifact_data));
+
+        assert_eq!(
+            artifact_storage
+                .partial_artifact_size(&artifact_id)
+                .await
+                .unwrap(),
+            0
+        );
+
+        // chunks can be written out of order
+        artifact_storage
+            .write_partial_artifact(&artifact_id, 30, &artifact_data[30..40])
+            .await
+            .unwrap();
+        artifact_storage
+            .write_partial_artifact(&artifact_id, 0, &artifact_data[..20])
+            .await
+            .unwrap();
+        assert_eq!(
+            artifact_storage
+                .partial_artifact_size(&artifact_id)
+                .await
+                .unwrap(),
+            40
+        );
+        artifact_storage
+            .truncate_partial_artifact(&artifact_id, 20)
+            .await
+            .unwrap();
+        assert_eq!(
+            artifact_storage
+                .partial_artifact_size(&artifact_id)
+                .await
+                .unwrap(),
+            20
+        );
+        assert!(artifact_storage.pull_artifact(&artifact_id).await.is_err());
+        assert!(artifact_storage.list_artifacts().await.unwrap().is_empty());
+
+        artifact_storage
+            .write_partial_artifact(&artifact_id, 20, &artifact_data[20..])
+            .await
+            .unwrap();
+        artifact_storage
+            .complete_partial_artifact(&artifact_id, &artifact_hash)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            artifact_storage
+                .partial_artifact_size(&artifact_id)
+                .await
+                .unwrap(),
+            0
+        );
+        check_artifact_is_written_correctly(&tmp_dir, &artifact_hash).unwrap();
+
+        artifact_storage
+            .write_partial_artifact(&artifact_id, 0, artifact_data)
+            .await
+            .unwrap();
+        assert_eq!(
+            artifact_storage
+                .complete_partial_artifact(&artifact_id, &artifact_hash)
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        artifact_storage
+            .discard_partial_artifact(&artifact_id)
+            .unwrap();
+        assert_eq!(
+            artifact_storage
+                .partial_artifact_size(&artifact_id)
+                .await
+                .unwrap(),
+            0
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn complete_partial_artifact_with_invalid_hash() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_id = Uuid::new_v4().to_string();
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+
+        artifact_storage
+            .write_partial_artifact(&artifact_id, 0, TEST_ARTIFACT_DATA.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(
+            artifact_storage
+                .complete_partial_artifact(&artifact_id, &"0".repeat(64))
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            artifact_storage
+                .partial_artifact_size(&artifact_id)
+                .await
+                .unwrap(),
+            0
+        );
+        assert!(artifact_storage.pull_artifact(&artifact_id).await.is_err());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    pub async fn push_artifact_then_pull_it() {
+        let tmp_dir = test_util::tests::setup();
+
+        let string_reader = StringReader::new(TEST_ARTIFACT_DATA);
+        let artifact_id = Uuid::new_v4().to_string();
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+
+        let content_hash = artifact_storage
+            .push_artifact(string_reader, &artifact_id)
+            .await
+            .context("Error from push_artifact")
+            .unwrap();
+
+        check_artifact_is_written_correctly(&tmp_dir, &content_hash).unwrap();
+
+        check_able_to_pull_artifact(&artifact_id, &artifact_storage)
+            .await
+            .unwrap();
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn eviction_candidates_are_least_recently_used_first() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+        for artifact_id in ["first", "second", "built"] {
+            artifact_storage
+                .push_artifact(artifact_id.as_bytes(), artifact_id)
+                .await
+                .unwrap();
+        }
+        artifact_storage.retain_artifact("built").await.unwrap();
+        artifact_storage.pull_artifact("first").await.unwrap();
+
+        assert!(artifact_storage
+            .is_artifact_retained("built")
+            .await
+            .unwrap());
+        assert_eq!(artifact_storage.usage().await.unwrap(), 16);
+        assert_eq!(
+            artifact_storage.eviction_candidates().await.unwrap(),
+            vec![("second".to_owned(), 6), ("first".to_owned(), 5)]
+        );
+
+        assert_eq!(
+            artifact_storage.remove_artifact("second").await.unwrap(),
+            Some(hex::encode(Sha256::digest("second")))
+        );
+        assert!(artifact_storage.pull_artifact("second").await.is_err());
+        assert_eq!(artifact_storage.usage().await.unwrap(), 10);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn identical_artifacts_share_their_content() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+        let content_hash = artifact_storage
+            .push_artifact(TEST_ARTIFACT_DATA.as_bytes(), "alias")
+            .await
+            .unwrap();
+        assert_eq!(
+            artifact_storage
+                .push_artifact(TEST_ARTIFACT_DATA.as_bytes(), "other_alias")
+                .await
+                .unwrap(),
+            content_hash
+        );
+        assert!(artifact_storage
+            .link_artifact("linked_alias", &content_hash)
+            .await
+            .unwrap());
+        assert!(!artifact_storage
+            .link_artifact("unknown", &hex::encode(Sha256::digest("unknown")))
+            .await
+            .unwrap());
+
+        assert_eq!(artifact_storage.list_artifacts().await.unwrap().len(), 1);
+        assert_eq!(
+            artifact_storage.usage().await.unwrap(),
+            TEST_ARTIFACT_DATA.len() as u64
+        );
+
+        // aliases survive a restart
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+        assert_eq!(
+            artifact_storage.remove_artifact("alias").await.unwrap(),
+            None
+        );
+        assert_eq!(
+            artifact_storage
+                .remove_artifact("linked_alias")
+                .await
+                .unwrap(),
+            None
+        );
+        check_able_to_pull_artifact("other_alias", &artifact_storage)
+            .await
+            .unwrap();
+        check_able_to_pull_artifact(&content_hash, &artifact_storage)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            artifact_storage
+                .remove_artifact("other_alias")
+                .await
+                .unwrap(),
+            Some(content_hash)
+        );
+        assert!(artifact_storage.list_artifacts().await.unwrap().is_empty());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn artifacts_are_stored_in_the_backend() {
+        let tmp_dir = test_util::tests::setup();
+        let backend_dir = tmp_dir.join("backend");
+        std::fs::create_dir(&backend_dir).unwrap();
+
+        let artifact_storage = ArtifactStorage::with_backend(
+            &tmp_dir,
+            Arc::new(FileSystemBackend::new(&backend_dir).unwrap()),
+        )
+        .expect("Error creating ArtifactManager");
+        let content_hash = artifact_storage
+            .push_artifact(TEST_ARTIFACT_DATA.as_bytes(), "alias")
+            .await
+            .unwrap();
+        artifact_storage
+            .write_partial_artifact("partial", 0, &[0; 10])
+            .await
+            .unwrap();
+
+        check_artifact_is_written_correctly(&backend_dir, &content_hash).unwrap();
+        assert!(backend_dir.join("alias.ref").exists());
+        assert!(tmp_dir.join("partial.part").exists());
+        assert_eq!(
+            artifact_storage.usage().await.unwrap(),
+            TEST_ARTIFACT_DATA.len() as u64 + 10
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn quarantine_corrupt_content() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+        let content_hash = artifact_storage
+            .push_artifact(TEST_ARTIFACT_DATA.as_bytes(), "alias")
+            .await
+            .unwrap();
+        artifact_storage
+            .link_artifact("other_alias", &content_hash)
+            .await
+            .unwrap();
+        assert_eq!(
+            artifact_storage.stored_contents().await.unwrap(),
+            vec![(
+                content_hash.clone(),
+                vec!["alias".to_owned(), "other_alias".to_owned()]
+            )]
+        );
+        assert_eq!(
+            artifact_storage.hash_content(&content_hash).await.unwrap(),
+            content_hash
+        );
+
+        let content_path = tmp_dir.join(format!("{}.{}", content_hash, FILE_EXTENSION));
+        std::fs::write(&content_path, "corrupt").unwrap();
+        assert_eq!(
+            artifact_storage.hash_content(&content_hash).await.unwrap(),
+            hex::encode(Sha256::digest("corrupt"))
+        );
+
+        artifact_storage
+            .quarantine_content(&content_hash)
+            .await
+            .unwrap();
+        assert!(!content_path.exists());
+        assert!(tmp_dir
+            .join(QUARANTINE_DIR)
+            .join(format!("{}.{}", content_hash, FILE_EXTENSION))
+            .exists());
+        assert!(artifact_storage.stored_contents().await.unwrap().is_empty());
+        assert!(artifact_storage.pull_artifact("alias").await.is_err());
+        assert!(!artifact_storage
+            .link_artifact("alias", &content_hash)
+            .await
+            .unwrap());
+
+        // the artifacts can be stored again
+        artifact_storage
+            .push_artifact(TEST_ARTIFACT_DATA.as_bytes(), "alias")
+            .await
+            .unwrap();
+        check_able_to_pull_artifact("alias", &artifact_storage)
+            .await
+            .unwrap();
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn compressed_artifacts_read_as_uncompressed() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_storage = ArtifactStorage::new(&tmp_dir)
+            .expect("Error creating ArtifactManager")
+            .with_compression_level(3);
+        let content = "{\"mediaType\": \"manifest\"}\n".repeat(1000);
+        let content_hash = artifact_storage
+            .push_artifact(io::Cursor::new(content.clone()), "manifest")
+            .await
+            .unwrap();
+        assert_eq!(content_hash, hex::encode(Sha256::digest(&content)));
+        assert!(tmp_dir.join(format!("{}.zst", content_hash)).exists());
+        assert!(!tmp_dir.join(format!("{}.file", content_hash)).exists());
+
+        let usage = artifact_storage.usage().await.unwrap();
+        let savings = artifact_storage.compression_savings().await.unwrap();
+        assert!(usage < content.len() as u64 / 10);
+        assert_eq!(usage + savings, content.len() as u64);
+
+        assert_eq!(
+            artifact_storage.pull_artifact("manifest").await.unwrap(),
+            content.as_bytes()
+        );
+        assert_eq!(
+            artifact_storage
+                .verify_local_artifact("manifest", &content_hash)
+                .await
+                .unwrap(),
+            Some(content.len() as u64)
+        );
+        assert_eq!(
+            artifact_storage.hash_content(&content_hash).await.unwrap(),
+            content_hash
+        );
+        let (size, mut stream) = artifact_storage
+            .stream_artifact("manifest", &content_hash)
+            .await
+            .unwrap();
+        assert_eq!(size, content.len() as u64);
+        let mut streamed = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            streamed.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(streamed, content.as_bytes());
+        assert_eq!(
+            artifact_storage
+                .read_artifact_chunk("manifest", 1000, 30)
+                .await
+                .unwrap(),
+            (
+                content.len() as u64,
+                content.as_bytes()[1000..1030].to_vec()
+            )
+        );
+
+        assert!(artifact_storage
+            .link_artifact("tag", &content_hash)
+            .await
+            .unwrap());
+        assert_eq!(
+            artifact_storage.remove_artifact("manifest").await.unwrap(),
+            None
+        );
+        assert_eq!(
+            artifact_storage.remove_artifact("tag").await.unwrap(),
+            Some(content_hash)
+        );
+        assert!(artifact_storage.list_artifacts().await.unwrap().is_empty());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn compressed_artifacts_read_by_frame() {
+        let tmp_dir = test_util::tests::setup();
+
+        // content that was compressed in a single frame, without a seek table
+        let legacy_content = "{\"mediaType\": \"manifest\"}\n".repeat(1000);
+        let legacy_hash = hex::encode(Sha256::digest(&legacy_content));
+        std::fs::write(
+            tmp_dir.join(format!("{}.zst", legacy_hash)),
+            zstd::bulk::compress(legacy_content.as_bytes(), 3).unwrap(),
+        )
+        .unwrap();
+
+        let artifact_storage = ArtifactStorage::new(&tmp_dir)
+            .expect("Error creating ArtifactManager")
+            .with_compression_level(3);
+        let content: String = (0..compression::FRAME_CONTENT_SIZE / 4)
+            .map(|i| format!("{:03}\n", i % 1000))
+            .collect::<String>()
+            .repeat(3);
+        let content_hash = artifact_storage
+            .push_artifact(io::Cursor::new(content.clone()), "index")
+            .await
+            .unwrap();
+        let offset = compression::FRAME_CONTENT_SIZE as u64 * 2 - 10;
+        assert_eq!(
+            artifact_storage
+                .read_artifact_chunk("index", offset, 20)
+                .await
+                .unwrap(),
+            (
+                content.len() as u64,
+                content.as_bytes()[offset as usize..offset as usize + 20].to_vec()
+            )
+        );
+        assert_eq!(
+            artifact_storage.hash_content(&content_hash).await.unwrap(),
+            content_hash
+        );
+
+        assert_eq!(
+            artifact_storage
+                .read_artifact_chunk(&legacy_hash, 1000, 30)
+                .await
+                .unwrap(),
+            (
+                legacy_content.len() as u64,
+                legacy_content.as_bytes()[1000..1030].to_vec()
+            )
+        );
+        assert_eq!(
+            artifact_storage.compression_savings().await.unwrap(),
+            content.len() as u64 + legacy_content.len() as u64
+                - artifact_storage.usage().await.unwrap()
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn compressed_artifacts_are_not_compressed_again() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_storage = ArtifactStorage::new(&tmp_dir)
+            .expect("Error creating ArtifactManager")
+            .with_compression_level(3);
+        let mut gzipped_layer = vec![0x1f, 0x8b, 0x08, 0x00];
+        gzipped_layer.extend_from_slice(&[0; 1000]);
+        let content_hash = artifact_storage
+            .push_artifact(io::Cursor::new(gzipped_layer.clone()), "layer")
+            .await
+            .unwrap();
+
+        assert!(tmp_dir.join(format!("{}.file", content_hash)).exists());
+        assert_eq!(artifact_storage.compression_savings().await.unwrap(), 0);
+        assert_eq!(
+            artifact_storage.pull_artifact("layer").await.unwrap(),
+            gzipped_layer
+        );
+        assert_eq!(std::fs::read_dir(&tmp_dir).unwrap().count(), 2);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    struct FailingReader {
+        remaining: usize,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.remaining == 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "disk full"));
+            }
+            let read = buf.len().min(self.remaining);
+            buf[..read].fill(7);
+            self.remaining -= read;
+            Ok(read)
+        }
+    }
+
+    #[tokio::test]
+    async fn interrupted_or_invalid_pushes_leave_nothing_behind() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+        assert!(artifact_storage
+            .push_artifact(FailingReader { remaining: 100 }, "artifact_id")
+            .await
+            .is_err());
+        assert_eq!(
+            artifact_storage
+                .push_verified_artifact(
+                    TEST_ARTIFACT_DATA.as_bytes(),
+                    "artifact_id",
+                    &"0".repeat(64)
+                )
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(std::fs::read_dir(&tmp_dir).unwrap().count(), 0);
+        assert!(artifact_storage.pull_artifact("artifact_id").await.is_err());
+
+        let content_hash = hex::encode(Sha256::digest(TEST_ARTIFACT_DATA));
+        assert_eq!(
+            artifact_storage
+                .push_verified_artifact(
+                    TEST_ARTIFACT_DATA.as_bytes(),
+                    "artifact_id",
+                    &content_hash.to_uppercase()
+                )
+                .await
+                .unwrap(),
+            content_hash
+        );
+        check_able_to_pull_artifact("artifact_id", &artifact_storage)
+            .await
+            .unwrap();
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn remove_stale_files_after_a_crash() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+        artifact_storage
+            .push_artifact(TEST_ARTIFACT_DATA.as_bytes(), "artifact_id")
+            .await
+            .unwrap();
+        artifact_storage
+            .write_partial_artifact("partial", 0, TEST_ARTIFACT_DATA.as_bytes())
+            .await
+            .unwrap();
+        let temp_file_path = artifact_storage.temp_file_path("pushed");
+        std::fs::write(&temp_file_path, "truncated").unwrap();
+
+        assert_eq!(
+            artifact_storage
+                .remove_stale_files(PARTIAL_ARTIFACT_MAX_AGE)
+                .unwrap(),
+            1
+        );
+        assert!(!temp_file_path.exists());
+        assert_eq!(
+            artifact_storage
+                .partial_artifact_size("partial")
+                .await
+                .unwrap(),
+            TEST_ARTIFACT_DATA.len() as u64
+        );
+
+        assert_eq!(
+            artifact_storage.remove_stale_files(Duration::ZERO).unwrap(),
+            1
+        );
+        assert_eq!(
+            artifact_storage
+                .partial_artifact_size("partial")
+                .await
+                .unwrap(),
+            0
+        );
+        check_able_to_pull_artifact("artifact_id", &artifact_storage)
+            .await
+            .unwrap();
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    fn check_artifact_is_written_correctly(dir_name: &Path, content_hash: &str) -> Result<()> {
+        let mut dir_name = dir_name.to_path_buf();
+        dir_name.push(content_hash);
+        dir_name.set_extension(FILE_EXTENSION);
+        let content_vec = std::fs::read(dir_name.as_path())
+            .context("reading pushed file")
+            .unwrap();
+        assert_eq!(content_vec.as_slice(), TEST_ARTIFACT_DATA.as_bytes());
+
+        Ok(())
+    }
+
+    async fn check_able_to_pull_artifact(
+        artifact_id: &str,
+        artifact_storage: &ArtifactStorage,
+    ) -> Result<()> {
+        let artifact = artifact_storage
+            .pull_artifact(artifact_id)
+            .await
+            .context("Error from pull_artifact")?;
+        assert_eq!(TEST_ARTIFACT_DATA.as_bytes(), artifact);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn pull_nonexistent_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_id = Uuid::new_v4().to_string();
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+        assert!(artifact_storage.pull_artifact(&artifact_id).await.is_err());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    pub async fn list_artifacts_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let string_reader = StringReader::new(TEST_ARTIFACT_DATA);
+        let artifact_id = Uuid::new_v4().to_string();
+        let artifact_storage =
+            ArtifactStorage::new(&tmp_dir).expect("Error creating ArtifactManager");
+
+        artifact_storage
+            .push_artifact(string_reader, &artifact_id)
+            .await
+            .context("Error from push_artifact")
+            .unwrap();
+
+        let result = artifact_storage.list_artifacts().await;
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().len(), 1);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn retain_artifacts_of_older_releases_once() {
+        let tmp_dir = test_util::tests::setup();
+
+        let artifact_storage = ArtifactStorage::new(&tmp_dir).unwrap();
+        for artifact_id in ["built_jar", "built_pom"] {
+            artifact_storage
+                .push_artifact(artifact_id.as_bytes(), artifact_id)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            artifact_storage
+                .retain_artifacts_of_older_releases(true)
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(artifact_storage
+            .eviction_candidates()
+            .await
+            .unwrap()
+            .is_empty());
+
+        // artifacts that are stored after the upgrade are not retained
+        artifact_storage
+            .push_artifact("downloaded".as_bytes(), "downloaded")
+            .await
+            .unwrap();
+        assert_eq!(
+            artifact_storage
+                .retain_artifacts_of_older_releases(true)
+                .await
+                .unwrap(),
+            0
+        );
+        assert!(!artifact_storage
+            .is_artifact_retained("downloaded")
+            .await
+            .unwrap());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn migrate_artifacts_of_the_legacy_layout() {
+        let tmp_dir = test_util::tests::setup();
+
+        // a repository of a release that stored artifacts under their id
+        let fixture_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/resources/artifact_storage/legacy");
+        for entry in std::fs::read_dir(fixture_path).unwrap() {
+            let entry = entry.unwrap();
+            std::fs::copy(entry.path(), tmp_dir.join(entry.file_name())).unwrap();
+        }
+        let jar_ids = [
+            "2f1c8a55-3a5e-4b8e-9d0f-6b7c1e2d4a90",
+            "e131322a-0c72-454d-b7a0-dcdb53c1bbdf",
+        ];
+        let pom_id = "9b0d7e44-1c2f-4f6a-8e3b-5a4d2c1b0f97";
+        let jar_hash = hex::encode(Sha256::digest("LEGACY_JAR"));
+        let pom_hash = hex::encode(Sha256::digest("LEGACY_POM"));
+
+        let artifact_storage = ArtifactStorage::new(&tmp_dir).unwrap();
+        assert_eq!(
+            artifact_storage.migrate_legacy_artifacts().await.unwrap(),
+            3
+        );
+
+        let mut content_keys = artifact_storage.list_artifacts().await.unwrap();
+        content_keys.sort();
+        let mut expected_keys = vec![jar_hash.clone(), pom_hash.clone()];
+        expected_keys.sort();
+        assert_eq!(content_keys, expected_keys);
+        for (artifact_id, content) in [
+            (jar_ids[0], "LEGACY_JAR"),
+            (jar_ids[1], "LEGACY_JAR"),
+            (pom_id, "LEGACY_POM"),
+            (jar_hash.as_str(), "LEGACY_JAR"),
+            (pom_hash.as_str(), "LEGACY_POM"),
+        ] {
+            assert_eq!(
+                artifact_storage.pull_artifact(artifact_id).await.unwrap(),
+                content.as_bytes()
+            );
+        }
+        assert!(artifact_storage.is_artifact_retained(pom_id).await.unwrap());
+        assert_eq!(artifact_storage.stored_contents().await.unwrap(), {
+            let mut contents = vec![
+                (jar_hash, jar_ids.iter().map(|id| id.to_string()).collect()),
+                (pom_hash, vec![pom_id.to_owned()]),
+            ];
+            contents.sort();
+            contents
+        });
+
+        // a restarted node finds nothing left to migrate
+        let artifact_storage = ArtifactStorage::new(&tmp_dir).unwrap();
+        assert_eq!(
+            artifact_storage.migrate_legacy_artifacts().await.unwrap(),
+            0
+        );
+        assert_eq!(
+            artifact_storage.pull_artifact(pom_id).await.unwrap(),
+            b"LEGACY_POM"
+        );
 
         test_util::tests::teardown(tmp_dir);
     }
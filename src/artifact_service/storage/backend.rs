@@ -0,0 +1,91 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::file_system::FileSystemBackend;
+use super::s3::{S3Backend, S3Config};
+use super::ArtifactStream;
+use crate::util::env_util::read_var;
+use anyhow::bail;
+use async_trait::async_trait;
+use bytes::BytesMut;
+use futures::StreamExt;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// The size and modification time of an object in a [`StorageBackend`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectMetadata {
+    pub key: String,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// A storage backend keeps the objects of the [`ArtifactStorage`](super::ArtifactStorage),
+/// like the content of artifacts and the aliases that refer to it, by key.
+/// Objects are written once from a local file and are read in streams.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Store the local file at `path` as the object with the specified key,
+    /// replacing any previous object. The local file is removed.
+    async fn put(&self, key: &str, path: &Path) -> io::Result<()>;
+
+    /// Store `data` as the object with the specified key, replacing any
+    /// previous object.
+    async fn put_bytes(&self, key: &str, data: Vec<u8>) -> io::Result<()>;
+
+    /// Stream the bytes of an object, starting at `offset`. At most `length`
+    /// bytes are streamed when a length is specified. Fails with
+    /// [`io::ErrorKind::NotFound`] when the object doesn't exist.
+    async fn get(&self, key: &str, offset: u64, length: Option<u64>) -> io::Result<ArtifactStream>;
+
+    /// The metadata of an object. Fails with [`io::ErrorKind::NotFound`] when
+    /// the object doesn't exist.
+    async fn metadata(&self, key: &str) -> io::Result<ObjectMetadata>;
+
+    /// Remove an object. Removing an object that doesn't exist succeeds.
+    async fn delete(&self, key: &str) -> io::Result<()>;
+
+    /// The metadata of all objects.
+    async fn list(&self) -> io::Result<Vec<ObjectMetadata>>;
+
+    /// Read all bytes of an object.
+    async fn read(&self, key: &str) -> io::Result<Vec<u8>> {
+        let mut stream = self.get(key, 0, None).await?;
+        let mut data = BytesMut::new();
+        while let Some(chunk) = stream.next().await {
+            data.extend_from_slice(&chunk?);
+        }
+        Ok(data.to_vec())
+    }
+}
+
+/// Create the storage backend that is configured with the `PYRSIA_STORAGE_BACKEND`
+/// environment variable. This is either `fs` (default), which keeps the artifacts
+/// in `repository_path`, or `s3`, which keeps them in an S3 compatible object
+/// store that is configured with the `PYRSIA_S3_*` environment variables, see
+/// [`S3Config::from_env`].
+pub fn from_env<P: AsRef<Path>>(repository_path: P) -> anyhow::Result<Arc<dyn StorageBackend>> {
+    match read_var("PYRSIA_STORAGE_BACKEND", "fs")
+        .to_lowercase()
+        .as_str()
+    {
+        "fs" => Ok(Arc::new(FileSystemBackend::new(repository_path)?)),
+        "s3" => Ok(Arc::new(S3Backend::new(S3Config::from_env()?)?)),
+        other => bail!("Unknown storage backend: {}", other),
+    }
+}
@@ -0,0 +1,506 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The zstd compression of artifacts at rest. The hashes of artifacts are
+//! always calculated on their uncompressed content, so compression is
+//! invisible outside of the [`ArtifactStorage`](super::ArtifactStorage).
+
+use super::{ArtifactStream, STREAM_CHUNK_SIZE};
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use zstd::stream::raw::{Decoder, Operation};
+
+/// The maximum size of the header of a zstd frame, which holds the size of
+/// the uncompressed content.
+pub const FRAME_HEADER_SIZE_MAX: u64 = 18;
+
+/// The size of the uncompressed content of every zstd frame of compressed
+/// content but the last. The frames are compressed independently, so a range
+/// of the content is read by only decompressing the frames that hold it.
+pub const FRAME_CONTENT_SIZE: usize = 1024 * 1024;
+
+/// The size of the footer at the end of the [`SeekTable`] of compressed
+/// content.
+pub const SEEK_TABLE_FOOTER_SIZE: u64 = 9;
+
+// The seek table is stored in a skippable frame at the end of compressed
+// content, in the zstd seekable format, so decoders that don't know about it
+// still decompress the content.
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184d_2a5e;
+const SKIPPABLE_FRAME_HEADER_SIZE: u64 = 8;
+const SEEKABLE_MAGIC: u32 = 0x8f92_eab1;
+const SEEK_TABLE_ENTRY_SIZE: u64 = 8;
+const SEEK_TABLE_CHECKSUM_FLAG: u8 = 0x80;
+
+// The number of bytes that are needed to detect the media type of content.
+const MEDIA_TYPE_HEADER_SIZE: usize = 512;
+
+/// Detect the media type of an artifact from the first bytes of its content.
+/// The storage doesn't know how artifacts are served, so it recognizes the
+/// formats that matter for compression by their magic numbers.
+pub fn detect_media_type(header: &[u8]) -> &'static str {
+    match header {
+        [0x1f, 0x8b, ..] => "application/gzip",
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => "application/zstd",
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => "application/x-xz",
+        [b'B', b'Z', b'h', ..] => "application/x-bzip2",
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'P', b'K', 0x03, 0x04, ..] => "application/zip",
+        _ if header.get(257..262) == Some(b"ustar") => "application/x-tar",
+        _ => match header.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(b'{') | Some(b'[') => "application/json",
+            Some(b'<') => "application/xml",
+            _ => "application/octet-stream",
+        },
+    }
+}
+
+/// Whether compressing content of the media type is worth it. Content that is
+/// already compressed, like gzipped Docker layers, only gets slower to read.
+pub fn is_compressible(media_type: &str) -> bool {
+    !matches!(
+        media_type,
+        "application/gzip"
+            | "application/zstd"
+            | "application/x-xz"
+            | "application/x-bzip2"
+            | "image/png"
+            | "image/jpeg"
+    )
+}
+
+/// Detect the media type of the content of the file at `path`.
+pub fn detect_file_media_type(path: &Path) -> io::Result<&'static str> {
+    let mut header = Vec::with_capacity(MEDIA_TYPE_HEADER_SIZE);
+    File::open(path)?
+        .take(MEDIA_TYPE_HEADER_SIZE as u64)
+        .read_to_end(&mut header)?;
+    Ok(detect_media_type(&header))
+}
+
+/// Compress the file at `source` into a new file at `target` and sync it to
+/// disk. The content is compressed in independent frames of
+/// [`FRAME_CONTENT_SIZE`] bytes, followed by the [`SeekTable`] of the frames.
+/// Returns the size of the compressed file.
+pub fn compress_file(source: &Path, target: &Path, level: i32) -> io::Result<u64> {
+    let mut source = BufReader::new(File::open(source)?);
+    let mut target = BufWriter::new(File::create(target)?);
+    let mut compressor = zstd::bulk::Compressor::new(level)?;
+    let mut seek_table = SeekTable::default();
+    let mut frame_content = Vec::with_capacity(FRAME_CONTENT_SIZE);
+    loop {
+        frame_content.clear();
+        (&mut source)
+            .take(FRAME_CONTENT_SIZE as u64)
+            .read_to_end(&mut frame_content)?;
+        if frame_content.is_empty() {
+            break;
+        }
+        let frame = compressor.compress(&frame_content)?;
+        target.write_all(&frame)?;
+        seek_table.frames.push(FrameSize {
+            compressed: frame.len() as u64,
+            uncompressed: frame_content.len() as u64,
+        });
+    }
+    target.write_all(&seek_table.to_bytes())?;
+    let compressed_file = target.into_inner().map_err(|e| e.into_error())?;
+    compressed_file.sync_all()?;
+    Ok(compressed_file.metadata()?.len())
+}
+
+/// The compressed and uncompressed size of every frame of compressed content,
+/// to find the frames that hold a range of the uncompressed content.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SeekTable {
+    frames: Vec<FrameSize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FrameSize {
+    compressed: u64,
+    uncompressed: u64,
+}
+
+/// The frames of compressed content that hold a range of its uncompressed
+/// content.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FrameRange {
+    /// The offset of the first frame in the compressed content.
+    pub offset: u64,
+    /// The compressed size of the frames.
+    pub length: u64,
+    /// The number of uncompressed bytes of the first frame before the range.
+    pub skip: u64,
+}
+
+impl SeekTable {
+    /// The seek table of content that is compressed in a single frame, like
+    /// the content that was compressed before it was split in frames.
+    pub fn single_frame(compressed_size: u64, size: u64) -> SeekTable {
+        SeekTable {
+            frames: vec![FrameSize {
+                compressed: compressed_size,
+                uncompressed: size,
+            }],
+        }
+    }
+
+    /// The size of the skippable frame that holds the seek table, read from
+    /// the footer at the end of compressed content. Returns `None` when the
+    /// compressed content has no seek table.
+    pub fn stored_size(footer: &[u8]) -> Option<u64> {
+        if footer.len() != SEEK_TABLE_FOOTER_SIZE as usize || read_u32(footer, 5) != SEEKABLE_MAGIC
+        {
+            return None;
+        }
+        Some(
+            SKIPPABLE_FRAME_HEADER_SIZE
+                + read_u32(footer, 0) as u64 * entry_size(footer[4])
+                + SEEK_TABLE_FOOTER_SIZE,
+        )
+    }
+
+    /// Parse the skippable frame that holds a seek table.
+    pub fn parse(frame: &[u8]) -> io::Result<SeekTable> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The compressed content has an invalid seek table",
+            )
+        };
+        let header_size = SKIPPABLE_FRAME_HEADER_SIZE as usize;
+        let footer_size = SEEK_TABLE_FOOTER_SIZE as usize;
+        if frame.len() < header_size + footer_size || read_u32(frame, 0) != SKIPPABLE_FRAME_MAGIC {
+            return Err(invalid());
+        }
+        let footer = &frame[frame.len() - footer_size..];
+        let entries = &frame[header_size..frame.len() - footer_size];
+        let entry_size = entry_size(footer[4]) as usize;
+        if entries.len() != read_u32(footer, 0) as usize * entry_size {
+            return Err(invalid());
+        }
+        Ok(SeekTable {
+            frames: entries
+                .chunks(entry_size)
+                .map(|entry| FrameSize {
+                    compressed: read_u32(entry, 0) as u64,
+                    uncompressed: read_u32(entry, 4) as u64,
+                })
+                .collect(),
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let entries_size = self.frames.len() as u64 * SEEK_TABLE_ENTRY_SIZE;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&SKIPPABLE_FRAME_MAGIC.to_le_bytes());
+        bytes.extend_from_slice(&((entries_size + SEEK_TABLE_FOOTER_SIZE) as u32).to_le_bytes());
+        for frame in &self.frames {
+            bytes.extend_from_slice(&(frame.compressed as u32).to_le_bytes());
+            bytes.extend_from_slice(&(frame.uncompressed as u32).to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+        bytes
+    }
+
+    /// The size of the uncompressed content.
+    pub fn size(&self) -> u64 {
+        self.frames.iter().map(|frame| frame.uncompressed).sum()
+    }
+
+    /// The frames that hold the uncompressed content from `offset`, up to
+    /// `length` bytes when a length is specified.
+    pub fn frame_range(&self, offset: u64, length: Option<u64>) -> FrameRange {
+        let end = length.map_or(u64::MAX, |length| offset.saturating_add(length));
+        let mut range = FrameRange {
+            offset: 0,
+            length: 0,
+            skip: 0,
+        };
+        let mut frame_start = 0;
+        for frame in &self.frames {
+            if frame_start >= end {
+                break;
+            }
+            let frame_end = frame_start + frame.uncompressed;
+            if frame_end <= offset {
+                range.offset += frame.compressed;
+            } else {
+                if range.length == 0 {
+                    range.skip = offset.saturating_sub(frame_start);
+                }
+                range.length += frame.compressed;
+            }
+            frame_start = frame_end;
+        }
+        range
+    }
+}
+
+// The size of the entries of a seek table with the specified descriptor,
+// which tells whether the entries hold a checksum.
+fn entry_size(descriptor: u8) -> u64 {
+    if descriptor & SEEK_TABLE_CHECKSUM_FLAG == 0 {
+        SEEK_TABLE_ENTRY_SIZE
+    } else {
+        SEEK_TABLE_ENTRY_SIZE + 4
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// The size of the uncompressed content, read from the header of its first
+/// zstd frame. Only content that is compressed in a single frame holds its
+/// size there.
+pub fn decompressed_size(header: &[u8]) -> io::Result<u64> {
+    match zstd::zstd_safe::get_frame_content_size(header) {
+        Ok(Some(size)) => Ok(size),
+        Ok(None) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The compressed content has an unknown size",
+        )),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The compressed content has an invalid header",
+        )),
+    }
+}
+
+/// Decompress a stream of zstd compressed bytes. The stream ends with an
+/// error when the compressed content is corrupt or truncated.
+pub fn decompress_stream(content: ArtifactStream) -> io::Result<ArtifactStream> {
+    let state = DecompressingReader {
+        content,
+        decoder: Decoder::new()?,
+        input: Bytes::new(),
+        frame_finished: true,
+        content_finished: false,
+    };
+
+    Ok(Box::pin(stream::try_unfold(
+        state,
+        |mut state| async move {
+            match state.next_chunk().await? {
+                Some(chunk) => Ok(Some((chunk, state))),
+                None => Ok(None),
+            }
+        },
+    )))
+}
+
+/// Skip the first `offset` bytes of a stream, and end it after at most
+/// `length` bytes when a length is specified.
+pub fn slice_stream(content: ArtifactStream, offset: u64, length: Option<u64>) -> ArtifactStream {
+    Box::pin(stream::try_unfold(
+        (content, offset, length),
+        |(mut content, mut skip, remaining)| async move {
+            if remaining == Some(0) {
+                return Ok(None);
+            }
+            while let Some(chunk) = content.next().await {
+                let mut chunk = chunk?;
+                if skip >= chunk.len() as u64 {
+                    skip -= chunk.len() as u64;
+                    continue;
+                }
+                chunk = chunk.slice(skip as usize..);
+                let remaining = remaining.map(|remaining| {
+                    chunk.truncate(remaining.min(chunk.len() as u64) as usize);
+                    remaining - chunk.len() as u64
+                });
+                return Ok(Some((chunk, (content, 0, remaining))));
+            }
+            Ok(None)
+        },
+    ))
+}
+
+struct DecompressingReader {
+    content: ArtifactStream,
+    decoder: Decoder<'static>,
+    input: Bytes,
+    frame_finished: bool,
+    content_finished: bool,
+}
+
+impl DecompressingReader {
+    async fn next_chunk(&mut self) -> io::Result<Option<Bytes>> {
+        let mut output = vec![0; STREAM_CHUNK_SIZE];
+        loop {
+            if self.input.is_empty() && !self.content_finished {
+                match self.content.next().await {
+                    Some(chunk) => self.input = chunk?,
+                    None => self.content_finished = true,
+                }
+            }
+
+            let status = self.decoder.run_on_buffers(&self.input, &mut output)?;
+            self.input = self.input.slice(status.bytes_read..);
+            if status.bytes_read > 0 || status.bytes_written > 0 {
+                self.frame_finished = status.remaining == 0;
+            }
+            if status.bytes_written > 0 {
+                output.truncate(status.bytes_written);
+                return Ok(Some(Bytes::from(output)));
+            }
+
+            if self.content_finished && self.input.is_empty() {
+                return if self.frame_finished {
+                    Ok(None)
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "The compressed content is truncated",
+                    ))
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::util::test_util;
+
+    fn stream_of(data: Vec<u8>, chunk_size: usize) -> ArtifactStream {
+        let chunks: Vec<io::Result<Bytes>> = data
+            .chunks(chunk_size)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        Box::pin(stream::iter(chunks))
+    }
+
+    async fn collect(mut content: ArtifactStream) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        while let Some(chunk) = content.next().await {
+            data.extend_from_slice(&chunk?);
+        }
+        Ok(data)
+    }
+
+    #[test]
+    fn detect_media_types() {
+        assert_eq!(detect_media_type(&[0x1f, 0x8b, 8, 0]), "application/gzip");
+        assert_eq!(detect_media_type(b"PK\x03\x04jar"), "application/zip");
+        assert_eq!(
+            detect_media_type(b"\n  {\"schemaVersion\": 2}"),
+            "application/json"
+        );
+        assert_eq!(detect_media_type(b"<?xml version"), "application/xml");
+        assert_eq!(detect_media_type(b""), "application/octet-stream");
+
+        assert!(!is_compressible("application/gzip"));
+        assert!(is_compressible("application/zip"));
+        assert!(is_compressible("application/json"));
+    }
+
+    #[tokio::test]
+    async fn compress_and_decompress_a_file() {
+        let tmp_dir = test_util::tests::setup();
+        let content = "{\"layers\": []}\n".repeat(10_000).into_bytes();
+        let source = tmp_dir.join("source");
+        let target = tmp_dir.join("target");
+        std::fs::write(&source, &content).unwrap();
+
+        let compressed_size = compress_file(&source, &target, 3).unwrap();
+        let compressed = std::fs::read(&target).unwrap();
+        assert_eq!(compressed_size, compressed.len() as u64);
+        assert!(compressed_size < content.len() as u64 / 10);
+        assert_eq!(
+            decompressed_size(&compressed[..FRAME_HEADER_SIZE_MAX as usize]).unwrap(),
+            content.len() as u64
+        );
+
+        let decompressed = decompress_stream(stream_of(compressed.clone(), 7)).unwrap();
+        assert_eq!(collect(decompressed).await.unwrap(), content);
+
+        let decompressed = decompress_stream(stream_of(compressed.clone(), 1000)).unwrap();
+        assert_eq!(
+            collect(slice_stream(decompressed, 100_000, Some(20)))
+                .await
+                .unwrap(),
+            &content[100_000..100_020]
+        );
+
+        let truncated = compressed[..compressed.len() - 5].to_vec();
+        let decompressed = decompress_stream(stream_of(truncated, 1000)).unwrap();
+        assert!(collect(decompressed).await.is_err());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn decompress_a_range_of_frames() {
+        let tmp_dir = test_util::tests::setup();
+        let content: Vec<u8> = (0..FRAME_CONTENT_SIZE * 7 / 2)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let source = tmp_dir.join("source");
+        let target = tmp_dir.join("target");
+        std::fs::write(&source, &content).unwrap();
+
+        compress_file(&source, &target, 3).unwrap();
+        let compressed = std::fs::read(&target).unwrap();
+        let footer = &compressed[compressed.len() - SEEK_TABLE_FOOTER_SIZE as usize..];
+        let table_size = SeekTable::stored_size(footer).unwrap();
+        let seek_table =
+            SeekTable::parse(&compressed[compressed.len() - table_size as usize..]).unwrap();
+        assert_eq!(seek_table.frames.len(), 4);
+        assert_eq!(seek_table.size(), content.len() as u64);
+
+        let decompressed = decompress_stream(stream_of(compressed.clone(), 1000)).unwrap();
+        assert_eq!(collect(decompressed).await.unwrap(), content);
+
+        let offset = FRAME_CONTENT_SIZE as u64 * 2 - 10;
+        let frames = seek_table.frame_range(offset, Some(20));
+        assert_eq!(frames.offset, seek_table.frames[0].compressed);
+        assert_eq!(
+            frames.length,
+            seek_table.frames[1].compressed + seek_table.frames[2].compressed
+        );
+        assert_eq!(frames.skip, FRAME_CONTENT_SIZE as u64 - 10);
+        let compressed_frames =
+            compressed[frames.offset as usize..][..frames.length as usize].to_vec();
+        let decompressed = decompress_stream(stream_of(compressed_frames, 1000)).unwrap();
+        assert_eq!(
+            collect(slice_stream(decompressed, frames.skip, Some(20)))
+                .await
+                .unwrap(),
+            &content[offset as usize..offset as usize + 20]
+        );
+
+        assert_eq!(seek_table.frame_range(content.len() as u64, None).length, 0);
+        assert_eq!(SeekTable::stored_size(&compressed[..9]), None);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+}
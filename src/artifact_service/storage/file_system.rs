@@ -0,0 +1,235 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::backend::{ObjectMetadata, StorageBackend};
+use super::{ArtifactStream, STREAM_CHUNK_SIZE, TEMP_FILE_EXTENSION};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bytes::BytesMut;
+use futures::stream;
+use log::error;
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use uuid::Uuid;
+
+/// A storage backend that keeps every object in a file, named after its key,
+/// in a local directory.
+pub struct FileSystemBackend {
+    root: PathBuf,
+}
+
+impl FileSystemBackend {
+    pub fn new<P: AsRef<Path>>(root: P) -> anyhow::Result<FileSystemBackend> {
+        let absolute_path = root.as_ref().to_path_buf().canonicalize()?;
+        if absolute_path.is_dir() {
+            Ok(FileSystemBackend {
+                root: absolute_path,
+            })
+        } else {
+            error!(
+                "Unable to create FileSystemBackend with inaccessible directory: {:?}",
+                absolute_path
+            );
+            Err(anyhow!("Not an accessible directory: {:?}", absolute_path))
+        }
+    }
+
+    fn object_path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    // A new temporary file next to the object, so it can be renamed into place.
+    fn temp_object_path(&self, key: &str) -> PathBuf {
+        self.root.join(format!(
+            "{}.{}.{}",
+            key,
+            Uuid::new_v4(),
+            TEMP_FILE_EXTENSION
+        ))
+    }
+
+    // Move a file that is synced to disk into place as the object with the
+    // specified key. Renames are atomic, so readers either see the previous
+    // object or the complete new one. The directory is synced as well, so the
+    // rename survives a crash.
+    async fn rename_into_place(&self, path: &Path, key: &str) -> io::Result<()> {
+        tokio::fs::rename(path, self.object_path(key)).await?;
+        sync_dir(&self.root).await
+    }
+
+    async fn write_temp_object(&self, key: &str, data: &[u8]) -> io::Result<PathBuf> {
+        let temp_path = self.temp_object_path(key);
+        let mut file = tokio::fs::File::create(&temp_path).await?;
+        let result = async {
+            file.write_all(data).await?;
+            file.sync_all().await
+        }
+        .await;
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        Ok(temp_path)
+    }
+
+    fn object_metadata(key: &str, metadata: &std::fs::Metadata) -> io::Result<ObjectMetadata> {
+        Ok(ObjectMetadata {
+            key: key.to_owned(),
+            size: metadata.len(),
+            modified: metadata.modified()?,
+        })
+    }
+}
+
+#[async_trait]
+impl StorageBackend for FileSystemBackend {
+    async fn put(&self, key: &str, path: &Path) -> io::Result<()> {
+        if self.rename_into_place(path, key).await.is_ok() {
+            return Ok(());
+        }
+
+        // the local file might be on another file system
+        let temp_path = self.temp_object_path(key);
+        let result = async {
+            tokio::fs::copy(path, &temp_path).await?;
+            tokio::fs::File::open(&temp_path).await?.sync_all().await?;
+            self.rename_into_place(&temp_path, key).await
+        }
+        .await;
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        tokio::fs::remove_file(path).await
+    }
+
+    async fn put_bytes(&self, key: &str, data: Vec<u8>) -> io::Result<()> {
+        let temp_path = self.write_temp_object(key, &data).await?;
+        if let Err(e) = self.rename_into_place(&temp_path, key).await {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str, offset: u64, length: Option<u64>) -> io::Result<ArtifactStream> {
+        let mut file = tokio::fs::File::open(self.object_path(key)).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+
+        let stream = stream::try_unfold(
+            file.take(length.unwrap_or(u64::MAX)),
+            |mut reader| async move {
+                let mut buffer = BytesMut::zeroed(STREAM_CHUNK_SIZE);
+                let read = reader.read(&mut buffer).await?;
+                if read == 0 {
+                    return Ok(None);
+                }
+                buffer.truncate(read);
+                Ok(Some((buffer.freeze(), reader)))
+            },
+        );
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn metadata(&self, key: &str) -> io::Result<ObjectMetadata> {
+        let metadata = tokio::fs::metadata(self.object_path(key)).await?;
+        if !metadata.is_file() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Self::object_metadata(key, &metadata)
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match tokio::fs::remove_file(self.object_path(key)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self) -> io::Result<Vec<ObjectMetadata>> {
+        let mut objects = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.root).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if let (true, Some(key)) = (metadata.is_file(), entry.file_name().to_str()) {
+                objects.push(Self::object_metadata(key, &metadata)?);
+            }
+        }
+        Ok(objects)
+    }
+}
+
+// Sync a directory to disk, so the files that were created in or renamed into
+// it survive a crash. Directories can't be synced on all platforms.
+async fn sync_dir(path: &Path) -> io::Result<()> {
+    if cfg!(unix) {
+        tokio::fs::File::open(path).await?.sync_all().await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::util::test_util;
+
+    #[tokio::test]
+    async fn put_get_and_delete_objects() {
+        let tmp_dir = test_util::tests::setup();
+        let backend = FileSystemBackend::new(&tmp_dir).unwrap();
+
+        let local_file = tmp_dir.join("local");
+        std::fs::write(&local_file, b"0123456789").unwrap();
+        backend.put("object", &local_file).await.unwrap();
+        backend.put_bytes("marker", Vec::new()).await.unwrap();
+
+        assert!(!local_file.exists());
+        assert_eq!(backend.read("object").await.unwrap(), b"0123456789");
+        let mut range = Vec::new();
+        let mut stream = backend.get("object", 2, Some(5)).await.unwrap();
+        while let Some(chunk) = futures::StreamExt::next(&mut stream).await {
+            range.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(range, b"23456");
+        assert_eq!(backend.metadata("object").await.unwrap().size, 10);
+
+        let mut keys: Vec<String> = backend
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|object| object.key)
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec!["marker", "object"]);
+
+        backend.delete("object").await.unwrap();
+        backend.delete("object").await.unwrap();
+        assert_eq!(
+            backend.metadata("object").await.unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            backend.get("object", 0, None).await.err().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+}
@@ -34,6 +34,13 @@ pub struct BlockSignature {
     public_key: PublicKey,
 }
 
+impl BlockSignature {
+    /// The encoded ed25519 public key of the node that signed the block.
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Decode, Encode, Hash)]
 pub struct Block {
     pub header: Header,
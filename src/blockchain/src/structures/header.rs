@@ -135,6 +135,11 @@ impl Header {
     pub fn hash(&self) -> HashDigest {
         self.hash
     }
+
+    /// Check that the hash of the header was calculated from its current fields
+    pub fn verify_hash(&self) -> bool {
+        matches!(calculate_hash(&PartialHeader::from(*self)), Ok(hash) if hash == self.hash)
+    }
 }
 
 #[cfg(test)]
@@ -156,4 +161,15 @@ mod tests {
         assert_eq!(5, header.ordinal);
         assert_eq!(expected_hash, header.hash());
     }
+
+    #[test]
+    fn test_verify_hash() {
+        let keypair = identity::ed25519::Keypair::generate();
+        let local_id = Address::from(identity::PublicKey::Ed25519(keypair.public()));
+        let mut header = Header::new(HashDigest::new(b""), HashDigest::new(b""), local_id, 5);
+        assert!(header.verify_hash());
+
+        header.ordinal = 6;
+        assert!(!header.verify_hash());
+    }
 }
@@ -19,7 +19,8 @@ use crate::blockchain_service::service::BlockchainService;
 use libp2p::PeerId;
 use log::{debug, error, warn};
 use pyrsia_blockchain_network::error::BlockchainError;
-use pyrsia_blockchain_network::structures::block::Block;
+use pyrsia_blockchain_network::signature::Signature;
+use pyrsia_blockchain_network::structures::block::{Block, PublicKey};
 use pyrsia_blockchain_network::structures::header::Ordinal;
 use tokio::sync::{mpsc, oneshot};
 
@@ -38,6 +39,13 @@ pub enum BlockchainEvent {
         end: Ordinal,
         sender: oneshot::Sender<Result<Vec<Block>, BlockchainError>>,
     },
+    PullAllBlocksLocal {
+        sender: oneshot::Sender<Result<Vec<Block>, BlockchainError>>,
+    },
+    SignPayload {
+        payload: Vec<u8>,
+        sender: oneshot::Sender<(PublicKey, Signature)>,
+    },
     HandleBlockBroadcast {
         block_ordinal: Ordinal,
         block: Box<Block>,
@@ -108,6 +116,31 @@ impl BlockchainEventClient {
         receiver.await.map_err(BlockchainError::ChannelClosed)?
     }
 
+    pub async fn pull_all_blocks_local(&self) -> Result<Vec<Block>, BlockchainError> {
+        let (sender, receiver) = oneshot::channel();
+        self.blockchain_event_sender
+            .send(BlockchainEvent::PullAllBlocksLocal { sender })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Error blockchain_event_sender. {:#?}", e);
+            });
+        receiver.await.map_err(BlockchainError::ChannelClosed)?
+    }
+
+    pub async fn sign_payload(
+        &self,
+        payload: Vec<u8>,
+    ) -> Result<(PublicKey, Signature), BlockchainError> {
+        let (sender, receiver) = oneshot::channel();
+        self.blockchain_event_sender
+            .send(BlockchainEvent::SignPayload { payload, sender })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Error blockchain_event_sender. {:#?}", e);
+            });
+        receiver.await.map_err(BlockchainError::ChannelClosed)
+    }
+
     pub async fn handle_broadcast_blockchain(
         &self,
         block_ordinal: Ordinal,
@@ -217,6 +250,20 @@ impl BlockchainEventLoop {
                     error!("pull blocks local error. {:#?}", e);
                 });
             }
+            BlockchainEvent::PullAllBlocksLocal { sender } => {
+                debug!("Handling pull all blocks");
+
+                let result = self.blockchain_service.pull_all_blocks().await;
+                sender.send(result).unwrap_or_else(|e| {
+                    error!("pull all blocks local error. {:#?}", e);
+                });
+            }
+            BlockchainEvent::SignPayload { payload, sender } => {
+                let result = self.blockchain_service.sign_payload(&payload);
+                sender.send(result).unwrap_or_else(|e| {
+                    error!("sign payload error. {:#?}", e);
+                });
+            }
             BlockchainEvent::HandleBlockBroadcast {
                 block_ordinal,
                 block,
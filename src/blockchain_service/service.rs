@@ -19,7 +19,8 @@ use libp2p::{identity, PeerId};
 use log::warn;
 use pyrsia_blockchain_network::blockchain::Blockchain;
 use pyrsia_blockchain_network::error::BlockchainError;
-use pyrsia_blockchain_network::structures::block::Block;
+use pyrsia_blockchain_network::signature::Signature;
+use pyrsia_blockchain_network::structures::block::{Block, PublicKey};
 use pyrsia_blockchain_network::structures::header::Ordinal;
 use std::cmp::Ordering;
 use std::fmt::{self, Debug, Formatter};
@@ -242,10 +243,27 @@ impl BlockchainService {
         self.blockchain.pull_blocks(start, end)
     }
 
+    /// Retrieve all blocks of the local blockchain, from the genesis block up to the last block.
+    pub async fn pull_all_blocks(&self) -> Result<Vec<Block>, BlockchainError> {
+        match self.blockchain.last_block() {
+            Some(last_block) => self.blockchain.pull_blocks(0, last_block.header.ordinal),
+            None => Ok(vec![]),
+        }
+    }
+
     pub async fn query_last_block(&self) -> Option<Block> {
         self.blockchain.last_block()
     }
 
+    /// Sign a payload with the keypair of the local node. Returns the public key that
+    /// verifies the signature together with the signature itself.
+    pub fn sign_payload(&self, payload: &[u8]) -> (PublicKey, Signature) {
+        (
+            self.keypair.public().encode(),
+            Signature::new(payload, &self.keypair),
+        )
+    }
+
     pub async fn init_pull_from_others(
         &mut self,
         other_peer_id: &PeerId,
@@ -392,6 +410,52 @@ mod tests {
         test_util::tests::teardown(tmp_dir);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_pull_all_blocks() {
+        let tmp_dir = test_util::tests::setup();
+
+        let mut blockchain_service = create_blockchain_service(&tmp_dir).await.0;
+        let genesis_block = blockchain_service.blockchain.last_block().unwrap();
+        let block = Block::new(
+            genesis_block.header.hash(),
+            1,
+            vec![],
+            &blockchain_service.keypair,
+        );
+        blockchain_service
+            .add_block(1, Box::new(block.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            vec![genesis_block, block],
+            blockchain_service.pull_all_blocks().await.unwrap()
+        );
+
+        let other_blockchain_service = create_other_blockchain_service(tmp_dir.join("other"));
+        assert!(other_blockchain_service
+            .pull_all_blocks()
+            .await
+            .unwrap()
+            .is_empty());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sign_payload() {
+        let tmp_dir = test_util::tests::setup();
+
+        let blockchain_service = create_blockchain_service(&tmp_dir).await.0;
+        let (public_key, signature) = blockchain_service.sign_payload(b"payload");
+
+        let public_key = identity::ed25519::PublicKey::decode(&public_key).unwrap();
+        assert!(public_key.verify(b"payload", &signature.clone().to_bytes()));
+        assert!(!public_key.verify(b"other payload", &signature.to_bytes()));
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_query_last_block() {
         let tmp_dir = test_util::tests::setup();
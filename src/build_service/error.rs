@@ -51,4 +51,6 @@ pub enum BuildError {
     PipelineServiceEndpointRequestFailure(String),
     #[error("Failed to fetch build status: {0}")]
     BuildStatusFailed(String),
+    #[error("Failed to remove stale build directories: {0}")]
+    CleanUpFailed(String),
 }
@@ -18,7 +18,7 @@ use crate::artifact_service::model::PackageType;
 use crate::artifact_service::service::ArtifactService;
 use crate::build_service::error::BuildError;
 use crate::build_service::model::{BuildResult, BuildStatus, BuildTrigger};
-use crate::build_service::service::BuildService;
+use crate::build_service::service::{BuildService, STALE_BUILD_DIR_AGE};
 use crate::verification_service::service::VerificationService;
 use libp2p::PeerId;
 use log::{debug, error, info, warn};
@@ -52,6 +52,10 @@ pub enum BuildEvent {
         build_trigger: BuildTrigger,
         build_result: BuildResult,
     },
+    CollectGarbage {
+        dry_run: bool,
+        sender: oneshot::Sender<Result<Vec<String>, BuildError>>,
+    },
 }
 
 #[derive(Clone)]
@@ -176,6 +180,22 @@ impl BuildEventClient {
                 error!("Error build_event_sender. {:#?}", e);
             });
     }
+
+    /// Remove the build directories that were left behind, see
+    /// [`BuildService::remove_stale_build_dirs`]. Returns the ids of the builds
+    /// of which the directory was (or, for a dry run, would be) removed.
+    pub async fn collect_garbage(&self, dry_run: bool) -> Result<Vec<String>, BuildError> {
+        let (sender, receiver) = oneshot::channel();
+        self.build_event_sender
+            .send(BuildEvent::CollectGarbage { dry_run, sender })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Error build_event_sender. {:#?}", e);
+            });
+        receiver
+            .await
+            .map_err(|e| BuildError::CleanUpFailed(e.to_string()))?
+    }
 }
 
 pub struct BuildEventLoop {
@@ -293,6 +313,15 @@ impl BuildEventLoop {
 
                 self.build_service.clean_up_build(&build_id);
             }
+            BuildEvent::CollectGarbage { dry_run, sender } => {
+                let result = self
+                    .build_service
+                    .remove_stale_build_dirs(STALE_BUILD_DIR_AGE, dry_run)
+                    .map_err(|e| BuildError::CleanUpFailed(e.to_string()));
+                sender.send(result).unwrap_or_else(|build_error| {
+                    error!("build error. {:#?}", build_error);
+                });
+            }
         }
     }
 }
@@ -379,6 +408,22 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_collect_garbage() {
+        let (client, mut receiver) = test_util::tests::create_build_event_client();
+
+        let result = tokio::spawn(async move { client.collect_garbage(true).await });
+
+        match receiver.recv().await {
+            Some(BuildEvent::CollectGarbage { dry_run, sender }) => {
+                assert!(dry_run);
+                let _ = sender.send(Ok(vec![String::from("build_id")]));
+            }
+            _ => panic!("Command must match BuildEvent::CollectGarbage"),
+        }
+        assert_eq!(result.await.unwrap().unwrap(), vec!["build_id"]);
+    }
+
     #[tokio::test]
     async fn test_build_succeeded() {
         let (client, mut receiver) = test_util::tests::create_build_event_client();
@@ -44,16 +44,47 @@ impl MappingService {
         package_specific_id: &str,
     ) -> Result<MappingInfo, BuildError> {
         match package_type {
+            PackageType::Cargo => self.get_cargo_mapping(package_specific_id).await,
             PackageType::Docker => Ok(MappingInfo {
                 package_type,
                 package_specific_id: package_specific_id.to_owned(),
                 source_repository: None,
                 build_spec_url: None,
             }),
+            PackageType::GoModule => self.get_go_module_mapping(package_specific_id).await,
             PackageType::Maven2 => self.get_maven_mapping(package_specific_id).await,
+            PackageType::Npm => self.get_npm_mapping(package_specific_id).await,
+            PackageType::PyPI => self.get_pypi_mapping(package_specific_id).await,
         }
     }
 
+    async fn get_go_module_mapping(
+        &self,
+        package_specific_id: &str,
+    ) -> Result<MappingInfo, BuildError> {
+        // go module package specific ids look like "github.com/pkg/errors@v0.9.1"
+        let (module, version) = package_specific_id
+            .rsplit_once('@')
+            .filter(|(module, version)| !module.is_empty() && !version.is_empty())
+            .ok_or_else(|| BuildError::MappingNotFound {
+                package_type: PackageType::GoModule,
+                package_specific_id: package_specific_id.to_owned(),
+            })?;
+        let base_name = module.rsplit('/').next().unwrap_or(module);
+
+        let remote_mapping_url = format!(
+            "{}/GoModule/{}/{}/{}-{}.mapping",
+            self.mapping_service_endpoint, module, version, base_name, version
+        );
+
+        self.fetch_mapping(
+            remote_mapping_url,
+            PackageType::GoModule,
+            package_specific_id,
+        )
+        .await
+    }
+
     async fn get_maven_mapping(
         &self,
         package_specific_id: &str,
@@ -69,6 +100,77 @@ impl MappingService {
             self.mapping_service_endpoint, group_id, artifact_id, version, artifact_id, version
         );
 
+        self.fetch_mapping(remote_mapping_url, PackageType::Maven2, package_specific_id)
+            .await
+    }
+
+    async fn get_cargo_mapping(
+        &self,
+        package_specific_id: &str,
+    ) -> Result<MappingInfo, BuildError> {
+        // cargo package specific ids look like "name@version"
+        let (name, version) = package_specific_id
+            .split_once('@')
+            .filter(|(name, version)| !name.is_empty() && !version.is_empty())
+            .ok_or_else(|| BuildError::MappingNotFound {
+                package_type: PackageType::Cargo,
+                package_specific_id: package_specific_id.to_owned(),
+            })?;
+
+        let remote_mapping_url = format!(
+            "{}/Cargo/{}/{}/{}-{}.mapping",
+            self.mapping_service_endpoint, name, version, name, version
+        );
+
+        self.fetch_mapping(remote_mapping_url, PackageType::Cargo, package_specific_id)
+            .await
+    }
+
+    async fn get_npm_mapping(&self, package_specific_id: &str) -> Result<MappingInfo, BuildError> {
+        // npm package specific ids look like "name@version" or "@scope/name@version"
+        let (name, version) = package_specific_id
+            .rsplit_once('@')
+            .filter(|(name, version)| !name.is_empty() && !version.is_empty())
+            .ok_or_else(|| BuildError::MappingNotFound {
+                package_type: PackageType::Npm,
+                package_specific_id: package_specific_id.to_owned(),
+            })?;
+        let base_name = name.rsplit('/').next().unwrap_or(name);
+
+        let remote_mapping_url = format!(
+            "{}/Npm/{}/{}/{}-{}.mapping",
+            self.mapping_service_endpoint, name, version, base_name, version
+        );
+
+        self.fetch_mapping(remote_mapping_url, PackageType::Npm, package_specific_id)
+            .await
+    }
+
+    async fn get_pypi_mapping(&self, package_specific_id: &str) -> Result<MappingInfo, BuildError> {
+        // python package specific ids look like "project==version"
+        let (project, version) = package_specific_id
+            .split_once("==")
+            .filter(|(project, version)| !project.is_empty() && !version.is_empty())
+            .ok_or_else(|| BuildError::MappingNotFound {
+                package_type: PackageType::PyPI,
+                package_specific_id: package_specific_id.to_owned(),
+            })?;
+
+        let remote_mapping_url = format!(
+            "{}/PyPI/{}/{}/{}-{}.mapping",
+            self.mapping_service_endpoint, project, version, project, version
+        );
+
+        self.fetch_mapping(remote_mapping_url, PackageType::PyPI, package_specific_id)
+            .await
+    }
+
+    async fn fetch_mapping(
+        &self,
+        remote_mapping_url: String,
+        package_type: PackageType,
+        package_specific_id: &str,
+    ) -> Result<MappingInfo, BuildError> {
         let client = reqwest::Client::new();
         let response = client
             .get(remote_mapping_url)
@@ -83,7 +185,7 @@ impl MappingService {
                 .map_err(|e| BuildError::InvalidMappingResponse(e.to_string()))
         } else if response.status() == hyper::StatusCode::NOT_FOUND {
             Err(BuildError::MappingNotFound {
-                package_type: PackageType::Maven2,
+                package_type,
                 package_specific_id: package_specific_id.to_owned(),
             })
         } else {
@@ -120,6 +222,38 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn cargo_mapping_info() {
+        let mapping_info = MappingInfo {
+            package_type: PackageType::Cargo,
+            package_specific_id: "serde@1.0.152".to_owned(),
+            source_repository: Some(SourceRepository::Git {
+                url: "https://github.com/serde-rs/serde".to_owned(),
+                tag: "v1.0.152".to_owned(),
+            }),
+            build_spec_url: None,
+        };
+
+        let http_server = Server::run();
+        http_server.expect(
+            Expectation::matching(matchers::request::method_path(
+                "GET",
+                "/Cargo/serde/1.0.152/serde-1.0.152.mapping",
+            ))
+            .respond_with(responders::json_encoded(&mapping_info)),
+        );
+
+        let mapping_service = MappingService::new(&http_server.url("/").to_string());
+
+        let result = mapping_service
+            .get_mapping(mapping_info.package_type, &mapping_info.package_specific_id)
+            .await;
+        assert!(result.is_ok());
+
+        let mapping_info_result = result.unwrap();
+        assert_eq!(mapping_info, mapping_info_result);
+    }
+
     #[tokio::test]
     async fn docker_mapping_info() {
         let mapping_info = MappingInfo {
@@ -145,6 +279,38 @@ mod tests {
         assert_eq!(mapping_info, mapping_info_result);
     }
 
+    #[tokio::test]
+    async fn go_module_mapping_info() {
+        let mapping_info = MappingInfo {
+            package_type: PackageType::GoModule,
+            package_specific_id: "github.com/pkg/errors@v0.9.1".to_owned(),
+            source_repository: Some(SourceRepository::Git {
+                url: "https://github.com/pkg/errors".to_owned(),
+                tag: "v0.9.1".to_owned(),
+            }),
+            build_spec_url: None,
+        };
+
+        let http_server = Server::run();
+        http_server.expect(
+            Expectation::matching(matchers::request::method_path(
+                "GET",
+                "/GoModule/github.com/pkg/errors/v0.9.1/errors-v0.9.1.mapping",
+            ))
+            .respond_with(responders::json_encoded(&mapping_info)),
+        );
+
+        let mapping_service = MappingService::new(&http_server.url("/").to_string());
+
+        let result = mapping_service
+            .get_mapping(mapping_info.package_type, &mapping_info.package_specific_id)
+            .await;
+        assert!(result.is_ok());
+
+        let mapping_info_result = result.unwrap();
+        assert_eq!(mapping_info, mapping_info_result);
+    }
+
     #[tokio::test]
     async fn maven_mapping_info() {
         let mapping_info = MappingInfo {
@@ -260,6 +426,87 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn npm_mapping_info() {
+        let mapping_info = MappingInfo {
+            package_type: PackageType::Npm,
+            package_specific_id: "@babel/core@7.20.12".to_owned(),
+            source_repository: Some(SourceRepository::Git {
+                url: "https://github.com/babel/babel".to_owned(),
+                tag: "v7.20.12".to_owned(),
+            }),
+            build_spec_url: None,
+        };
+
+        let http_server = Server::run();
+        http_server.expect(
+            Expectation::matching(matchers::request::method_path(
+                "GET",
+                "/Npm/@babel/core/7.20.12/core-7.20.12.mapping",
+            ))
+            .respond_with(responders::json_encoded(&mapping_info)),
+        );
+
+        let mapping_service = MappingService::new(&http_server.url("/").to_string());
+
+        let result = mapping_service
+            .get_mapping(mapping_info.package_type, &mapping_info.package_specific_id)
+            .await;
+        assert!(result.is_ok());
+
+        let mapping_info_result = result.unwrap();
+        assert_eq!(mapping_info, mapping_info_result);
+    }
+
+    #[tokio::test]
+    async fn npm_mapping_invalid_package_specific_id() {
+        let mapping_service = MappingService::new("");
+
+        let error = mapping_service
+            .get_mapping(PackageType::Npm, "left-pad")
+            .await
+            .unwrap_err();
+
+        match error {
+            BuildError::MappingNotFound { package_type, .. } => {
+                assert_eq!(package_type, PackageType::Npm);
+            }
+            _ => panic!("Invalid BuildError: {}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn pypi_mapping_info() {
+        let mapping_info = MappingInfo {
+            package_type: PackageType::PyPI,
+            package_specific_id: "requests==2.28.2".to_owned(),
+            source_repository: Some(SourceRepository::Git {
+                url: "https://github.com/psf/requests".to_owned(),
+                tag: "v2.28.2".to_owned(),
+            }),
+            build_spec_url: None,
+        };
+
+        let http_server = Server::run();
+        http_server.expect(
+            Expectation::matching(matchers::request::method_path(
+                "GET",
+                "/PyPI/requests/2.28.2/requests-2.28.2.mapping",
+            ))
+            .respond_with(responders::json_encoded(&mapping_info)),
+        );
+
+        let mapping_service = MappingService::new(&http_server.url("/").to_string());
+
+        let result = mapping_service
+            .get_mapping(mapping_info.package_type, &mapping_info.package_specific_id)
+            .await;
+        assert!(result.is_ok());
+
+        let mapping_info_result = result.unwrap();
+        assert_eq!(mapping_info, mapping_info_result);
+    }
+
     #[tokio::test]
     #[should_panic(expected = "MappingServiceEndpointRequestFailure")]
     async fn maven_mapping_http_error() {
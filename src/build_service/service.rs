@@ -24,11 +24,17 @@ use crate::build_service::model::BuildInfo;
 use crate::network::client::Client;
 use crate::transparency_log::log::TransparencyLogService;
 use bytes::Buf;
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
 use multihash::Hasher;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The time after which the directory of a build is considered to be left
+/// behind, see [`BuildService::remove_stale_build_dirs`]. Builds clean up
+/// their directory as soon as their result is handled, which takes far less.
+pub const STALE_BUILD_DIR_AGE: Duration = Duration::from_secs(60 * 60);
 
 /// The build service is a component used by authorized nodes only. It is
 /// the entrypoint to the authorized node's build pipeline infrastructure.
@@ -194,6 +200,11 @@ impl BuildService {
         }
     }
 
+    /// Downloads the artifacts of a successful build and determines the package
+    /// specific artifact ids under which they are registered. For Docker builds,
+    /// the url ending with `/manifest` is the image manifest (or manifest list /
+    /// image index), urls containing `/manifests/` are the platform manifests of
+    /// a multi-architecture image and all other urls are blobs.
     async fn process_artifact_urls(
         &self,
         build_id: &str,
@@ -214,6 +225,14 @@ impl BuildService {
                 .map_err(|e| BuildError::Failure(build_id.to_owned(), e.to_string()))?;
 
             let artifact_specific_ids = match package_type {
+                PackageType::Cargo => {
+                    let prefix = package_specific_id.replacen('@', "/", 1);
+                    let artifact_filename = match artifact_url.rfind('/') {
+                        Some(position) => String::from(&artifact_url[position + 1..]),
+                        None => artifact_url,
+                    };
+                    vec![format!("{}/{}", prefix, artifact_filename)]
+                }
                 PackageType::Docker => {
                     if artifact_url.ends_with("/manifest") {
                         if package_specific_id.contains('@') {
@@ -225,6 +244,11 @@ impl BuildService {
                                 format!("{}@sha256:{}", docker_image_name, artifact_hash),
                             ]
                         }
+                    } else if artifact_url.contains("/manifests/") {
+                        // the platform manifests referenced by a manifest list
+                        // or image index are registered by their digest
+                        let docker_image_name = get_docker_image_name(&package_specific_id);
+                        vec![format!("{}@sha256:{}", docker_image_name, artifact_hash)]
                     } else {
                         let artifact_filename = match artifact_url.rfind('/') {
                             Some(position_slash) => {
@@ -240,6 +264,16 @@ impl BuildService {
                         vec![format!("{}@{}", docker_image_name, blob_digest)]
                     }
                 }
+                PackageType::GoModule => {
+                    let (module, _version) = package_specific_id
+                        .rsplit_once('@')
+                        .unwrap_or((&package_specific_id, ""));
+                    let artifact_filename = match artifact_url.rfind('/') {
+                        Some(position) => String::from(&artifact_url[position + 1..]),
+                        None => artifact_url,
+                    };
+                    vec![format!("{}/@v/{}", module, artifact_filename)]
+                }
                 PackageType::Maven2 => {
                     let prefix = package_specific_id.replace(':', "/");
                     let artifact_filename = match artifact_url.rfind('/') {
@@ -248,6 +282,30 @@ impl BuildService {
                     };
                     vec![format!("{}/{}", prefix, artifact_filename)]
                 }
+                PackageType::Npm => {
+                    let (name, version) = package_specific_id
+                        .rsplit_once('@')
+                        .unwrap_or((&package_specific_id, ""));
+                    let artifact_filename = match artifact_url.rfind('/') {
+                        Some(position) => String::from(&artifact_url[position + 1..]),
+                        None => artifact_url,
+                    };
+                    if artifact_filename == "package.json" {
+                        vec![format!("{}/{}", name, version)]
+                    } else {
+                        vec![format!("{}/-/{}", name, artifact_filename)]
+                    }
+                }
+                PackageType::PyPI => {
+                    let (project, _version) = package_specific_id
+                        .split_once("==")
+                        .unwrap_or((&package_specific_id, ""));
+                    let artifact_filename = match artifact_url.rfind('/') {
+                        Some(position) => String::from(&artifact_url[position + 1..]),
+                        None => artifact_url,
+                    };
+                    vec![format!("{}/{}", project, artifact_filename)]
+                }
             };
 
             debug!(
@@ -287,6 +345,40 @@ impl BuildService {
         }
     }
 
+    /// Remove the build directories that weren't modified for `max_age`. These
+    /// were left behind by builds that didn't clean up, e.g. because the node
+    /// stopped while handling their result. Nothing is removed on a dry run.
+    /// Returns the ids of the builds of which the directory was removed.
+    pub fn remove_stale_build_dirs(
+        &self,
+        max_age: Duration,
+        dry_run: bool,
+    ) -> io::Result<Vec<String>> {
+        let builds_path = self.repository_path.join("builds");
+        let entries = match fs::read_dir(&builds_path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut build_ids = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let age = entry.metadata()?.modified()?.elapsed().unwrap_or_default();
+            if !entry.file_type()?.is_dir() || age < max_age {
+                continue;
+            }
+            let build_id = entry.file_name().to_string_lossy().into_owned();
+            if !dry_run {
+                info!("Removing stale build directory {:?}", entry.path());
+                fs::remove_dir_all(entry.path())?;
+            }
+            build_ids.push(build_id);
+        }
+        build_ids.sort();
+        Ok(build_ids)
+    }
+
     pub async fn get_build_status(&self, build_id: &str) -> Result<BuildInfo, BuildError> {
         self.pipeline_service.get_build_status(build_id).await
     }
@@ -404,6 +496,136 @@ mod tests {
         test_util::tests::teardown(tmp_dir);
     }
 
+    #[tokio::test]
+    async fn test_process_artifact_urls_with_platform_manifests() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (sender, _) = mpsc::channel(1);
+
+        let (p2p_client, _) = test_util::tests::create_p2p_client();
+        let (transparency_log_service, _) =
+            test_util::tests::create_transparency_log_service(&tmp_dir);
+        let build_event_client = BuildEventClient::new(sender);
+
+        let http_server = Server::run();
+        for (path, content) in [
+            ("/build/1/manifest", "image index"),
+            ("/build/1/manifests/linux-amd64", "amd64 manifest"),
+            ("/build/1/manifests/linux-arm64", "arm64 manifest"),
+        ] {
+            http_server.expect(
+                Expectation::matching(matchers::request::method_path("GET", path))
+                    .respond_with(responders::status_code(200).body(content)),
+            );
+        }
+
+        let build_service = BuildService::new(
+            &tmp_dir,
+            build_event_client,
+            p2p_client,
+            transparency_log_service,
+            "https://mapping-service.pyrsia.io/",
+            &http_server.url_str("/"),
+        )
+        .unwrap();
+
+        let build_path = build_service.get_build_path("1");
+        fs::create_dir_all(&build_path).unwrap();
+
+        let build_result = build_service
+            .process_artifact_urls(
+                "1",
+                PackageType::Docker,
+                "library/multiarch:1.0".to_owned(),
+                vec![
+                    "/build/1/manifest".to_owned(),
+                    "/build/1/manifests/linux-amd64".to_owned(),
+                    "/build/1/manifests/linux-arm64".to_owned(),
+                ],
+                &build_path,
+            )
+            .await
+            .unwrap();
+
+        let artifact_specific_ids: Vec<String> = build_result
+            .artifacts
+            .into_iter()
+            .map(|artifact| artifact.artifact_specific_id)
+            .collect();
+        assert_eq!(
+            artifact_specific_ids,
+            vec![
+                "library/multiarch:1.0".to_owned(),
+                format!(
+                    "library/multiarch@sha256:{}",
+                    calculate_hash(b"image index")
+                ),
+                format!(
+                    "library/multiarch@sha256:{}",
+                    calculate_hash(b"amd64 manifest")
+                ),
+                format!(
+                    "library/multiarch@sha256:{}",
+                    calculate_hash(b"arm64 manifest")
+                ),
+            ]
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_remove_stale_build_dirs() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (sender, _) = mpsc::channel(1);
+
+        let (p2p_client, _) = test_util::tests::create_p2p_client();
+        let (transparency_log_service, _) =
+            test_util::tests::create_transparency_log_service(&tmp_dir);
+        let build_event_client = BuildEventClient::new(sender);
+
+        let build_service = BuildService::new(
+            &tmp_dir,
+            build_event_client,
+            p2p_client,
+            transparency_log_service,
+            "https://mapping-service.pyrsia.io/",
+            "https://pipeline-service.pyrsia.io/",
+        )
+        .unwrap();
+
+        assert!(build_service
+            .remove_stale_build_dirs(Duration::ZERO, false)
+            .unwrap()
+            .is_empty());
+
+        let build_path = build_service.get_build_path("1");
+        fs::create_dir_all(&build_path).unwrap();
+        hash_and_store_data(&build_path, b"artifact").unwrap();
+
+        assert!(build_service
+            .remove_stale_build_dirs(STALE_BUILD_DIR_AGE, false)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            build_service
+                .remove_stale_build_dirs(Duration::ZERO, true)
+                .unwrap(),
+            vec!["1"]
+        );
+        assert!(build_path.exists());
+        assert_eq!(
+            build_service
+                .remove_stale_build_dirs(Duration::ZERO, false)
+                .unwrap(),
+            vec!["1"]
+        );
+        assert!(!build_path.exists());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
     #[tokio::test]
     async fn test_start_build_triggered_from_unauthorized_node() {
         let tmp_dir = test_util::tests::setup();
@@ -19,6 +19,7 @@ use std::net::Ipv4Addr;
 use std::path::PathBuf;
 
 use anyhow::{anyhow, Result};
+use byte_unit::Byte;
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -200,6 +201,21 @@ pub fn valid_disk_space(input: String) -> Result<String, String> {
     Err("Invalid value for Disk Allocation".to_owned())
 }
 
+/// Returns the number of bytes of a disk space that is accepted by [`valid_disk_space`]
+pub fn disk_space_to_bytes(input: &str) -> Result<u64, String> {
+    let disk_space = valid_disk_space(input.to_owned())?;
+    Byte::from_str(disk_space)
+        .map(|bytes| bytes.get_bytes())
+        .map_err(|e| e.to_string())
+}
+
+/// Formats a number of bytes as a human readable disk space, e.g. "1.50 GB"
+pub fn format_disk_space(bytes: u64) -> String {
+    Byte::from_bytes(bytes)
+        .get_appropriate_unit(false)
+        .to_string()
+}
+
 pub fn get_config() -> Result<CliConfig> {
     let config_path = get_config_path()?;
 
@@ -428,4 +444,16 @@ mod tests {
             .into_iter()
             .any(|x| valid_disk_space(x.to_owned()).is_ok()));
     }
+
+    #[test]
+    fn test_disk_space_to_bytes() {
+        assert_eq!(disk_space_to_bytes("10 GB"), Ok(10_000_000_000));
+        assert!(disk_space_to_bytes("0 GB").is_err());
+    }
+
+    #[test]
+    fn test_format_disk_space() {
+        assert_eq!(format_disk_space(1_500_000_000), "1.50 GB");
+        assert_eq!(format_disk_space(512), "512 B");
+    }
 }
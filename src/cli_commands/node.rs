@@ -14,18 +14,24 @@
    limitations under the License.
 */
 
+use crate::artifact_service::model::{BundleImportReport, PinStatus};
 use crate::cli_commands::model::BuildResultResponse;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use futures::StreamExt;
 use reqwest::Response;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::Value;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
 
 use crate::build_service::model::BuildStatus;
 use crate::node_api::model::request::{
-    RequestAddAuthorizedNode, RequestBuildStatus, RequestDockerBuild, RequestDockerLog,
-    RequestMavenBuild, RequestMavenLog, Status,
+    RequestAddAuthorizedNode, RequestBuildStatus, RequestBundleExport, RequestCargoBuild,
+    RequestDockerBuild, RequestDockerLog, RequestMavenBuild, RequestMavenLog, RequestNpmBuild,
+    RequestPin, RequestPypiBuild, Status,
 };
 
 use super::config::get_config;
@@ -76,6 +82,30 @@ pub async fn request_maven_build(request: RequestMavenBuild) -> Result<BuildResu
     .await
 }
 
+pub async fn request_cargo_build(request: RequestCargoBuild) -> Result<BuildResultResponse> {
+    post_and_parse_json_result_as_object::<RequestCargoBuild, BuildResultResponse>(
+        format!("http://{}/build/cargo", get_url()),
+        request,
+    )
+    .await
+}
+
+pub async fn request_npm_build(request: RequestNpmBuild) -> Result<BuildResultResponse> {
+    post_and_parse_json_result_as_object::<RequestNpmBuild, BuildResultResponse>(
+        format!("http://{}/build/npm", get_url()),
+        request,
+    )
+    .await
+}
+
+pub async fn request_pypi_build(request: RequestPypiBuild) -> Result<BuildResultResponse> {
+    post_and_parse_json_result_as_object::<RequestPypiBuild, BuildResultResponse>(
+        format!("http://{}/build/pypi", get_url()),
+        request,
+    )
+    .await
+}
+
 pub async fn inspect_docker_transparency_log(request: RequestDockerLog) -> Result<String> {
     post_and_parse_result_as_text(format!("http://{}/inspect/docker", get_url()), request).await
 }
@@ -84,6 +114,80 @@ pub async fn inspect_maven_transparency_log(request: RequestMavenLog) -> Result<
     post_and_parse_result_as_text(format!("http://{}/inspect/maven", get_url()), request).await
 }
 
+/// Export packages into an offline bundle and write it to `output`. Returns the
+/// size of the bundle.
+pub async fn export_bundle(request: RequestBundleExport, output: &Path) -> Result<u64> {
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://{}/bundle/export", get_url()))
+        .json(&request)
+        .send()
+        .await?
+        .error_for_status_with_body()
+        .await?;
+
+    let mut bundle_stream = response.bytes_stream();
+    let result: Result<u64> = async {
+        let mut bundle_file = File::create(output)?;
+        let mut size = 0;
+        while let Some(chunk) = bundle_stream.next().await {
+            let chunk = chunk?;
+            bundle_file.write_all(&chunk)?;
+            size += chunk.len() as u64;
+        }
+        bundle_file.sync_all()?;
+        Ok(size)
+    }
+    .await;
+
+    // never leave a truncated bundle behind
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    result
+}
+
+pub async fn import_bundle(input: &Path) -> Result<BundleImportReport> {
+    let bundle_file = tokio::fs::File::open(input).await?;
+    let client = reqwest::Client::new();
+    client
+        .post(format!("http://{}/bundle/import", get_url()))
+        .header("Content-Type", "application/octet-stream")
+        .body(bundle_file)
+        .send()
+        .await?
+        .object_or_error_with_body::<BundleImportReport>()
+        .await
+}
+
+pub async fn pins() -> Result<Vec<PinStatus>> {
+    let node_url = format!("http://{}/pins", get_url());
+    reqwest::get(node_url)
+        .await?
+        .object_or_error_with_body::<Vec<PinStatus>>()
+        .await
+}
+
+pub async fn pin_package(request: RequestPin) -> Result<PinStatus> {
+    post_and_parse_json_result_as_object::<RequestPin, PinStatus>(
+        format!("http://{}/pins", get_url()),
+        request,
+    )
+    .await
+}
+
+pub async fn unpin_package(request: RequestPin) -> Result<()> {
+    let client = reqwest::Client::new();
+    client
+        .delete(format!("http://{}/pins", get_url()))
+        .json(&request)
+        .send()
+        .await?
+        .text_or_error_with_body()
+        .await
+        .map(|_| ())
+}
+
 pub fn get_url() -> String {
     let result = get_config();
     let mut host = String::new();
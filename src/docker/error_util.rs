@@ -39,6 +39,9 @@ pub struct ErrorMessages {
 pub enum RegistryErrorCode {
     BlobUnknown,
     ManifestUnknown,
+    /// The requested package or version is unknown, for package types other
+    /// than Docker.
+    NotFound(String),
     BadRequest(String),
     Unknown(String),
 }
@@ -48,6 +51,20 @@ pub struct RegistryError {
     pub code: RegistryErrorCode,
 }
 
+impl RegistryError {
+    /// The error for an artifact that couldn't be retrieved. An artifact that
+    /// isn't known in the transparency log is reported as not found, with
+    /// `not_found_message`, so that clients can fall back to other sources.
+    pub fn from_artifact_error(err: anyhow::Error, not_found_message: String) -> RegistryError {
+        match err.downcast_ref::<TransparencyLogError>() {
+            Some(TransparencyLogError::ArtifactNotFound { .. }) => RegistryError {
+                code: RegistryErrorCode::NotFound(not_found_message),
+            },
+            _ => err.into(),
+        }
+    }
+}
+
 impl From<anyhow::Error> for RegistryError {
     fn from(err: anyhow::Error) -> RegistryError {
         RegistryError {
@@ -182,6 +199,11 @@ pub async fn custom_recover(err: Rejection) -> Result<impl Reply, Infallible> {
                 status_code = StatusCode::NOT_FOUND;
                 error_message.code = RegistryErrorCode::ManifestUnknown;
             }
+            RegistryErrorCode::NotFound(m) => {
+                status_code = StatusCode::NOT_FOUND;
+                error_message.code = RegistryErrorCode::NotFound(m.clone());
+                error_message.message = m.clone();
+            }
             RegistryErrorCode::BadRequest(m) => {
                 status_code = StatusCode::BAD_REQUEST;
                 error_message.code = RegistryErrorCode::BadRequest(m.clone());
@@ -227,6 +249,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn from_artifact_error() {
+        let artifact_not_found_error = TransparencyLogError::ArtifactNotFound {
+            package_type: PackageType::Npm,
+            package_specific_artifact_id: "left-pad/-/left-pad-1.3.0.tgz".to_owned(),
+        };
+        let registry_error = RegistryError::from_artifact_error(
+            artifact_not_found_error.into(),
+            "npm package left-pad@1.3.0 not found".to_owned(),
+        );
+        assert_eq!(
+            registry_error.code,
+            RegistryErrorCode::NotFound("npm package left-pad@1.3.0 not found".to_owned())
+        );
+
+        let registry_error = RegistryError::from_artifact_error(
+            anyhow::anyhow!("other error"),
+            "npm package left-pad@1.3.0 not found".to_owned(),
+        );
+        assert_eq!(
+            registry_error.code,
+            RegistryErrorCode::Unknown("other error".to_owned())
+        );
+    }
+
     #[test]
     fn from_from_hex_error() {
         let from_hex_error = hex::FromHexError::OddLength;
@@ -305,6 +352,28 @@ mod tests {
         verify_recover_response(response, expected_body, StatusCode::NOT_FOUND).await;
     }
 
+    #[tokio::test]
+    async fn custom_recover_from_registry_error_for_not_found() {
+        let registry_error = RegistryError {
+            code: RegistryErrorCode::NotFound("Unknown package".to_owned()),
+        };
+
+        let expected_body = serde_json::to_string(&ErrorMessages {
+            errors: vec![ErrorMessage {
+                code: RegistryErrorCode::NotFound("Unknown package".to_owned()),
+                message: "Unknown package".to_string(),
+            }],
+        })
+        .expect("Generating JSON body should not fail.");
+
+        let response = custom_recover(registry_error.into())
+            .await
+            .expect("Reply should be created.")
+            .into_response();
+
+        verify_recover_response(response, expected_body, StatusCode::NOT_FOUND).await;
+    }
+
     #[tokio::test]
     async fn custom_recover_from_registry_error_for_manifest_unknown() {
         let registry_error = RegistryError {
@@ -17,13 +17,16 @@
 use crate::artifact_service::model::PackageType;
 use crate::artifact_service::service::ArtifactService;
 use crate::docker::error_util::{RegistryError, RegistryErrorCode};
+use crate::util::range_util;
 use log::debug;
 use std::result::Result;
+use warp::hyper::Body;
 use warp::{http::StatusCode, Rejection, Reply};
 
 pub async fn handle_get_blobs(
     name: String,
     digest: String,
+    range: Option<String>,
     mut artifact_service: ArtifactService,
 ) -> Result<impl Reply, Rejection> {
     debug!(
@@ -31,8 +34,25 @@ pub async fn handle_get_blobs(
         &get_package_specific_artifact_id(&name, &digest)
     );
 
-    let blob_content = artifact_service
-        .get_artifact_or_build(
+    if let Some(range) = range {
+        if let Ok(Some(blob_range)) = artifact_service
+            .get_artifact_range(
+                PackageType::Docker,
+                &get_package_specific_artifact_id(&name, &digest),
+                &range,
+            )
+            .await
+        {
+            return Ok(range_util::create_range_response(
+                blob_range,
+                "application/octet-stream",
+            ));
+        }
+        debug!("Range {} can't be served, serving the full blob", range);
+    }
+
+    let (blob_size, blob_stream) = artifact_service
+        .get_artifact_stream_or_build(
             PackageType::Docker,
             &get_package_specific_artifact_id(&name, &digest),
             &get_package_specific_artifact_id(&name, &digest),
@@ -46,17 +66,55 @@ pub async fn handle_get_blobs(
 
     Ok(warp::http::response::Builder::new()
         .header("Content-Type", "application/octet-stream")
+        .header("Content-Length", blob_size)
+        .header("Docker-Content-Digest", &digest)
+        .header("Accept-Ranges", "bytes")
+        .status(StatusCode::OK)
+        .body(Body::wrap_stream(blob_stream))
+        .unwrap())
+}
+
+// Handles HEAD endpoint documented at https://docs.docker.com/registry/spec/api/#existing-layers
+pub async fn handle_head_blobs(
+    name: String,
+    digest: String,
+    mut artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    debug!(
+        "Checking existence of blob with digest: {:?}",
+        &get_package_specific_artifact_id(&name, &digest)
+    );
+
+    let (blob_size, _) = artifact_service
+        .get_artifact_stream(
+            PackageType::Docker,
+            &get_package_specific_artifact_id(&name, &digest),
+        )
+        .await
+        .map_err(|_| {
+            warp::reject::custom(RegistryError {
+                code: RegistryErrorCode::BlobUnknown,
+            })
+        })?;
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/octet-stream")
+        .header("Content-Length", blob_size)
+        .header("Docker-Content-Digest", &digest)
+        .header("Accept-Ranges", "bytes")
         .status(StatusCode::OK)
-        .body(blob_content.to_vec())
+        .body(Body::empty())
         .unwrap())
 }
 
+/// Returns the package specific artifact id of a blob. The full repository
+/// name is kept, only names without a namespace are considered official images
+/// and prefixed with `library/`.
 fn get_package_specific_artifact_id(name: &str, digest: &str) -> String {
-    let combined_tag = format!("{}@{}", name, digest);
-    if combined_tag.contains('/') {
-        combined_tag
+    if name.contains('/') {
+        format!("{}@{}", name, digest)
     } else {
-        format!("library/{}", combined_tag)
+        format!("library/{}@{}", name, digest)
     }
 }
 
@@ -99,6 +157,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_package_specific_artifact_id_with_nested_name_from_digest() {
+        let name = "bitnami/redis";
+        let tag = "sha256:1e014f84205d569a5cc3be4e108ca614055f7e21d11928946113ab3f36054801";
+
+        assert_eq!(
+            get_package_specific_artifact_id(name, tag),
+            format!("{}@{}", name, tag)
+        );
+    }
+
     #[tokio::test]
     async fn test_handle_get_blobs_unknown_in_artifact_service() {
         let tmp_dir = test_util::tests::setup();
@@ -108,7 +177,8 @@ mod tests {
 
         let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
 
-        let result = handle_get_blobs(name.to_owned(), hash.to_owned(), artifact_service).await;
+        let result =
+            handle_get_blobs(name.to_owned(), hash.to_owned(), None, artifact_service).await;
 
         assert!(result.is_err());
         let rejection = result.err().unwrap();
@@ -177,9 +247,16 @@ mod tests {
             &artifact_service.artifact_storage,
             &transparency_log.artifact_id,
         )
+        .await
         .unwrap();
 
-        let result = handle_get_blobs(name.to_owned(), digest, artifact_service).await;
+        let result = handle_get_blobs(
+            name.to_owned(),
+            digest.clone(),
+            None,
+            artifact_service.clone(),
+        )
+        .await;
 
         assert!(result.is_ok());
 
@@ -189,6 +266,54 @@ mod tests {
             response.headers().get("Content-Type"),
             Some(&HeaderValue::from_static("application/octet-stream"))
         );
+        assert_eq!(
+            response.headers().get("Content-Length"),
+            Some(&HeaderValue::from_static("4903"))
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body.len(), 4903);
+
+        let result = handle_get_blobs(
+            name.to_owned(),
+            digest.clone(),
+            Some("bytes=4900-".to_owned()),
+            artifact_service.clone(),
+        )
+        .await;
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers().get("Content-Range"),
+            Some(&HeaderValue::from_static("bytes 4900-4902/4903"))
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body.len(), 3);
+
+        let result = handle_get_blobs(
+            name.to_owned(),
+            digest.clone(),
+            Some("bytes=5000-".to_owned()),
+            artifact_service.clone(),
+        )
+        .await;
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        let result = handle_head_blobs(name.to_owned(), digest.clone(), artifact_service).await;
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Length"),
+            Some(&HeaderValue::from_static("4903"))
+        );
+        assert_eq!(
+            response.headers().get("Docker-Content-Digest").unwrap(),
+            &digest
+        );
 
         test_util::tests::teardown(tmp_dir);
     }
@@ -203,12 +328,13 @@ mod tests {
         Ok(reader)
     }
 
-    fn create_artifact(
+    async fn create_artifact(
         artifact_storage: &ArtifactStorage,
         artifact_id: &str,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<String, anyhow::Error> {
         artifact_storage
-            .push_artifact(&mut get_file_reader()?, artifact_id)
+            .push_artifact(get_file_reader()?, artifact_id)
+            .await
             .context("Error while pushing artifact")
     }
 }
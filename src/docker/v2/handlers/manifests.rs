@@ -16,8 +16,11 @@
 
 use crate::artifact_service::model::PackageType;
 use crate::artifact_service::service::ArtifactService;
+use crate::docker::constants::*;
 use crate::docker::error_util::{RegistryError, RegistryErrorCode};
 use log::debug;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
 use warp::http::StatusCode;
 use warp::{Rejection, Reply};
 
@@ -25,6 +28,7 @@ use warp::{Rejection, Reply};
 pub async fn fetch_manifest(
     name: String,
     tag: String,
+    accept: Option<String>,
     mut artifact_service: ArtifactService,
 ) -> Result<impl Reply, Rejection> {
     debug!(
@@ -44,22 +48,13 @@ pub async fn fetch_manifest(
             })
         })?;
 
-    let len = manifest_content.len();
-
-    Ok(warp::http::response::Builder::new()
-        .header(
-            "Content-Type",
-            "application/vnd.docker.distribution.manifest.v2+json",
-        )
-        .header("Content-Length", len)
-        .status(StatusCode::OK)
-        .body(manifest_content.to_vec())
-        .unwrap())
+    create_manifest_response(manifest_content, accept)
 }
 
 pub async fn fetch_manifest_or_build(
     name: String,
     tag: String,
+    accept: Option<String>,
     mut artifact_service: ArtifactService,
 ) -> Result<impl Reply, Rejection> {
     debug!(
@@ -79,29 +74,82 @@ pub async fn fetch_manifest_or_build(
             })
         })?;
 
-    let len = manifest_content.len();
+    create_manifest_response(manifest_content, accept)
+}
+
+/// Creates the response for a manifest, honouring the media types accepted by
+/// the client. A manifest list or image index is only served to clients that
+/// accept it, as the platform of the client is unknown.
+fn create_manifest_response(
+    manifest_content: Vec<u8>,
+    accept: Option<String>,
+) -> Result<warp::http::Response<Vec<u8>>, Rejection> {
+    let media_type = get_manifest_media_type(&manifest_content);
+
+    if is_index_media_type(&media_type) && !accepts_media_type(accept.as_deref(), &media_type) {
+        debug!("Client does not accept {}", media_type);
+        return Err(warp::reject::custom(RegistryError {
+            code: RegistryErrorCode::ManifestUnknown,
+        }));
+    }
+
+    let digest = format!("sha256:{}", hex::encode(Sha256::digest(&manifest_content)));
 
     Ok(warp::http::response::Builder::new()
-        .header(
-            "Content-Type",
-            "application/vnd.docker.distribution.manifest.v2+json",
-        )
-        .header("Content-Length", len)
+        .header("Content-Type", media_type)
+        .header("Content-Length", manifest_content.len())
+        .header("Docker-Content-Digest", digest)
         .status(StatusCode::OK)
-        .body(manifest_content.to_vec())
+        .body(manifest_content)
         .unwrap())
 }
 
+/// Determines the media type of a manifest from its `mediaType` field. OCI
+/// manifests and indexes may omit that field, in which case the media type is
+/// derived from the structure of the manifest.
+fn get_manifest_media_type(manifest_content: &[u8]) -> String {
+    let manifest: Value = serde_json::from_slice(manifest_content).unwrap_or(Value::Null);
+    if let Some(media_type) = manifest["mediaType"].as_str() {
+        return media_type.to_owned();
+    }
+
+    if manifest["manifests"].is_array() {
+        MEDIA_TYPE_OCI_IMAGE_INDEX.to_owned()
+    } else if manifest["layers"].is_array() {
+        MEDIA_TYPE_OCI_IMAGE_MANIFEST.to_owned()
+    } else {
+        MEDIA_TYPE_IMAGE_MANIFEST.to_owned()
+    }
+}
+
+fn is_index_media_type(media_type: &str) -> bool {
+    media_type == MEDIA_TYPE_MANIFEST_LIST || media_type == MEDIA_TYPE_OCI_IMAGE_INDEX
+}
+
+fn accepts_media_type(accept: Option<&str>, media_type: &str) -> bool {
+    accept
+        .map(|accept| {
+            accept
+                .split(',')
+                .map(|accepted| accepted.split(';').next().unwrap_or_default().trim())
+                .any(|accepted| accepted == media_type || accepted == "*/*")
+        })
+        .unwrap_or(false)
+}
+
+/// Returns the package specific artifact id of a manifest. The full repository
+/// name is kept, only names without a namespace are considered official images
+/// and prefixed with `library/`.
 fn get_package_specific_artifact_id(name: &str, tag: &str) -> String {
-    let combined_tag = if tag.starts_with("sha256:") {
-        format!("{}@{}", name, tag)
+    let name = if name.contains('/') {
+        name.to_owned()
     } else {
-        format!("{}:{}", name, tag)
+        format!("library/{}", name)
     };
-    if combined_tag.contains('/') {
-        combined_tag
+    if tag.starts_with("sha256:") {
+        format!("{}@{}", name, tag)
     } else {
-        format!("library/{}", combined_tag)
+        format!("{}:{}", name, tag)
     }
 }
 
@@ -119,8 +167,16 @@ mod tests {
     use std::borrow::Borrow;
     use std::collections::HashSet;
     use std::fs::File;
+    use std::io::Cursor;
     use std::path::PathBuf;
 
+    const PLATFORM_MANIFEST: &str = r#"{"schemaVersion":2,"mediaType":"application/vnd.oci.image.manifest.v1+json","config":{},"layers":[]}"#;
+    const PLATFORM_MANIFEST_HASH: &str =
+        "21d94c4ae81f56d81b98c4468796264cbff924c3fae1fd9e8379120a7d264f31";
+    const IMAGE_INDEX: &str = r#"{"schemaVersion":2,"manifests":[{"mediaType":"application/vnd.oci.image.manifest.v1+json","digest":"sha256:21d94c4ae81f56d81b98c4468796264cbff924c3fae1fd9e8379120a7d264f31","platform":{"architecture":"amd64","os":"linux"}}]}"#;
+    const IMAGE_INDEX_HASH: &str =
+        "10193bd95051dd28b2b745583f5f7b260bb8dd28da47047c89b21f79b9eb813e";
+
     #[test]
     fn test_get_package_specific_artifact_id_from_digest() {
         let name = "library/alpine";
@@ -167,6 +223,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_package_specific_artifact_id_with_nested_name_from_tag() {
+        let name = "org/team/image";
+        let tag = "1.0";
+
+        assert_eq!(
+            get_package_specific_artifact_id(name, tag),
+            format!("{}:{}", name, tag)
+        );
+    }
+
     #[tokio::test]
     async fn test_fetch_manifest_unknown_in_artifact_service() {
         let tmp_dir = test_util::tests::setup();
@@ -176,7 +243,8 @@ mod tests {
 
         let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
 
-        let result = fetch_manifest(name.to_string(), tag.to_string(), artifact_service).await;
+        let result =
+            fetch_manifest(name.to_string(), tag.to_string(), None, artifact_service).await;
 
         assert!(result.is_err());
         let rejection = result.err().unwrap();
@@ -244,9 +312,11 @@ mod tests {
             &artifact_service.artifact_storage,
             &transparency_log.artifact_id,
         )
+        .await
         .unwrap();
 
-        let result = fetch_manifest(name.to_string(), tag.to_string(), artifact_service).await;
+        let result =
+            fetch_manifest(name.to_string(), tag.to_string(), None, artifact_service).await;
 
         assert!(result.is_ok());
 
@@ -266,6 +336,147 @@ mod tests {
         test_util::tests::teardown(tmp_dir);
     }
 
+    #[test]
+    fn test_get_manifest_media_type() {
+        assert_eq!(
+            get_manifest_media_type(PLATFORM_MANIFEST.as_bytes()),
+            MEDIA_TYPE_OCI_IMAGE_MANIFEST
+        );
+        assert_eq!(
+            get_manifest_media_type(IMAGE_INDEX.as_bytes()),
+            MEDIA_TYPE_OCI_IMAGE_INDEX
+        );
+        assert_eq!(
+            get_manifest_media_type(b"{\"schemaVersion\":1}"),
+            MEDIA_TYPE_IMAGE_MANIFEST
+        );
+    }
+
+    #[test]
+    fn test_accepts_media_type() {
+        let accept = format!(
+            "{}, {};q=0.5",
+            MEDIA_TYPE_IMAGE_MANIFEST, MEDIA_TYPE_OCI_IMAGE_INDEX
+        );
+        assert!(accepts_media_type(
+            Some(&accept),
+            MEDIA_TYPE_OCI_IMAGE_INDEX
+        ));
+        assert!(!accepts_media_type(Some(&accept), MEDIA_TYPE_MANIFEST_LIST));
+        assert!(accepts_media_type(Some("*/*"), MEDIA_TYPE_MANIFEST_LIST));
+        assert!(!accepts_media_type(None, MEDIA_TYPE_MANIFEST_LIST));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_manifest_image_index() {
+        let tmp_dir = test_util::tests::setup();
+
+        let name = "library/multiarch";
+        let tag = "1.0";
+
+        let (artifact_service, mut blockchain_event_receiver, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    }
+                    _ => panic!("Command must match Command::ListPeers"),
+                }
+            }
+        });
+
+        let platform_digest = format!("sha256:{}", PLATFORM_MANIFEST_HASH);
+        for (package_specific_artifact_id, hash, content) in [
+            (
+                get_package_specific_artifact_id(name, tag),
+                IMAGE_INDEX_HASH,
+                IMAGE_INDEX,
+            ),
+            (
+                get_package_specific_artifact_id(name, &platform_digest),
+                PLATFORM_MANIFEST_HASH,
+                PLATFORM_MANIFEST,
+            ),
+        ] {
+            let transparency_log = artifact_service
+                .transparency_log_service
+                .add_artifact(AddArtifactRequest {
+                    package_type: PackageType::Docker,
+                    package_specific_id: format!("{}:{}", name, tag),
+                    num_artifacts: 2,
+                    package_specific_artifact_id,
+                    artifact_hash: hash.to_owned(),
+                })
+                .await
+                .unwrap()
+                .0;
+
+            artifact_service
+                .artifact_storage
+                .push_artifact(
+                    Cursor::new(content.as_bytes()),
+                    &transparency_log.artifact_id,
+                )
+                .await
+                .unwrap();
+        }
+
+        let result = fetch_manifest(
+            name.to_owned(),
+            tag.to_owned(),
+            Some(format!(
+                "{}, {}",
+                MEDIA_TYPE_IMAGE_MANIFEST, MEDIA_TYPE_OCI_IMAGE_INDEX
+            )),
+            artifact_service.clone(),
+        )
+        .await;
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type"),
+            Some(&HeaderValue::from_static(MEDIA_TYPE_OCI_IMAGE_INDEX))
+        );
+        assert_eq!(
+            response.headers().get("Docker-Content-Digest").unwrap(),
+            &format!("sha256:{}", IMAGE_INDEX_HASH)
+        );
+
+        let result = fetch_manifest(
+            name.to_owned(),
+            tag.to_owned(),
+            Some(MEDIA_TYPE_IMAGE_MANIFEST.to_owned()),
+            artifact_service,
+        )
+        .await;
+
+        let rejection = result.err().unwrap();
+        let registry_error = rejection.find::<RegistryError>().unwrap();
+        assert_eq!(
+            *registry_error,
+            RegistryError {
+                code: RegistryErrorCode::ManifestUnknown,
+            }
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
     fn get_file_reader() -> Result<File, anyhow::Error> {
         // test artifact file in resources/test dir
         let mut curr_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -276,12 +487,13 @@ mod tests {
         Ok(reader)
     }
 
-    fn create_artifact(
+    async fn create_artifact(
         artifact_storage: &ArtifactStorage,
         artifact_id: &str,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<String, anyhow::Error> {
         artifact_storage
-            .push_artifact(&mut get_file_reader()?, artifact_id)
+            .push_artifact(get_file_reader()?, artifact_id)
+            .await
             .context("Error while pushing artifact")
     }
 }
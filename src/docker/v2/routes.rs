@@ -19,6 +19,11 @@ use crate::artifact_service::service::ArtifactService;
 
 use super::handlers::blobs::*;
 use super::handlers::manifests::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::convert::Infallible;
+use warp::http::header::ACCEPT;
+use warp::http::HeaderMap;
 use warp::Filter;
 
 pub fn make_docker_routes(
@@ -40,34 +45,95 @@ pub fn make_docker_routes(
 
     let artifact_service_filter = warp::any().map(move || artifact_service.clone());
 
-    let v2_manifests_get = warp::path!("v2" / "library" / String / "manifests" / String)
+    let v2_manifests_get = repository_path("manifests")
         .and(warp::get())
+        .and(accept_header())
         .and(artifact_service_filter.clone())
         .and_then(fetch_manifest_or_build);
 
-    let v2_manifests_head = warp::path!("v2" / "library" / String / "manifests" / String)
+    let v2_manifests_head = repository_path("manifests")
         .and(warp::head())
+        .and(accept_header())
         .and(artifact_service_filter.clone())
         .and_then(fetch_manifest);
 
-    let v2_blobs = warp::path!("v2" / "library" / String / "blobs" / String)
+    let v2_blobs_get = repository_path("blobs")
         .and(warp::get())
-        .and(warp::path::end())
-        .and(artifact_service_filter)
+        .and(warp::header::optional::<String>("range"))
+        .and(artifact_service_filter.clone())
         .and_then(handle_get_blobs);
 
+    let v2_blobs_head = repository_path("blobs")
+        .and(warp::head())
+        .and(artifact_service_filter)
+        .and_then(handle_head_blobs);
+
     warp::any().and(
         v2_base
             .or(v2_manifests_get)
             .or(v2_manifests_head)
-            .or(v2_blobs),
+            .or(v2_blobs_get)
+            .or(v2_blobs_head),
     )
 }
 
+/// Extracts the media types accepted by the client as a single comma
+/// separated list. The Docker daemon sends each accepted media type in its own
+/// `Accept` header, so the values of all of them are joined.
+fn accept_header() -> impl Filter<Extract = (Option<String>,), Error = Infallible> + Clone {
+    warp::header::headers_cloned().map(|headers: HeaderMap| {
+        let accepted: Vec<&str> = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect();
+        if accepted.is_empty() {
+            None
+        } else {
+            Some(accepted.join(", "))
+        }
+    })
+}
+
+/// Matches `v2/<name>/<kind>/<reference>` and extracts the repository name
+/// and the reference. The repository name may consist of multiple path
+/// components, e.g. `library/alpine`, `bitnami/redis` or `org/team/image`.
+fn repository_path(
+    kind: &'static str,
+) -> impl Filter<Extract = (String, String), Error = warp::Rejection> + Clone {
+    warp::path("v2")
+        .and(warp::path::tail())
+        .and_then(move |tail: warp::path::Tail| async move {
+            parse_repository_path(tail.as_str(), kind).ok_or_else(warp::reject::not_found)
+        })
+        .untuple_one()
+}
+
+fn parse_repository_path(path: &str, kind: &str) -> Option<(String, String)> {
+    let (name, reference) = path.rsplit_once(&format!("/{}/", kind))?;
+    if reference.is_empty() || reference.contains('/') || !is_valid_repository_name(name) {
+        return None;
+    }
+    Some((name.to_owned(), reference.to_owned()))
+}
+
+/// Verifies a repository name against the grammar of the distribution spec:
+/// one or more `/` separated components of lowercase alphanumerics, optionally
+/// separated by `.`, `_`, `__` or any number of `-`.
+fn is_valid_repository_name(name: &str) -> bool {
+    lazy_static! {
+        static ref REPOSITORY_NAME_REGEX: Regex =
+            Regex::new(r"^[a-z0-9]+((\.|_|__|-+)[a-z0-9]+)*(/[a-z0-9]+((\.|_|__|-+)[a-z0-9]+)*)*$")
+                .unwrap();
+    }
+    REPOSITORY_NAME_REGEX.is_match(name)
+}
+
 #[cfg(test)]
 #[cfg(not(tarpaulin_include))]
 mod tests {
     use super::*;
+    use crate::docker::constants::*;
     use crate::docker::error_util::{RegistryError, RegistryErrorCode};
     use crate::util::test_util;
     use std::str;
@@ -134,4 +200,136 @@ mod tests {
 
         test_util::tests::teardown(tmp_dir);
     }
+
+    #[test]
+    fn parse_repository_path_official_image() {
+        assert_eq!(
+            parse_repository_path("library/alpine/manifests/3.16", "manifests"),
+            Some(("library/alpine".to_owned(), "3.16".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_repository_path_nested_name() {
+        assert_eq!(
+            parse_repository_path("org/team/image/blobs/sha256:abcd", "blobs"),
+            Some(("org/team/image".to_owned(), "sha256:abcd".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_repository_path_invalid() {
+        assert_eq!(
+            parse_repository_path("alpine/manifests/", "manifests"),
+            None
+        );
+        assert_eq!(
+            parse_repository_path("alpine/blobs/sha256:abcd", "manifests"),
+            None
+        );
+        assert_eq!(
+            parse_repository_path("Alpine/manifests/3.16", "manifests"),
+            None
+        );
+        assert_eq!(
+            parse_repository_path("org//image/manifests/1", "manifests"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn accept_header_joins_all_accept_headers() {
+        let filter = accept_header().map(|accept: Option<String>| accept.unwrap_or_default());
+        let (address, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let response = reqwest::Client::new()
+            .get(format!("http://{}/", address))
+            .header(ACCEPT, MEDIA_TYPE_IMAGE_MANIFEST)
+            .header(ACCEPT, MEDIA_TYPE_MANIFEST_LIST)
+            .header(ACCEPT, MEDIA_TYPE_OCI_IMAGE_INDEX)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.text().await.unwrap(),
+            format!(
+                "{}, {}, {}",
+                MEDIA_TYPE_IMAGE_MANIFEST, MEDIA_TYPE_MANIFEST_LIST, MEDIA_TYPE_OCI_IMAGE_INDEX
+            )
+        );
+    }
+
+    #[test]
+    fn valid_repository_names() {
+        for name in [
+            "alpine",
+            "library/alpine",
+            "org/team/image",
+            "my.image",
+            "my_image",
+            "my__image",
+            "my-image",
+            "my---image",
+            "a0/b1.c2_d3__e4-f5",
+        ] {
+            assert!(is_valid_repository_name(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn invalid_repository_names() {
+        for name in [
+            "",
+            "/",
+            "Alpine",
+            "library/Alpine",
+            "-image",
+            "image-",
+            ".image",
+            "image.",
+            "_image",
+            "image_",
+            "my..image",
+            "my___image",
+            "my._image",
+            "my_.image",
+            "my.-image",
+            "my-.image",
+            "my_-image",
+            "my-_image",
+            "my__-image",
+            "/library/alpine",
+            "library/alpine/",
+            "library//alpine",
+            "my image",
+            "my:image",
+        ] {
+            assert!(!is_valid_repository_name(name), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn docker_routes_manifests_nested_name() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let filter = make_docker_routes(artifact_service);
+        let response = warp::test::request()
+            .path("/v2/bitnami/redis/manifests/7.0")
+            .reply(&filter)
+            .await;
+
+        let expected_error = RegistryError {
+            code: RegistryErrorCode::ManifestUnknown,
+        };
+        let expected_body = format!("Unhandled rejection: {:?}", expected_error);
+
+        assert_eq!(response.status(), 500);
+        assert_eq!(expected_body, str::from_utf8(response.body()).unwrap());
+
+        test_util::tests::teardown(tmp_dir);
+    }
 }
@@ -0,0 +1,530 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::artifact_service::model::PackageType;
+use crate::artifact_service::service::ArtifactService;
+use crate::docker::error_util::{RegistryError, RegistryErrorCode};
+use anyhow::{anyhow, bail};
+use lazy_static::lazy_static;
+use log::debug;
+use regex::Regex;
+use semver::Version;
+use warp::hyper::Body;
+use warp::{http::StatusCode, Rejection, Reply};
+
+#[derive(Debug, Eq, PartialEq)]
+enum GoProxyRequest {
+    List {
+        module: String,
+    },
+    Latest {
+        module: String,
+    },
+    File {
+        module: String,
+        version: String,
+        extension: String,
+    },
+}
+
+pub async fn handle_get_go_module(
+    full_path: String,
+    mut artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    debug!("Requesting go module: {}", full_path);
+    let goproxy_request = parse_goproxy_request(&full_path).map_err(|err| {
+        debug!("Error parsing goproxy request: {:?}", err);
+        warp::reject::custom(RegistryError {
+            code: RegistryErrorCode::Unknown(err.to_string()),
+        })
+    })?;
+
+    let (content_type, body) = match goproxy_request {
+        GoProxyRequest::List { module } => {
+            let versions = get_versions(&artifact_service, &module).map_err(|err| {
+                debug!("Error retrieving module versions: {:?}", err);
+                warp::reject::custom(RegistryError {
+                    code: RegistryErrorCode::Unknown(err.to_string()),
+                })
+            })?;
+            if versions.is_empty() {
+                return Err(module_not_found(&module));
+            }
+            let mut body = versions.join("\n");
+            if !body.is_empty() {
+                body.push('\n');
+            }
+            ("text/plain", body.into_bytes())
+        }
+        GoProxyRequest::Latest { module } => {
+            let info = get_latest_info(&mut artifact_service, &module)
+                .await
+                .map_err(|err| {
+                    debug!("Error retrieving latest module version: {:?}", err);
+                    warp::reject::custom(RegistryError {
+                        code: RegistryErrorCode::Unknown(err.to_string()),
+                    })
+                })?
+                .ok_or_else(|| module_not_found(&module))?;
+            ("application/json", info)
+        }
+        GoProxyRequest::File {
+            module,
+            version,
+            extension,
+        } => {
+            let package_specific_id = get_package_specific_id(&module, &version);
+            let package_specific_artifact_id =
+                get_package_specific_artifact_id(&module, &version, &extension);
+            debug!(
+                "Requesting artifact with package specific id: {}, and package specific artifact id: {}. If not found a build will be requested",
+                package_specific_id, package_specific_artifact_id
+            );
+
+            let (artifact_size, artifact_stream) = artifact_service
+                .get_artifact_stream_or_build(
+                    PackageType::GoModule,
+                    &package_specific_id,
+                    &package_specific_artifact_id,
+                )
+                .await
+                .map_err(|err| {
+                    debug!("Error retrieving artifact: {:?}", err);
+                    // the go command only falls back to the next proxy or to
+                    // the origin on a 404 or 410 response
+                    warp::reject::custom(RegistryError::from_artifact_error(
+                        err,
+                        format!(
+                            "go module {}@{} not found, a build was requested",
+                            module, version
+                        ),
+                    ))
+                })?;
+
+            return Ok(warp::http::response::Builder::new()
+                .header("Content-Type", get_content_type(&extension))
+                .header("Content-Length", artifact_size)
+                .status(StatusCode::OK)
+                .body(Body::wrap_stream(artifact_stream))
+                .unwrap());
+        }
+    };
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", content_type)
+        .status(StatusCode::OK)
+        .body(Body::from(body))
+        .unwrap())
+}
+
+/// Lists all versions of a module of which the `.info` file is known in the
+/// transparency log, ordered from oldest to newest.
+fn get_versions(
+    artifact_service: &ArtifactService,
+    module: &str,
+) -> Result<Vec<String>, anyhow::Error> {
+    let transparency_logs = artifact_service
+        .transparency_log_service
+        .search_transparency_logs_by_prefix(&PackageType::GoModule, &format!("{}@", module))?;
+
+    Ok(transparency_logs
+        .iter()
+        .filter_map(|transparency_log| {
+            let version = &transparency_log.package_specific_id[module.len() + 1..];
+            (transparency_log.package_specific_artifact_id
+                == get_package_specific_artifact_id(module, version, "info"))
+            .then(|| version.to_owned())
+        })
+        .collect())
+}
+
+/// Returns the `.info` file of the latest version of a module, the way the go
+/// command chooses it: the highest release, or else the highest pre-release,
+/// or else the highest pseudo-version. Returns None when no version of the
+/// module is known.
+async fn get_latest_info(
+    artifact_service: &mut ArtifactService,
+    module: &str,
+) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let versions = get_versions(artifact_service, module)?;
+    let latest = match versions
+        .iter()
+        .filter_map(|version| version_precedence(version).map(|key| (key, version)))
+        .max()
+    {
+        Some((_, latest)) => latest,
+        None => return Ok(None),
+    };
+
+    artifact_service
+        .get_artifact(
+            PackageType::GoModule,
+            &get_package_specific_artifact_id(module, latest, "info"),
+        )
+        .await
+        .map(Some)
+}
+
+/// Returns the key to order module versions by when looking for the latest
+/// version, or None when the version isn't a valid semantic version.
+fn version_precedence(version: &str) -> Option<(u8, Version)> {
+    lazy_static! {
+        static ref PSEUDO_VERSION_REGEX: Regex = Regex::new(
+            r"^v[0-9]+\.(0\.0-|\d+\.\d+-([^+]*\.)?0\.)\d{14}-[A-Za-z0-9]+(\+[0-9A-Za-z-]+)?$"
+        )
+        .unwrap();
+    }
+
+    let semver = Version::parse(version.strip_prefix('v')?).ok()?;
+    let kind = if PSEUDO_VERSION_REGEX.is_match(version) {
+        0
+    } else if !semver.pre.is_empty() {
+        1
+    } else {
+        2
+    };
+    Some((kind, semver))
+}
+
+fn module_not_found(module: &str) -> Rejection {
+    warp::reject::custom(RegistryError {
+        code: RegistryErrorCode::NotFound(format!("go module {} not found", module)),
+    })
+}
+
+fn get_content_type(extension: &str) -> &'static str {
+    match extension {
+        "info" => "application/json",
+        "zip" => "application/zip",
+        _ => "text/plain",
+    }
+}
+
+fn get_package_specific_id(module: &str, version: &str) -> String {
+    format!("{}@{}", module, version)
+}
+
+fn get_package_specific_artifact_id(module: &str, version: &str, extension: &str) -> String {
+    format!("{}/@v/{}.{}", module, version, extension)
+}
+
+/// Decodes a module path or version as escaped by the go command: every upper
+/// case letter is sent as an exclamation mark followed by the lower case letter.
+fn decode_case(escaped: &str) -> Result<String, anyhow::Error> {
+    let mut decoded = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c == '!' {
+            match chars.next() {
+                Some(next) if next.is_ascii_lowercase() => decoded.push(next.to_ascii_uppercase()),
+                _ => bail!("Error, invalid escaped path: {}", escaped),
+            }
+        } else if c.is_ascii_uppercase() {
+            bail!("Error, invalid escaped path: {}", escaped);
+        } else {
+            decoded.push(c);
+        }
+    }
+    Ok(decoded)
+}
+
+fn parse_goproxy_request(full_path: &str) -> Result<GoProxyRequest, anyhow::Error> {
+    // go module requests look like:
+    //   "GET /go/github.com/pkg/errors/@v/list" for the list of versions,
+    //   "GET /go/github.com/pkg/errors/@latest" for the latest version and
+    //   "GET /go/github.com/pkg/errors/@v/v0.9.1.{info,mod,zip}" for the files
+    //   of a single version.
+    let path = full_path
+        .strip_prefix("/go/")
+        .ok_or_else(|| anyhow!("Error, invalid full path: {}", full_path))?;
+
+    if let Some(escaped_module) = path.strip_suffix("/@latest") {
+        return Ok(GoProxyRequest::Latest {
+            module: decode_case(escaped_module)?,
+        });
+    }
+
+    let (escaped_module, file) = path
+        .rsplit_once("/@v/")
+        .filter(|(escaped_module, _)| !escaped_module.is_empty())
+        .ok_or_else(|| anyhow!("Error, invalid full path: {}", full_path))?;
+    let module = decode_case(escaped_module)?;
+
+    if file == "list" {
+        return Ok(GoProxyRequest::List { module });
+    }
+
+    match file.rsplit_once('.') {
+        Some((version, extension))
+            if !version.is_empty() && matches!(extension, "info" | "mod" | "zip") =>
+        {
+            Ok(GoProxyRequest::File {
+                module,
+                version: decode_case(version)?,
+                extension: extension.to_owned(),
+            })
+        }
+        _ => bail!("Error, invalid full path: {}", full_path),
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::artifact_service::storage::ArtifactStorage;
+    use crate::blockchain_service::event::BlockchainEvent;
+    use crate::network::client::command::Command;
+    use crate::transparency_log::log::AddArtifactRequest;
+    use crate::util::test_util;
+    use anyhow::Context;
+    use hyper::header::HeaderValue;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    const INFO: &str = r#"{"Version":"v0.9.1","Time":"2020-01-14T19:47:44Z"}"#;
+    const INFO_HASH: &str = "5d8c58c4cf06efa07ac322b254ced1c5a61a2515113f54f7b63c324a144c1280";
+
+    #[test]
+    fn decode_case_test() {
+        assert_eq!(
+            decode_case("github.com/!burnt!sushi/toml").unwrap(),
+            "github.com/BurntSushi/toml"
+        );
+        assert!(decode_case("github.com/BurntSushi/toml").is_err());
+        assert!(decode_case("github.com/!").is_err());
+    }
+
+    #[test]
+    fn parse_list_request_test() {
+        assert_eq!(
+            parse_goproxy_request("/go/github.com/pkg/errors/@v/list").unwrap(),
+            GoProxyRequest::List {
+                module: "github.com/pkg/errors".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_latest_request_test() {
+        assert_eq!(
+            parse_goproxy_request("/go/github.com/pkg/errors/@latest").unwrap(),
+            GoProxyRequest::Latest {
+                module: "github.com/pkg/errors".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_file_request_test() {
+        assert_eq!(
+            parse_goproxy_request("/go/github.com/pkg/errors/@v/v0.9.1.mod").unwrap(),
+            GoProxyRequest::File {
+                module: "github.com/pkg/errors".to_owned(),
+                version: "v0.9.1".to_owned(),
+                extension: "mod".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_request_test() {
+        assert!(parse_goproxy_request("/go/github.com/pkg/errors").is_err());
+        assert!(parse_goproxy_request("/go/@v/list").is_err());
+        assert!(parse_goproxy_request("/go/github.com/pkg/errors/@v/v0.9.1.tar").is_err());
+    }
+
+    #[tokio::test]
+    async fn handle_get_go_module_list_and_latest_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, mut blockchain_event_receiver, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    }
+                    _ => panic!("Command must match Command::ListPeers"),
+                }
+            }
+        });
+
+        let transparency_log = artifact_service
+            .transparency_log_service
+            .add_artifact(AddArtifactRequest {
+                package_type: PackageType::GoModule,
+                package_specific_id: "github.com/pkg/errors@v0.9.1".to_owned(),
+                num_artifacts: 3,
+                package_specific_artifact_id: "github.com/pkg/errors/@v/v0.9.1.info".to_owned(),
+                artifact_hash: INFO_HASH.to_owned(),
+            })
+            .await
+            .unwrap()
+            .0;
+        create_artifact(
+            &artifact_service.artifact_storage,
+            &transparency_log.artifact_id,
+        )
+        .await
+        .unwrap();
+
+        let result = handle_get_go_module(
+            "/go/github.com/pkg/errors/@v/list".to_owned(),
+            artifact_service.clone(),
+        )
+        .await;
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"v0.9.1\n");
+
+        let result = handle_get_go_module(
+            "/go/github.com/pkg/errors/@latest".to_owned(),
+            artifact_service,
+        )
+        .await;
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type"),
+            Some(&HeaderValue::from_static("application/json"))
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], INFO.as_bytes());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[test]
+    fn latest_version_precedence_test() {
+        let latest = |versions: &[&'static str]| {
+            versions
+                .iter()
+                .filter_map(|version| version_precedence(version).map(|key| (key, *version)))
+                .max()
+                .map(|(_, version)| version)
+        };
+
+        assert_eq!(latest(&["v0.9.1", "v0.10.0", "v0.8.0"]), Some("v0.10.0"));
+        assert_eq!(latest(&["v0.9.1", "v1.0.0-rc.1"]), Some("v0.9.1"));
+        assert_eq!(
+            latest(&["v0.9.1", "v0.9.2-0.20200114194744-614d223910a1"]),
+            Some("v0.9.1")
+        );
+        assert_eq!(
+            latest(&[
+                "v1.0.0-rc.1",
+                "v1.0.0-rc.2",
+                "v0.0.0-20200114194744-614d223910a1"
+            ]),
+            Some("v1.0.0-rc.2")
+        );
+        assert_eq!(
+            latest(&[
+                "v0.0.0-20191109021931-daa7c04131f5",
+                "v0.0.0-20200114194744-614d223910a1"
+            ]),
+            Some("v0.0.0-20200114194744-614d223910a1")
+        );
+        assert_eq!(
+            latest(&["v2.0.0+incompatible", "v1.9.0"]),
+            Some("v2.0.0+incompatible")
+        );
+        assert_eq!(latest(&["latest", "1.0.0"]), None);
+    }
+
+    #[tokio::test]
+    async fn handle_get_go_module_of_unknown_module_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        for full_path in [
+            "/go/github.com/pkg/errors/@v/list",
+            "/go/github.com/pkg/errors/@latest",
+        ] {
+            let result = handle_get_go_module(full_path.to_owned(), artifact_service.clone()).await;
+
+            assert!(result.is_err());
+            let rejection = result.err().unwrap();
+            let registry_error = rejection.find::<RegistryError>().unwrap();
+            assert_eq!(
+                *registry_error,
+                RegistryError {
+                    code: RegistryErrorCode::NotFound(
+                        "go module github.com/pkg/errors not found".to_owned()
+                    ),
+                }
+            );
+        }
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn handle_get_go_module_file_of_unknown_version_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let result = handle_get_go_module(
+            "/go/github.com/pkg/errors/@v/v0.9.1.mod".to_owned(),
+            artifact_service,
+        )
+        .await;
+
+        assert!(result.is_err());
+        let rejection = result.err().unwrap();
+        let registry_error = rejection.find::<RegistryError>().unwrap();
+        assert_eq!(
+            *registry_error,
+            RegistryError {
+                code: RegistryErrorCode::NotFound(
+                    "go module github.com/pkg/errors@v0.9.1 not found, a build was requested"
+                        .to_owned()
+                ),
+            }
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    async fn create_artifact(
+        artifact_storage: &ArtifactStorage,
+        artifact_id: &str,
+    ) -> Result<String, anyhow::Error> {
+        artifact_storage
+            .push_artifact(Cursor::new(INFO.as_bytes()), artifact_id)
+            .await
+            .context("Error while pushing artifact")
+    }
+}
@@ -17,12 +17,15 @@
 use crate::artifact_service::model::PackageType;
 use crate::artifact_service::service::ArtifactService;
 use crate::docker::error_util::{RegistryError, RegistryErrorCode};
+use crate::util::range_util;
 use anyhow::{anyhow, bail};
 use log::debug;
+use warp::hyper::Body;
 use warp::{http::StatusCode, Rejection, Reply};
 
 pub async fn handle_get_maven_artifact(
     full_path: String,
+    range: Option<String>,
     mut artifact_service: ArtifactService,
 ) -> Result<impl Reply, Rejection> {
     debug!("Requesting maven artifact: {}", full_path);
@@ -50,8 +53,21 @@ pub async fn handle_get_maven_artifact(
         package_specific_id, package_specific_artifact_id
     );
 
-    let artifact_content = artifact_service
-        .get_artifact_or_build(
+    if let Some(range) = range {
+        if let Ok(Some(artifact_range)) = artifact_service
+            .get_artifact_range(PackageType::Maven2, &package_specific_artifact_id, &range)
+            .await
+        {
+            return Ok(range_util::create_range_response(
+                artifact_range,
+                "application/octet-stream",
+            ));
+        }
+        debug!("Range {} can't be served, serving the full artifact", range);
+    }
+
+    let (artifact_size, artifact_stream) = artifact_service
+        .get_artifact_stream_or_build(
             PackageType::Maven2,
             &package_specific_id,
             &package_specific_artifact_id,
@@ -66,8 +82,10 @@ pub async fn handle_get_maven_artifact(
 
     Ok(warp::http::response::Builder::new()
         .header("Content-Type", "application/octet-stream")
+        .header("Content-Length", artifact_size)
+        .header("Accept-Ranges", "bytes")
         .status(StatusCode::OK)
-        .body(artifact_content)
+        .body(Body::wrap_stream(artifact_stream))
         .unwrap())
 }
 
@@ -216,9 +234,12 @@ mod tests {
             &artifact_service.artifact_storage,
             &transparency_log.artifact_id,
         )
+        .await
         .unwrap();
 
-        let result = handle_get_maven_artifact(VALID_FULL_PATH.to_string(), artifact_service).await;
+        let result =
+            handle_get_maven_artifact(VALID_FULL_PATH.to_string(), None, artifact_service.clone())
+                .await;
 
         assert!(result.is_ok());
 
@@ -228,6 +249,26 @@ mod tests {
             response.headers().get("Content-Type"),
             Some(&HeaderValue::from_static("application/octet-stream"))
         );
+        assert_eq!(
+            response.headers().get("Accept-Ranges"),
+            Some(&HeaderValue::from_static("bytes"))
+        );
+
+        let result = handle_get_maven_artifact(
+            VALID_FULL_PATH.to_string(),
+            Some("bytes=0-9".to_owned()),
+            artifact_service,
+        )
+        .await;
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers().get("Content-Length"),
+            Some(&HeaderValue::from_static("10"))
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body.len(), 10);
 
         test_util::tests::teardown(tmp_dir);
     }
@@ -242,12 +283,13 @@ mod tests {
         Ok(reader)
     }
 
-    fn create_artifact(
+    async fn create_artifact(
         artifact_storage: &ArtifactStorage,
         artifact_id: &str,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<String, anyhow::Error> {
         artifact_storage
-            .push_artifact(&mut get_file_reader()?, artifact_id)
+            .push_artifact(get_file_reader()?, artifact_id)
+            .await
             .context("Error while pushing artifact")
     }
 }
@@ -31,6 +31,7 @@ pub fn make_maven_routes(
             debug!("route full path: {}", full_path);
             full_path
         })
+        .and(warp::header::optional::<String>("range"))
         .and(artifact_service_filter)
         .and_then(handle_get_maven_artifact);
 
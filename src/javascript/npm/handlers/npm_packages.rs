@@ -0,0 +1,637 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::artifact_service::model::PackageType;
+use crate::artifact_service::service::ArtifactService;
+use crate::docker::error_util::{RegistryError, RegistryErrorCode};
+use crate::transparency_log::log::{Operation, TransparencyLog};
+use anyhow::{anyhow, bail};
+use base64::Engine;
+use log::debug;
+use semver::Version;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use warp::hyper::Body;
+use warp::{http::StatusCode, Rejection, Reply};
+
+#[derive(Debug, Eq, PartialEq)]
+enum NpmRequest {
+    Packument {
+        name: String,
+    },
+    PackageVersion {
+        name: String,
+        version: String,
+    },
+    Tarball {
+        name: String,
+        version: String,
+        file_name: String,
+    },
+}
+
+/// Serves npm packages. The packument of a package only lists the versions of
+/// which the manifest is available on this node, so npm never starts a build
+/// through it: a package version gets in through a build requested with
+/// `pyrsia build npm --package <name>@<version>` (`POST /build/npm` on the node
+/// API), or when a version or tarball is requested directly. Packages and
+/// versions that are unknown are answered with a 404.
+pub async fn handle_get_npm_package(
+    full_path: String,
+    base_url: Option<String>,
+    mut artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    debug!("Requesting npm package: {}", full_path);
+    let npm_request = parse_npm_request(&full_path).map_err(|err| {
+        debug!("Error parsing npm request: {:?}", err);
+        warp::reject::custom(RegistryError {
+            code: RegistryErrorCode::NotFound(err.to_string()),
+        })
+    })?;
+
+    let (content_type, body) = match npm_request {
+        NpmRequest::Packument { name } => {
+            let packument = get_packument(&mut artifact_service, &name, base_url.as_deref())
+                .await
+                .map_err(|err| {
+                    debug!("Error retrieving packument: {:?}", err);
+                    warp::reject::custom(RegistryError::from(err))
+                })?
+                .ok_or_else(|| {
+                    warp::reject::custom(RegistryError {
+                        code: RegistryErrorCode::NotFound(format!(
+                            "npm package {} not found",
+                            name
+                        )),
+                    })
+                })?;
+            ("application/json", packument.to_string().into_bytes())
+        }
+        NpmRequest::PackageVersion { name, version } => {
+            let package_version =
+                get_package_version(&mut artifact_service, &name, &version, base_url.as_deref())
+                    .await
+                    .map_err(|err| {
+                        debug!("Error retrieving package version: {:?}", err);
+                        warp::reject::custom(RegistryError::from_artifact_error(
+                            err,
+                            format!(
+                                "npm package {}@{} not found, a build was requested",
+                                name, version
+                            ),
+                        ))
+                    })?;
+            ("application/json", package_version.to_string().into_bytes())
+        }
+        NpmRequest::Tarball {
+            name,
+            version,
+            file_name,
+        } => {
+            let package_specific_id = get_package_specific_id(&name, &version);
+            let package_specific_artifact_id = get_tarball_artifact_id(&name, &file_name);
+            debug!(
+                "Requesting artifact with package specific id: {}, and package specific artifact id: {}. If not found a build will be requested",
+                package_specific_id, package_specific_artifact_id
+            );
+
+            let (artifact_size, artifact_stream) = artifact_service
+                .get_artifact_stream_or_build(
+                    PackageType::Npm,
+                    &package_specific_id,
+                    &package_specific_artifact_id,
+                )
+                .await
+                .map_err(|err| {
+                    debug!("Error retrieving artifact: {:?}", err);
+                    warp::reject::custom(RegistryError::from_artifact_error(
+                        err,
+                        format!(
+                            "npm package {}@{} not found, a build was requested",
+                            name, version
+                        ),
+                    ))
+                })?;
+
+            return Ok(warp::http::response::Builder::new()
+                .header("Content-Type", "application/octet-stream")
+                .header("Content-Length", artifact_size)
+                .status(StatusCode::OK)
+                .body(Body::wrap_stream(artifact_stream))
+                .unwrap());
+        }
+    };
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", content_type)
+        .status(StatusCode::OK)
+        .body(Body::from(body))
+        .unwrap())
+}
+
+/// Build the packument (the document listing all versions of a package) from
+/// the versions of the package that are known in the transparency log. The
+/// manifests are only read from the local storage: a version of which the
+/// manifest isn't available on this node is left out, without fetching or
+/// building it. Returns None when no version of the package is available. The
+/// `latest` tag is the highest release, or the highest pre-release when the
+/// package has no releases.
+async fn get_packument(
+    artifact_service: &mut ArtifactService,
+    name: &str,
+    base_url: Option<&str>,
+) -> Result<Option<Value>, anyhow::Error> {
+    let transparency_logs = artifact_service
+        .transparency_log_service
+        .search_transparency_logs_by_prefix(&PackageType::Npm, &format!("{}@", name))?;
+
+    let mut versions = BTreeMap::new();
+    let mut latest: Option<Version> = None;
+    for manifest_log in transparency_logs.iter() {
+        let version = &manifest_log.package_specific_id[name.len() + 1..];
+        if manifest_log.package_specific_artifact_id != get_manifest_artifact_id(name, version) {
+            continue;
+        }
+
+        let tarball_log = match find_tarball_log(&transparency_logs, name, version) {
+            Some(tarball_log) => tarball_log,
+            None => continue,
+        };
+
+        let mut package_version = match artifact_service
+            .get_artifact_locally(&manifest_log.artifact_id)
+            .await
+            .and_then(|content| serde_json::from_slice(&content).map_err(|e| anyhow!(e)))
+        {
+            Ok(package_version) => package_version,
+            Err(err) => {
+                debug!("Skipping npm package version {}: {:?}", version, err);
+                continue;
+            }
+        };
+        add_dist(&mut package_version, name, tarball_log, base_url)?;
+
+        versions.insert(version.to_owned(), package_version);
+        match Version::parse(version) {
+            Ok(version)
+                if latest
+                    .as_ref()
+                    .map_or(true, |latest| is_later(&version, latest)) =>
+            {
+                latest = Some(version)
+            }
+            Ok(_) => {}
+            Err(err) => debug!("npm package version {} is not semver: {:?}", version, err),
+        }
+    }
+
+    if versions.is_empty() {
+        return Ok(None);
+    }
+    let mut packument = json!({
+        "name": name,
+        "versions": versions,
+    });
+    if let Some(latest) = latest {
+        packument["dist-tags"] = json!({ "latest": latest.to_string() });
+    }
+    Ok(Some(packument))
+}
+
+// Whether `version` should replace `latest` as the latest version: releases
+// take precedence over pre-releases, and higher versions over lower ones.
+fn is_later(version: &Version, latest: &Version) -> bool {
+    match (version.pre.is_empty(), latest.pre.is_empty()) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => version > latest,
+    }
+}
+
+async fn get_package_version(
+    artifact_service: &mut ArtifactService,
+    name: &str,
+    version: &str,
+    base_url: Option<&str>,
+) -> Result<Value, anyhow::Error> {
+    let package_specific_id = get_package_specific_id(name, version);
+    let manifest_content = artifact_service
+        .get_artifact_or_build(
+            PackageType::Npm,
+            &package_specific_id,
+            &get_manifest_artifact_id(name, version),
+        )
+        .await?;
+    let mut package_version = serde_json::from_slice(&manifest_content)?;
+
+    let transparency_logs = artifact_service
+        .transparency_log_service
+        .search_transparency_logs(&PackageType::Npm, &package_specific_id, false)?;
+    if let Some(tarball_log) = find_tarball_log(&transparency_logs, name, version) {
+        add_dist(&mut package_version, name, tarball_log, base_url)?;
+    }
+
+    Ok(package_version)
+}
+
+fn find_tarball_log<'a>(
+    transparency_logs: &'a [TransparencyLog],
+    name: &str,
+    version: &str,
+) -> Option<&'a TransparencyLog> {
+    let package_specific_id = get_package_specific_id(name, version);
+    let tarball_prefix = format!("{}/-/", name);
+    transparency_logs.iter().find(|transparency_log| {
+        transparency_log.operation == Operation::AddArtifact
+            && transparency_log.package_specific_id == package_specific_id
+            && transparency_log
+                .package_specific_artifact_id
+                .starts_with(&tarball_prefix)
+    })
+}
+
+/// Point the `dist` section of a package version to the tarball served by this
+/// node, together with the integrity of the tarball as recorded in the
+/// transparency log.
+fn add_dist(
+    package_version: &mut Value,
+    name: &str,
+    tarball_log: &TransparencyLog,
+    base_url: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let tarball = format!(
+        "{}/npm/{}",
+        base_url.unwrap_or_default(),
+        tarball_log.package_specific_artifact_id
+    );
+    let integrity = format!(
+        "sha256-{}",
+        base64::engine::general_purpose::STANDARD.encode(hex::decode(&tarball_log.artifact_hash)?)
+    );
+
+    let package_version = package_version
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Invalid package.json for npm package {}", name))?;
+    let mut dist = Map::new();
+    dist.insert("tarball".to_owned(), Value::String(tarball));
+    dist.insert("integrity".to_owned(), Value::String(integrity));
+    package_version.insert("dist".to_owned(), Value::Object(dist));
+
+    Ok(())
+}
+
+fn get_package_specific_id(name: &str, version: &str) -> String {
+    format!("{}@{}", name, version)
+}
+
+fn get_manifest_artifact_id(name: &str, version: &str) -> String {
+    format!("{}/{}", name, version)
+}
+
+fn get_tarball_artifact_id(name: &str, file_name: &str) -> String {
+    format!("{}/-/{}", name, file_name)
+}
+
+fn parse_npm_request(full_path: &str) -> Result<NpmRequest, anyhow::Error> {
+    // npm requests look like:
+    //   "GET /npm/left-pad" or "GET /npm/@scope%2fname" for the packument,
+    //   "GET /npm/left-pad/1.3.0" for a single package version and
+    //   "GET /npm/left-pad/-/left-pad-1.3.0.tgz" for the tarball.
+    let decoded_path = full_path
+        .replace("%2f", "/")
+        .replace("%2F", "/")
+        .replace("%40", "@");
+
+    // split, and remove first two strings: "" and "npm":
+    let pieces: Vec<&str> = decoded_path
+        .split('/')
+        .skip(2)
+        .filter(|piece| !piece.is_empty())
+        .collect();
+
+    let name_length = match pieces.first() {
+        Some(scope) if scope.starts_with('@') => 2,
+        Some(_) => 1,
+        None => bail!("Error, invalid full path: {}", full_path),
+    };
+    if pieces.len() < name_length {
+        bail!("Error, invalid full path: {}", full_path);
+    }
+    let name = pieces[..name_length].join("/");
+    let base_name = pieces[name_length - 1];
+
+    match &pieces[name_length..] {
+        [] => Ok(NpmRequest::Packument { name }),
+        [version] => Ok(NpmRequest::PackageVersion {
+            name,
+            version: version.to_string(),
+        }),
+        ["-", file_name] => {
+            let version = file_name
+                .strip_prefix(&format!("{}-", base_name))
+                .and_then(|rest| rest.strip_suffix(".tgz"))
+                .filter(|version| !version.is_empty())
+                .ok_or_else(|| anyhow!("Error, invalid tarball name: {}", file_name))?;
+            Ok(NpmRequest::Tarball {
+                name,
+                version: version.to_owned(),
+                file_name: file_name.to_string(),
+            })
+        }
+        _ => bail!("Error, invalid full path: {}", full_path),
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::artifact_service::storage::ArtifactStorage;
+    use crate::blockchain_service::event::BlockchainEvent;
+    use crate::network::client::command::Command;
+    use crate::transparency_log::log::AddArtifactRequest;
+    use crate::util::test_util;
+    use anyhow::Context;
+    use hyper::header::HeaderValue;
+    use std::collections::HashSet;
+    use std::fs::File;
+    use std::path::PathBuf;
+
+    const VALID_ARTIFACT_HASH: &str =
+        "865c8d988be4669f3e48f73b98f9bc2507be0246ea35e0098cf6054d3644c14f";
+
+    #[test]
+    fn parse_packument_request_test() {
+        assert_eq!(
+            parse_npm_request("/npm/left-pad").unwrap(),
+            NpmRequest::Packument {
+                name: "left-pad".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_scoped_packument_request_test() {
+        assert_eq!(
+            parse_npm_request("/npm/@babel%2fcore").unwrap(),
+            NpmRequest::Packument {
+                name: "@babel/core".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_package_version_request_test() {
+        assert_eq!(
+            parse_npm_request("/npm/@babel/core/7.20.12").unwrap(),
+            NpmRequest::PackageVersion {
+                name: "@babel/core".to_owned(),
+                version: "7.20.12".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_tarball_request_test() {
+        assert_eq!(
+            parse_npm_request("/npm/@babel/core/-/core-7.20.12.tgz").unwrap(),
+            NpmRequest::Tarball {
+                name: "@babel/core".to_owned(),
+                version: "7.20.12".to_owned(),
+                file_name: "core-7.20.12.tgz".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_request_test() {
+        assert!(parse_npm_request("/npm").is_err());
+        assert!(parse_npm_request("/npm/@babel").is_err());
+        assert!(parse_npm_request("/npm/left-pad/-/right-pad-1.3.0.tgz").is_err());
+        assert!(parse_npm_request("/npm/left-pad/1.3.0/extra").is_err());
+    }
+
+    #[tokio::test]
+    async fn handle_get_npm_packument_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, mut blockchain_event_receiver, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    }
+                    _ => panic!("Command must match Command::ListPeers"),
+                }
+            }
+        });
+
+        for package_specific_artifact_id in ["left-pad/1.3.0", "left-pad/-/left-pad-1.3.0.tgz"] {
+            let transparency_log_tuple = artifact_service
+                .transparency_log_service
+                .add_artifact(AddArtifactRequest {
+                    package_type: PackageType::Npm,
+                    package_specific_id: "left-pad@1.3.0".to_owned(),
+                    num_artifacts: 2,
+                    package_specific_artifact_id: package_specific_artifact_id.to_owned(),
+                    artifact_hash: VALID_ARTIFACT_HASH.to_owned(),
+                })
+                .await
+                .unwrap();
+            let transparency_log = transparency_log_tuple.0;
+
+            create_artifact(
+                &artifact_service.artifact_storage,
+                &transparency_log.artifact_id,
+            )
+            .await
+            .unwrap();
+        }
+
+        let result = handle_get_npm_package(
+            "/npm/left-pad".to_owned(),
+            Some("https://localhost:7888".to_owned()),
+            artifact_service,
+        )
+        .await;
+
+        assert!(result.is_ok());
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type"),
+            Some(&HeaderValue::from_static("application/json"))
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let packument: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(packument["name"], "left-pad");
+        assert_eq!(packument["dist-tags"]["latest"], "1.3.0");
+        assert_eq!(
+            packument["versions"]["1.3.0"]["dist"]["tarball"],
+            "https://localhost:7888/npm/left-pad/-/left-pad-1.3.0.tgz"
+        );
+        assert_eq!(
+            packument["versions"]["1.3.0"]["dist"]["integrity"],
+            "sha256-hlyNmIvkZp8+SPc7mPm8JQe+AkbqNeAJjPYFTTZEwU8="
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn handle_get_npm_packument_of_unknown_package_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let result =
+            handle_get_npm_package("/npm/left-pad".to_owned(), None, artifact_service).await;
+
+        assert!(result.is_err());
+        let rejection = result.err().unwrap();
+        let registry_error = rejection.find::<RegistryError>().unwrap();
+        assert_eq!(
+            *registry_error,
+            RegistryError {
+                code: RegistryErrorCode::NotFound("npm package left-pad not found".to_owned()),
+            }
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn handle_get_npm_packument_without_local_manifest_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, mut blockchain_event_receiver, ..) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        // the manifest is known in the transparency log, but not stored locally
+        for package_specific_artifact_id in ["left-pad/1.3.0", "left-pad/-/left-pad-1.3.0.tgz"] {
+            artifact_service
+                .transparency_log_service
+                .add_artifact(AddArtifactRequest {
+                    package_type: PackageType::Npm,
+                    package_specific_id: "left-pad@1.3.0".to_owned(),
+                    num_artifacts: 2,
+                    package_specific_artifact_id: package_specific_artifact_id.to_owned(),
+                    artifact_hash: VALID_ARTIFACT_HASH.to_owned(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let result =
+            handle_get_npm_package("/npm/left-pad".to_owned(), None, artifact_service).await;
+
+        let rejection = result.err().unwrap();
+        let registry_error = rejection.find::<RegistryError>().unwrap();
+        assert_eq!(
+            *registry_error,
+            RegistryError {
+                code: RegistryErrorCode::NotFound("npm package left-pad not found".to_owned()),
+            }
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn handle_get_npm_tarball_of_unknown_version_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let result = handle_get_npm_package(
+            "/npm/left-pad/-/left-pad-1.3.0.tgz".to_owned(),
+            None,
+            artifact_service,
+        )
+        .await;
+
+        let rejection = result.err().unwrap();
+        let registry_error = rejection.find::<RegistryError>().unwrap();
+        assert_eq!(
+            *registry_error,
+            RegistryError {
+                code: RegistryErrorCode::NotFound(
+                    "npm package left-pad@1.3.0 not found, a build was requested".to_owned()
+                ),
+            }
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[test]
+    fn latest_version_prefers_releases_test() {
+        let version = |v| Version::parse(v).unwrap();
+
+        assert!(is_later(&version("1.10.0"), &version("1.9.0")));
+        assert!(!is_later(&version("1.9.0"), &version("1.10.0")));
+        assert!(is_later(&version("1.0.0"), &version("2.0.0-beta.1")));
+        assert!(!is_later(&version("2.0.0-beta.1"), &version("1.0.0")));
+        assert!(is_later(&version("2.0.0-beta.2"), &version("2.0.0-beta.1")));
+    }
+
+    fn get_file_reader() -> Result<File, anyhow::Error> {
+        // test artifact file in resources/test dir
+        let mut curr_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        curr_dir.push("tests/resources/artifact_test.json");
+
+        let path = String::from(curr_dir.to_string_lossy());
+        let reader = File::open(path.as_str()).unwrap();
+        Ok(reader)
+    }
+
+    async fn create_artifact(
+        artifact_storage: &ArtifactStorage,
+        artifact_id: &str,
+    ) -> Result<String, anyhow::Error> {
+        artifact_storage
+            .push_artifact(get_file_reader()?, artifact_id)
+            .await
+            .context("Error while pushing artifact")
+    }
+}
@@ -0,0 +1,75 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::handlers::npm_packages::handle_get_npm_package;
+use crate::artifact_service::service::ArtifactService;
+use crate::util::url_util;
+use log::debug;
+use warp::Filter;
+
+pub fn make_npm_routes(
+    artifact_service: ArtifactService,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let artifact_service_filter = warp::any().map(move || artifact_service.clone());
+
+    let npm_root = warp::path("npm")
+        .and(warp::get())
+        .and(warp::path::full())
+        .map(|path: warp::path::FullPath| {
+            let full_path: String = path.as_str().to_string();
+            debug!("route full path: {}", full_path);
+            full_path
+        })
+        .and(url_util::base_url())
+        .and(artifact_service_filter)
+        .and_then(handle_get_npm_package);
+
+    warp::any().and(npm_root)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::docker::error_util::{RegistryError, RegistryErrorCode};
+    use crate::util::test_util;
+    use std::str;
+
+    #[tokio::test]
+    async fn npm_routes() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let filter = make_npm_routes(artifact_service);
+        let response = warp::test::request()
+            .path("/npm/left-pad/-/left-pad-1.3.0.tgz")
+            .reply(&filter)
+            .await;
+
+        let expected_error = RegistryError {
+            code: RegistryErrorCode::NotFound(
+                "npm package left-pad@1.3.0 not found, a build was requested".to_owned(),
+            ),
+        };
+        let expected_body = format!("Unhandled rejection: {:?}", expected_error);
+
+        assert_eq!(response.status(), 500);
+        assert_eq!(expected_body, str::from_utf8(response.body()).unwrap());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+}
@@ -21,11 +21,15 @@ pub mod blockchain_service;
 pub mod build_service;
 pub mod cli_commands;
 pub mod docker;
+pub mod go;
 pub mod java;
+pub mod javascript;
 pub mod logging;
 pub mod network;
 pub mod node_api;
 pub mod peer_metrics;
+pub mod python;
+pub mod rust;
 pub mod transparency_log;
 pub mod util;
 pub mod verification_service;
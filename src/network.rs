@@ -23,3 +23,4 @@ pub mod client;
 pub mod event_loop;
 pub mod idle_metric_protocol;
 pub mod p2p;
+pub mod reputation;
@@ -21,25 +21,48 @@ use libp2p::request_response::RequestResponseCodec;
 use log::debug;
 use std::io;
 
+/// The maximum number of artifact bytes that are transferred in a single
+/// chunk. Requests for a larger length are answered with a chunk of this size.
+pub const MAX_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
 #[derive(Debug, Clone)]
 pub struct ArtifactExchangeProtocol();
 /// The `ArtifactExchangeCodec` defines the request and response types
 /// for the [`RequestResponse`](crate::RequestResponse) protocol for
-/// exchanging artifacts. At the moment, the implementation for
-/// encoding/decoding writes all bytes of a single artifact at once.
+/// exchanging artifacts. Artifacts are transferred in chunks: every
+/// request asks for `length` bytes of an artifact starting at `offset`
+/// and every response contains at most [`MAX_CHUNK_SIZE`] bytes of the
+/// artifact, together with the total size of the artifact.
 #[derive(Clone)]
 pub struct ArtifactExchangeCodec();
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ArtifactRequest(pub String);
+pub struct ArtifactRequest {
+    pub artifact_id: String,
+    pub offset: u64,
+    pub length: u64,
+}
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ArtifactResponse(pub Vec<u8>);
+pub struct ArtifactResponse {
+    pub total_size: u64,
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
 
 impl ProtocolName for ArtifactExchangeProtocol {
     fn protocol_name(&self) -> &[u8] {
-        "/artifact-exchange/1".as_bytes()
+        "/artifact-exchange/2".as_bytes()
     }
 }
 
+async fn read_u64<T>(io: &mut T) -> io::Result<u64>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    let mut bytes = [0u8; 8];
+    io.read_exact(&mut bytes).await?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
 #[async_trait]
 impl RequestResponseCodec for ArtifactExchangeCodec {
     type Protocol = ArtifactExchangeProtocol;
@@ -59,10 +82,20 @@ impl RequestResponseCodec for ArtifactExchangeCodec {
             return Err(io::ErrorKind::UnexpectedEof.into());
         }
 
-        let artifact_id = String::from_utf8(hash_vec).unwrap();
-        debug!("Read ArtifactRequest: {:?}", artifact_id);
-
-        Ok(ArtifactRequest(artifact_id))
+        let artifact_id = String::from_utf8(hash_vec)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let offset = read_u64(io).await?;
+        let length = read_u64(io).await?;
+        debug!(
+            "Read ArtifactRequest: {:?} offset={} length={}",
+            artifact_id, offset, length
+        );
+
+        Ok(ArtifactRequest {
+            artifact_id,
+            offset,
+            length,
+        })
     }
 
     async fn read_response<T>(
@@ -73,27 +106,42 @@ impl RequestResponseCodec for ArtifactExchangeCodec {
     where
         T: AsyncRead + Unpin + Send,
     {
-        let vec = read_length_prefixed(io, 100_000_000).await?;
+        let total_size = read_u64(io).await?;
+        let offset = read_u64(io).await?;
+        let data = read_length_prefixed(io, MAX_CHUNK_SIZE as usize).await?;
 
-        if vec.is_empty() {
+        if data.is_empty() && offset < total_size {
             return Err(io::ErrorKind::UnexpectedEof.into());
         }
 
-        Ok(ArtifactResponse(vec))
+        Ok(ArtifactResponse {
+            total_size,
+            offset,
+            data,
+        })
     }
 
     async fn write_request<T>(
         &mut self,
         _: &ArtifactExchangeProtocol,
         io: &mut T,
-        ArtifactRequest(artifact_id): ArtifactRequest,
+        ArtifactRequest {
+            artifact_id,
+            offset,
+            length,
+        }: ArtifactRequest,
     ) -> io::Result<()>
     where
         T: AsyncWrite + Unpin + Send,
     {
-        debug!("Write ArtifactRequest: {:?}", artifact_id);
+        debug!(
+            "Write ArtifactRequest: {:?} offset={} length={}",
+            artifact_id, offset, length
+        );
 
         write_length_prefixed(io, artifact_id).await?;
+        io.write_all(&offset.to_be_bytes()).await?;
+        io.write_all(&length.to_be_bytes()).await?;
         io.close().await?;
 
         Ok(())
@@ -103,14 +151,95 @@ impl RequestResponseCodec for ArtifactExchangeCodec {
         &mut self,
         _: &ArtifactExchangeProtocol,
         io: &mut T,
-        ArtifactResponse(data): ArtifactResponse,
+        ArtifactResponse {
+            total_size,
+            offset,
+            data,
+        }: ArtifactResponse,
     ) -> io::Result<()>
     where
         T: AsyncWrite + Unpin + Send,
     {
+        io.write_all(&total_size.to_be_bytes()).await?;
+        io.write_all(&offset.to_be_bytes()).await?;
         write_length_prefixed(io, data).await?;
         io.close().await?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use futures::io::Cursor;
+
+    #[tokio::test]
+    async fn test_request_round_trip() {
+        let request = ArtifactRequest {
+            artifact_id: "artifact_id".to_owned(),
+            offset: 42,
+            length: MAX_CHUNK_SIZE,
+        };
+
+        let mut buffer = Cursor::new(Vec::new());
+        ArtifactExchangeCodec()
+            .write_request(&ArtifactExchangeProtocol(), &mut buffer, request.clone())
+            .await
+            .unwrap();
+
+        let mut reader = Cursor::new(buffer.into_inner());
+        let read_request = ArtifactExchangeCodec()
+            .read_request(&ArtifactExchangeProtocol(), &mut reader)
+            .await
+            .unwrap();
+
+        assert_eq!(read_request, request);
+    }
+
+    #[tokio::test]
+    async fn test_response_round_trip() {
+        let response = ArtifactResponse {
+            total_size: 100,
+            offset: 90,
+            data: vec![7; 10],
+        };
+
+        let mut buffer = Cursor::new(Vec::new());
+        ArtifactExchangeCodec()
+            .write_response(&ArtifactExchangeProtocol(), &mut buffer, response.clone())
+            .await
+            .unwrap();
+
+        let mut reader = Cursor::new(buffer.into_inner());
+        let read_response = ArtifactExchangeCodec()
+            .read_response(&ArtifactExchangeProtocol(), &mut reader)
+            .await
+            .unwrap();
+
+        assert_eq!(read_response, response);
+    }
+
+    #[tokio::test]
+    async fn test_read_response_with_missing_data() {
+        let response = ArtifactResponse {
+            total_size: 100,
+            offset: 0,
+            data: vec![],
+        };
+
+        let mut buffer = Cursor::new(Vec::new());
+        ArtifactExchangeCodec()
+            .write_response(&ArtifactExchangeProtocol(), &mut buffer, response)
+            .await
+            .unwrap();
+
+        let mut reader = Cursor::new(buffer.into_inner());
+        let result = ArtifactExchangeCodec()
+            .read_response(&ArtifactExchangeProtocol(), &mut reader)
+            .await;
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -24,21 +24,34 @@ use crate::network::build_protocol::BuildResponse;
 use crate::network::build_status_protocol::BuildStatusResponse;
 use crate::network::client::command::Command;
 use crate::network::idle_metric_protocol::{IdleMetricResponse, PeerMetrics};
+use crate::network::reputation::{PeerOffense, PeerReputation, PeerScore};
 use crate::node_api::model::request::Status;
+use futures::stream::{FuturesUnordered, StreamExt};
 use libp2p::core::{Multiaddr, PeerId};
 use libp2p::request_response::ResponseChannel;
 use log::debug;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, oneshot};
 
 /* peer metrics support */
 const PEER_METRIC_THRESHOLD: f64 = 0.5_f64;
+/// The default time that peers get to answer an idle metric request.
+pub const DEFAULT_IDLE_METRIC_TIMEOUT: Duration = Duration::from_secs(2);
+/// The time an idle metric of a peer is reused before it is requested again.
+const IDLE_METRIC_CACHE_TTL: Duration = Duration::from_secs(30);
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 struct IdleMetric {
     pub peer: PeerId,
     pub metric: f64,
 }
+#[derive(Clone, Debug)]
+struct CachedIdleMetric {
+    metric: f64,
+    retrieved_at: Instant,
+}
 /* peer metric support */
 
 /// A utility struct for easily defining a hash from different
@@ -83,6 +96,9 @@ pub struct VerifyBuildData {
 pub struct Client {
     pub sender: mpsc::Sender<Command>,
     pub local_peer_id: PeerId,
+    idle_metric_timeout: Duration,
+    idle_metrics: Arc<Mutex<HashMap<PeerId, CachedIdleMetric>>>,
+    reputation: PeerReputation,
 }
 
 impl Client {
@@ -90,9 +106,19 @@ impl Client {
         Self {
             sender,
             local_peer_id,
+            idle_metric_timeout: DEFAULT_IDLE_METRIC_TIMEOUT,
+            idle_metrics: Default::default(),
+            reputation: Default::default(),
         }
     }
 
+    /// Set the time that peers get to answer an idle metric request
+    /// in [`Client::get_idle_peer`].
+    pub fn with_idle_metric_timeout(mut self, idle_metric_timeout: Duration) -> Self {
+        self.idle_metric_timeout = idle_metric_timeout;
+        self
+    }
+
     /// Add a probe address for AutoNAT discovery. When adding the probe
     /// was handled successfully, the kademlia DHT will be bootstrapped.
     pub async fn add_probe_address(
@@ -169,6 +195,30 @@ impl Client {
         Ok(receiver.await?)
     }
 
+    /// Record an offense of `peer`. Peers that reach the ban score are
+    /// disconnected and banned from the swarm.
+    pub async fn report_peer(&self, peer: &PeerId, offense: PeerOffense) -> anyhow::Result<()> {
+        if self.reputation.record_offense(peer, offense) {
+            self.sender.send(Command::BanPeer { peer: *peer }).await?;
+        }
+        Ok(())
+    }
+
+    /// Record a successful transfer from `peer`.
+    pub fn report_success(&self, peer: &PeerId) {
+        self.reputation.record_success(peer);
+    }
+
+    /// Whether `peer` has a high enough reputation to download from.
+    pub fn is_trusted_peer(&self, peer: &PeerId) -> bool {
+        self.reputation.is_trusted(peer)
+    }
+
+    /// The reputation scores of all peers that committed an offense.
+    pub fn peer_scores(&self) -> Vec<PeerScore> {
+        self.reputation.scores()
+    }
+
     /// Get the status of the node including nearby peers cnt and my peer addrs
     pub async fn status(&self) -> anyhow::Result<Status> {
         let (sender, receiver) = oneshot::channel();
@@ -191,6 +241,19 @@ impl Client {
         Ok(receiver.await?)
     }
 
+    /// Inform the swarm that this node no longer provides
+    /// the artifact with the specified `artifact_id`.
+    pub async fn stop_providing(&self, artifact_id: &str) -> anyhow::Result<()> {
+        debug!("p2p::Client::stop_providing {:?}", artifact_id);
+
+        self.sender
+            .send(Command::StopProviding {
+                artifact_id: artifact_id.to_owned(),
+            })
+            .await?;
+        Ok(())
+    }
+
     /// List all peers in the swarm that are providing
     /// the artifact with the specified `artifact_id`.
     pub async fn list_providers(&self, artifact_id: &str) -> anyhow::Result<HashSet<PeerId>> {
@@ -272,22 +335,26 @@ impl Client {
         Ok(())
     }
 
-    /// Request an artifact with the specified `artifact_id`
-    /// from the swarm.
+    /// Request a chunk of at most `length` bytes, starting at `offset`,
+    /// of the artifact with the specified `artifact_id` from the swarm.
     pub async fn request_artifact(
         &self,
         peer: &PeerId,
         artifact_id: &str,
-    ) -> anyhow::Result<Vec<u8>> {
+        offset: u64,
+        length: u64,
+    ) -> anyhow::Result<ArtifactResponse> {
         debug!(
-            "p2p::Client::request_artifact {:?}: {:?}",
-            peer, artifact_id
+            "p2p::Client::request_artifact {:?}: {:?} offset={} length={}",
+            peer, artifact_id, offset, length
         );
 
         let (sender, receiver) = oneshot::channel();
         self.sender
             .send(Command::RequestArtifact {
                 artifact_id: artifact_id.to_owned(),
+                offset,
+                length,
                 peer: *peer,
                 sender,
             })
@@ -295,14 +362,19 @@ impl Client {
         receiver.await?
     }
 
-    /// Put the artifact as a response to an incoming artifact
-    /// request.
+    /// Put a chunk of the artifact as a response to an incoming
+    /// artifact request.
     pub async fn respond_artifact(
         &self,
-        artifact: Vec<u8>,
+        artifact: ArtifactResponse,
         channel: ResponseChannel<ArtifactResponse>,
     ) -> anyhow::Result<()> {
-        debug!("p2p::Client::respond_artifact size={:?}", artifact.len());
+        debug!(
+            "p2p::Client::respond_artifact offset={:?} size={:?} total_size={:?}",
+            artifact.offset,
+            artifact.data.len(),
+            artifact.total_size
+        );
 
         self.sender
             .send(Command::RespondArtifact { artifact, channel })
@@ -311,8 +383,12 @@ impl Client {
         Ok(())
     }
 
-    //get a peer with a low enough work load to download artifact otherwise the lowest work load of the set
-    //TODO: chunk the peers to some limit to keep from shotgunning the network
+    /// Get a peer with a low enough work load to download an artifact from,
+    /// otherwise the peer with the lowest work load of the set. Peers with a
+    /// low reputation score are skipped. Idle metrics
+    /// that were retrieved recently are reused, all other providers are asked
+    /// for their idle metric at once. Providers that don't answer within the
+    /// idle metric timeout are ignored.
     pub async fn get_idle_peer(
         &self,
         providers: HashSet<PeerId>,
@@ -322,43 +398,79 @@ impl Client {
             providers.len()
         );
         let mut idle_metrics: Vec<IdleMetric> = Vec::new();
-        for peer in providers.iter() {
-            let (sender, receiver) = oneshot::channel();
-            self.sender
-                .send(Command::RequestIdleMetric {
-                    peer: *peer,
-                    sender,
+        let mut uncached_peers: Vec<PeerId> = Vec::new();
+        {
+            let cache = self.idle_metrics.lock().unwrap();
+            for peer in providers
+                .into_iter()
+                .filter(|peer| self.is_trusted_peer(peer))
+            {
+                match cache.get(&peer) {
+                    Some(cached) if cached.retrieved_at.elapsed() < IDLE_METRIC_CACHE_TTL => {
+                        idle_metrics.push(IdleMetric {
+                            peer,
+                            metric: cached.metric,
+                        })
+                    }
+                    _ => uncached_peers.push(peer),
+                }
+            }
+        }
+
+        if !idle_metrics
+            .iter()
+            .any(|idle_metric| idle_metric.metric < PEER_METRIC_THRESHOLD)
+        {
+            let mut responses: FuturesUnordered<_> = uncached_peers
+                .into_iter()
+                .map(|peer| async move {
+                    let metric = self
+                        .request_peer_metrics(&peer)
+                        .await
+                        .map(|peer_metrics| peer_metrics.idle_metric);
+                    (peer, metric)
                 })
-                .await?;
-
-            match receiver.await.expect("Sender not to be dropped.") {
-                Ok(peer_metric) => {
-                    let metric: f64 = f64::from_le_bytes(peer_metric.idle_metric);
-                    let idle_metric = IdleMetric {
-                        peer: *peer,
-                        metric,
-                    };
-                    if idle_metric.metric < PEER_METRIC_THRESHOLD {
-                        debug!(
-                                "p2p::Client::get_idle_peer() Found peer with a below threshold idle value {}",
-                                metric
+                .collect();
+            let deadline = tokio::time::sleep(self.idle_metric_timeout);
+            tokio::pin!(deadline);
+
+            loop {
+                tokio::select! {
+                    response = responses.next() => match response {
+                        Some((peer, Ok(metric))) => {
+                            self.idle_metrics.lock().unwrap().insert(
+                                peer,
+                                CachedIdleMetric {
+                                    metric,
+                                    retrieved_at: Instant::now(),
+                                },
+                            );
+                            idle_metrics.push(IdleMetric { peer, metric });
+                            if metric < PEER_METRIC_THRESHOLD {
+                                debug!(
+                                    "p2p::Client::get_idle_peer() Found peer with a below threshold idle value {}",
+                                    metric
+                                );
+                                break;
+                            }
+                        }
+                        Some((peer, Err(e))) => {
+                            debug!(
+                                "p2p::Client::get_idle_peer() Unable to get peer metric for peer {} error {}",
+                                peer, e
                             );
-                        return Ok(Some(idle_metric.peer));
-                    } else {
+                        }
+                        None => break,
+                    },
+                    _ = &mut deadline => {
                         debug!(
-                            "p2p::Client::get_idle_peer() Pushing idle peer with value {}",
-                            metric
+                            "p2p::Client::get_idle_peer() {} peers did not answer in time",
+                            responses.len()
                         );
-                        idle_metrics.push(idle_metric);
+                        break;
                     }
                 }
-                Err(e) => {
-                    debug!(
-                            "p2p::Client::get_idle_peer() Unable to get peer metric for peer {} error {}",
-                            peer, e
-                        );
-                }
-            };
+            }
         }
 
         //sort the peers in ascending order according to their idle metric and return top of list
@@ -366,6 +478,20 @@ impl Client {
         Ok(idle_metrics.first().map(|idle_metric| idle_metric.peer))
     }
 
+    /// Request the metrics of a single peer, including the individual
+    /// components of its idle metric.
+    pub async fn request_peer_metrics(&self, peer: &PeerId) -> anyhow::Result<PeerMetrics> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender
+            .send(Command::RequestIdleMetric {
+                peer: *peer,
+                sender,
+            })
+            .await?;
+
+        receiver.await?
+    }
+
     pub async fn respond_idle_metric(
         &self,
         metric: PeerMetrics,
@@ -557,7 +683,8 @@ mod tests {
                 Some(Command::RequestIdleMetric { peer, sender }) => {
                     assert_eq!(peer, local_peer_id);
                     let peer_metric = PeerMetrics {
-                        idle_metric: 8675309f64.to_le_bytes(),
+                        idle_metric: 8675309f64,
+                        ..Default::default()
                     };
                     let _ = sender.send(Ok(peer_metric));
                 },
@@ -567,6 +694,141 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_get_idle_peer_requests_metrics_concurrently() {
+        let (client, mut receiver) = test_util::tests::create_p2p_client();
+
+        let peers: Vec<PeerId> = (0..3).map(|_| PeerId::random()).collect();
+        let most_idle_peer = peers[1];
+        let metrics: HashMap<PeerId, f64> =
+            HashMap::from([(peers[0], 3.0), (peers[1], 1.0), (peers[2], 2.0)]);
+        let providers: HashSet<PeerId> = peers.into_iter().collect();
+        let idle_peer = tokio::spawn(async move { client.get_idle_peer(providers).await });
+
+        // all requests are sent before any of them is answered
+        let mut senders = Vec::new();
+        for _ in 0..3 {
+            match receiver.recv().await {
+                Some(Command::RequestIdleMetric { peer, sender }) => senders.push((peer, sender)),
+                _ => panic!("Command must match Command::RequestIdleMetric"),
+            }
+        }
+        for (peer, sender) in senders {
+            let _ = sender.send(Ok(PeerMetrics {
+                idle_metric: metrics[&peer],
+                ..Default::default()
+            }));
+        }
+
+        assert_eq!(idle_peer.await.unwrap().unwrap(), Some(most_idle_peer));
+    }
+
+    #[tokio::test]
+    async fn test_get_idle_peer_ignores_peers_after_deadline() {
+        let (client, mut receiver) = test_util::tests::create_p2p_client();
+        let client = client.with_idle_metric_timeout(Duration::from_millis(100));
+
+        let answering_peer = PeerId::random();
+        let providers = HashSet::from([answering_peer, PeerId::random()]);
+        let idle_peer = tokio::spawn(async move { client.get_idle_peer(providers).await });
+
+        let mut stalled_senders = Vec::new();
+        for _ in 0..2 {
+            match receiver.recv().await {
+                Some(Command::RequestIdleMetric { peer, sender }) if peer == answering_peer => {
+                    let _ = sender.send(Ok(PeerMetrics {
+                        idle_metric: 0.9_f64,
+                        ..Default::default()
+                    }));
+                }
+                Some(Command::RequestIdleMetric { sender, .. }) => stalled_senders.push(sender),
+                _ => panic!("Command must match Command::RequestIdleMetric"),
+            }
+        }
+
+        assert_eq!(idle_peer.await.unwrap().unwrap(), Some(answering_peer));
+    }
+
+    #[tokio::test]
+    async fn test_get_idle_peer_uses_cached_metrics() {
+        let (client, mut receiver) = test_util::tests::create_p2p_client();
+
+        let peer = PeerId::random();
+        let cloned_client = client.clone();
+        let idle_peer =
+            tokio::spawn(async move { cloned_client.get_idle_peer(HashSet::from([peer])).await });
+        match receiver.recv().await {
+            Some(Command::RequestIdleMetric { sender, .. }) => {
+                let _ = sender.send(Ok(PeerMetrics {
+                    idle_metric: 0.9_f64,
+                    ..Default::default()
+                }));
+            }
+            _ => panic!("Command must match Command::RequestIdleMetric"),
+        }
+        assert_eq!(idle_peer.await.unwrap().unwrap(), Some(peer));
+
+        assert_eq!(
+            client.get_idle_peer(HashSet::from([peer])).await.unwrap(),
+            Some(peer)
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_idle_peer_skips_untrusted_peers() {
+        let (client, mut receiver) = test_util::tests::create_p2p_client();
+
+        let untrusted_peer = PeerId::random();
+        let trusted_peer = PeerId::random();
+        for _ in 0..2 {
+            client
+                .report_peer(&untrusted_peer, PeerOffense::HashMismatch)
+                .await
+                .unwrap();
+        }
+
+        let providers = HashSet::from([untrusted_peer, trusted_peer]);
+        let idle_peer = tokio::spawn(async move { client.get_idle_peer(providers).await });
+
+        match receiver.recv().await {
+            Some(Command::RequestIdleMetric { peer, sender }) => {
+                assert_eq!(peer, trusted_peer);
+                let _ = sender.send(Ok(PeerMetrics {
+                    idle_metric: 0.9,
+                    ..Default::default()
+                }));
+            }
+            _ => panic!("Command must match Command::RequestIdleMetric"),
+        }
+
+        assert_eq!(idle_peer.await.unwrap().unwrap(), Some(trusted_peer));
+    }
+
+    #[tokio::test]
+    async fn test_report_peer_bans_repeat_offender() {
+        let (client, mut receiver) = test_util::tests::create_p2p_client();
+
+        let peer = PeerId::random();
+        for _ in 0..2 {
+            client
+                .report_peer(&peer, PeerOffense::HashMismatch)
+                .await
+                .unwrap();
+            assert!(receiver.try_recv().is_err());
+        }
+
+        client
+            .report_peer(&peer, PeerOffense::HashMismatch)
+            .await
+            .unwrap();
+        match receiver.try_recv() {
+            Ok(Command::BanPeer { peer: banned_peer }) => assert_eq!(banned_peer, peer),
+            _ => panic!("Command must match Command::BanPeer"),
+        }
+        assert_eq!(client.peer_scores()[0].hash_mismatches, 3);
+    }
+
     #[tokio::test]
     async fn test_provide() {
         let (client, mut receiver) = test_util::tests::create_p2p_client();
@@ -586,6 +848,21 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_stop_providing() {
+        let (client, mut receiver) = test_util::tests::create_p2p_client();
+
+        let random_artifact_id = test_util::tests::random_string(30);
+        client.stop_providing(&random_artifact_id).await.unwrap();
+
+        match receiver.try_recv() {
+            Ok(Command::StopProviding { artifact_id }) => {
+                assert_eq!(artifact_id, random_artifact_id)
+            }
+            _ => panic!("Command must match Command::StopProviding"),
+        }
+    }
+
     #[tokio::test]
     async fn test_list_providers() {
         let (client, mut receiver) = test_util::tests::create_p2p_client();
@@ -614,16 +891,22 @@ mod tests {
         let cloned_random_artifact_id = random_artifact_id.clone();
         tokio::spawn(async move {
             client
-                .request_artifact(&other_peer_id, &random_artifact_id)
+                .request_artifact(&other_peer_id, &random_artifact_id, 10, 20)
                 .await
         });
 
         tokio::select! {
             command = receiver.recv() => match command {
-                Some(Command::RequestArtifact { peer, artifact_id, sender }) => {
+                Some(Command::RequestArtifact { peer, artifact_id, offset, length, sender }) => {
                     assert_eq!(peer, other_peer_id);
                     assert_eq!(artifact_id, cloned_random_artifact_id);
-                    let _ = sender.send(Ok(vec![]));
+                    assert_eq!(offset, 10);
+                    assert_eq!(length, 20);
+                    let _ = sender.send(Ok(ArtifactResponse {
+                        total_size: 30,
+                        offset,
+                        data: vec![0; 20],
+                    }));
                 },
                 _ => panic!("Command must match Command::RequestArtifact")
             }
@@ -693,10 +976,10 @@ mod tests {
         let (sender, mut receiver) = mpsc::channel(1);
         let local_key = identity::ed25519::Keypair::generate();
 
-        let client = Client {
+        let client = Client::new(
             sender,
-            local_peer_id: identity::PublicKey::Ed25519(local_key.public()).to_peer_id(),
-        };
+            identity::PublicKey::Ed25519(local_key.public()).to_peer_id(),
+        );
 
         let other_peer_id = PeerId::random();
 
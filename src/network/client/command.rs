@@ -57,6 +57,9 @@ pub enum Command {
     ListPeers {
         sender: oneshot::Sender<HashSet<PeerId>>,
     },
+    BanPeer {
+        peer: PeerId,
+    },
     Status {
         sender: oneshot::Sender<Status>,
     },
@@ -64,6 +67,9 @@ pub enum Command {
         artifact_id: String,
         sender: oneshot::Sender<()>,
     },
+    StopProviding {
+        artifact_id: String,
+    },
     ListProviders {
         artifact_id: String,
         sender: oneshot::Sender<HashSet<PeerId>>,
@@ -85,11 +91,13 @@ pub enum Command {
     },
     RequestArtifact {
         artifact_id: String,
+        offset: u64,
+        length: u64,
         peer: PeerId,
-        sender: oneshot::Sender<anyhow::Result<Vec<u8>>>,
+        sender: oneshot::Sender<anyhow::Result<ArtifactResponse>>,
     },
     RespondArtifact {
-        artifact: Vec<u8>,
+        artifact: ArtifactResponse,
         channel: ResponseChannel<ArtifactResponse>,
     },
     RequestIdleMetric {
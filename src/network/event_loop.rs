@@ -48,7 +48,8 @@ type PendingBootstrapMap = HashMap<QueryId, oneshot::Sender<anyhow::Result<()>>>
 type PendingDialMap = HashMap<PeerId, oneshot::Sender<anyhow::Result<()>>>;
 type PendingListProvidersMap = HashMap<QueryId, PendingListProviders>;
 type PendingStartProvidingMap = HashMap<QueryId, oneshot::Sender<()>>;
-type PendingRequestArtifactMap = HashMap<RequestId, oneshot::Sender<anyhow::Result<Vec<u8>>>>;
+type PendingRequestArtifactMap =
+    HashMap<RequestId, oneshot::Sender<anyhow::Result<ArtifactResponse>>>;
 type PendingRequestBuildMap = HashMap<RequestId, oneshot::Sender<anyhow::Result<String>>>;
 type PendingRequestIdleMetricMap = HashMap<RequestId, oneshot::Sender<anyhow::Result<PeerMetrics>>>;
 type PendingRequestBlockchainMap = HashMap<RequestId, oneshot::Sender<anyhow::Result<Vec<u8>>>>;
@@ -340,7 +341,9 @@ impl PyrsiaEventLoop {
                 } => {
                     self.event_sender
                         .send(PyrsiaEvent::RequestArtifact {
-                            artifact_id: request.0,
+                            artifact_id: request.artifact_id,
+                            offset: request.offset,
+                            length: request.length,
                             channel,
                         })
                         .await
@@ -353,7 +356,7 @@ impl PyrsiaEventLoop {
                     self.pending_request_artifact
                         .remove(&request_id)
                         .expect("Request to still be pending.")
-                        .send(Ok(response.0))
+                        .send(Ok(response))
                         .unwrap_or_else(|e| {
                             error!(
                                 "Handle RequestResponseEvent match arm: {}. Error: {:?}",
@@ -719,6 +722,11 @@ impl PyrsiaEventLoop {
                     error!("Handle Command match arm: {}.", command_str);
                 });
             }
+            Command::BanPeer { peer } => {
+                warn!("Banning peer {}", peer);
+                self.swarm.behaviour_mut().kademlia.remove_peer(&peer);
+                self.swarm.ban_peer_id(peer);
+            }
             Command::Status { sender } => {
                 let swarm = &self.swarm;
                 let local_peer_id = *swarm.local_peer_id();
@@ -753,6 +761,7 @@ impl PyrsiaEventLoop {
                     peers_count: swarm.connected_peers().count(),
                     peer_id: local_peer_id.to_string(),
                     peer_addrs,
+                    ..Default::default()
                 };
 
                 sender.send(status).unwrap();
@@ -769,6 +778,12 @@ impl PyrsiaEventLoop {
                     .expect("No store error.");
                 self.pending_start_providing.insert(query_id, sender);
             }
+            Command::StopProviding { artifact_id } => {
+                self.swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .stop_providing(&artifact_id.into_bytes().into());
+            }
             Command::ListProviders {
                 artifact_id,
                 sender,
@@ -836,21 +851,26 @@ impl PyrsiaEventLoop {
             }
             Command::RequestArtifact {
                 artifact_id,
+                offset,
+                length,
                 peer,
                 sender,
             } => {
-                let request_id = self
-                    .swarm
-                    .behaviour_mut()
-                    .request_response
-                    .send_request(&peer, ArtifactRequest(artifact_id));
+                let request_id = self.swarm.behaviour_mut().request_response.send_request(
+                    &peer,
+                    ArtifactRequest {
+                        artifact_id,
+                        offset,
+                        length,
+                    },
+                );
                 self.pending_request_artifact.insert(request_id, sender);
             }
             Command::RespondArtifact { artifact, channel } => {
                 self.swarm
                     .behaviour_mut()
                     .request_response
-                    .send_response(channel, ArtifactResponse(artifact))
+                    .send_response(channel, artifact)
                     .expect("Connection to peer to be still open.");
             }
             Command::RequestIdleMetric { peer, sender } => {
@@ -926,6 +946,8 @@ impl PyrsiaEventLoop {
 pub enum PyrsiaEvent {
     RequestArtifact {
         artifact_id: String,
+        offset: u64,
+        length: u64,
         channel: ResponseChannel<ArtifactResponse>,
     },
     RequestBuild {
@@ -16,7 +16,7 @@
 
 use async_trait::async_trait;
 use futures::prelude::*;
-use libp2p::core::upgrade::{read_varint, write_length_prefixed, ProtocolName};
+use libp2p::core::upgrade::{read_length_prefixed, write_length_prefixed, ProtocolName};
 use libp2p::request_response::RequestResponseCodec;
 use log::debug;
 use serde::{Deserialize, Serialize};
@@ -24,27 +24,22 @@ use std::io;
 
 /// The `IdleMetricExchangeCodec` defines the request and response types
 /// for the [`RequestResponse`](crate::RequestResponse) protocol for
-/// exchanging peer metrics. The peer metric is passed through the framework
-/// in the PeerMetrics structure but over the network as the bytes needed
-/// for passing a floating point as bits of the idle metric field of the
-/// PeerMetrics structure.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// exchanging peer metrics. Next to the combined `idle_metric`, the
+/// PeerMetrics structure contains the individual components it is
+/// calculated from, so requesters can apply their own policy. It is
+/// passed over the network as JSON.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct PeerMetrics {
-    pub idle_metric: [u8; 8],
-}
-
-impl PartialEq for PeerMetrics {
-    fn eq(&self, other: &Self) -> bool {
-        self.idle_metric == other.idle_metric
-    }
-}
-
-impl Eq for PeerMetrics {}
-
-impl AsRef<[u8]> for PeerMetrics {
-    fn as_ref(&self) -> &[u8] {
-        &self.idle_metric
-    }
+    /// The combined load of the peer, between 0 (idle) and 1 (fully loaded).
+    pub idle_metric: f64,
+    /// The CPU load average over the last minute per core.
+    pub cpu_load: f64,
+    /// The recent network throughput in bytes per second.
+    pub network_throughput: f64,
+    /// The number of artifact transfers of the peer that are in flight.
+    pub in_flight_transfers: u64,
+    /// The fraction of free space on the disk of the artifact storage.
+    pub disk_headroom: f64,
 }
 
 #[derive(Debug, Clone)]
@@ -54,12 +49,12 @@ pub struct IdleMetricExchangeProtocol();
 pub struct IdleMetricExchangeCodec();
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IdleMetricRequest();
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IdleMetricResponse(pub PeerMetrics);
 
 impl ProtocolName for IdleMetricExchangeProtocol {
     fn protocol_name(&self) -> &[u8] {
-        "/metric-exchange/1".as_bytes()
+        "/metric-exchange/2".as_bytes()
     }
 }
 #[async_trait]
@@ -91,21 +86,13 @@ impl RequestResponseCodec for IdleMetricExchangeCodec {
     where
         T: AsyncRead + Unpin + Send,
     {
-        let mut buff: [u8; 8] = [0; 8];
-        let mut size = read_varint(io).await?;
-        if size != 8 {
-            return Err(io::ErrorKind::InvalidData.into());
-        }
-
-        size = io.read(&mut buff).await?;
-        if size != 8 {
-            return Err(io::ErrorKind::InvalidData.into());
-        }
+        let vec = read_length_prefixed(io, 1_000).await?;
+        let metric: PeerMetrics = serde_json::from_slice(&vec)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        let metric = PeerMetrics { idle_metric: buff };
         debug!(
             "p2p::idle_metric_protocol::read_response Reading response to idle metric request with value ={:?}",
-            metric.idle_metric
+            metric
         );
         Ok(IdleMetricResponse(metric))
     }
@@ -141,7 +128,8 @@ impl RequestResponseCodec for IdleMetricExchangeCodec {
             "p2p::idle_metric_protocol::write_response sending PeerMetric metric value {:?}",
             data
         );
-        write_length_prefixed(io, data.idle_metric).await?;
+        let vec = serde_json::to_vec(&data)?;
+        write_length_prefixed(io, vec).await?;
         io.close().await?;
 
         Ok(())
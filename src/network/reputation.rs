@@ -0,0 +1,322 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use libp2p::request_response::OutboundFailure;
+use libp2p::PeerId;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The score of a peer that hasn't misbehaved.
+pub const INITIAL_SCORE: i32 = 100;
+/// Peers with a score below this value are not selected to download from.
+pub const MIN_TRUSTED_SCORE: i32 = 50;
+/// Peers with a score at or below this value are disconnected and banned.
+pub const BAN_SCORE: i32 = 0;
+// A successful transfer slowly restores the score of a peer.
+const SUCCESS_REWARD: i32 = 5;
+// The score of a peer that isn't banned recovers by `RECOVERY_REWARD` every
+// `RECOVERY_INTERVAL`, so a peer that is no longer trusted is selected again
+// eventually, even though it gets no transfers to restore its score with.
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(60);
+const RECOVERY_REWARD: i32 = 1;
+
+/// The kinds of misbehavior that lower the reputation of a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerOffense {
+    /// The peer sent artifact data that doesn't match the transparency log hash.
+    /// Only reported when the corrupt data is traced to the peer, e.g. because
+    /// the peer sent the whole artifact. A single mismatch leaves the peer
+    /// trusted, in case the data was corrupted in transit.
+    HashMismatch,
+    /// The peer didn't answer a request in time.
+    Timeout,
+    /// The peer answered a request with an invalid response.
+    ProtocolError,
+}
+
+impl PeerOffense {
+    fn penalty(&self) -> i32 {
+        match self {
+            PeerOffense::HashMismatch => 40,
+            PeerOffense::Timeout => 10,
+            PeerOffense::ProtocolError => 20,
+        }
+    }
+
+    /// The offense that corresponds to a failed outbound request, if the
+    /// failure can be attributed to the peer. A peer that doesn't support the
+    /// protocol runs another version of pyrsia, which isn't misbehavior.
+    pub fn from_error(error: &anyhow::Error) -> Option<PeerOffense> {
+        match error.downcast_ref::<OutboundFailure>() {
+            Some(OutboundFailure::Timeout) => Some(PeerOffense::Timeout),
+            _ => None,
+        }
+    }
+}
+
+/// The reputation of a single peer, as exposed by the node API.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PeerScore {
+    pub peer_id: String,
+    pub score: i32,
+    pub hash_mismatches: u32,
+    pub timeouts: u32,
+    pub protocol_errors: u32,
+    pub banned: bool,
+}
+
+impl PeerScore {
+    fn new(peer_id: &PeerId) -> Self {
+        PeerScore {
+            peer_id: peer_id.to_string(),
+            score: INITIAL_SCORE,
+            hash_mismatches: 0,
+            timeouts: 0,
+            protocol_errors: 0,
+            banned: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PeerRecord {
+    score: PeerScore,
+    recovered_at: Instant,
+}
+
+impl PeerRecord {
+    fn new(peer_id: &PeerId) -> Self {
+        PeerRecord {
+            score: PeerScore::new(peer_id),
+            recovered_at: Instant::now(),
+        }
+    }
+
+    // Restore the score for every `RECOVERY_INTERVAL` that passed since the
+    // score last recovered. Banned peers don't recover.
+    fn recover(&mut self, now: Instant) -> &mut PeerScore {
+        let intervals = now.saturating_duration_since(self.recovered_at).as_secs()
+            / RECOVERY_INTERVAL.as_secs();
+        if self.score.banned || self.score.score >= INITIAL_SCORE {
+            self.recovered_at = now;
+        } else if intervals > 0 {
+            let reward = i32::try_from(intervals)
+                .unwrap_or(i32::MAX)
+                .saturating_mul(RECOVERY_REWARD);
+            self.score.score = self.score.score.saturating_add(reward).min(INITIAL_SCORE);
+            self.recovered_at += RECOVERY_INTERVAL * intervals as u32;
+        }
+        &mut self.score
+    }
+}
+
+/// The `PeerReputation` keeps track of the misbehavior of peers. Every
+/// offense lowers the score of a peer, while successful transfers and time
+/// slowly restore it. Clones share the same scores.
+#[derive(Clone, Debug, Default)]
+pub struct PeerReputation {
+    scores: Arc<Mutex<HashMap<PeerId, PeerRecord>>>,
+}
+
+impl PeerReputation {
+    /// Record an offense of `peer`. Returns true when the peer reached the
+    /// ban score with this offense and should be banned.
+    pub fn record_offense(&self, peer: &PeerId, offense: PeerOffense) -> bool {
+        let mut scores = self.scores.lock().unwrap();
+        let peer_score = scores
+            .entry(*peer)
+            .or_insert_with(|| PeerRecord::new(peer))
+            .recover(Instant::now());
+
+        match offense {
+            PeerOffense::HashMismatch => peer_score.hash_mismatches += 1,
+            PeerOffense::Timeout => peer_score.timeouts += 1,
+            PeerOffense::ProtocolError => peer_score.protocol_errors += 1,
+        }
+        peer_score.score -= offense.penalty();
+        warn!(
+            "Peer {} committed offense {:?}, score is now {}",
+            peer, offense, peer_score.score
+        );
+
+        if peer_score.score <= BAN_SCORE && !peer_score.banned {
+            peer_score.banned = true;
+            return true;
+        }
+        false
+    }
+
+    /// Record a successful transfer from `peer`.
+    pub fn record_success(&self, peer: &PeerId) {
+        if let Some(peer_record) = self.scores.lock().unwrap().get_mut(peer) {
+            let peer_score = peer_record.recover(Instant::now());
+            if !peer_score.banned {
+                peer_score.score = (peer_score.score + SUCCESS_REWARD).min(INITIAL_SCORE);
+                debug!("Peer {} score is now {}", peer, peer_score.score);
+            }
+        }
+    }
+
+    /// Whether `peer` has a high enough score to download from.
+    pub fn is_trusted(&self, peer: &PeerId) -> bool {
+        self.scores
+            .lock()
+            .unwrap()
+            .get_mut(peer)
+            .map_or(true, |peer_record| {
+                let peer_score = peer_record.recover(Instant::now());
+                !peer_score.banned && peer_score.score >= MIN_TRUSTED_SCORE
+            })
+    }
+
+    /// The scores of all peers that committed an offense, lowest score first.
+    pub fn scores(&self) -> Vec<PeerScore> {
+        let now = Instant::now();
+        let mut scores: Vec<PeerScore> = self
+            .scores
+            .lock()
+            .unwrap()
+            .values_mut()
+            .map(|peer_record| peer_record.recover(now).clone())
+            .collect();
+        scores.sort_by(|a, b| a.score.cmp(&b.score).then(a.peer_id.cmp(&b.peer_id)));
+        scores
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_peer_is_trusted() {
+        let reputation = PeerReputation::default();
+
+        assert!(reputation.is_trusted(&PeerId::random()));
+        assert!(reputation.scores().is_empty());
+    }
+
+    #[test]
+    fn test_offenses_lower_score_until_ban() {
+        let reputation = PeerReputation::default();
+        let peer = PeerId::random();
+
+        assert!(!reputation.record_offense(&peer, PeerOffense::Timeout));
+        assert!(reputation.is_trusted(&peer));
+        assert!(!reputation.record_offense(&peer, PeerOffense::HashMismatch));
+        assert!(reputation.is_trusted(&peer));
+        assert!(!reputation.record_offense(&peer, PeerOffense::ProtocolError));
+        assert!(!reputation.is_trusted(&peer));
+        assert!(reputation.record_offense(&peer, PeerOffense::HashMismatch));
+        // a banned peer is only reported to be banned once
+        assert!(!reputation.record_offense(&peer, PeerOffense::Timeout));
+
+        assert_eq!(
+            reputation.scores(),
+            vec![PeerScore {
+                peer_id: peer.to_string(),
+                score: -20,
+                hash_mismatches: 2,
+                timeouts: 2,
+                protocol_errors: 1,
+                banned: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_success_restores_score() {
+        let reputation = PeerReputation::default();
+        let peer = PeerId::random();
+
+        reputation.record_offense(&peer, PeerOffense::HashMismatch);
+        reputation.record_offense(&peer, PeerOffense::HashMismatch);
+        assert!(!reputation.is_trusted(&peer));
+
+        for _ in 0..6 {
+            reputation.record_success(&peer);
+        }
+        assert!(reputation.is_trusted(&peer));
+
+        for _ in 0..20 {
+            reputation.record_success(&peer);
+        }
+        assert_eq!(reputation.scores()[0].score, INITIAL_SCORE);
+    }
+
+    #[test]
+    fn test_time_restores_score() {
+        let reputation = PeerReputation::default();
+        let peer = PeerId::random();
+        let banned_peer = PeerId::random();
+
+        for _ in 0..6 {
+            reputation.record_offense(&peer, PeerOffense::Timeout);
+        }
+        for _ in 0..3 {
+            reputation.record_offense(&banned_peer, PeerOffense::HashMismatch);
+        }
+        assert!(!reputation.is_trusted(&peer));
+        assert!(!reputation.is_trusted(&banned_peer));
+
+        let recover_since = |peer: &PeerId, intervals: u32| {
+            let mut scores = reputation.scores.lock().unwrap();
+            let peer_record = scores.get_mut(peer).unwrap();
+            peer_record.recovered_at -= RECOVERY_INTERVAL * intervals;
+        };
+
+        recover_since(&peer, 9);
+        assert!(!reputation.is_trusted(&peer));
+        recover_since(&peer, 1);
+        assert!(reputation.is_trusted(&peer));
+
+        recover_since(&peer, 1000);
+        recover_since(&banned_peer, 1000);
+        assert!(!reputation.is_trusted(&banned_peer));
+        assert_eq!(
+            reputation
+                .scores()
+                .iter()
+                .map(|peer_score| peer_score.score)
+                .collect::<Vec<i32>>(),
+            vec![-20, INITIAL_SCORE]
+        );
+    }
+
+    #[test]
+    fn test_offense_from_error() {
+        assert_eq!(
+            PeerOffense::from_error(&OutboundFailure::Timeout.into()),
+            Some(PeerOffense::Timeout)
+        );
+        assert_eq!(
+            PeerOffense::from_error(&OutboundFailure::UnsupportedProtocols.into()),
+            None
+        );
+        assert_eq!(
+            PeerOffense::from_error(&OutboundFailure::ConnectionClosed.into()),
+            None
+        );
+        assert_eq!(
+            PeerOffense::from_error(&anyhow::anyhow!("other error")),
+            None
+        );
+    }
+}
@@ -14,7 +14,7 @@
    limitations under the License.
 */
 
-use crate::artifact_service::model::PackageType;
+use crate::artifact_service::model::{BundlePackage, PackageType};
 use crate::docker::error_util::{RegistryError, RegistryErrorCode};
 use crate::network::client::Client;
 use crate::node_api::model::request::*;
@@ -24,11 +24,15 @@ use std::future::Future;
 use crate::artifact_service::service::ArtifactService;
 use crate::build_service::error::BuildError;
 use crate::node_api::model::response::BuildSuccessResponse;
+use bytes::Buf;
+use futures::{Stream, StreamExt};
 use libp2p::PeerId;
 use log::debug;
 use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
+use std::io;
 use std::str::FromStr;
+use warp::hyper::Body;
 use warp::{http::StatusCode, Rejection, Reply};
 
 #[derive(Default)]
@@ -219,6 +223,66 @@ pub async fn handle_build_maven(
         .body(build_id_as_json))
 }
 
+pub async fn handle_build_cargo(
+    request_cargo_build: RequestCargoBuild,
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let request_build_result = || async {
+        artifact_service
+            .request_build(PackageType::Cargo, request_cargo_build.package)
+            .await
+    };
+
+    let build_id = handle_err_artifact_already_exists(request_build_result).await?;
+
+    let build_id_as_json = serde_json::to_string(&build_id).map_err(RegistryError::from)?;
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(build_id.success_status_code)
+        .body(build_id_as_json))
+}
+
+pub async fn handle_build_npm(
+    request_npm_build: RequestNpmBuild,
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let request_build_result = || async {
+        artifact_service
+            .request_build(PackageType::Npm, request_npm_build.package)
+            .await
+    };
+
+    let build_id = handle_err_artifact_already_exists(request_build_result).await?;
+
+    let build_id_as_json = serde_json::to_string(&build_id).map_err(RegistryError::from)?;
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(build_id.success_status_code)
+        .body(build_id_as_json))
+}
+
+pub async fn handle_build_pypi(
+    request_pypi_build: RequestPypiBuild,
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let request_build_result = || async {
+        artifact_service
+            .request_build(PackageType::PyPI, request_pypi_build.package)
+            .await
+    };
+
+    let build_id = handle_err_artifact_already_exists(request_build_result).await?;
+
+    let build_id_as_json = serde_json::to_string(&build_id).map_err(RegistryError::from)?;
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(build_id.success_status_code)
+        .body(build_id_as_json))
+}
+
 pub async fn handle_build_status(
     request_build_status: RequestBuildStatus,
     mut artifact_service: ArtifactService,
@@ -252,8 +316,36 @@ pub async fn handle_get_peers(p2p_client: Client) -> Result<impl Reply, Rejectio
         .unwrap())
 }
 
-pub async fn handle_get_status(p2p_client: Client) -> Result<impl Reply, Rejection> {
-    let status = p2p_client.status().await.map_err(RegistryError::from)?;
+pub async fn handle_get_peer_scores(p2p_client: Client) -> Result<impl Reply, Rejection> {
+    let peer_scores = p2p_client.peer_scores();
+    debug!("Got peer scores: {:?}", peer_scores);
+
+    let peer_scores_as_json = serde_json::to_string(&peer_scores).unwrap();
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(StatusCode::OK)
+        .body(peer_scores_as_json)
+        .unwrap())
+}
+
+pub async fn handle_get_status(artifact_service: ArtifactService) -> Result<impl Reply, Rejection> {
+    let mut status = artifact_service
+        .p2p_client
+        .status()
+        .await
+        .map_err(RegistryError::from)?;
+    status.disk_usage = artifact_service
+        .artifact_storage
+        .usage()
+        .await
+        .map_err(RegistryError::from)?;
+    status.disk_quota = artifact_service.disk_quota();
+    status.compression_savings = artifact_service
+        .artifact_storage
+        .compression_savings()
+        .await
+        .map_err(RegistryError::from)?;
 
     let status_as_json = serde_json::to_string(&status).unwrap();
 
@@ -264,6 +356,190 @@ pub async fn handle_get_status(p2p_client: Client) -> Result<impl Reply, Rejecti
         .unwrap())
 }
 
+pub async fn handle_get_scrub_report(
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let scrub_report_as_json = serde_json::to_string(&artifact_service.scrub_report()).unwrap();
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(StatusCode::OK)
+        .body(scrub_report_as_json)
+        .unwrap())
+}
+
+pub async fn handle_scrub(artifact_service: ArtifactService) -> Result<impl Reply, Rejection> {
+    let scrub_report = artifact_service
+        .scrub_artifacts()
+        .await
+        .map_err(RegistryError::from)?;
+
+    let scrub_report_as_json = serde_json::to_string(&scrub_report).unwrap();
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(StatusCode::OK)
+        .body(scrub_report_as_json)
+        .unwrap())
+}
+
+pub async fn handle_get_gc_report(
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let gc_report_as_json = serde_json::to_string(&artifact_service.gc_report()).unwrap();
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(StatusCode::OK)
+        .body(gc_report_as_json)
+        .unwrap())
+}
+
+pub async fn handle_collect_garbage(
+    request_garbage_collection: RequestGarbageCollection,
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let gc_report = artifact_service
+        .collect_garbage(request_garbage_collection.dry_run)
+        .await
+        .map_err(RegistryError::from)?;
+
+    let gc_report_as_json = serde_json::to_string(&gc_report).unwrap();
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(StatusCode::OK)
+        .body(gc_report_as_json)
+        .unwrap())
+}
+
+pub async fn handle_export_bundle(
+    request_bundle_export: RequestBundleExport,
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let packages = request_bundle_export
+        .packages
+        .into_iter()
+        .map(|package| match package.package_type {
+            PackageType::Docker => BundlePackage {
+                package_specific_id: get_package_specific_id(&package.package_specific_id),
+                ..package
+            },
+            _ => package,
+        })
+        .collect();
+
+    let (bundle_size, bundle_stream) = artifact_service
+        .export_bundle(packages)
+        .await
+        .map_err(RegistryError::from)?;
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/octet-stream")
+        .header("Content-Length", bundle_size)
+        .status(StatusCode::OK)
+        .body(Body::wrap_stream(bundle_stream))
+        .unwrap())
+}
+
+pub async fn handle_import_bundle<S, B>(
+    bundle: S,
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection>
+where
+    S: Stream<Item = Result<B, warp::Error>> + Send + 'static,
+    B: Buf,
+{
+    let bundle = bundle.map(|chunk| {
+        chunk
+            .map(|mut buf| buf.copy_to_bytes(buf.remaining()))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    });
+
+    let import_report = artifact_service
+        .import_bundle(Box::pin(bundle))
+        .await
+        .map_err(RegistryError::from)?;
+
+    let import_report_as_json = serde_json::to_string(&import_report).unwrap();
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(StatusCode::OK)
+        .body(import_report_as_json)
+        .unwrap())
+}
+
+pub async fn handle_get_pins(artifact_service: ArtifactService) -> Result<impl Reply, Rejection> {
+    let pins_as_json = serde_json::to_string(&artifact_service.pins()).unwrap();
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(StatusCode::OK)
+        .body(pins_as_json)
+        .unwrap())
+}
+
+pub async fn handle_pin_package(
+    request_pin: RequestPin,
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let package_specific_id = normalize_package_specific_id(&request_pin);
+    let pin_status = artifact_service
+        .pin_package(request_pin.package_type, &package_specific_id)
+        .map_err(RegistryError::from)?;
+
+    // The artifacts of the package are fetched in the background, the progress
+    // is reported through the status of the pin.
+    let package_type = request_pin.package_type;
+    tokio::spawn(async move {
+        if let Err(error) = artifact_service
+            .refresh_pin(package_type, &package_specific_id)
+            .await
+        {
+            debug!("Unable to refresh pin: {:?}", error);
+        }
+    });
+
+    let pin_status_as_json = serde_json::to_string(&pin_status).unwrap();
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/json")
+        .status(StatusCode::OK)
+        .body(pin_status_as_json)
+        .unwrap())
+}
+
+pub async fn handle_unpin_package(
+    request_pin: RequestPin,
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let package_specific_id = normalize_package_specific_id(&request_pin);
+    let unpinned = artifact_service
+        .unpin_package(request_pin.package_type, &package_specific_id)
+        .map_err(RegistryError::from)?;
+    if !unpinned {
+        return Err(RegistryError {
+            code: RegistryErrorCode::BadRequest(format!(
+                "Package {} of type {} is not pinned",
+                package_specific_id, request_pin.package_type
+            )),
+        }
+        .into());
+    }
+
+    Ok(warp::http::response::Builder::new()
+        .status(StatusCode::OK)
+        .body(""))
+}
+
+fn normalize_package_specific_id(request_pin: &RequestPin) -> String {
+    match request_pin.package_type {
+        PackageType::Docker => get_package_specific_id(&request_pin.package_specific_id),
+        _ => request_pin.package_specific_id.clone(),
+    }
+}
+
 pub async fn handle_inspect_log_docker(
     request_docker_log: RequestDockerLog,
     artifact_service: ArtifactService,
@@ -14,6 +14,7 @@
    limitations under the License.
 */
 
+use crate::artifact_service::model::{BundlePackage, PackageType};
 use crate::docker::error_util::RegistryError;
 use crate::node_api::handlers::swarm::OutputTransparencyLog;
 use serde::{Deserialize, Serialize};
@@ -24,6 +25,19 @@ pub struct Status {
     pub peers_count: usize,
     pub peer_id: String,
     pub peer_addrs: Vec<String>,
+    #[serde(default)]
+    pub disk_usage: u64,
+    #[serde(default)]
+    pub disk_quota: Option<u64>,
+    #[serde(default)]
+    pub compression_savings: u64,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RequestGarbageCollection {
+    /// Only report the garbage, without removing it. Default value is false.
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -58,6 +72,24 @@ pub struct RequestMavenBuild {
     pub gav: String,
 }
 
+/// A crate version to build, e.g. `serde@1.0.152`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestCargoBuild {
+    pub package: String,
+}
+
+/// An npm package version to build, e.g. `left-pad@1.3.0`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestNpmBuild {
+    pub package: String,
+}
+
+/// A python project version to build, e.g. `requests==2.28.2`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestPypiBuild {
+    pub package: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RequestMavenLog {
     pub gav: String,
@@ -72,6 +104,17 @@ pub struct RequestBuildStatus {
     pub build_id: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestBundleExport {
+    pub packages: Vec<BundlePackage>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestPin {
+    pub package_type: PackageType,
+    pub package_specific_id: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub enum ContentType {
     JSON,
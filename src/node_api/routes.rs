@@ -15,11 +15,14 @@
 */
 
 use super::handlers::swarm::*;
-use super::model::request::{RequestDockerBuild, RequestMavenBuild};
+use super::model::request::{
+    RequestCargoBuild, RequestDockerBuild, RequestMavenBuild, RequestNpmBuild, RequestPypiBuild,
+};
 use crate::artifact_service::service::ArtifactService;
 use crate::network::client::Client;
 use crate::node_api::model::request::{
-    RequestAddAuthorizedNode, RequestBuildStatus, RequestDockerLog, RequestMavenLog,
+    RequestAddAuthorizedNode, RequestBuildStatus, RequestBundleExport, RequestDockerLog,
+    RequestGarbageCollection, RequestMavenLog, RequestPin,
 };
 use warp::Filter;
 
@@ -54,6 +57,30 @@ pub fn make_node_routes(
         .and(artifact_service_filter.clone())
         .and_then(handle_build_maven);
 
+    let build_cargo = warp::path!("build" / "cargo")
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::content_length_limit(1024 * 8))
+        .and(warp::body::json::<RequestCargoBuild>())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_build_cargo);
+
+    let build_npm = warp::path!("build" / "npm")
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::content_length_limit(1024 * 8))
+        .and(warp::body::json::<RequestNpmBuild>())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_build_npm);
+
+    let build_pypi = warp::path!("build" / "pypi")
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::content_length_limit(1024 * 8))
+        .and(warp::body::json::<RequestPypiBuild>())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_build_pypi);
+
     let build_status = warp::path!("build" / "status")
         .and(warp::post())
         .and(warp::path::end())
@@ -68,12 +95,80 @@ pub fn make_node_routes(
         .and(p2p_client_filter.clone())
         .and_then(handle_get_peers);
 
-    let status = warp::path!("status")
+    let peer_scores = warp::path!("peers" / "reputation")
         .and(warp::get())
         .and(warp::path::end())
         .and(p2p_client_filter)
+        .and_then(handle_get_peer_scores);
+
+    let status = warp::path!("status")
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(artifact_service_filter.clone())
         .and_then(handle_get_status);
 
+    let scrub_report = warp::path!("scrub")
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_get_scrub_report);
+
+    let scrub = warp::path!("scrub")
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_scrub);
+
+    let gc_report = warp::path!("gc")
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_get_gc_report);
+
+    let gc = warp::path!("gc")
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::query::<RequestGarbageCollection>())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_collect_garbage);
+
+    let export_bundle = warp::path!("bundle" / "export")
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::content_length_limit(1024 * 64))
+        .and(warp::body::json::<RequestBundleExport>())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_export_bundle);
+
+    let import_bundle = warp::path!("bundle" / "import")
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::stream())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_import_bundle);
+
+    let pins = warp::path!("pins")
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_get_pins);
+
+    let pin_package = warp::path!("pins")
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::content_length_limit(1024 * 8))
+        .and(warp::body::json::<RequestPin>())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_pin_package);
+
+    let unpin_package = warp::path!("pins")
+        .and(warp::delete())
+        .and(warp::path::end())
+        .and(warp::body::content_length_limit(1024 * 8))
+        .and(warp::body::json::<RequestPin>())
+        .and(artifact_service_filter.clone())
+        .and_then(handle_unpin_package);
+
     let inspect_docker = warp::path!("inspect" / "docker")
         .and(warp::post())
         .and(warp::path::end())
@@ -94,8 +189,21 @@ pub fn make_node_routes(
         add_authorized_node
             .or(build_docker)
             .or(build_maven)
+            .or(build_cargo)
+            .or(build_npm)
+            .or(build_pypi)
             .or(peers)
+            .or(peer_scores)
             .or(status)
+            .or(scrub_report)
+            .or(scrub)
+            .or(gc_report)
+            .or(gc)
+            .or(export_bundle)
+            .or(import_bundle)
+            .or(pins)
+            .or(pin_package)
+            .or(unpin_package)
             .or(inspect_docker)
             .or(inspect_maven)
             .or(build_status),
@@ -106,10 +214,13 @@ pub fn make_node_routes(
 #[cfg(not(tarpaulin_include))]
 mod tests {
     use super::*;
-    use crate::artifact_service::model::PackageType;
+    use crate::artifact_service::model::{
+        BundlePackage, GarbageCollectionReport, PackageType, PinState, PinStatus, ScrubReport,
+    };
     use crate::blockchain_service::event::BlockchainEvent;
     use crate::build_service::event::BuildEvent;
     use crate::network::client::command::Command;
+    use crate::network::reputation::{PeerOffense, PeerScore};
     use crate::node_api::model::request::*;
     use crate::node_api::model::response::BuildSuccessResponse;
     use crate::transparency_log::log::{
@@ -118,6 +229,7 @@ mod tests {
     use crate::util::test_util;
     use csv;
     use httptest::http;
+    use libp2p::PeerId;
     use std::collections::HashSet;
     use std::future::Future;
     use std::str;
@@ -309,6 +421,216 @@ mod tests {
         test_util::tests::teardown(tmp_dir);
     }
 
+    #[tokio::test]
+    async fn node_routes_build_cargo() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (p2p_client, mut p2p_command_receiver) = test_util::tests::create_p2p_client();
+        let (artifact_service, mut blockchain_event_receiver, mut build_event_receiver) =
+            test_util::tests::create_artifact_service_with_p2p_client(&tmp_dir, p2p_client.clone());
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        let build_id = uuid::Uuid::new_v4();
+        tokio::spawn(async move {
+            loop {
+                match build_event_receiver.recv().await {
+                    Some(BuildEvent::Start { sender, .. }) => {
+                        let _ = sender.send(Ok(build_id.to_string()));
+                    }
+                    _ => {
+                        panic!("BuildEvent must match BuildEvent::Start")
+                    }
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    }
+                    _ => panic!("Command must match Command::ListPeers"),
+                }
+            }
+        });
+
+        artifact_service
+            .transparency_log_service
+            .add_authorized_node(p2p_client.local_peer_id)
+            .await
+            .expect("Error adding authorized node");
+
+        let filter = make_node_routes(artifact_service, p2p_client);
+        let request = RequestCargoBuild {
+            package: "serde@1.0.152".to_owned(),
+        };
+        let response = warp::test::request()
+            .method("POST")
+            .path("/build/cargo")
+            .json(&request)
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+
+        let build_id_result: BuildSuccessResponse =
+            serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(build_id_result.build_id.unwrap(), build_id.to_string());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn node_routes_build_npm() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (p2p_client, mut p2p_command_receiver) = test_util::tests::create_p2p_client();
+        let (artifact_service, mut blockchain_event_receiver, mut build_event_receiver) =
+            test_util::tests::create_artifact_service_with_p2p_client(&tmp_dir, p2p_client.clone());
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        let build_id = uuid::Uuid::new_v4();
+        tokio::spawn(async move {
+            loop {
+                match build_event_receiver.recv().await {
+                    Some(BuildEvent::Start { sender, .. }) => {
+                        let _ = sender.send(Ok(build_id.to_string()));
+                    }
+                    _ => {
+                        panic!("BuildEvent must match BuildEvent::Start")
+                    }
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    }
+                    _ => panic!("Command must match Command::ListPeers"),
+                }
+            }
+        });
+
+        artifact_service
+            .transparency_log_service
+            .add_authorized_node(p2p_client.local_peer_id)
+            .await
+            .expect("Error adding authorized node");
+
+        let filter = make_node_routes(artifact_service, p2p_client);
+        let request = RequestNpmBuild {
+            package: "left-pad@1.3.0".to_owned(),
+        };
+        let response = warp::test::request()
+            .method("POST")
+            .path("/build/npm")
+            .json(&request)
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+
+        let build_id_result: BuildSuccessResponse =
+            serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(build_id_result.build_id.unwrap(), build_id.to_string());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn node_routes_build_pypi() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (p2p_client, mut p2p_command_receiver) = test_util::tests::create_p2p_client();
+        let (artifact_service, mut blockchain_event_receiver, mut build_event_receiver) =
+            test_util::tests::create_artifact_service_with_p2p_client(&tmp_dir, p2p_client.clone());
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        let build_id = uuid::Uuid::new_v4();
+        tokio::spawn(async move {
+            loop {
+                match build_event_receiver.recv().await {
+                    Some(BuildEvent::Start { sender, .. }) => {
+                        let _ = sender.send(Ok(build_id.to_string()));
+                    }
+                    _ => {
+                        panic!("BuildEvent must match BuildEvent::Start")
+                    }
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    }
+                    _ => panic!("Command must match Command::ListPeers"),
+                }
+            }
+        });
+
+        artifact_service
+            .transparency_log_service
+            .add_authorized_node(p2p_client.local_peer_id)
+            .await
+            .expect("Error adding authorized node");
+
+        let filter = make_node_routes(artifact_service, p2p_client);
+        let request = RequestPypiBuild {
+            package: "requests==2.28.2".to_owned(),
+        };
+        let response = warp::test::request()
+            .method("POST")
+            .path("/build/pypi")
+            .json(&request)
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+
+        let build_id_result: BuildSuccessResponse =
+            serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(build_id_result.build_id.unwrap(), build_id.to_string());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
     #[tokio::test]
     async fn node_routes_peers() {
         let tmp_dir = test_util::tests::setup();
@@ -342,6 +664,44 @@ mod tests {
         test_util::tests::teardown(tmp_dir);
     }
 
+    #[tokio::test]
+    async fn node_routes_peer_scores() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, _, _, _p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+        let p2p_client = artifact_service.p2p_client.clone();
+
+        let peer_id = PeerId::random();
+        p2p_client
+            .report_peer(&peer_id, PeerOffense::Timeout)
+            .await
+            .unwrap();
+
+        let filter = make_node_routes(artifact_service, p2p_client);
+        let response = warp::test::request()
+            .path("/peers/reputation")
+            .reply(&filter)
+            .await;
+
+        let peer_scores: Vec<PeerScore> = serde_json::from_slice(response.body()).unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            peer_scores,
+            vec![PeerScore {
+                peer_id: peer_id.to_string(),
+                score: 90,
+                hash_mismatches: 0,
+                timeouts: 1,
+                protocol_errors: 0,
+                banned: false,
+            }]
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
     #[tokio::test]
     async fn node_routes_status() {
         let tmp_dir = test_util::tests::setup();
@@ -349,6 +709,12 @@ mod tests {
         let (p2p_client, mut p2p_command_receiver) = test_util::tests::create_p2p_client();
         let (artifact_service, ..) =
             test_util::tests::create_artifact_service_with_p2p_client(&tmp_dir, p2p_client.clone());
+        let artifact_service = artifact_service.with_disk_quota(1024);
+        artifact_service
+            .artifact_storage
+            .push_artifact("artifact".as_bytes(), "artifact_id")
+            .await
+            .unwrap();
 
         let local_peer_id = p2p_client.local_peer_id;
         tokio::spawn(async move {
@@ -364,6 +730,7 @@ mod tests {
                             peers_count: 0,
                             peer_addrs: Vec::new(),
                             peer_id: local_peer_id.to_string(),
+                            ..Default::default()
                         };
 
                         let _ = sender.send(status);
@@ -380,6 +747,9 @@ mod tests {
             peers_count: 0,
             peer_id: p2p_client.local_peer_id.to_string(),
             peer_addrs: Vec::new(),
+            disk_usage: 8,
+            disk_quota: Some(1024),
+            compression_savings: 0,
         };
 
         let expected_body = bytes::Bytes::from(serde_json::to_string(&expected_status).unwrap());
@@ -390,6 +760,251 @@ mod tests {
         test_util::tests::teardown(tmp_dir);
     }
 
+    #[tokio::test]
+    async fn node_routes_scrub() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+        artifact_service
+            .artifact_storage
+            .push_artifact("artifact".as_bytes(), "artifact_id")
+            .await
+            .unwrap();
+
+        let filter = make_node_routes(
+            artifact_service.clone(),
+            artifact_service.p2p_client.clone(),
+        );
+        let response = warp::test::request().path("/scrub").reply(&filter).await;
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "null");
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/scrub")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let scrub_report: ScrubReport = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(scrub_report.scanned, 1);
+        assert_eq!(scrub_report.healthy, 1);
+        assert!(scrub_report.corrupt.is_empty());
+
+        let response = warp::test::request().path("/scrub").reply(&filter).await;
+
+        assert_eq!(
+            serde_json::from_slice::<ScrubReport>(response.body()).unwrap(),
+            scrub_report
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn node_routes_pins() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let filter = make_node_routes(
+            artifact_service.clone(),
+            artifact_service.p2p_client.clone(),
+        );
+        let response = warp::test::request().path("/pins").reply(&filter).await;
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "[]");
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/pins")
+            .json(&RequestPin {
+                package_type: PackageType::Docker,
+                package_specific_id: "alpine:3.15".to_owned(),
+            })
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let pin_status: PinStatus = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(pin_status.package_type, PackageType::Docker);
+        assert_eq!(pin_status.package_specific_id, "library/alpine:3.15");
+        assert_eq!(pin_status.state, PinState::Pending);
+
+        let response = warp::test::request().path("/pins").reply(&filter).await;
+
+        assert_eq!(response.status(), 200);
+        let pins: Vec<PinStatus> = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(pins.len(), 1);
+        assert_eq!(pins[0].package_specific_id, "library/alpine:3.15");
+
+        let unpin_request = RequestPin {
+            package_type: PackageType::Docker,
+            package_specific_id: "library/alpine:3.15".to_owned(),
+        };
+        let response = warp::test::request()
+            .method("DELETE")
+            .path("/pins")
+            .json(&unpin_request)
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        assert!(artifact_service.pins().is_empty());
+
+        let response = warp::test::request()
+            .method("DELETE")
+            .path("/pins")
+            .json(&unpin_request)
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 500);
+        assert!(str::from_utf8(response.body())
+            .unwrap()
+            .contains("Package library/alpine:3.15 of type Docker is not pinned"));
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn node_routes_gc() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, _, mut build_event_receiver, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+        artifact_service
+            .artifact_storage
+            .push_artifact("artifact".as_bytes(), "artifact_id")
+            .await
+            .unwrap();
+
+        tokio::spawn(async move {
+            while let Some(build_event) = build_event_receiver.recv().await {
+                match build_event {
+                    BuildEvent::CollectGarbage { sender, .. } => {
+                        let _ = sender.send(Ok(Vec::new()));
+                    }
+                    _ => panic!("Build event must match BuildEvent::CollectGarbage"),
+                }
+            }
+        });
+        tokio::spawn(async move {
+            while let Some(command) = p2p_command_receiver.recv().await {
+                match command {
+                    Command::StopProviding { .. } => {}
+                    _ => panic!("Command must match Command::StopProviding"),
+                }
+            }
+        });
+
+        let filter = make_node_routes(
+            artifact_service.clone(),
+            artifact_service.p2p_client.clone(),
+        );
+        let response = warp::test::request().path("/gc").reply(&filter).await;
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "null");
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/gc?dry_run=true")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let gc_report: GarbageCollectionReport = serde_json::from_slice(response.body()).unwrap();
+        assert!(gc_report.dry_run);
+        assert_eq!(gc_report.orphaned_artifacts, vec!["artifact_id"]);
+        assert!(artifact_service
+            .artifact_storage
+            .pull_artifact("artifact_id")
+            .await
+            .is_ok());
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/gc")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let gc_report: GarbageCollectionReport = serde_json::from_slice(response.body()).unwrap();
+        assert!(!gc_report.dry_run);
+        assert_eq!(gc_report.orphaned_artifacts, vec!["artifact_id"]);
+        assert!(artifact_service
+            .artifact_storage
+            .pull_artifact("artifact_id")
+            .await
+            .is_err());
+
+        let response = warp::test::request().path("/gc").reply(&filter).await;
+
+        assert_eq!(
+            serde_json::from_slice::<GarbageCollectionReport>(response.body()).unwrap(),
+            gc_report
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn node_routes_bundle() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, mut blockchain_event_receiver, _, _) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            while let Some(blockchain_event) = blockchain_event_receiver.recv().await {
+                match blockchain_event {
+                    BlockchainEvent::PullAllBlocksLocal { sender } => {
+                        let _ = sender.send(Ok(Vec::new()));
+                    }
+                    _ => panic!("Blockchain event must match BlockchainEvent::PullAllBlocksLocal"),
+                }
+            }
+        });
+
+        let filter = make_node_routes(
+            artifact_service.clone(),
+            artifact_service.p2p_client.clone(),
+        );
+        let response = warp::test::request()
+            .method("POST")
+            .path("/bundle/export")
+            .json(&RequestBundleExport {
+                packages: vec![BundlePackage {
+                    package_type: PackageType::Docker,
+                    package_specific_id: "alpine:3.15".to_owned(),
+                }],
+            })
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 500);
+        assert!(str::from_utf8(response.body())
+            .unwrap()
+            .contains("Package library/alpine:3.15 of type Docker not found"));
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/bundle/import")
+            .body("not a bundle")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 500);
+        assert!(str::from_utf8(response.body())
+            .unwrap()
+            .contains("The file is not a pyrsia bundle"));
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
     // Inspect Transparency Log Tests
 
     #[tokio::test]
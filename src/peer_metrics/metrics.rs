@@ -15,77 +15,170 @@
 */
 
 /// Peer Quality Metrics
-use std::thread;
-use std::time;
-use sysinfo::{NetworkExt, ProcessExt, System, SystemExt};
+use crate::artifact_service::storage::ARTIFACTS_DIR;
+use crate::network::idle_metric_protocol::PeerMetrics as PeerMetricsResponse;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use sysinfo::{CpuRefreshKind, DiskExt, NetworkExt, NetworksExt, RefreshKind, System, SystemExt};
 
 // peer metric constants
-const CPU_STRESS_WEIGHT: f64 = 2_f64;
-const NETWORK_STRESS_WEIGHT: f64 = 0.001_f64;
-const DISK_STRESS_WEIGHT: f64 = 0.001_f64;
+const CPU_STRESS_WEIGHT: f64 = 0.4_f64;
+const NETWORK_STRESS_WEIGHT: f64 = 0.3_f64;
+const TRANSFER_STRESS_WEIGHT: f64 = 0.2_f64;
+const DISK_STRESS_WEIGHT: f64 = 0.1_f64;
+// The network throughput in bytes per second that is considered a fully
+// loaded network, 1 Gbit/s. The capacity of the network card is not known.
+const NETWORK_CAPACITY: f64 = 125_000_000_f64;
+// The number of concurrent artifact transfers that is considered a fully
+// loaded node.
+const TRANSFER_CAPACITY: f64 = 8_f64;
+// Network throughput is only sampled again after this interval, so frequent
+// metric requests don't measure the throughput over a tiny window.
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+static IN_FLIGHT_TRANSFERS: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps track of a transfer of artifact data between this node and
+/// another peer for as long as it's alive. See [`start_transfer`].
+pub struct TransferGuard(());
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT_TRANSFERS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Register a transfer of artifact data between this node and another
+/// peer. The transfer is counted as in flight until the returned guard
+/// is dropped.
+pub fn start_transfer() -> TransferGuard {
+    IN_FLIGHT_TRANSFERS.fetch_add(1, Ordering::SeqCst);
+    TransferGuard(())
+}
+
+/// The number of transfers of artifact data between this node and other
+/// peers that are currently in flight.
+pub fn in_flight_transfers() -> u64 {
+    IN_FLIGHT_TRANSFERS.load(Ordering::SeqCst)
+}
 
-#[derive(Default)]
 pub struct PeerMetrics {
     system: System,
+    storage_path: PathBuf,
+    last_sample: Instant,
+    last_network_bytes: u64,
+    network_throughput: f64,
+}
+
+impl Default for PeerMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PeerMetrics {
     pub fn new() -> Self {
+        let storage_path = Path::new(ARTIFACTS_DIR.as_str())
+            .canonicalize()
+            .unwrap_or_else(|_| PathBuf::from("/"));
         let mut peer_metrics = Self {
-            system: System::new_all(),
+            system: System::new_with_specifics(
+                RefreshKind::new()
+                    .with_cpu(CpuRefreshKind::new())
+                    .with_disks_list()
+                    .with_networks_list(),
+            ),
+            storage_path,
+            last_sample: Instant::now(),
+            last_network_bytes: 0,
+            network_throughput: 0_f64,
         };
-        peer_metrics.initialize();
+        peer_metrics.last_network_bytes = get_network_bytes(&mut peer_metrics.system);
         peer_metrics
     }
 
-    fn initialize(&mut self) {
-        self.system.refresh_all();
-        thread::sleep(time::Duration::from_millis(500));
-        self.system.refresh_all();
+    /// Get the local stress metrics to advertise to peers. All components
+    /// are measured at the time of the call, network throughput is the
+    /// average since the previous call.
+    pub fn get_peer_metrics(&mut self) -> PeerMetricsResponse {
+        let cpu_load = get_cpu_load(&mut self.system);
+        let network_throughput = self.get_network_throughput();
+        let in_flight_transfers = in_flight_transfers();
+        let disk_headroom = get_disk_headroom(&mut self.system, &self.storage_path);
+
+        PeerMetricsResponse {
+            idle_metric: get_quality_metric(
+                cpu_load,
+                network_throughput,
+                in_flight_transfers,
+                disk_headroom,
+            ),
+            cpu_load,
+            network_throughput,
+            in_flight_transfers,
+            disk_headroom,
+        }
     }
 
-    /// Get the local stress metric to advertise to peers
-    pub fn get_quality_metric(&mut self) -> f64 {
-        let mut qm = get_cpu_stress(&mut self.system) * CPU_STRESS_WEIGHT;
-        qm += get_network_stress(&mut self.system) * NETWORK_STRESS_WEIGHT;
-        qm + get_disk_stress(&mut self.system) * DISK_STRESS_WEIGHT
+    fn get_network_throughput(&mut self) -> f64 {
+        let elapsed = self.last_sample.elapsed();
+        if elapsed >= MIN_SAMPLE_INTERVAL {
+            let network_bytes = get_network_bytes(&mut self.system);
+            self.network_throughput = network_bytes.saturating_sub(self.last_network_bytes) as f64
+                / elapsed.as_secs_f64();
+            self.last_network_bytes = network_bytes;
+            self.last_sample = Instant::now();
+        }
+        self.network_throughput
     }
 }
 
-// This function gets the current CPU load on the system.
-fn get_cpu_stress(system: &mut System) -> f64 {
-    system.refresh_all();
-
-    let load_avg = system.load_average();
-    load_avg.one //using the average over the last 1 minute
+// Combine the components in a single metric between 0 (idle) and 1 (fully loaded).
+fn get_quality_metric(
+    cpu_load: f64,
+    network_throughput: f64,
+    in_flight_transfers: u64,
+    disk_headroom: f64,
+) -> f64 {
+    let mut qm = cpu_load.min(1_f64) * CPU_STRESS_WEIGHT;
+    qm += (network_throughput / NETWORK_CAPACITY).min(1_f64) * NETWORK_STRESS_WEIGHT;
+    qm += (in_flight_transfers as f64 / TRANSFER_CAPACITY).min(1_f64) * TRANSFER_STRESS_WEIGHT;
+    qm + (1_f64 - disk_headroom.clamp(0_f64, 1_f64)) * DISK_STRESS_WEIGHT
 }
 
-// This function gets the current network load on the system
-fn get_network_stress(system: &mut System) -> f64 {
-    system.refresh_all();
+// This function gets the current CPU load per core, using the average over
+// the last minute. A value of 1 means that all cores are fully loaded.
+fn get_cpu_load(system: &mut System) -> f64 {
+    let cores = system.cpus().len().max(1);
+    system.load_average().one / cores as f64
+}
 
-    let networks = system.networks();
+// This function gets the total number of bytes received and transmitted over
+// all network interfaces.
+fn get_network_bytes(system: &mut System) -> u64 {
+    system.refresh_networks();
 
-    let mut packets_in = 0;
-    let mut packets_out = 0;
-    for (_interface_name, network) in networks {
-        packets_in += network.received();
-        packets_out += network.transmitted();
-    }
-    (packets_in as f64) + (packets_out as f64)
+    system
+        .networks()
+        .iter()
+        .map(|(_interface_name, network)| network.total_received() + network.total_transmitted())
+        .sum()
     //TODO: add network card capabilities to the metric. cards with > network capacity should get a lower stress number.
 }
 
-fn get_disk_stress(system: &mut System) -> f64 {
-    system.refresh_all();
-
-    // Sum up the disk usage measured as total read and writes per process:
-    let mut total_usage = 0_u64;
-    for process in system.processes().values() {
-        let usage = process.disk_usage();
-        total_usage = total_usage + usage.total_written_bytes + usage.total_read_bytes;
-    }
-    total_usage as f64
+// This function gets the fraction of free space on the disk that holds the
+// artifact storage. When that disk can't be found, no headroom issue is assumed.
+fn get_disk_headroom(system: &mut System, storage_path: &Path) -> f64 {
+    system.refresh_disks();
+
+    system
+        .disks()
+        .iter()
+        .filter(|disk| storage_path.starts_with(disk.mount_point()) && disk.total_space() > 0)
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space() as f64 / disk.total_space() as f64)
+        .unwrap_or(1_f64)
 }
 
 #[cfg(test)]
@@ -97,27 +190,37 @@ mod tests {
     fn cpu_load_test() {
         let mut peer_metrics = PeerMetrics::new();
 
-        get_cpu_stress(&mut peer_metrics.system);
+        assert!(get_cpu_load(&mut peer_metrics.system) >= 0_f64);
     }
 
     #[test]
-    fn network_load_test() {
+    fn disk_headroom_test() {
         let mut peer_metrics = PeerMetrics::new();
 
-        get_network_stress(&mut peer_metrics.system);
+        let disk_headroom = get_disk_headroom(&mut peer_metrics.system, &peer_metrics.storage_path);
+        assert!((0_f64..=1_f64).contains(&disk_headroom));
     }
 
     #[test]
-    fn disk_load_test() {
-        let mut peer_metrics = PeerMetrics::new();
+    fn in_flight_transfers_test() {
+        let transfer = start_transfer();
+        assert!(in_flight_transfers() >= 1);
+        drop(transfer);
+    }
 
-        get_disk_stress(&mut peer_metrics.system);
+    #[test]
+    fn quality_metric_is_normalized() {
+        assert_eq!(get_quality_metric(0_f64, 0_f64, 0, 1_f64), 0_f64);
+        assert!((get_quality_metric(4_f64, 1e12_f64, 100, 0_f64) - 1_f64).abs() < 1e-9);
+        assert!(get_quality_metric(0.5_f64, 0_f64, 0, 1_f64) < 0.5_f64);
     }
 
     #[test]
-    fn quality_metric_test() {
+    fn peer_metrics_test() {
         let mut peer_metrics = PeerMetrics::new();
 
-        peer_metrics.get_quality_metric();
+        let metrics = peer_metrics.get_peer_metrics();
+        assert!((0_f64..=1_f64).contains(&metrics.idle_metric));
+        assert!(metrics.network_throughput >= 0_f64);
     }
 }
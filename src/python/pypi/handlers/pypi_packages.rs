@@ -0,0 +1,410 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::artifact_service::model::PackageType;
+use crate::artifact_service::service::ArtifactService;
+use crate::docker::error_util::{RegistryError, RegistryErrorCode};
+use crate::transparency_log::log::TransparencyLog;
+use anyhow::anyhow;
+use log::debug;
+use serde_json::json;
+use warp::hyper::Body;
+use warp::{http::StatusCode, Rejection, Reply};
+
+const MEDIA_TYPE_SIMPLE_HTML: &str = "application/vnd.pypi.simple.v1+html";
+const MEDIA_TYPE_SIMPLE_JSON: &str = "application/vnd.pypi.simple.v1+json";
+
+/// Returns the simple index (PEP 503 / PEP 691) of a python project. The index
+/// only lists the files that are already known in the transparency log: the
+/// index request doesn't name a version, so it can't start a build. A project
+/// version gets in through a build requested with `pyrsia build pypi --package
+/// <project>==<version>` (`POST /build/pypi` on the node API), or when one of
+/// its files is requested through [`handle_get_pypi_file`], e.g. by pinning it
+/// as `https://<node>/pypi/packages/<project>/<file name>`. Unknown projects
+/// are answered with a 404, so that pip can fall back to the other indexes it
+/// is configured with.
+pub async fn handle_get_simple_index(
+    project: String,
+    accept: Option<String>,
+    artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    let project = normalize_project_name(&project);
+    debug!("Requesting simple index for python project: {}", project);
+
+    let files = get_project_files(&artifact_service, &project).map_err(|err| {
+        debug!("Error retrieving project files: {:?}", err);
+        warp::reject::custom(RegistryError {
+            code: RegistryErrorCode::Unknown(err.to_string()),
+        })
+    })?;
+    if files.is_empty() {
+        return Err(warp::reject::custom(RegistryError {
+            code: RegistryErrorCode::NotFound(format!("python project {} not found", project)),
+        }));
+    }
+
+    let (content_type, body) = if accepts_json(accept.as_deref()) {
+        (MEDIA_TYPE_SIMPLE_JSON, render_json_index(&project, &files))
+    } else {
+        (MEDIA_TYPE_SIMPLE_HTML, render_html_index(&project, &files))
+    };
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", content_type)
+        .status(StatusCode::OK)
+        .body(body)
+        .unwrap())
+}
+
+pub async fn handle_get_pypi_file(
+    project: String,
+    file_name: String,
+    mut artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    debug!("Requesting python file: {}/{}", project, file_name);
+    let project = normalize_project_name(&project);
+    let version = parse_version_from_file_name(&file_name).map_err(|err| {
+        debug!("Error parsing python file name: {:?}", err);
+        warp::reject::custom(RegistryError {
+            code: RegistryErrorCode::NotFound(err.to_string()),
+        })
+    })?;
+
+    let package_specific_id = get_package_specific_id(&project, &version);
+    let package_specific_artifact_id = get_package_specific_artifact_id(&project, &file_name);
+
+    debug!(
+        "Requesting artifact with package specific id: {}, and package specific artifact id: {}. If not found a build will be requested",
+        package_specific_id, package_specific_artifact_id
+    );
+
+    let (artifact_size, artifact_stream) = artifact_service
+        .get_artifact_stream_or_build(
+            PackageType::PyPI,
+            &package_specific_id,
+            &package_specific_artifact_id,
+        )
+        .await
+        .map_err(|err| {
+            debug!("Error retrieving artifact: {:?}", err);
+            warp::reject::custom(RegistryError::from_artifact_error(
+                err,
+                format!(
+                    "python file {}/{} not found, a build was requested",
+                    project, file_name
+                ),
+            ))
+        })?;
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", "application/octet-stream")
+        .header("Content-Length", artifact_size)
+        .status(StatusCode::OK)
+        .body(Body::wrap_stream(artifact_stream))
+        .unwrap())
+}
+
+/// Finds all files (wheels and sdists) of the specified project that have been
+/// added to the transparency log, as (file name, sha256 hash) pairs.
+fn get_project_files(
+    artifact_service: &ArtifactService,
+    project: &str,
+) -> Result<Vec<(String, String)>, anyhow::Error> {
+    let transparency_logs = artifact_service
+        .transparency_log_service
+        .search_transparency_logs_by_prefix(
+            &PackageType::PyPI,
+            &get_package_specific_id(project, ""),
+        )?;
+
+    Ok(transparency_logs
+        .iter()
+        .filter_map(|transparency_log| get_file_entry(project, transparency_log))
+        .collect())
+}
+
+fn get_file_entry(project: &str, transparency_log: &TransparencyLog) -> Option<(String, String)> {
+    transparency_log
+        .package_specific_artifact_id
+        .strip_prefix(&format!("{}/", project))
+        .map(|file_name| (file_name.to_owned(), transparency_log.artifact_hash.clone()))
+}
+
+fn get_file_url(project: &str, file_name: &str, hash: &str) -> String {
+    format!("../../packages/{}/{}#sha256={}", project, file_name, hash)
+}
+
+fn render_html_index(project: &str, files: &[(String, String)]) -> Vec<u8> {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n  <head>\n    <meta name=\"pypi:repository-version\" content=\"1.0\">\n    <title>Links for {}</title>\n  </head>\n  <body>\n    <h1>Links for {}</h1>\n",
+        project, project
+    );
+    for (file_name, hash) in files {
+        html.push_str(&format!(
+            "    <a href=\"{}\">{}</a><br/>\n",
+            get_file_url(project, file_name, hash),
+            file_name
+        ));
+    }
+    html.push_str("  </body>\n</html>\n");
+    html.into_bytes()
+}
+
+fn render_json_index(project: &str, files: &[(String, String)]) -> Vec<u8> {
+    let files: Vec<_> = files
+        .iter()
+        .map(|(file_name, hash)| {
+            json!({
+                "filename": file_name,
+                "url": get_file_url(project, file_name, hash),
+                "hashes": { "sha256": hash },
+            })
+        })
+        .collect();
+
+    json!({
+        "meta": { "api-version": "1.0" },
+        "name": project,
+        "files": files,
+    })
+    .to_string()
+    .into_bytes()
+}
+
+fn accepts_json(accept: Option<&str>) -> bool {
+    accept
+        .map(|accept| accept.contains(MEDIA_TYPE_SIMPLE_JSON))
+        .unwrap_or(false)
+}
+
+/// Normalizes a python project name as described in PEP 503: lowercase, with
+/// all runs of `-`, `_` and `.` replaced by a single `-`.
+fn normalize_project_name(project: &str) -> String {
+    let mut normalized = String::with_capacity(project.len());
+    let mut previous_was_separator = false;
+    for c in project.chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !previous_was_separator {
+                normalized.push('-');
+            }
+            previous_was_separator = true;
+        } else {
+            normalized.extend(c.to_lowercase());
+            previous_was_separator = false;
+        }
+    }
+    normalized
+}
+
+fn parse_version_from_file_name(file_name: &str) -> Result<String, anyhow::Error> {
+    // wheels are named "{distribution}-{version}(-{build})?-{python}-{abi}-{platform}.whl"
+    // and sdists "{distribution}-{version}.tar.gz" (or .zip for older releases).
+    let version = if let Some(stem) = file_name.strip_suffix(".whl") {
+        stem.split('-').nth(1)
+    } else if let Some(stem) = file_name
+        .strip_suffix(".tar.gz")
+        .or_else(|| file_name.strip_suffix(".zip"))
+    {
+        stem.rsplit_once('-').map(|(_, version)| version)
+    } else {
+        None
+    };
+
+    version
+        .filter(|version| !version.is_empty())
+        .map(|version| version.to_owned())
+        .ok_or_else(|| anyhow!("Error, invalid python file name: {}", file_name))
+}
+
+fn get_package_specific_id(project: &str, version: &str) -> String {
+    format!("{}=={}", project, version)
+}
+
+fn get_package_specific_artifact_id(project: &str, file_name: &str) -> String {
+    format!("{}/{}", project, file_name)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::blockchain_service::event::BlockchainEvent;
+    use crate::transparency_log::log::AddArtifactRequest;
+    use crate::util::test_util;
+    use hyper::header::HeaderValue;
+
+    const VALID_ARTIFACT_HASH: &str =
+        "865c8d988be4669f3e48f73b98f9bc2507be0246ea35e0098cf6054d3644c14f";
+    const VALID_WHEEL: &str = "requests-2.28.2-py3-none-any.whl";
+
+    #[test]
+    fn normalize_project_name_test() {
+        assert_eq!(normalize_project_name("Django"), "django");
+        assert_eq!(normalize_project_name("zope.interface"), "zope-interface");
+        assert_eq!(normalize_project_name("Foo__Bar-.baz"), "foo-bar-baz");
+    }
+
+    #[test]
+    fn parse_version_from_wheel_test() {
+        assert_eq!(parse_version_from_file_name(VALID_WHEEL).unwrap(), "2.28.2");
+        assert_eq!(
+            parse_version_from_file_name("numpy-1.24.1-cp311-cp311-manylinux_2_17_x86_64.whl")
+                .unwrap(),
+            "1.24.1"
+        );
+    }
+
+    #[test]
+    fn parse_version_from_sdist_test() {
+        assert_eq!(
+            parse_version_from_file_name("requests-2.28.2.tar.gz").unwrap(),
+            "2.28.2"
+        );
+        assert_eq!(
+            parse_version_from_file_name("zope.interface-5.5.2.zip").unwrap(),
+            "5.5.2"
+        );
+    }
+
+    #[test]
+    fn parse_version_from_invalid_file_name_test() {
+        assert!(parse_version_from_file_name("requests.whl").is_err());
+        assert!(parse_version_from_file_name("requests-2.28.2.exe").is_err());
+    }
+
+    #[test]
+    fn accepts_json_test() {
+        assert!(accepts_json(Some(
+            "application/vnd.pypi.simple.v1+json, application/vnd.pypi.simple.v1+html;q=0.2"
+        )));
+        assert!(!accepts_json(Some("text/html")));
+        assert!(!accepts_json(None));
+    }
+
+    #[tokio::test]
+    async fn handle_get_simple_index_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, mut blockchain_event_receiver, ..) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        artifact_service
+            .transparency_log_service
+            .add_artifact(AddArtifactRequest {
+                package_type: PackageType::PyPI,
+                package_specific_id: "requests==2.28.2".to_owned(),
+                num_artifacts: 1,
+                package_specific_artifact_id: format!("requests/{}", VALID_WHEEL),
+                artifact_hash: VALID_ARTIFACT_HASH.to_owned(),
+            })
+            .await
+            .unwrap();
+
+        let result = handle_get_simple_index(
+            "Requests".to_owned(),
+            Some(MEDIA_TYPE_SIMPLE_JSON.to_owned()),
+            artifact_service.clone(),
+        )
+        .await;
+
+        assert!(result.is_ok());
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type"),
+            Some(&HeaderValue::from_static(MEDIA_TYPE_SIMPLE_JSON))
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let index: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(index["name"], "requests");
+        assert_eq!(index["files"][0]["filename"], VALID_WHEEL);
+        assert_eq!(index["files"][0]["hashes"]["sha256"], VALID_ARTIFACT_HASH);
+
+        let result = handle_get_simple_index("requests".to_owned(), None, artifact_service).await;
+
+        let response = result.unwrap().into_response();
+        assert_eq!(
+            response.headers().get("Content-Type"),
+            Some(&HeaderValue::from_static(MEDIA_TYPE_SIMPLE_HTML))
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains(&format!(
+            "<a href=\"../../packages/requests/{}#sha256={}\">{}</a>",
+            VALID_WHEEL, VALID_ARTIFACT_HASH, VALID_WHEEL
+        )));
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn handle_get_simple_index_unknown_project_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let result = handle_get_simple_index("unknown".to_owned(), None, artifact_service).await;
+
+        assert!(result.is_err());
+        let rejection = result.err().unwrap();
+        let registry_error = rejection.find::<RegistryError>().unwrap();
+        assert_eq!(
+            *registry_error,
+            RegistryError {
+                code: RegistryErrorCode::NotFound("python project unknown not found".to_owned()),
+            }
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn handle_get_pypi_file_with_invalid_name_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let result = handle_get_pypi_file(
+            "requests".to_owned(),
+            "requests.whl".to_owned(),
+            artifact_service,
+        )
+        .await;
+
+        let rejection = result.err().unwrap();
+        let registry_error = rejection.find::<RegistryError>().unwrap();
+        assert!(matches!(
+            registry_error.code,
+            RegistryErrorCode::NotFound(_)
+        ));
+
+        test_util::tests::teardown(tmp_dir);
+    }
+}
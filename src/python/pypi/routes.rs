@@ -0,0 +1,73 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::handlers::pypi_packages::{handle_get_pypi_file, handle_get_simple_index};
+use crate::artifact_service::service::ArtifactService;
+use warp::Filter;
+
+pub fn make_pypi_routes(
+    artifact_service: ArtifactService,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let artifact_service_filter = warp::any().map(move || artifact_service.clone());
+
+    let pypi_simple_index = warp::path!("pypi" / "simple" / String)
+        .and(warp::get())
+        .and(warp::header::optional::<String>("accept"))
+        .and(artifact_service_filter.clone())
+        .and_then(handle_get_simple_index);
+
+    let pypi_files = warp::path!("pypi" / "packages" / String / String)
+        .and(warp::get())
+        .and(artifact_service_filter)
+        .and_then(handle_get_pypi_file);
+
+    warp::any().and(pypi_simple_index.or(pypi_files))
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::docker::error_util::{RegistryError, RegistryErrorCode};
+    use crate::util::test_util;
+    use std::str;
+
+    #[tokio::test]
+    async fn pypi_routes() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let filter = make_pypi_routes(artifact_service);
+        let response = warp::test::request()
+            .path("/pypi/packages/requests/requests-2.28.2-py3-none-any.whl")
+            .reply(&filter)
+            .await;
+
+        let expected_error = RegistryError {
+            code: RegistryErrorCode::NotFound(
+                "python file requests/requests-2.28.2-py3-none-any.whl not found, a build was requested"
+                    .to_owned(),
+            ),
+        };
+        let expected_body = format!("Unhandled rejection: {:?}", expected_error);
+
+        assert_eq!(response.status(), 500);
+        assert_eq!(expected_body, str::from_utf8(response.body()).unwrap());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+}
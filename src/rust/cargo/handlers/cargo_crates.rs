@@ -0,0 +1,442 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::artifact_service::model::PackageType;
+use crate::artifact_service::service::ArtifactService;
+use crate::docker::error_util::{RegistryError, RegistryErrorCode};
+use crate::transparency_log::log::TransparencyLog;
+use anyhow::{anyhow, bail};
+use log::debug;
+use serde_json::{json, Value};
+use warp::hyper::Body;
+use warp::{http::StatusCode, Rejection, Reply};
+
+/// The name of the artifact, built next to the `.crate` file, that holds the
+/// index entry of a crate version (dependencies, features, ...).
+const INDEX_ENTRY_FILE_NAME: &str = "index.json";
+
+#[derive(Debug, Eq, PartialEq)]
+enum CargoRequest {
+    Config,
+    IndexFile { name: String },
+    Crate { name: String, version: String },
+}
+
+/// Serves a sparse cargo registry. Cargo resolves every crate from its index
+/// file first, which only lists the versions that are already known in the
+/// transparency log, and an unknown crate is answered with a 404. The index
+/// request doesn't name a version, so it can't start a build: a crate version
+/// gets in through a build requested with `pyrsia build cargo --package
+/// <name>@<version>` (`POST /build/cargo` on the node API), through the
+/// network when another node built it, or through an imported bundle.
+pub async fn handle_get_cargo(
+    full_path: String,
+    base_url: Option<String>,
+    mut artifact_service: ArtifactService,
+) -> Result<impl Reply, Rejection> {
+    debug!("Requesting cargo resource: {}", full_path);
+    let cargo_request = parse_cargo_request(&full_path).map_err(|err| {
+        debug!("Error parsing cargo request: {:?}", err);
+        warp::reject::custom(RegistryError {
+            code: RegistryErrorCode::Unknown(err.to_string()),
+        })
+    })?;
+
+    let (content_type, body) = match cargo_request {
+        CargoRequest::Config => ("application/json", get_config(base_url.as_deref())),
+        CargoRequest::IndexFile { name } => {
+            let index_file = get_index_file(&mut artifact_service, &name)
+                .await
+                .map_err(|err| {
+                    debug!("Error retrieving index file: {:?}", err);
+                    warp::reject::custom(RegistryError {
+                        code: RegistryErrorCode::Unknown(err.to_string()),
+                    })
+                })?
+                .ok_or_else(|| {
+                    warp::reject::custom(RegistryError {
+                        code: RegistryErrorCode::NotFound(format!("crate {} not found", name)),
+                    })
+                })?;
+            ("text/plain", index_file)
+        }
+        CargoRequest::Crate { name, version } => {
+            let package_specific_id = get_package_specific_id(&name, &version);
+            let package_specific_artifact_id = get_package_specific_artifact_id(
+                &name,
+                &version,
+                &get_crate_file_name(&name, &version),
+            );
+            debug!(
+                "Requesting artifact with package specific id: {}, and package specific artifact id: {}. If not found a build will be requested",
+                package_specific_id, package_specific_artifact_id
+            );
+
+            let (artifact_size, artifact_stream) = artifact_service
+                .get_artifact_stream_or_build(
+                    PackageType::Cargo,
+                    &package_specific_id,
+                    &package_specific_artifact_id,
+                )
+                .await
+                .map_err(|err| {
+                    debug!("Error retrieving artifact: {:?}", err);
+                    warp::reject::custom(RegistryError::from_artifact_error(
+                        err,
+                        format!(
+                            "crate {}@{} not found, a build was requested",
+                            name, version
+                        ),
+                    ))
+                })?;
+
+            return Ok(warp::http::response::Builder::new()
+                .header("Content-Type", "application/octet-stream")
+                .header("Content-Length", artifact_size)
+                .status(StatusCode::OK)
+                .body(Body::wrap_stream(artifact_stream))
+                .unwrap());
+        }
+    };
+
+    Ok(warp::http::response::Builder::new()
+        .header("Content-Type", content_type)
+        .status(StatusCode::OK)
+        .body(Body::from(body))
+        .unwrap())
+}
+
+fn get_config(base_url: Option<&str>) -> Vec<u8> {
+    let download_url = format!("{}/cargo/crates", base_url.unwrap_or_default());
+    json!({ "dl": download_url }).to_string().into_bytes()
+}
+
+/// Build the sparse index file of a crate, containing one line per version
+/// known in the transparency log. The checksum of each version is taken from
+/// the transparency log, so it matches the verified `.crate` file. Returns None
+/// when no version of the crate is known.
+async fn get_index_file(
+    artifact_service: &mut ArtifactService,
+    name: &str,
+) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let transparency_logs = artifact_service
+        .transparency_log_service
+        .search_transparency_logs_by_prefix(&PackageType::Cargo, &format!("{}@", name))?;
+
+    let mut index_file = Vec::new();
+    for entry_log in transparency_logs.iter() {
+        let version = &entry_log.package_specific_id[name.len() + 1..];
+        if entry_log.package_specific_artifact_id
+            != get_package_specific_artifact_id(name, version, INDEX_ENTRY_FILE_NAME)
+        {
+            continue;
+        }
+
+        let crate_log = match find_crate_log(&transparency_logs, name, version) {
+            Some(crate_log) => crate_log,
+            None => continue,
+        };
+
+        let mut index_entry: Value = match artifact_service
+            .get_artifact(PackageType::Cargo, &entry_log.package_specific_artifact_id)
+            .await
+            .and_then(|content| serde_json::from_slice(&content).map_err(|e| anyhow!(e)))
+        {
+            Ok(index_entry) => index_entry,
+            Err(err) => {
+                debug!("Skipping crate version {}: {:?}", version, err);
+                continue;
+            }
+        };
+
+        let index_entry_object = index_entry
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("Invalid index entry for crate {}@{}", name, version))?;
+        index_entry_object
+            .entry("name")
+            .or_insert_with(|| Value::String(name.to_owned()));
+        index_entry_object.insert("vers".to_owned(), Value::String(version.to_owned()));
+        index_entry_object.insert(
+            "cksum".to_owned(),
+            Value::String(crate_log.artifact_hash.clone()),
+        );
+
+        index_file.extend(index_entry.to_string().into_bytes());
+        index_file.push(b'\n');
+    }
+
+    if index_file.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(index_file))
+}
+
+fn find_crate_log<'a>(
+    transparency_logs: &'a [TransparencyLog],
+    name: &str,
+    version: &str,
+) -> Option<&'a TransparencyLog> {
+    let package_specific_artifact_id =
+        get_package_specific_artifact_id(name, version, &get_crate_file_name(name, version));
+    transparency_logs.iter().find(|transparency_log| {
+        transparency_log.package_specific_artifact_id == package_specific_artifact_id
+    })
+}
+
+fn get_package_specific_id(name: &str, version: &str) -> String {
+    format!("{}@{}", name, version)
+}
+
+fn get_package_specific_artifact_id(name: &str, version: &str, file_name: &str) -> String {
+    format!("{}/{}/{}", name, version, file_name)
+}
+
+fn get_crate_file_name(name: &str, version: &str) -> String {
+    format!("{}-{}.crate", name, version)
+}
+
+/// Returns the path of the index file of a crate, relative to the index root,
+/// as defined by the cargo registry index format.
+fn get_index_path(name: &str) -> String {
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
+
+fn parse_cargo_request(full_path: &str) -> Result<CargoRequest, anyhow::Error> {
+    // cargo requests look like:
+    //   "GET /cargo/index/config.json" for the registry configuration,
+    //   "GET /cargo/index/se/rd/serde" for the index file of a crate and
+    //   "GET /cargo/crates/serde/1.0.152/download" for the crate itself.
+
+    // split, and remove first two strings: "" and "cargo":
+    let pieces: Vec<&str> = full_path.split('/').skip(2).collect();
+    match pieces.as_slice() {
+        ["index", "config.json"] => Ok(CargoRequest::Config),
+        ["index", index_path @ .., name] if !index_path.is_empty() => {
+            let name = name.to_lowercase();
+            if !name.is_ascii()
+                || format!("{}/{}", index_path.join("/"), name) != get_index_path(&name)
+            {
+                bail!("Error, invalid index path: {}", full_path);
+            }
+            Ok(CargoRequest::IndexFile { name })
+        }
+        ["crates", name, version, "download"] if !name.is_empty() && !version.is_empty() => {
+            Ok(CargoRequest::Crate {
+                name: name.to_string(),
+                version: version.to_string(),
+            })
+        }
+        _ => bail!("Error, invalid full path: {}", full_path),
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::artifact_service::storage::ArtifactStorage;
+    use crate::blockchain_service::event::BlockchainEvent;
+    use crate::network::client::command::Command;
+    use crate::transparency_log::log::AddArtifactRequest;
+    use crate::util::test_util;
+    use anyhow::Context;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    const INDEX_ENTRY: &str = r#"{"deps":[],"features":{},"yanked":false}"#;
+    const INDEX_ENTRY_HASH: &str =
+        "2f6a4b63829b96dc8f3c887f3ca6ef94017775eb917636b43c940b2713be0e5b";
+
+    #[test]
+    fn get_index_path_test() {
+        assert_eq!(get_index_path("a"), "1/a");
+        assert_eq!(get_index_path("cc"), "2/cc");
+        assert_eq!(get_index_path("syn"), "3/s/syn");
+        assert_eq!(get_index_path("serde"), "se/rd/serde");
+    }
+
+    #[test]
+    fn parse_config_request_test() {
+        assert_eq!(
+            parse_cargo_request("/cargo/index/config.json").unwrap(),
+            CargoRequest::Config
+        );
+    }
+
+    #[test]
+    fn parse_index_file_request_test() {
+        assert_eq!(
+            parse_cargo_request("/cargo/index/se/rd/serde").unwrap(),
+            CargoRequest::IndexFile {
+                name: "serde".to_owned()
+            }
+        );
+        assert_eq!(
+            parse_cargo_request("/cargo/index/3/s/syn").unwrap(),
+            CargoRequest::IndexFile {
+                name: "syn".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_crate_request_test() {
+        assert_eq!(
+            parse_cargo_request("/cargo/crates/serde/1.0.152/download").unwrap(),
+            CargoRequest::Crate {
+                name: "serde".to_owned(),
+                version: "1.0.152".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_request_test() {
+        assert!(parse_cargo_request("/cargo/index/xx/yy/serde").is_err());
+        assert!(parse_cargo_request("/cargo/index/serde").is_err());
+        assert!(parse_cargo_request("/cargo/crates/serde/1.0.152").is_err());
+    }
+
+    #[tokio::test]
+    async fn handle_get_index_file_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, mut blockchain_event_receiver, _, mut p2p_command_receiver) =
+            test_util::tests::create_artifact_service(&tmp_dir);
+
+        tokio::spawn(async move {
+            loop {
+                match blockchain_event_receiver.recv().await {
+                    Some(BlockchainEvent::AddBlock { sender, .. }) => {
+                        let _ = sender.send(Ok(()));
+                    }
+                    _ => panic!("BlockchainEvent must match BlockchainEvent::AddBlock"),
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match p2p_command_receiver.recv().await {
+                    Some(Command::ListPeers { sender, .. }) => {
+                        let _ = sender.send(HashSet::new());
+                    }
+                    _ => panic!("Command must match Command::ListPeers"),
+                }
+            }
+        });
+
+        let index_entry_log = artifact_service
+            .transparency_log_service
+            .add_artifact(AddArtifactRequest {
+                package_type: PackageType::Cargo,
+                package_specific_id: "serde@1.0.152".to_owned(),
+                num_artifacts: 2,
+                package_specific_artifact_id: "serde/1.0.152/index.json".to_owned(),
+                artifact_hash: INDEX_ENTRY_HASH.to_owned(),
+            })
+            .await
+            .unwrap()
+            .0;
+        create_artifact(
+            &artifact_service.artifact_storage,
+            &index_entry_log.artifact_id,
+        )
+        .await
+        .unwrap();
+
+        artifact_service
+            .transparency_log_service
+            .add_artifact(AddArtifactRequest {
+                package_type: PackageType::Cargo,
+                package_specific_id: "serde@1.0.152".to_owned(),
+                num_artifacts: 2,
+                package_specific_artifact_id: "serde/1.0.152/serde-1.0.152.crate".to_owned(),
+                artifact_hash: "bb".repeat(32),
+            })
+            .await
+            .unwrap();
+
+        let result = handle_get_cargo(
+            "/cargo/index/se/rd/serde".to_owned(),
+            None,
+            artifact_service,
+        )
+        .await;
+
+        assert!(result.is_ok());
+
+        let response = result.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let lines: Vec<&[u8]> = body
+            .split(|b| *b == b'\n')
+            .filter(|l| !l.is_empty())
+            .collect();
+        assert_eq!(lines.len(), 1);
+
+        let index_entry: Value = serde_json::from_slice(lines[0]).unwrap();
+        assert_eq!(index_entry["name"], "serde");
+        assert_eq!(index_entry["vers"], "1.0.152");
+        assert_eq!(index_entry["cksum"], "bb".repeat(32));
+        assert_eq!(index_entry["yanked"], false);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn handle_get_index_file_of_unknown_crate_test() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let result = handle_get_cargo(
+            "/cargo/index/se/rd/serde".to_owned(),
+            None,
+            artifact_service,
+        )
+        .await;
+
+        assert!(result.is_err());
+        let rejection = result.err().unwrap();
+        let registry_error = rejection.find::<RegistryError>().unwrap();
+        assert_eq!(
+            *registry_error,
+            RegistryError {
+                code: RegistryErrorCode::NotFound("crate serde not found".to_owned()),
+            }
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    async fn create_artifact(
+        artifact_storage: &ArtifactStorage,
+        artifact_id: &str,
+    ) -> Result<String, anyhow::Error> {
+        artifact_storage
+            .push_artifact(Cursor::new(INDEX_ENTRY.as_bytes()), artifact_id)
+            .await
+            .context("Error while pushing artifact")
+    }
+}
@@ -0,0 +1,97 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use super::handlers::cargo_crates::handle_get_cargo;
+use crate::artifact_service::service::ArtifactService;
+use crate::util::url_util;
+use log::debug;
+use warp::Filter;
+
+pub fn make_cargo_routes(
+    artifact_service: ArtifactService,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let artifact_service_filter = warp::any().map(move || artifact_service.clone());
+
+    let cargo_root = warp::path("cargo")
+        .and(warp::get())
+        .and(warp::path::full())
+        .map(|path: warp::path::FullPath| {
+            let full_path: String = path.as_str().to_string();
+            debug!("route full path: {}", full_path);
+            full_path
+        })
+        .and(url_util::base_url())
+        .and(artifact_service_filter)
+        .and_then(handle_get_cargo);
+
+    warp::any().and(cargo_root)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::docker::error_util::{RegistryError, RegistryErrorCode};
+    use crate::util::test_util;
+    use std::str;
+
+    #[tokio::test]
+    async fn cargo_routes() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let filter = make_cargo_routes(artifact_service);
+        let response = warp::test::request()
+            .path("/cargo/crates/serde/1.0.152/download")
+            .reply(&filter)
+            .await;
+
+        let expected_error = RegistryError {
+            code: RegistryErrorCode::NotFound(
+                "crate serde@1.0.152 not found, a build was requested".to_owned(),
+            ),
+        };
+        let expected_body = format!("Unhandled rejection: {:?}", expected_error);
+
+        assert_eq!(response.status(), 500);
+        assert_eq!(expected_body, str::from_utf8(response.body()).unwrap());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn cargo_config_route() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (artifact_service, ..) = test_util::tests::create_artifact_service(&tmp_dir);
+
+        let filter = make_cargo_routes(artifact_service);
+        let response = warp::test::request()
+            .path("/cargo/index/config.json")
+            .header("host", "localhost:7888")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            str::from_utf8(response.body()).unwrap(),
+            r#"{"dl":"http://localhost:7888/cargo/crates"}"#
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+}
@@ -15,3 +15,4 @@
 */
 
 pub mod log;
+pub mod query;
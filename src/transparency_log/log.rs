@@ -16,22 +16,26 @@
 
 use crate::artifact_service::model::PackageType;
 use crate::blockchain_service::event::BlockchainEventClient;
+use crate::util::migration_util::MigrationError;
 use libp2p::core::ParseError;
 use libp2p::PeerId;
 use log::{debug, error};
 use num_traits::ToPrimitive;
 use pyrsia_blockchain_network::error::BlockchainError;
-use rusqlite::types::{ToSqlOutput, Value};
-use rusqlite::{params, Connection, ToSql};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, ToSql};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use uuid::Uuid;
 
+use super::query::{self, LogQuery};
+
 #[derive(Debug, Error)]
 pub enum TransparencyLogError {
     #[error("TransparencyLog with ID {id} not found")]
@@ -78,6 +82,8 @@ pub enum TransparencyLogError {
     },
     #[error("Failure while accessing underlying storage: {0}")]
     DatabaseFailure(#[from] rusqlite::Error),
+    #[error("Failure while migrating the transparency log database: {0}")]
+    MigrationFailure(#[from] MigrationError),
     #[error("Failure while accessing underlying storage: {0}")]
     StorageFailure(#[from] io::Error),
     #[error("Failure while adding block to the blockchain: {0}")]
@@ -109,6 +115,12 @@ impl ToSql for Operation {
     }
 }
 
+impl FromSql for Operation {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Operation::from_str(value.as_str()?).map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct TransparencyLog {
     pub id: String,
@@ -147,10 +159,10 @@ pub struct AuthorizedNode {
 /// transactions and to reach consensus on the publication of new transactions.
 ///
 /// It uses a local database to store and index transparency log information to simplify
-/// access.
+/// access. The database connection is opened once and shared by all clones of the service.
 #[derive(Clone)]
 pub struct TransparencyLogService {
-    storage_path: PathBuf,
+    connection: Arc<Mutex<Connection>>,
     blockchain_event_client: BlockchainEventClient,
 }
 
@@ -184,12 +196,18 @@ impl TransparencyLogService {
     ) -> Result<Self, TransparencyLogError> {
         let mut absolute_path = repository_path.as_ref().to_path_buf().canonicalize()?;
         absolute_path.push("transparency_log");
+        let connection = Self::open_db(&absolute_path)?;
         Ok(TransparencyLogService {
-            storage_path: absolute_path,
+            connection: Arc::new(Mutex::new(connection)),
             blockchain_event_client,
         })
     }
 
+    /// The client of the blockchain that the transparency logs are published on.
+    pub fn blockchain_event_client(&self) -> &BlockchainEventClient {
+        &self.blockchain_event_client
+    }
+
     /// Add a new authorized node to the p2p network.
     pub async fn add_authorized_node(&self, peer_id: PeerId) -> Result<(), TransparencyLogError> {
         self.verify_node_does_not_exist(&peer_id.to_string())?;
@@ -310,6 +328,18 @@ impl TransparencyLogService {
         }
     }
 
+    /// Search the transparency log database for all AddArtifact transparency logs of which
+    /// the package specific id starts with the specified prefix. This is used by package
+    /// types that need to list all known versions of a package, e.g. to render a npm
+    /// packument.
+    pub fn search_transparency_logs_by_prefix(
+        &self,
+        package_type: &PackageType,
+        package_specific_id_prefix: &str,
+    ) -> Result<Vec<TransparencyLog>, TransparencyLogError> {
+        self.read_transparency_logs_by_prefix(package_type, package_specific_id_prefix)
+    }
+
     /// Verifies that a specified package can be added to the transparency log database.
     /// For that, the database should not contain the artifact yet, or if it does,
     /// its latest operation is not RemoveArtifact. If that is not the case,
@@ -347,66 +377,45 @@ impl TransparencyLogService {
     /// Verifies that the database does not contain the node yet or node was removed.
     /// If that is not the case, an NodeAlreadyExists error is returned.
     pub fn verify_node_does_not_exist(&self, peer_id: &str) -> Result<(), TransparencyLogError> {
-        let query = format!(
-            "SELECT
-              operation
-            FROM TRANSPARENCYLOG
-            WHERE (operation = '{}' or operation = '{}') and node_id = '{}'
-            ORDER BY timestamp DESC limit 1",
-            Operation::AddNode,
-            Operation::RemoveNode,
-            peer_id
-        );
+        let operation = self.with_db(|conn| query::latest_node_operation(conn, peer_id))?;
 
-        let res = self
-            .open_db()?
-            .query_row(query.as_str(), [], |row| row.get::<usize, String>(0));
-
-        if let Ok(val) = res {
-            if val.eq("AddNode") {
-                return Err(TransparencyLogError::NodeAlreadyExists {
-                    node_id: peer_id.to_owned(),
-                });
-            }
+        if operation == Some(Operation::AddNode) {
+            return Err(TransparencyLogError::NodeAlreadyExists {
+                node_id: peer_id.to_owned(),
+            });
         }
         Ok(())
     }
 
-    fn open_db(&self) -> Result<Connection, TransparencyLogError> {
-        let mut db_path = self.storage_path.to_owned();
-        fs::create_dir_all(db_path.clone())?;
-        db_path.push("transparency_log.db");
-        let conn = Connection::open(db_path)?;
-        match conn.execute(
-            "CREATE TABLE IF NOT EXISTS TRANSPARENCYLOG (
-                id TEXT PRIMARY KEY,
-                package_type TEXT,
-                package_specific_id TEXT,
-                num_artifacts INTEGER,
-                package_specific_artifact_id TEXT,
-                artifact_hash TEXT,
-                source_hash TEXT,
-                artifact_id TEXT,
-                source_id TEXT,
-                timestamp INTEGER,
-                operation TEXT NOT NULL,
-                node_id TEXT,
-                node_public_key TEXT
-            )",
-            [],
-        ) {
-            Ok(_) => Ok(conn),
+    fn open_db(storage_path: &Path) -> Result<Connection, TransparencyLogError> {
+        fs::create_dir_all(storage_path)?;
+        let mut conn = Connection::open(storage_path.join("transparency_log.db"))?;
+        match query::migrate(&mut conn) {
+            Ok(version) => {
+                debug!("Transparency log database at schema version {}", version);
+                Ok(conn)
+            }
             Err(err) => {
-                debug!("Error creating transparency log database table: {:?}", err);
+                debug!("Error migrating transparency log database: {:?}", err);
                 Err(err.into())
             }
         }
     }
 
-    pub fn find_transparency_log(&self, id: &str) -> Result<TransparencyLog, TransparencyLogError> {
-        let query = ["SELECT * FROM TRANSPARENCYLOG WHERE id = '", id, "';"];
+    fn with_db<T>(
+        &self,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+    ) -> Result<T, TransparencyLogError> {
+        let conn = self.connection.lock().unwrap();
+        Ok(f(&conn)?)
+    }
+
+    fn fetch(&self, query: LogQuery) -> Result<Vec<TransparencyLog>, TransparencyLogError> {
+        self.with_db(|conn| query.fetch(conn))
+    }
 
-        let results = self.process_query(query.join("").as_str())?;
+    pub fn find_transparency_log(&self, id: &str) -> Result<TransparencyLog, TransparencyLogError> {
+        let results = self.fetch(LogQuery::new().id(id))?;
 
         if results.len() == 1 {
             Ok(results.into_iter().next().unwrap())
@@ -415,39 +424,43 @@ impl TransparencyLogService {
         }
     }
 
+    /// Finds the latest transparency log that added the artifact with the
+    /// specified artifact id.
+    pub fn find_transparency_log_by_artifact_id(
+        &self,
+        artifact_id: &str,
+    ) -> Result<TransparencyLog, TransparencyLogError> {
+        self.fetch(
+            LogQuery::new()
+                .artifact_id(artifact_id)
+                .operation(Operation::AddArtifact)
+                .latest_first()
+                .limit(1),
+        )?
+        .into_iter()
+        .next()
+        .ok_or_else(|| TransparencyLogError::LogNotFound {
+            id: artifact_id.to_owned(),
+        })
+    }
+
+    /// Finds the AddArtifact transparency logs of the artifacts that were not
+    /// removed afterwards, by a RemoveArtifact transparency log of either the
+    /// same artifact id or the same package specific artifact id.
+    pub fn find_added_artifacts(&self) -> Result<Vec<TransparencyLog>, TransparencyLogError> {
+        self.with_db(query::added_artifacts)
+    }
+
     fn write_transparency_log(
         &self,
         transparency_log: &TransparencyLog,
     ) -> Result<(), TransparencyLogError> {
-        let conn = self.open_db()?;
-
-        match conn.execute(
-            "INSERT INTO TRANSPARENCYLOG (id, package_type, package_specific_id, num_artifacts, package_specific_artifact_id, artifact_hash, source_hash, artifact_id, source_id, timestamp, operation, node_id, node_public_key) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
-            params![
-                transparency_log.id,
-                transparency_log.package_type,
-                transparency_log.package_specific_id,
-                transparency_log.num_artifacts,
-                transparency_log.package_specific_artifact_id,
-                transparency_log.artifact_hash,
-                transparency_log.source_hash,
-                transparency_log.artifact_id,
-                transparency_log.source_id,
-                transparency_log.timestamp,
-                transparency_log.operation,
-                transparency_log.node_id,
-                transparency_log.node_public_key,
-            ],
-        ) {
-            Ok(_) => {
-                debug!(
-                    "Transparency log inserted into database with id: {}",
-                    transparency_log.id
-                );
-                Ok(())
-            }
-            Err(err) => Err(err.into()),
-        }
+        self.with_db(|conn| query::insert(conn, transparency_log))?;
+        debug!(
+            "Transparency log inserted into database with id: {}",
+            transparency_log.id
+        );
+        Ok(())
     }
 
     fn read_transparency_log(
@@ -455,34 +468,20 @@ impl TransparencyLogService {
         package_type: &PackageType,
         package_specific_artifact_id: &str,
     ) -> Result<TransparencyLog, TransparencyLogError> {
-        let query = [
-            "SELECT * FROM TRANSPARENCYLOG WHERE package_type = '",
-            &*package_type.to_string(),
-            "' AND package_specific_artifact_id = '",
-            package_specific_artifact_id,
-            "';",
-        ];
-        let results = self.process_query(query.join("").as_str())?;
-
-        let mut vector: Vec<TransparencyLog> = Vec::new();
-        for record in results {
-            if record.operation == Operation::AddArtifact
-                || record.operation == Operation::RemoveArtifact
-            {
-                vector.push(record);
-            }
-        }
-
-        vector.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-
-        let latest_record =
-            vector
-                .into_iter()
-                .next()
-                .ok_or(TransparencyLogError::ArtifactNotFound {
-                    package_type: *package_type,
-                    package_specific_artifact_id: package_specific_artifact_id.to_owned(),
-                })?;
+        let latest_record = self
+            .fetch(
+                LogQuery::new()
+                    .package_type(package_type)
+                    .package_specific_artifact_id(package_specific_artifact_id)
+                    .add_or_remove_artifact()
+                    .limit(1),
+            )?
+            .into_iter()
+            .next()
+            .ok_or(TransparencyLogError::ArtifactNotFound {
+                package_type: *package_type,
+                package_specific_artifact_id: package_specific_artifact_id.to_owned(),
+            })?;
 
         if latest_record.operation == Operation::RemoveArtifact {
             return Err(TransparencyLogError::InvalidOperation {
@@ -499,36 +498,30 @@ impl TransparencyLogService {
         package_type: &PackageType,
         package_specific_id: &str,
     ) -> Result<Vec<TransparencyLog>, TransparencyLogError> {
-        // Get еру first record for given package_type and package_specific_id
-        let query = format!(
-            "SELECT * FROM TRANSPARENCYLOG
-            WHERE operation in ('{}', '{}') and package_type = '{}' and package_specific_id = '{}'
-            ORDER BY timestamp DESC LIMIT 1",
-            Operation::AddArtifact,
-            Operation::RemoveArtifact,
-            package_type,
-            package_specific_id
-        );
-
-        let mut res = self.process_query(query.as_str())?;
+        // Get the first record for given package_type and package_specific_id
+        let mut res = self.fetch(
+            LogQuery::new()
+                .package_type(package_type)
+                .package_specific_id(package_specific_id)
+                .add_or_remove_artifact()
+                .latest_first()
+                .limit(1),
+        )?;
         if let Some(log) = res.first() {
             // If the record is not at `removed` status and there are several artifacts
             // related to given `package_type` and `package_specific_id`,
             // then we try to get all records
             let num_artifacts = log.num_artifacts;
             if log.operation == Operation::AddArtifact && log.num_artifacts > 1 {
-                let query = format!(
-                    "SELECT * FROM TRANSPARENCYLOG
-                    WHERE operation = '{}' and package_type = '{}' and
-                    package_specific_id = '{}' and id != '{}'
-                    ORDER BY timestamp DESC LIMIT {}",
-                    Operation::AddArtifact,
-                    package_type,
-                    package_specific_id,
-                    log.id,
-                    num_artifacts - 1
-                );
-                let second_res = self.process_query(query.as_str())?;
+                let second_res = self.fetch(
+                    LogQuery::new()
+                        .package_type(package_type)
+                        .package_specific_id(package_specific_id)
+                        .operation(Operation::AddArtifact)
+                        .excluding_id(&log.id)
+                        .latest_first()
+                        .limit(num_artifacts - 1),
+                )?;
                 for rec in second_res {
                     // Operation of all records must be `AddArtifact`
                     if rec.operation.clone() != Operation::AddArtifact {
@@ -561,85 +554,31 @@ impl TransparencyLogService {
         package_type: &PackageType,
         package_specific_id: &str,
     ) -> Result<Vec<TransparencyLog>, TransparencyLogError> {
-        let query = format!(
-            "SELECT *
-            FROM TRANSPARENCYLOG
-            WHERE operation in ('{}', '{}') and package_type = '{}' and package_specific_id = '{}'
-            ORDER BY timestamp ASC",
-            Operation::AddArtifact,
-            Operation::RemoveArtifact,
-            package_type,
-            package_specific_id
-        );
-
-        let res = self.process_query(query.as_str())?;
-
-        Ok(res)
+        self.fetch(
+            LogQuery::new()
+                .package_type(package_type)
+                .package_specific_id(package_specific_id)
+                .add_or_remove_artifact(),
+        )
     }
 
-    fn find_added_nodes(&self) -> Result<Vec<TransparencyLog>, TransparencyLogError> {
-        let select = format!(
-            "SELECT * FROM (
-              SELECT
-               id, package_type, package_specific_id,
-               num_artifacts, package_specific_artifact_id, artifact_hash, source_hash, artifact_id,
-               source_id, max(timestamp), operation, node_id, node_public_key
-              FROM TRANSPARENCYLOG
-              WHERE operation = '{}' or operation = '{}'
-              GROUP BY node_id
-            ) WHERE operation = '{}'",
-            Operation::AddNode,
-            Operation::RemoveNode,
-            Operation::AddNode
-        );
-
-        let res = self.process_query(select.as_str())?;
-
-        Ok(res)
+    /// Reads all AddArtifact transparency logs for the given `package_type` of which
+    /// the `package_specific_id` starts with the given prefix, ordered by timestamp.
+    fn read_transparency_logs_by_prefix(
+        &self,
+        package_type: &PackageType,
+        package_specific_id_prefix: &str,
+    ) -> Result<Vec<TransparencyLog>, TransparencyLogError> {
+        self.fetch(
+            LogQuery::new()
+                .package_type(package_type)
+                .package_specific_id_prefix(package_specific_id_prefix)
+                .operation(Operation::AddArtifact),
+        )
     }
 
-    fn process_query(&self, query: &str) -> Result<Vec<TransparencyLog>, TransparencyLogError> {
-        let conn = self.open_db()?;
-        let mut stmt = conn.prepare(query)?;
-
-        let transparency_log_records = stmt.query_map([], |row| {
-            Ok(TransparencyLog {
-                id: row.get(0)?,
-                package_type: {
-                    let value: Value = row.get(1)?;
-                    match value {
-                        Value::Text(pt) => Ok(Some(PackageType::from_str(&pt).unwrap())),
-                        Value::Null => Ok(None),
-                        _ => Err(rusqlite::Error::InvalidColumnType(
-                            1,
-                            "package_type".to_owned(),
-                            value.data_type(),
-                        )),
-                    }?
-                },
-                package_specific_id: row.get(2)?,
-                num_artifacts: row.get(3)?,
-                package_specific_artifact_id: row.get(4)?,
-                artifact_hash: row.get(5)?,
-                source_hash: row.get(6)?,
-                artifact_id: row.get(7)?,
-                source_id: row.get(8)?,
-                timestamp: row.get(9)?,
-                operation: {
-                    let op: String = row.get(10)?;
-                    Operation::from_str(&op).unwrap()
-                },
-                node_id: row.get(11)?,
-                node_public_key: row.get(12)?,
-            })
-        })?;
-
-        let mut vector: Vec<TransparencyLog> = Vec::new();
-        for transparency_log_record in transparency_log_records {
-            vector.push(transparency_log_record?);
-        }
-
-        Ok(vector)
+    fn find_added_nodes(&self) -> Result<Vec<TransparencyLog>, TransparencyLogError> {
+        self.with_db(query::added_nodes)
     }
 }
 
@@ -648,8 +587,9 @@ impl TransparencyLogService {
 mod tests {
     use super::*;
     use crate::blockchain_service::event::BlockchainEvent;
-    use crate::util::test_util;
+    use crate::util::{migration_util, test_util};
     use libp2p::identity::Keypair;
+    use std::path::PathBuf;
     use std::time::Duration;
 
     #[test]
@@ -709,16 +649,142 @@ mod tests {
 
         let (log, _) = test_util::tests::create_transparency_log_service(&tmp_dir);
 
-        let result = log.open_db();
-        assert!(result.is_ok());
+        let path = tmp_dir
+            .canonicalize()
+            .unwrap()
+            .join("transparency_log")
+            .join("transparency_log.db");
+        assert_eq!(
+            log.connection.lock().unwrap().path().unwrap(),
+            path.as_path()
+        );
+
+        // clones of the service share the same connection
+        let clone = log.clone();
+        assert!(Arc::ptr_eq(&log.connection, &clone.connection));
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    // Copy a database of an older release into the repository at `tmp_dir`.
+    fn copy_fixture_db(tmp_dir: &Path, fixture: &str) -> PathBuf {
+        let mut fixture_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fixture_path.push("tests/resources/transparency_log");
+        fixture_path.push(fixture);
+
+        let db_path = tmp_dir.join("transparency_log").join("transparency_log.db");
+        fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+        fs::copy(fixture_path, &db_path).unwrap();
+        db_path
+    }
+
+    fn index_names(log: &TransparencyLogService) -> Vec<String> {
+        let conn = log.connection.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'index' AND name LIKE 'TRANSPARENCYLOG%' ORDER BY name")
+            .unwrap();
+        let names = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_unversioned_db() {
+        let tmp_dir = test_util::tests::setup();
+        copy_fixture_db(&tmp_dir, "unversioned.db");
+
+        let (log, _) = test_util::tests::create_transparency_log_service(&tmp_dir);
+
+        assert_eq!(
+            migration_util::schema_version(&log.connection.lock().unwrap()).unwrap(),
+            2
+        );
+        assert_eq!(
+            index_names(&log),
+            vec![
+                "TRANSPARENCYLOG_ARTIFACT_ID",
+                "TRANSPARENCYLOG_NODE_ID",
+                "TRANSPARENCYLOG_OPERATION",
+                "TRANSPARENCYLOG_PACKAGE",
+                "TRANSPARENCYLOG_PACKAGE_ARTIFACT",
+            ]
+        );
+
+        assert_eq!(
+            log.get_authorized_nodes().unwrap(),
+            vec![PeerId::from_str("12D3KooW9tJMax94Lrqw7Y5Qw36viGQAS2gTEPQ5Wg1vTk7xPfQs").unwrap()]
+        );
+        let logs = log
+            .search_transparency_logs(&PackageType::Maven2, "com.example:lib:1.0", true)
+            .unwrap();
+        assert_eq!(
+            logs.iter().map(|log| log.id.as_str()).collect::<Vec<_>>(),
+            vec!["pom-log", "jar-log"]
+        );
+        assert_eq!(
+            log.find_transparency_log_by_artifact_id("jar-artifact")
+                .unwrap()
+                .package_specific_artifact_id,
+            "com/example/lib/1.0/lib-1.0.jar"
+        );
+
+        // the upgraded database is used as is by the next start
+        drop(log);
+        let (log, _) = test_util::tests::create_transparency_log_service(&tmp_dir);
+        assert_eq!(log.find_added_artifacts().unwrap().len(), 2);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_version_1_db() {
+        let tmp_dir = test_util::tests::setup();
+        copy_fixture_db(&tmp_dir, "version_1.db");
+
+        let (log, _) = test_util::tests::create_transparency_log_service(&tmp_dir);
+
+        assert_eq!(
+            migration_util::schema_version(&log.connection.lock().unwrap()).unwrap(),
+            2
+        );
+        assert_eq!(index_names(&log).len(), 5);
+        assert_eq!(
+            log.find_transparency_log("image-log")
+                .unwrap()
+                .package_specific_id,
+            "library/alpine:3.15"
+        );
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_refuse_db_of_newer_release() {
+        let tmp_dir = test_util::tests::setup();
+        let db_path = copy_fixture_db(&tmp_dir, "version_1.db");
+        Connection::open(&db_path)
+            .unwrap()
+            .pragma_update(None, "user_version", 99)
+            .unwrap();
 
-        let conn = result.unwrap();
-        let mut path = log.storage_path;
-        path.push("transparency_log.db");
-        assert_eq!(conn.path().unwrap(), path.as_path());
+        let (blockchain_event_client, _) = test_util::tests::create_blockchain_event_client();
+        let result = TransparencyLogService::new(&tmp_dir, blockchain_event_client);
 
-        let close_result = conn.close();
-        assert!(close_result.is_ok());
+        assert!(matches!(
+            result,
+            Err(TransparencyLogError::MigrationFailure(
+                MigrationError::DatabaseTooNew {
+                    version: 99,
+                    supported: 2
+                }
+            ))
+        ));
+        // the database is left untouched
+        let conn = Connection::open(&db_path).unwrap();
+        assert_eq!(migration_util::schema_version(&conn).unwrap(), 99);
 
         test_util::tests::teardown(tmp_dir);
     }
@@ -797,6 +863,76 @@ mod tests {
         test_util::tests::teardown(tmp_dir);
     }
 
+    #[tokio::test]
+    async fn test_find_transparency_log_by_artifact_id() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (log, _) = test_util::tests::create_transparency_log_service(&tmp_dir);
+
+        let transparency_log = new_artifact_transparency_log_default();
+        assert!(log.write_transparency_log(&transparency_log).is_ok());
+
+        let result_find = log
+            .find_transparency_log_by_artifact_id(&transparency_log.artifact_id)
+            .unwrap();
+        assert_eq!(result_find, transparency_log);
+        assert!(matches!(
+            log.find_transparency_log_by_artifact_id("unknown_artifact_id"),
+            Err(TransparencyLogError::LogNotFound { .. })
+        ));
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_find_added_artifacts() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (log, _) = test_util::tests::create_transparency_log_service(&tmp_dir);
+
+        let kept = new_artifact_transparency_log(
+            Some(PackageType::Maven2),
+            Operation::AddArtifact,
+            None,
+            Some("kept"),
+        );
+        let removed = new_artifact_transparency_log(
+            Some(PackageType::Maven2),
+            Operation::AddArtifact,
+            None,
+            Some("removed"),
+        );
+        let removal = new_artifact_transparency_log(
+            Some(PackageType::Maven2),
+            Operation::RemoveArtifact,
+            None,
+            Some("removed"),
+        );
+        let added_again = new_artifact_transparency_log(
+            Some(PackageType::Maven2),
+            Operation::AddArtifact,
+            None,
+            Some("removed"),
+        );
+        for transparency_log in [&kept, &removed, &removal] {
+            log.write_transparency_log(transparency_log).unwrap();
+        }
+
+        let added_ids = || -> Vec<String> {
+            log.find_added_artifacts()
+                .unwrap()
+                .into_iter()
+                .map(|transparency_log| transparency_log.id)
+                .collect()
+        };
+        assert_eq!(added_ids(), vec![kept.id.clone()]);
+
+        log.write_transparency_log(&added_again).unwrap();
+        assert_eq!(added_ids(), vec![kept.id, added_again.id]);
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
     #[tokio::test]
     async fn test_read_transparency_log() {
         let tmp_dir = test_util::tests::setup();
@@ -921,6 +1057,42 @@ mod tests {
         test_util::tests::teardown(tmp_dir);
     }
 
+    #[tokio::test]
+    async fn test_read_transparency_logs_by_prefix() {
+        let tmp_dir = test_util::tests::setup();
+
+        let (log, _) = test_util::tests::create_transparency_log_service(&tmp_dir);
+
+        let transparency_log1 = new_artifact_transparency_log(
+            Some(PackageType::Npm),
+            Operation::AddArtifact,
+            Some("left_pad@1.3.0"),
+            Some("left_pad/1.3.0"),
+        );
+        assert!(log.write_transparency_log(&transparency_log1).is_ok());
+
+        let transparency_log2 = new_artifact_transparency_log(
+            Some(PackageType::Npm),
+            Operation::AddArtifact,
+            Some("leftXpad@1.3.0"),
+            Some("leftXpad/1.3.0"),
+        );
+        assert!(log.write_transparency_log(&transparency_log2).is_ok());
+
+        let result_read = log
+            .read_transparency_logs_by_prefix(&PackageType::Npm, "left_pad@")
+            .unwrap();
+        assert_eq!(result_read.len(), 1);
+        assert_eq!(result_read[0].package_specific_id, "left_pad@1.3.0");
+
+        let result_read_other_type = log
+            .read_transparency_logs_by_prefix(&PackageType::Maven2, "left_pad@")
+            .unwrap();
+        assert!(result_read_other_type.is_empty());
+
+        test_util::tests::teardown(tmp_dir);
+    }
+
     #[tokio::test]
     async fn test_verify_artifact_can_be_added_to_transparency_logs() {
         let tmp_dir = test_util::tests::setup();
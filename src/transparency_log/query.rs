@@ -0,0 +1,433 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The queries on the transparency log database. Every value that ends up in a
+//! query is bound as a parameter, the SQL itself is only ever built from the
+//! fixed fragments in this module.
+
+use super::log::{Operation, TransparencyLog};
+use crate::artifact_service::model::PackageType;
+use crate::util::migration_util::{self, Migration, MigrationError};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+
+const COLUMNS: &str = "id, package_type, package_specific_id, num_artifacts, \
+    package_specific_artifact_id, artifact_hash, source_hash, artifact_id, source_id, \
+    timestamp, operation, node_id, node_public_key";
+
+/// The schema history of the transparency log database. The first two
+/// migrations adopt databases that were created before the schema was
+/// versioned, which is why they only create what doesn't exist yet.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create the transparency log table",
+        sql: "CREATE TABLE IF NOT EXISTS TRANSPARENCYLOG (
+            id TEXT PRIMARY KEY,
+            package_type TEXT,
+            package_specific_id TEXT,
+            num_artifacts INTEGER,
+            package_specific_artifact_id TEXT,
+            artifact_hash TEXT,
+            source_hash TEXT,
+            artifact_id TEXT,
+            source_id TEXT,
+            timestamp INTEGER,
+            operation TEXT NOT NULL,
+            node_id TEXT,
+            node_public_key TEXT
+        );",
+    },
+    Migration {
+        version: 2,
+        description: "Index the transparency log lookups",
+        sql: "CREATE INDEX IF NOT EXISTS TRANSPARENCYLOG_PACKAGE
+            ON TRANSPARENCYLOG (package_type, package_specific_id, operation, timestamp);
+        CREATE INDEX IF NOT EXISTS TRANSPARENCYLOG_PACKAGE_ARTIFACT
+            ON TRANSPARENCYLOG (package_type, package_specific_artifact_id, operation, timestamp);
+        CREATE INDEX IF NOT EXISTS TRANSPARENCYLOG_OPERATION
+            ON TRANSPARENCYLOG (operation, timestamp);
+        CREATE INDEX IF NOT EXISTS TRANSPARENCYLOG_ARTIFACT_ID
+            ON TRANSPARENCYLOG (artifact_id, operation, timestamp);
+        CREATE INDEX IF NOT EXISTS TRANSPARENCYLOG_NODE_ID
+            ON TRANSPARENCYLOG (node_id, operation, timestamp);",
+    },
+];
+
+/// Migrate the transparency log database to the latest schema version.
+pub fn migrate(conn: &mut Connection) -> Result<u32, MigrationError> {
+    migration_util::migrate(conn, MIGRATIONS)
+}
+
+pub fn insert(conn: &Connection, transparency_log: &TransparencyLog) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "INSERT INTO TRANSPARENCYLOG ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        COLUMNS
+    ))?;
+    stmt.execute(params![
+        transparency_log.id,
+        transparency_log.package_type,
+        transparency_log.package_specific_id,
+        transparency_log.num_artifacts,
+        transparency_log.package_specific_artifact_id,
+        transparency_log.artifact_hash,
+        transparency_log.source_hash,
+        transparency_log.artifact_id,
+        transparency_log.source_id,
+        transparency_log.timestamp,
+        transparency_log.operation,
+        transparency_log.node_id,
+        transparency_log.node_public_key,
+    ])?;
+    Ok(())
+}
+
+/// The operation of the latest AddNode or RemoveNode transparency log of the
+/// node with the specified id.
+pub fn latest_node_operation(
+    conn: &Connection,
+    node_id: &str,
+) -> rusqlite::Result<Option<Operation>> {
+    conn.prepare_cached(
+        "SELECT operation FROM TRANSPARENCYLOG
+        WHERE node_id = ?1 AND operation IN (?2, ?3)
+        ORDER BY timestamp DESC, rowid DESC LIMIT 1",
+    )?
+    .query_row(
+        params![node_id, Operation::AddNode, Operation::RemoveNode],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// The latest AddNode transparency log of every node that wasn't removed
+/// afterwards.
+pub fn added_nodes(conn: &Connection) -> rusqlite::Result<Vec<TransparencyLog>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT * FROM (
+          SELECT
+           id, package_type, package_specific_id,
+           num_artifacts, package_specific_artifact_id, artifact_hash, source_hash, artifact_id,
+           source_id, max(timestamp), operation, node_id, node_public_key
+          FROM TRANSPARENCYLOG
+          WHERE operation IN (?1, ?2)
+          GROUP BY node_id
+        ) WHERE operation = ?1",
+    )?;
+    let rows = stmt.query_map(
+        params![Operation::AddNode, Operation::RemoveNode],
+        transparency_log_from_row,
+    )?;
+    rows.collect()
+}
+
+/// The AddArtifact transparency logs of the artifacts that were not removed
+/// afterwards, by a RemoveArtifact transparency log of either the same artifact
+/// id or the same package specific artifact id.
+pub fn added_artifacts(conn: &Connection) -> rusqlite::Result<Vec<TransparencyLog>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT added.* FROM TRANSPARENCYLOG added
+        WHERE added.operation = ?1 AND NOT EXISTS (
+          SELECT 1 FROM TRANSPARENCYLOG removed
+          WHERE removed.operation = ?2 AND removed.timestamp >= added.timestamp
+          AND (removed.artifact_id = added.artifact_id
+            OR (removed.package_type = added.package_type
+              AND removed.package_specific_artifact_id = added.package_specific_artifact_id))
+        )
+        ORDER BY added.timestamp ASC, added.rowid ASC",
+    )?;
+    let rows = stmt.query_map(
+        params![Operation::AddArtifact, Operation::RemoveArtifact],
+        transparency_log_from_row,
+    )?;
+    rows.collect()
+}
+
+/// A query for transparency logs that match all of the specified conditions,
+/// ordered by timestamp.
+#[derive(Debug, Default)]
+pub struct LogQuery {
+    conditions: Vec<&'static str>,
+    params: Vec<String>,
+    latest_first: bool,
+    limit: Option<u32>,
+}
+
+impl LogQuery {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn id(self, id: &str) -> Self {
+        self.condition("id = ?", [id])
+    }
+
+    pub fn excluding_id(self, id: &str) -> Self {
+        self.condition("id != ?", [id])
+    }
+
+    pub fn package_type(self, package_type: &PackageType) -> Self {
+        self.condition("package_type = ?", [&package_type.to_string()])
+    }
+
+    pub fn package_specific_id(self, package_specific_id: &str) -> Self {
+        self.condition("package_specific_id = ?", [package_specific_id])
+    }
+
+    /// Only match the transparency logs of which the package specific id starts
+    /// with `prefix`. This is expressed as a range, so the lookup can use the
+    /// package index.
+    pub fn package_specific_id_prefix(self, prefix: &str) -> Self {
+        match prefix_upper_bound(prefix) {
+            Some(upper_bound) => self.condition(
+                "package_specific_id >= ? AND package_specific_id < ?",
+                [prefix, &upper_bound],
+            ),
+            None => self.condition("package_specific_id >= ?", [prefix]),
+        }
+    }
+
+    pub fn package_specific_artifact_id(self, package_specific_artifact_id: &str) -> Self {
+        self.condition(
+            "package_specific_artifact_id = ?",
+            [package_specific_artifact_id],
+        )
+    }
+
+    pub fn artifact_id(self, artifact_id: &str) -> Self {
+        self.condition("artifact_id = ?", [artifact_id])
+    }
+
+    pub fn operation(self, operation: Operation) -> Self {
+        self.condition("operation = ?", [&operation.to_string()])
+    }
+
+    pub fn add_or_remove_artifact(self) -> Self {
+        self.condition(
+            "operation IN (?, ?)",
+            [
+                &Operation::AddArtifact.to_string(),
+                &Operation::RemoveArtifact.to_string(),
+            ],
+        )
+    }
+
+    /// Return the most recent transparency logs first.
+    pub fn latest_first(mut self) -> Self {
+        self.latest_first = true;
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn fetch(self, conn: &Connection) -> rusqlite::Result<Vec<TransparencyLog>> {
+        let mut stmt = conn.prepare_cached(&self.sql())?;
+        let rows = stmt.query_map(params_from_iter(self.params), transparency_log_from_row)?;
+        rows.collect()
+    }
+
+    fn condition<const N: usize>(mut self, condition: &'static str, params: [&str; N]) -> Self {
+        self.conditions.push(condition);
+        self.params.extend(params.map(str::to_owned));
+        self
+    }
+
+    fn sql(&self) -> String {
+        let mut sql = format!("SELECT {} FROM TRANSPARENCYLOG", COLUMNS);
+        if !self.conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.conditions.join(" AND "));
+        }
+        // the rowid keeps the order of logs with the same timestamp stable
+        if self.latest_first {
+            sql.push_str(" ORDER BY timestamp DESC, rowid DESC");
+        } else {
+            sql.push_str(" ORDER BY timestamp ASC, rowid ASC");
+        }
+        if let Some(limit) = self.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        sql
+    }
+}
+
+// The smallest string that is larger than every string that starts with
+// `prefix`, or None when there is no such string. Text is compared by its
+// UTF-8 bytes, which sort in the same order as the chars they encode.
+fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        if let Some(next) = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32) {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+fn transparency_log_from_row(row: &Row) -> rusqlite::Result<TransparencyLog> {
+    Ok(TransparencyLog {
+        id: row.get(0)?,
+        package_type: row.get(1)?,
+        package_specific_id: row.get(2)?,
+        num_artifacts: row.get(3)?,
+        package_specific_artifact_id: row.get(4)?,
+        artifact_hash: row.get(5)?,
+        source_hash: row.get(6)?,
+        artifact_id: row.get(7)?,
+        source_id: row.get(8)?,
+        timestamp: row.get(9)?,
+        operation: row.get(10)?,
+        node_id: row.get(11)?,
+        node_public_key: row.get(12)?,
+    })
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    fn new_transparency_log(
+        id: &str,
+        package_specific_id: &str,
+        timestamp: u64,
+    ) -> TransparencyLog {
+        TransparencyLog {
+            id: id.to_owned(),
+            package_type: Some(PackageType::Npm),
+            package_specific_id: package_specific_id.to_owned(),
+            num_artifacts: 1,
+            package_specific_artifact_id: format!("{}.tgz", package_specific_id),
+            artifact_hash: "artifact_hash".to_owned(),
+            source_hash: "source_hash".to_owned(),
+            artifact_id: format!("artifact_{}", id),
+            source_id: "source_id".to_owned(),
+            timestamp,
+            operation: Operation::AddArtifact,
+            node_id: "node_id".to_owned(),
+            node_public_key: "node_public_key".to_owned(),
+        }
+    }
+
+    fn open_in_memory() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn values_are_never_interpreted_as_sql() {
+        let conn = open_in_memory();
+        insert(&conn, &new_transparency_log("1", "left-pad@1.3.0", 10)).unwrap();
+
+        for id in ["' OR '1'='1", "1'; DROP TABLE TRANSPARENCYLOG; --"] {
+            assert!(LogQuery::new().id(id).fetch(&conn).unwrap().is_empty());
+        }
+        assert_eq!(LogQuery::new().id("1").fetch(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn query_by_package_specific_id_prefix() {
+        let conn = open_in_memory();
+        for (id, package_specific_id, timestamp) in [
+            ("1", "left-pad@1.3.0", 30),
+            ("2", "left-pad@1.1.0", 10),
+            ("3", "left-pads@1.0.0", 20),
+            ("4", "left-pac@1.0.0", 40),
+            ("5", "left-pad\u{10FFFF}@1.0.0", 50),
+        ] {
+            insert(
+                &conn,
+                &new_transparency_log(id, package_specific_id, timestamp),
+            )
+            .unwrap();
+        }
+
+        let ids = |prefix: &str| {
+            LogQuery::new()
+                .package_type(&PackageType::Npm)
+                .package_specific_id_prefix(prefix)
+                .fetch(&conn)
+                .unwrap()
+                .into_iter()
+                .map(|log| log.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("left-pad@"), vec!["2", "1"]);
+        assert_eq!(ids("left-pad"), vec!["2", "3", "1", "5"]);
+        assert_eq!(ids(""), vec!["2", "3", "1", "4", "5"]);
+
+        assert_eq!(prefix_upper_bound("ab"), Some("ac".to_owned()));
+        assert_eq!(prefix_upper_bound("a\u{10FFFF}"), Some("b".to_owned()));
+        assert_eq!(prefix_upper_bound("\u{D7FF}"), Some("\u{E000}".to_owned()));
+        assert_eq!(prefix_upper_bound("\u{10FFFF}"), None);
+    }
+
+    #[test]
+    fn lookups_use_an_index() {
+        let conn = open_in_memory();
+
+        let query_plan = |query: LogQuery| {
+            let mut stmt = conn
+                .prepare(&format!("EXPLAIN QUERY PLAN {}", query.sql()))
+                .unwrap();
+            let details = stmt
+                .query_map(params_from_iter(query.params), |row| {
+                    row.get::<_, String>(3)
+                })
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .unwrap();
+            details.join("\n")
+        };
+
+        assert!(query_plan(
+            LogQuery::new()
+                .package_type(&PackageType::Docker)
+                .package_specific_id("alpine:3.15")
+                .add_or_remove_artifact()
+                .latest_first()
+                .limit(1)
+        )
+        .contains("USING INDEX TRANSPARENCYLOG_PACKAGE "));
+        assert!(query_plan(
+            LogQuery::new()
+                .package_type(&PackageType::Npm)
+                .package_specific_id_prefix("left-pad@")
+                .operation(Operation::AddArtifact)
+        )
+        .contains("USING INDEX TRANSPARENCYLOG_PACKAGE "));
+        assert!(query_plan(
+            LogQuery::new()
+                .package_type(&PackageType::Docker)
+                .package_specific_artifact_id("sha256:1234")
+                .add_or_remove_artifact()
+        )
+        .contains("USING INDEX TRANSPARENCYLOG_PACKAGE_ARTIFACT"));
+        assert!(query_plan(
+            LogQuery::new()
+                .artifact_id("artifact_id")
+                .operation(Operation::AddArtifact)
+                .latest_first()
+                .limit(1)
+        )
+        .contains("USING INDEX TRANSPARENCYLOG_ARTIFACT_ID"));
+        assert!(query_plan(LogQuery::new().id("id")).contains("USING INDEX sqlite_autoindex"));
+    }
+}
@@ -16,4 +16,7 @@
 
 pub mod env_util;
 pub mod keypair_util;
+pub mod migration_util;
+pub mod range_util;
 pub mod test_util;
+pub mod url_util;
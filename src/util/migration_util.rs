@@ -0,0 +1,172 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Versioned schema migrations for the SQLite databases of the node. The schema
+//! version of a database is kept in its `user_version` pragma, which is 0 for a
+//! database that was never migrated.
+
+use log::info;
+use rusqlite::{Connection, TransactionBehavior};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("Database schema version {version} is newer than the latest version {supported} that this Pyrsia node supports, please upgrade the node")]
+    DatabaseTooNew { version: u32, supported: u32 },
+    #[error("Migration to schema version {version} failed: {source}")]
+    MigrationFailed {
+        version: u32,
+        source: rusqlite::Error,
+    },
+    #[error("Failure while reading the database schema version: {0}")]
+    DatabaseFailure(#[from] rusqlite::Error),
+}
+
+/// A single step in the schema history of a database.
+pub struct Migration {
+    /// The schema version of the database after this migration ran. The
+    /// migrations of a database are numbered 1, 2, 3...
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// The schema version of the database.
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Run the migrations that the database didn't run yet, in order. Each migration
+/// runs in its own transaction together with the update of the schema version,
+/// so a failed migration leaves the database at the previous version. Returns
+/// the schema version of the migrated database.
+///
+/// A database with a schema version that is newer than the last migration was
+/// written by a newer release, and is refused rather than used with a schema
+/// that this release doesn't understand.
+pub fn migrate(conn: &mut Connection, migrations: &[Migration]) -> Result<u32, MigrationError> {
+    debug_assert!(migrations
+        .iter()
+        .enumerate()
+        .all(|(index, migration)| migration.version as usize == index + 1));
+
+    let supported = migrations.last().map_or(0, |migration| migration.version);
+    let version = schema_version(conn)?;
+    if version > supported {
+        return Err(MigrationError::DatabaseTooNew { version, supported });
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > version) {
+        let migration_failed = |source| MigrationError::MigrationFailed {
+            version: migration.version,
+            source,
+        };
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(migration_failed)?;
+        // another process may have migrated the database in the meantime
+        if schema_version(&tx)? >= migration.version {
+            continue;
+        }
+        tx.execute_batch(migration.sql)
+            .and_then(|_| tx.pragma_update(None, "user_version", migration.version))
+            .and_then(|_| tx.commit())
+            .map_err(migration_failed)?;
+        info!(
+            "Migrated database to schema version {}: {}",
+            migration.version, migration.description
+        );
+    }
+
+    Ok(supported)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            description: "Create the item table",
+            sql: "CREATE TABLE ITEM (id TEXT PRIMARY KEY);",
+        },
+        Migration {
+            version: 2,
+            description: "Add the item name",
+            sql: "ALTER TABLE ITEM ADD COLUMN name TEXT;",
+        },
+    ];
+
+    #[test]
+    fn migrate_runs_pending_migrations_once() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+
+        assert_eq!(migrate(&mut conn, &MIGRATIONS[..1]).unwrap(), 1);
+        conn.execute("INSERT INTO ITEM (id) VALUES ('a')", [])
+            .unwrap();
+
+        assert_eq!(migrate(&mut conn, MIGRATIONS).unwrap(), 2);
+        assert_eq!(migrate(&mut conn, MIGRATIONS).unwrap(), 2);
+        assert_eq!(schema_version(&conn).unwrap(), 2);
+        let name: Option<String> = conn
+            .query_row("SELECT name FROM ITEM WHERE id = 'a'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, None);
+    }
+
+    #[test]
+    fn migrate_refuses_a_newer_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", 3).unwrap();
+
+        let error = migrate(&mut conn, MIGRATIONS).unwrap_err();
+        assert!(matches!(
+            error,
+            MigrationError::DatabaseTooNew {
+                version: 3,
+                supported: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn failed_migration_keeps_the_previous_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let broken = [
+            Migration {
+                version: 1,
+                ..MIGRATIONS[0]
+            },
+            Migration {
+                version: 2,
+                description: "Broken",
+                sql: "ALTER TABLE ITEM ADD COLUMN name TEXT; ALTER TABLE MISSING ADD COLUMN x;",
+            },
+        ];
+
+        let error = migrate(&mut conn, &broken).unwrap_err();
+        assert!(matches!(
+            error,
+            MigrationError::MigrationFailed { version: 2, .. }
+        ));
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        // the part of the migration that succeeded was rolled back as well
+        assert!(conn.prepare("SELECT name FROM ITEM").is_err());
+    }
+}
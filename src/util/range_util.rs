@@ -0,0 +1,170 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::artifact_service::storage::ArtifactRange;
+use warp::http::{Response, StatusCode};
+use warp::hyper::Body;
+
+/// The outcome of evaluating a `Range` request header against the size of
+/// the requested content.
+#[derive(Debug, Eq, PartialEq)]
+pub enum RangeRequest {
+    /// The header can be served as the inclusive byte range `start..=end`.
+    Satisfiable { start: u64, end: u64 },
+    /// The header is valid, but none of its bytes fall within the content.
+    Unsatisfiable,
+    /// The header is malformed, uses another unit or requests multiple ranges.
+    /// In that case the full content should be served.
+    Ignored,
+}
+
+/// Parses a `Range` header containing a single byte range, as described in
+/// RFC 9110, section 14.1.2: `bytes=start-end`, `bytes=start-` or `bytes=-suffix`.
+pub fn parse_range(range_header: &str, size: u64) -> RangeRequest {
+    let range = match range_header.trim().strip_prefix("bytes=") {
+        Some(range) if !range.contains(',') => range.trim(),
+        _ => return RangeRequest::Ignored,
+    };
+    let (start, end) = match range.split_once('-') {
+        Some(bounds) => bounds,
+        None => return RangeRequest::Ignored,
+    };
+
+    match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => {
+            if start >= size {
+                RangeRequest::Unsatisfiable
+            } else {
+                RangeRequest::Satisfiable {
+                    start,
+                    end: end.min(size - 1),
+                }
+            }
+        }
+        (Ok(start), Err(_)) if end.is_empty() => {
+            if start >= size {
+                RangeRequest::Unsatisfiable
+            } else {
+                RangeRequest::Satisfiable {
+                    start,
+                    end: size - 1,
+                }
+            }
+        }
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 || size == 0 {
+                RangeRequest::Unsatisfiable
+            } else {
+                RangeRequest::Satisfiable {
+                    start: size.saturating_sub(suffix),
+                    end: size - 1,
+                }
+            }
+        }
+        _ => RangeRequest::Ignored,
+    }
+}
+
+/// Creates the response for a byte range of an artifact: `206 Partial Content`
+/// with the requested bytes, or `416 Range Not Satisfiable` when the range
+/// doesn't overlap with the artifact.
+pub fn create_range_response(artifact_range: ArtifactRange, content_type: &str) -> Response<Body> {
+    match artifact_range {
+        ArtifactRange::Partial {
+            start,
+            end,
+            size,
+            stream,
+        } => Response::builder()
+            .header("Content-Type", content_type)
+            .header("Content-Length", end - start + 1)
+            .header("Content-Range", format!("bytes {}-{}/{}", start, end, size))
+            .header("Accept-Ranges", "bytes")
+            .status(StatusCode::PARTIAL_CONTENT)
+            .body(Body::wrap_stream(stream))
+            .unwrap(),
+        ArtifactRange::Unsatisfiable { size } => Response::builder()
+            .header("Content-Range", format!("bytes */{}", size))
+            .header("Accept-Ranges", "bytes")
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .body(Body::empty())
+            .unwrap(),
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range_with_start_and_end() {
+        assert_eq!(
+            parse_range("bytes=0-99", 1000),
+            RangeRequest::Satisfiable { start: 0, end: 99 }
+        );
+        assert_eq!(
+            parse_range("bytes=900-2000", 1000),
+            RangeRequest::Satisfiable {
+                start: 900,
+                end: 999
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_range_open_ended() {
+        assert_eq!(
+            parse_range("bytes=500-", 1000),
+            RangeRequest::Satisfiable {
+                start: 500,
+                end: 999
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_range_suffix() {
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            RangeRequest::Satisfiable {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse_range("bytes=-2000", 1000),
+            RangeRequest::Satisfiable { start: 0, end: 999 }
+        );
+    }
+
+    #[test]
+    fn test_parse_range_unsatisfiable() {
+        assert_eq!(
+            parse_range("bytes=1000-", 1000),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn test_parse_range_ignored() {
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), RangeRequest::Ignored);
+        assert_eq!(parse_range("items=0-1", 1000), RangeRequest::Ignored);
+        assert_eq!(parse_range("bytes=5-1", 1000), RangeRequest::Ignored);
+        assert_eq!(parse_range("bytes=abc", 1000), RangeRequest::Ignored);
+    }
+}
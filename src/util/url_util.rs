@@ -0,0 +1,66 @@
+/*
+   Copyright 2021 JFrog Ltd
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use warp::Filter;
+
+/// Extracts the base URL (e.g. `https://pyrsia.example.com`) under which the
+/// client reached this node, to build absolute URLs in responses. The scheme is
+/// taken from the `X-Forwarded-Proto` header that a reverse proxy terminating
+/// TLS sets, and is `http` otherwise. There is no base URL without a `Host`
+/// header.
+pub fn base_url() -> impl Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("host")
+        .and(warp::header::optional::<String>("x-forwarded-proto"))
+        .map(|host: Option<String>, proto: Option<String>| {
+            let scheme = proto
+                .as_deref()
+                .and_then(|proto| proto.split(',').next())
+                .map(str::trim)
+                .filter(|scheme| !scheme.is_empty())
+                .unwrap_or("http")
+                .to_owned();
+            host.map(|host| format!("{}://{}", scheme, host))
+        })
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn base_url_test() {
+        let filter = base_url();
+
+        let url = warp::test::request()
+            .header("host", "localhost:7888")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(url, Some("http://localhost:7888".to_owned()));
+
+        let url = warp::test::request()
+            .header("host", "pyrsia.example.com")
+            .header("x-forwarded-proto", "https, http")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(url, Some("https://pyrsia.example.com".to_owned()));
+
+        let url = warp::test::request().filter(&filter).await.unwrap();
+        assert_eq!(url, None);
+    }
+}